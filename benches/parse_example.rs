@@ -12,8 +12,30 @@ fn parse_example() {
     }).expect("Failed to process file");
 }
 
+/// A document of deeply nested annotations and commands behind long `#` prefixes
+fn hash_heavy_document() -> String {
+    let mut source = String::new();
+    for i in 0..2_000 {
+        source.push_str(&"#".repeat(9 + i % 16));
+        source.push_str(" 注释 annotation text\n");
+        source.push_str("########cmd 1 2\n");
+    }
+    source
+}
+
+fn parse_hash_heavy(source: &str) {
+    let input = parser::StringInputSource::new(source);
+    let config = parser::ParserConfig::default().with_command_threshold(8);
+    let mut parser = parser::Parser::new(input, config);
+    parser.process_with(|_| {
+        Ok::<bool, Box<parser::ParseError>>(true)
+    }).expect("Failed to process document");
+}
+
 fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("parse_example", |b| b.iter(|| parse_example()));
+    let source = hash_heavy_document();
+    c.bench_function("parse_hash_heavy", |b| b.iter(|| parse_hash_heavy(&source)));
 }
 
 criterion_group!(benches, criterion_benchmark);
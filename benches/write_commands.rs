@@ -0,0 +1,36 @@
+use koicore::command::{Command, Parameter};
+use koicore::writer::{Writer, WriterConfig};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+fn simple_commands() -> Vec<Command> {
+    (0..1000)
+        .map(|i| {
+            Command::new("move", vec![
+                Parameter::from(i),
+                Parameter::from(i * 2),
+                Parameter::from(0.5),
+                Parameter::from("fast"),
+            ])
+        })
+        .collect()
+}
+
+fn write_commands(commands: &[Command]) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(32 * commands.len());
+    let mut writer = Writer::new(&mut buffer, WriterConfig::default());
+    for command in commands {
+        writer.write_command(command).expect("Failed to write command");
+    }
+    drop(writer);
+    buffer
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let commands = simple_commands();
+    c.bench_function("write_simple_commands", |b| {
+        b.iter(|| write_commands(black_box(&commands)))
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -1,10 +1,15 @@
 use anyhow::{Context, Result};
-use clap::{Parser as ClapParser, Subcommand};
+use clap::{Args, Parser as ClapParser, Subcommand};
 use koicore::Command;
-use koicore::parser::{BufReadWrapper, FileInputSource, Parser, ParserConfig};
+use koicore::balance::BalanceValidator;
+use koicore::convert::{ConvertConfig, ConvertError, Format, convert, write_commands};
+use koicore::parser::{
+    BufReadWrapper, FileInputSource, ParseError, Parser, ParserConfig, TextInputSource,
+};
 use koicore::writer::{Writer, WriterConfig};
+use regex::Regex;
 use std::fs::File;
-use std::io::{BufReader, Write};
+use std::io::{BufRead, BufReader, IsTerminal, Write};
 use std::path::PathBuf;
 
 #[derive(ClapParser)]
@@ -29,6 +34,13 @@ enum Commands {
         /// Pretty print JSON
         #[arg(short, long)]
         pretty: bool,
+
+        /// Keep only commands whose name matches this pattern
+        #[arg(long, value_name = "PATTERN")]
+        filter: Option<String>,
+
+        #[command(flatten)]
+        match_args: MatchArgs,
     },
     /// Convert JSON to KoiLang
     FromJson {
@@ -39,7 +51,228 @@ enum Commands {
         /// Output KoiLang file (defaults to stdout)
         #[arg(short, long)]
         output: Option<PathBuf>,
+
+        #[command(flatten)]
+        style: StyleArgs,
+    },
+    /// Reformat a KoiLang file
+    Format {
+        /// Input KoiLang file (defaults to stdin)
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+
+        /// Output KoiLang file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        #[command(flatten)]
+        style: StyleArgs,
+    },
+    /// Keep only the commands whose name matches a pattern
+    ///
+    /// Kept lines are copied unchanged. Text lines are commands named @text
+    /// and annotations are named @annotation.
+    Filter {
+        /// Glob pattern (or regular expression with --regex) for command names
+        pattern: String,
+
+        /// Input KoiLang file (defaults to stdin)
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+
+        /// Output KoiLang file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        #[command(flatten)]
+        match_args: MatchArgs,
+    },
+    /// Show the structural differences between two KoiLang files
+    Diff {
+        /// Original KoiLang file
+        old: PathBuf,
+
+        /// Modified KoiLang file
+        new: PathBuf,
     },
+    /// Check that paired commands such as #begin/#end are balanced
+    CheckBalance {
+        /// Input KoiLang file (defaults to stdin)
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+
+        /// Opening and closing command names (defaults to begin=end)
+        #[arg(long = "pair", value_name = "OPEN=CLOSE", value_parser = parse_pair)]
+        pairs: Vec<(String, String)>,
+    },
+    /// Print the JSON Schema of the to-json output
+    Schema {
+        /// Output JSON file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+/// Layout options for generated KoiLang
+#[derive(Args)]
+struct StyleArgs {
+    /// Number of spaces per indentation level
+    #[arg(long, value_name = "N", conflicts_with = "tabs")]
+    indent: Option<usize>,
+
+    /// Indent with tabs instead of spaces
+    #[arg(long)]
+    tabs: bool,
+
+    /// Use compact formatting (no indentation or optional whitespace)
+    #[arg(long)]
+    compact: bool,
+}
+
+impl StyleArgs {
+    fn writer_config(&self) -> WriterConfig {
+        let mut config = WriterConfig::default();
+        let options = &mut config.global_options;
+        if let Some(indent) = self.indent {
+            options.indent = indent;
+        }
+        options.use_tabs = self.tabs;
+        options.compact = self.compact;
+        config
+    }
+}
+
+/// How command names are matched against a filter pattern
+#[derive(Args)]
+struct MatchArgs {
+    /// Read the pattern as a regular expression instead of a glob
+    #[arg(long)]
+    regex: bool,
+
+    /// Keep the commands that don't match instead
+    #[arg(long)]
+    invert: bool,
+}
+
+/// Matches command names against a glob or regular expression
+struct NameFilter {
+    regex: Regex,
+    invert: bool,
+}
+
+impl NameFilter {
+    /// Build a filter from a pattern
+    ///
+    /// Globs must match the whole name, with `*` matching any run of
+    /// characters and `?` any single character. Regular expressions match
+    /// anywhere in the name unless anchored.
+    fn new(pattern: &str, args: &MatchArgs) -> Result<Self> {
+        let source = if args.regex {
+            pattern.to_string()
+        } else {
+            let mut source = String::from("^");
+            for c in pattern.chars() {
+                match c {
+                    '*' => source.push_str(".*"),
+                    '?' => source.push('.'),
+                    c => source.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+                }
+            }
+            source.push('$');
+            source
+        };
+        let regex = Regex::new(&source)
+            .with_context(|| format!("Invalid filter pattern: {:?}", pattern))?;
+        Ok(Self {
+            regex,
+            invert: args.invert,
+        })
+    }
+
+    /// Whether a command is kept by the filter
+    fn keeps(&self, command: &Command) -> bool {
+        self.regex.is_match(command.name()) != self.invert
+    }
+}
+
+/// Tracks the nesting level of commands from the indentation of their source lines
+#[derive(Default)]
+struct IndentTracker {
+    widths: Vec<usize>,
+}
+
+impl IndentTracker {
+    /// Get the nesting level of a line with the given leading whitespace
+    fn level(&mut self, leading: &str) -> usize {
+        let width = leading.chars().map(|c| if c == '\t' { 8 } else { 1 }).sum();
+        while self.widths.last().is_some_and(|&top| width < top) {
+            self.widths.pop();
+        }
+        if width > 0 && self.widths.last() != Some(&width) {
+            self.widths.push(width);
+        }
+        self.widths.len()
+    }
+}
+
+/// Parse an `OPEN=CLOSE` command pair
+fn parse_pair(pair: &str) -> Result<(String, String), String> {
+    match pair.split_once('=') {
+        Some((open, close)) if !open.is_empty() && !close.is_empty() => {
+            Ok((open.to_string(), close.to_string()))
+        }
+        _ => Err(format!("expected OPEN=CLOSE, got {:?}", pair)),
+    }
+}
+
+/// Open the input file, or stdin if no path is given
+fn open_input(input: Option<PathBuf>) -> Result<Box<dyn TextInputSource>> {
+    Ok(match input {
+        Some(path) => Box::new(
+            FileInputSource::new(&path)
+                .with_context(|| format!("Failed to open input file: {:?}", path))?,
+        ),
+        None => Box::new(BufReadWrapper(std::io::stdin().lock())),
+    })
+}
+
+/// Parse all commands from a KoiLang file
+fn read_commands(path: PathBuf) -> Result<Vec<Command>> {
+    Parser::new(open_input(Some(path))?, ParserConfig::default())
+        .into_commands()
+        .map_err(parse_error)
+}
+
+/// Write the output to a file, or stdout if no path is given
+fn write_output(output: Option<PathBuf>, data: &[u8]) -> Result<()> {
+    if let Some(path) = output {
+        File::create(&path)
+            .with_context(|| format!("Failed to create output file: {:?}", path))?
+            .write_all(data)?;
+    } else {
+        std::io::stdout().write_all(data)?;
+    }
+    Ok(())
+}
+
+/// Convert a parse error for reporting, colored when stderr is a terminal
+fn parse_error(e: Box<ParseError>) -> anyhow::Error {
+    if std::io::stderr().is_terminal() {
+        anyhow::anyhow!("Parse error: {}", e.display_colored())
+    } else {
+        anyhow::anyhow!("Parse error: {}", e)
+    }
+}
+
+/// Convert a conversion error for reporting
+///
+/// JSON errors are reported with `json_context`, e.g. "Failed to parse JSON".
+fn convert_error(e: ConvertError, json_context: &'static str) -> anyhow::Error {
+    match e {
+        ConvertError::Parse(e) => parse_error(e),
+        ConvertError::Json(e) => anyhow::Error::new(e).context(json_context),
+        e => e.into(),
+    }
 }
 
 fn main() -> Result<()> {
@@ -50,76 +283,138 @@ fn main() -> Result<()> {
             input,
             output,
             pretty,
+            filter,
+            match_args,
         } => {
-            let config = ParserConfig::default();
+            let filter = filter
+                .map(|pattern| NameFilter::new(&pattern, &match_args))
+                .transpose()?;
+            let mut parser = Parser::new(open_input(input)?, ParserConfig::default());
             let mut commands = Vec::new();
-
-            if let Some(path) = input {
-                let source = FileInputSource::new(&path)
-                    .with_context(|| format!("Failed to open input file: {:?}", path))?;
-                let mut parser = Parser::new(source, config);
-                while let Some(command) = parser
-                    .next_command()
-                    .map_err(|e| anyhow::anyhow!("Parse error: {}", e))?
-                {
-                    commands.push(command);
-                }
-            } else {
-                let stdin = std::io::stdin();
-                let source = BufReadWrapper(stdin.lock());
-                let mut parser = Parser::new(source, config);
-                while let Some(command) = parser
-                    .next_command()
-                    .map_err(|e| anyhow::anyhow!("Parse error: {}", e))?
-                {
+            while let Some(command) = parser.next_command().map_err(parse_error)? {
+                if filter.as_ref().is_none_or(|filter| filter.keeps(&command)) {
                     commands.push(command);
                 }
             }
 
-            let json = if pretty {
-                serde_json::to_string_pretty(&commands)?
-            } else {
-                serde_json::to_string(&commands)?
+            let config = ConvertConfig {
+                pretty,
+                ..Default::default()
             };
-
-            if let Some(path) = output {
-                File::create(&path)
-                    .with_context(|| format!("Failed to create output file: {:?}", path))?
-                    .write_all(json.as_bytes())?;
-            } else {
-                std::io::stdout().write_all(json.as_bytes())?;
-                println!(); // Add newline if stdout
+            let mut buffer = Vec::new();
+            write_commands(&commands, &mut buffer, Format::Json, &config)
+                .map_err(|e| convert_error(e, "Failed to write JSON"))?;
+            if output.is_none() {
+                buffer.push(b'\n'); // Add newline if stdout
             }
+            write_output(output, &buffer)?;
         }
-        Commands::FromJson { input, output } => {
-            let commands: Vec<Command> = if let Some(path) = input {
+        Commands::FromJson { input, output, style } => {
+            let reader: Box<dyn BufRead> = if let Some(path) = input {
                 let file = File::open(&path)
                     .with_context(|| format!("Failed to open input file: {:?}", path))?;
-                serde_json::from_reader(BufReader::new(file))
-                    .with_context(|| "Failed to parse JSON")?
+                Box::new(BufReader::new(file))
             } else {
-                serde_json::from_reader(std::io::stdin().lock())
-                    .with_context(|| "Failed to parse JSON")?
+                Box::new(std::io::stdin().lock())
             };
 
-            let config = WriterConfig::default();
+            let config = ConvertConfig {
+                writer: style.writer_config(),
+                ..Default::default()
+            };
+            let mut buffer = Vec::new();
+            convert(reader, Format::Json, &mut buffer, Format::Koi, &config)
+                .map_err(|e| convert_error(e, "Failed to parse JSON"))?;
+            write_output(output, &buffer)?;
+        }
+        Commands::Format {
+            input,
+            output,
+            style,
+        } => {
+            let config = ParserConfig::default().with_preserve_empty_lines(true);
+            let mut parser = Parser::new(open_input(input)?, config);
             let mut buffer = Vec::new();
-            let mut writer = Writer::new(&mut buffer, config);
+            let mut writer = Writer::new(&mut buffer, style.writer_config());
+            let mut indents = IndentTracker::default();
 
-            for cmd in commands {
+            while let Some((cmd, source)) = parser
+                .next_command_with_source()
+                .map_err(parse_error)?
+            {
+                // Keep blank lines, but without indentation
+                let level = if source.text.trim().is_empty() {
+                    0
+                } else {
+                    let content = source.text.trim_start();
+                    indents.level(&source.text[..source.text.len() - content.len()])
+                };
+                while writer.get_indent() < level {
+                    writer.inc_indent();
+                }
+                while writer.get_indent() > level {
+                    writer.dec_indent();
+                }
                 writer
                     .write_command(&cmd)
                     .context("Failed to write command")?;
             }
 
-            if let Some(path) = output {
-                File::create(&path)
-                    .with_context(|| format!("Failed to create output file: {:?}", path))?
-                    .write_all(&buffer)?;
-            } else {
-                std::io::stdout().write_all(&buffer)?;
+            write_output(output, &buffer)?;
+        }
+        Commands::Filter {
+            pattern,
+            input,
+            output,
+            match_args,
+        } => {
+            let filter = NameFilter::new(&pattern, &match_args)?;
+            let mut parser = Parser::new(open_input(input)?, ParserConfig::default());
+            let mut buffer = Vec::new();
+            while let Some((cmd, source)) = parser
+                .next_command_with_source()
+                .map_err(parse_error)?
+            {
+                if filter.keeps(&cmd) {
+                    let line = source.text.trim_end_matches(['\r', '\n']);
+                    buffer.extend_from_slice(line.as_bytes());
+                    buffer.push(b'\n');
+                }
+            }
+            write_output(output, &buffer)?;
+        }
+        Commands::Diff { old, new } => {
+            let old = read_commands(old)?;
+            let new = read_commands(new)?;
+            for change in koicore::diff(&old, &new) {
+                println!("{}", change);
             }
         }
+        Commands::CheckBalance { input, mut pairs } => {
+            if pairs.is_empty() {
+                pairs.push(("begin".to_string(), "end".to_string()));
+            }
+            let mut validator = BalanceValidator::new(pairs);
+            let mut parser = Parser::new(open_input(input)?, ParserConfig::default());
+            while let Some((cmd, source)) = parser
+                .next_command_with_source()
+                .map_err(parse_error)?
+            {
+                validator.feed(&cmd, source.lineno);
+            }
+            let errors = validator.finish();
+            for error in &errors {
+                println!("{}", error);
+            }
+            if !errors.is_empty() {
+                anyhow::bail!("{} unbalanced command(s)", errors.len());
+            }
+        }
+        Commands::Schema { output } => {
+            let mut json = serde_json::to_string_pretty(&koicore::json_schema())?;
+            json.push('\n');
+            write_output(output, json.as_bytes())?;
+        }
     }
 
     Ok(())
@@ -0,0 +1,154 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Run koicli with the given arguments, feeding `input` on stdin
+fn run(args: &[&str], input: &str) -> std::process::Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_koicli"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to start koicli");
+    // koicli may exit before reading its input, e.g. on invalid arguments
+    let _ = child.stdin.take().unwrap().write_all(input.as_bytes());
+    child.wait_with_output().expect("Failed to run koicli")
+}
+
+const NESTED: &str = "\
+#space hello
+    #file \"Bob.txt\"
+    Hello   Bob.
+      #line 1
+
+#endspace
+";
+
+#[test]
+fn test_format_indentation() {
+    let output = run(&["format", "--tabs"], NESTED);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "#space hello\n\t#file \"Bob.txt\"\n\tHello   Bob.\n\t\t#line 1\n\n#endspace\n"
+    );
+
+    let output = run(&["format", "--indent", "2"], NESTED);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "#space hello\n  #file \"Bob.txt\"\n  Hello   Bob.\n    #line 1\n\n#endspace\n"
+    );
+}
+
+#[test]
+fn test_format_compact() {
+    let output = run(&["format", "--compact"], "#pos p(x: 1, y: 2)\n  #child\n");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "#pos p(x:1,y:2)\n#child\n");
+}
+
+#[test]
+fn test_tabs_conflicts_with_indent() {
+    let output = run(&["format", "--tabs", "--indent", "2"], NESTED);
+    assert!(!output.status.success());
+
+    let output = run(&["from-json", "--tabs", "--indent", "2"], "[]");
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_diff() {
+    let dir = std::env::temp_dir();
+    let old = dir.join("koicli_test_diff_old.ktxt");
+    let new = dir.join("koicli_test_diff_new.ktxt");
+    std::fs::write(&old, "#begin\n#draw Line 2\n#end\n").unwrap();
+    std::fs::write(&new, "#begin\n#draw Line 3\n#fill red\n#end\n").unwrap();
+
+    let output = run(&["diff", old.to_str().unwrap(), new.to_str().unwrap()], "");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "~ [1 -> 1] draw\n    param 1: 2 -> 3\n+ [2] fill red\n"
+    );
+
+    std::fs::remove_file(old).unwrap();
+    std::fs::remove_file(new).unwrap();
+}
+
+#[test]
+fn test_schema_validates_to_json() {
+    let output = run(&["schema"], "");
+    assert!(output.status.success());
+    let schema: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let validator = jsonschema::validator_for(&schema).unwrap();
+
+    let output = run(
+        &["to-json"],
+        "#draw Line 2 pos(x: 0, y: 1.5) tags(a, \"b c\") visible(true)\nHello\n## note\n#end\n",
+    );
+    assert!(output.status.success());
+    let document: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(validator.is_valid(&document));
+
+    let invalid = serde_json::json!([{ "name": "draw", "params": [{ "a": 1, "b": 2 }] }]);
+    assert!(!validator.is_valid(&invalid));
+}
+
+#[test]
+fn test_check_balance() {
+    let output = run(&["check-balance"], "#begin\n  #draw\n#end\n");
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    let input = "#scene\n#begin\n#end_scene\n";
+    let output = run(&["check-balance", "--pair", "begin=end", "--pair", "scene=end_scene"], input);
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "line 3: expected #end to close #begin from line 2, found #end_scene\n"
+    );
+
+    let output = run(&["check-balance", "--pair", "begin"], "");
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_to_json_non_finite_float() {
+    // 1e999 overflows to infinity, which JSON cannot represent
+    let output = run(&["to-json"], "#scale 1e999\n");
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Failed to write JSON"), "{}", stderr);
+    assert!(stderr.contains("non-finite float inf"), "{}", stderr);
+}
+
+#[test]
+fn test_filter() {
+    let input = "#draw Line  2\n#move 1 2\nSome text\n#drawText \"hi\"\n#draw Circle\n";
+    let output = run(&["filter", "draw"], input);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "#draw Line  2\n#draw Circle\n"
+    );
+
+    let output = run(&["filter", "draw*", "--invert"], input);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "#move 1 2\nSome text\n");
+
+    let output = run(&["filter", "--regex", "^(move|drawText)$"], input);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "#move 1 2\n#drawText \"hi\"\n");
+
+    let output = run(&["to-json", "--filter", "draw"], input);
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json, serde_json::json!([
+        {"name": "draw", "params": ["Line", 2]},
+        {"name": "draw", "params": ["Circle"]},
+    ]));
+
+    let output = run(&["filter", "--regex", "("], input);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Invalid filter pattern"));
+}
@@ -10,6 +10,24 @@
 namespace koicore {
 #endif  // __cplusplus
 
+/**
+ * How integer literals that don't fit in a 64-bit signed integer are handled
+ */
+typedef enum KoiIntOverflow {
+  /**
+   * Reject the value with a parse error
+   */
+  Error = 0,
+  /**
+   * Clamp the value to the minimum or maximum 64-bit integer
+   */
+  Saturate = 1,
+  /**
+   * Keep the low 64 bits in two's complement
+   */
+  Wrap = 2,
+} KoiIntOverflow;
+
 /**
  * Strategy for handling encoding errors when reading files
  *
@@ -90,6 +108,17 @@ typedef struct KoiCompositeList {
 
 } KoiCompositeList;
 
+/**
+ * Opaque handle for composite set parameter
+ *
+ * This structure represents a set parameter in a KoiLang command, written
+ * with braces like `tags{1, 2}`. Sets hold distinct values in source order.
+ * Sets are read-only through this interface.
+ */
+typedef struct KoiCompositeSet {
+
+} KoiCompositeSet;
+
 /**
  * Opaque handle for composite single parameter
  *
@@ -136,6 +165,89 @@ typedef struct KoiParserConfig {
    * If set to false, empty lines will be skipped.
    */
   bool preserve_empty_lines;
+  /**
+   * Whether to accept set literals in composite parameters
+   *
+   * If set to true, `name{v1, v2}` is parsed as a set with duplicates removed.
+   * If set to false, braces are a syntax error.
+   */
+  bool enable_set_literals;
+  /**
+   * Additional separator between commands on the same line
+   *
+   * Can be NULL to separate commands by newlines only.
+   */
+  const char *command_separator;
+  /**
+   * Whether to accept percent-encoded `u"..."` string parameters
+   *
+   * If set to true, `u"a%20b"` is parsed as the string `a b`.
+   */
+  bool enable_percent_strings;
+  /**
+   * How to handle integer literals outside the 64-bit signed range
+   */
+  enum KoiIntOverflow int_overflow;
+  /**
+   * Whether indented lines continue the parameters of the preceding command
+   *
+   * If set to true, lines without a # prefix that are indented further than the
+   * preceding command line are parsed as more parameters of that command.
+   */
+  bool indent_continuation;
+  /**
+   * Whether annotation content keeps its leading # characters
+   *
+   * If set to true, `### note` is stored as `### note` instead of `note`.
+   */
+  bool annotation_keep_hashes;
+  /**
+   * Opening and closing characters of list composites, as Unicode code points
+   *
+   * Invalid code points fall back to parentheses.
+   */
+  uint32_t list_delimiters[2];
+  /**
+   * Opening and closing characters of dictionary composites, as Unicode code points
+   *
+   * Invalid code points fall back to parentheses.
+   */
+  uint32_t dict_delimiters[2];
+  /**
+   * Whether numbers are kept as strings
+   *
+   * If set to true, `007` is parsed as the string `007` instead of the integer 7.
+   */
+  bool numbers_as_strings;
+  /**
+   * Whether text lines ending in `\` continue on the next line
+   *
+   * If set to true, the lines are joined into one text command without the backslash.
+   */
+  bool text_line_continuation;
+  /**
+   * Character that starts command and annotation lines, as a Unicode code point
+   *
+   * Invalid code points fall back to `#`.
+   */
+  uint32_t prefix_char;
+  /**
+   * Marker starting a trailing comment on command lines, e.g. `//`
+   *
+   * Can be NULL for command lines without comments.
+   */
+  const char *comment_marker;
+  /**
+   * Whether to record the indentation of command lines
+   *
+   * If set to true, the leading whitespace of each command line is kept
+   * with the command and written back by format-preserving writes.
+   */
+  bool record_command_indent;
+  /**
+   * Whether to record the source line and column of each command
+   */
+  bool record_spans;
 } KoiParserConfig;
 
 /**
@@ -228,6 +340,20 @@ typedef struct KoiFormatterOptions {
   bool newline_before_param;
   bool newline_after_param;
   bool should_override;
+  bool type_annotations;
+  bool percent_encode_strings;
+  /**
+   * Space after commas in composite values: 1 = yes, 0 = no, -1 = unless compact
+   */
+  int8_t composite_space_after_comma;
+  /**
+   * Space after colons in dictionary entries: 1 = yes, 0 = no, -1 = unless compact
+   */
+  int8_t composite_space_after_colon;
+  bool quote_dict_string_values;
+  bool canonicalize_composites;
+  bool force_single_line;
+  bool blank_after_composite_commands;
 } KoiFormatterOptions;
 
 /**
@@ -255,6 +381,41 @@ typedef struct KoiWriterConfig {
    * Can be NULL if no command options.
    */
   const struct KoiCommandOption *command_options;
+  /**
+   * Whether every command is terminated by the command separator.
+   * If false, separators are only written between commands.
+   */
+  bool trailing_newline;
+  /**
+   * Separator written between commands.
+   * Can be NULL to separate commands with newlines.
+   */
+  const char *command_separator;
+  /**
+   * Whether to write the # prefix before commands.
+   * If false, commands are written bare; text and annotations are unchanged.
+   */
+  bool emit_prefix;
+  /**
+   * Opening and closing characters of list composites, as Unicode code points.
+   * Invalid code points fall back to parentheses.
+   */
+  uint32_t list_delimiters[2];
+  /**
+   * Opening and closing characters of dictionary composites, as Unicode code points.
+   * Invalid code points fall back to parentheses.
+   */
+  uint32_t dict_delimiters[2];
+  /**
+   * Maximum width of output lines; wider commands continue on the next line.
+   * 0 for no limit.
+   */
+  uintptr_t max_width;
+  /**
+   * Character written before commands and annotations, as a Unicode code point.
+   * Invalid code points fall back to #.
+   */
+  uint32_t prefix_char;
 } KoiWriterConfig;
 
 /**
@@ -360,6 +521,22 @@ struct KoiCommand *KoiCommand_NewNumber(int64_t value);
  */
 void KoiCommand_Del(struct KoiCommand *command);
 
+/**
+ * Free an array of command pointers
+ *
+ * Frees the array itself, as returned by KoiParser_ParseAll. The commands in
+ * it are not freed; free each of them with KoiCommand_Del first.
+ *
+ * # Arguments
+ * * `commands` - Array pointer to free
+ * * `count` - Number of commands in the array
+ *
+ * # Safety
+ * The array must have been returned by KoiParser_ParseAll together with
+ * `count`, and must not be used after this call.
+ */
+void KoiCommandArray_Del(struct KoiCommand **commands, uintptr_t count);
+
 /**
  * Set command name
  *
@@ -1501,6 +1678,191 @@ int32_t KoiCommand_AddBoolParameter(struct KoiCommand *command, int32_t value);
  */
 int32_t KoiCommand_SetBoolParameter(struct KoiCommand *command, uintptr_t index, int32_t value);
 
+/**
+ * Get composite set parameter from command
+ *
+ * This function retrieves a set parameter from a command at the specified index.
+ * The parameter must be of set type, otherwise NULL is returned.
+ *
+ * # Ownership and Lifetime
+ *
+ * The returned pointer is a borrowed reference to data owned by the command.
+ * It must NOT be freed. The pointer is only valid as long as the command
+ * object exists and is not modified or destroyed.
+ *
+ * # Arguments
+ *
+ * * `command` - Pointer to the command object
+ * * `index` - Zero-based index of the parameter to retrieve
+ *
+ * # Returns
+ *
+ * Pointer to the composite set parameter, or NULL if:
+ * - command is NULL
+ * - index is out of bounds
+ * - the parameter at the specified index is not a set
+ *
+ * # Safety
+ *
+ * The `command` pointer must be either NULL or point to a valid KoiCommand object.
+ * The returned pointer becomes invalid if the command is destroyed or modified.
+ */
+struct KoiCompositeSet *KoiCommand_GetCompositeSet(struct KoiCommand *command, uintptr_t index);
+
+/**
+ * Get composite set parameter length
+ *
+ * # Arguments
+ *
+ * * `set` - Pointer to the composite set parameter
+ *
+ * # Returns
+ *
+ * Number of values in the set, or 0 if the set pointer is NULL or invalid.
+ *
+ * # Safety
+ *
+ * The `set` pointer must be either NULL or point to a valid KoiCompositeSet object
+ * obtained from KoiCommand_GetCompositeSet.
+ */
+uintptr_t KoiCompositeSet_GetLength(struct KoiCompositeSet *set);
+
+/**
+ * Get value type from composite set by index
+ *
+ * # Arguments
+ *
+ * * `set` - Pointer to the composite set parameter
+ * * `index` - Zero-based index of the value to query
+ *
+ * # Returns
+ *
+ * The type of the value as a KoiParamType enum value, or KoiParamType::Invalid
+ * if the set pointer is NULL, invalid, or the index is out of bounds.
+ *
+ * # Safety
+ *
+ * The `set` pointer must be either NULL or point to a valid KoiCompositeSet object.
+ */
+int32_t KoiCompositeSet_GetValueType(struct KoiCompositeSet *set, uintptr_t index);
+
+/**
+ * Get integer value from composite set by index
+ *
+ * # Arguments
+ *
+ * * `set` - Pointer to the composite set parameter
+ * * `index` - Zero-based index of the value to retrieve
+ * * `out_value` - Pointer to store the retrieved integer value
+ *
+ * # Returns
+ *
+ * 0 on success, or a non-zero error code on failure:
+ * - -1: set pointer is NULL or out_value is NULL
+ * - -2: index is out of bounds
+ * - -3: value at the specified index is not an integer
+ * - -4: set pointer is invalid
+ *
+ * # Safety
+ *
+ * The `set` pointer must be either NULL or point to a valid KoiCompositeSet object.
+ * The `out_value` pointer must point to a valid i64 variable.
+ */
+int32_t KoiCompositeSet_GetIntValue(struct KoiCompositeSet *set,
+                                    uintptr_t index,
+                                    int64_t *out_value);
+
+/**
+ * Get float value from composite set by index
+ *
+ * # Arguments
+ *
+ * * `set` - Pointer to the composite set parameter
+ * * `index` - Zero-based index of the value to retrieve
+ * * `out_value` - Pointer to store the retrieved float value
+ *
+ * # Returns
+ *
+ * 0 on success, or a non-zero error code on failure:
+ * - -1: set pointer is NULL or out_value is NULL
+ * - -2: index is out of bounds
+ * - -3: value at the specified index is not a float
+ * - -4: set pointer is invalid
+ *
+ * # Safety
+ *
+ * The `set` pointer must be either NULL or point to a valid KoiCompositeSet object.
+ * The `out_value` pointer must point to a valid f64 variable.
+ */
+int32_t KoiCompositeSet_GetFloatValue(struct KoiCompositeSet *set,
+                                      uintptr_t index,
+                                      double *out_value);
+
+/**
+ * Get boolean value from composite set by index
+ *
+ * # Arguments
+ * * `set` - Pointer to the composite set parameter
+ * * `index` - Zero-based index of the value to retrieve
+ * * `out_value` - Pointer to store boolean value (1 for true, 0 for false)
+ *
+ * # Returns
+ * 0 on success, non-zero on error, with the same codes as
+ * KoiCompositeSet_GetIntValue
+ *
+ * # Safety
+ *
+ * The `set` pointer must be either NULL or point to a valid KoiCompositeSet object.
+ * The `out_value` pointer must point to a valid i32 variable.
+ */
+int32_t KoiCompositeSet_GetBoolValue(struct KoiCompositeSet *set,
+                                     uintptr_t index,
+                                     int32_t *out_value);
+
+/**
+ * Get string value from composite set by index
+ *
+ * # Arguments
+ *
+ * * `set` - Pointer to the composite set parameter
+ * * `index` - Zero-based index of the value to retrieve
+ * * `buffer` - Buffer to store the retrieved string value
+ * * `buffer_size` - Size of the buffer in bytes
+ *
+ * # Returns
+ *
+ * The number of bytes required for the string including the null terminator.
+ * If the buffer is too small, no data is written and the required size is returned.
+ * Returns 0 on error or if the value at the specified index is not a string.
+ *
+ * # Safety
+ *
+ * The `set` pointer must be either NULL or point to a valid KoiCompositeSet object.
+ * If `buffer` is not NULL, it must point to a valid memory region of at least `buffer_size` bytes.
+ */
+uintptr_t KoiCompositeSet_GetStringValue(struct KoiCompositeSet *set,
+                                         uintptr_t index,
+                                         char *buffer,
+                                         uintptr_t buffer_size);
+
+/**
+ * Get string value length from composite set by index
+ *
+ * # Arguments
+ *
+ * * `set` - Pointer to the composite set parameter
+ * * `index` - Zero-based index of the value to query
+ *
+ * # Returns
+ *
+ * Required buffer size (including null terminator), or 0 on error or type mismatch.
+ *
+ * # Safety
+ *
+ * The `set` pointer must be either NULL or point to a valid KoiCompositeSet object.
+ */
+uintptr_t KoiCompositeSet_GetStringValueLen(struct KoiCompositeSet *set, uintptr_t index);
+
 /**
  * Create a new composite single parameter
  *
@@ -1636,6 +1998,35 @@ void KoiParser_Del(struct KoiParser *parser);
  */
 struct KoiCommand *KoiParser_NextCommand(struct KoiParser *parser);
 
+/**
+ * Parse all remaining commands from the parser
+ *
+ * Reads commands until the end of the input and returns them as an array of
+ * command pointers. On a parsing error, no commands are returned and the
+ * error can be retrieved using KoiParser_Error.
+ *
+ * # Arguments
+ * * `parser` - Parser pointer
+ * * `out_commands` - Receives the array of command pointers
+ * * `out_count` - Receives the number of commands in the array
+ *
+ * # Returns
+ * 0 on success, or a negative value on error:
+ * - -1 if any pointer is null
+ * - -2 if a parsing error occurred
+ *
+ * On error, `*out_commands` is set to null and `*out_count` to 0.
+ *
+ * # Safety
+ * The parser pointer must be a valid KoiParser created with KoiParser_New.
+ * `out_commands` and `out_count` must be valid for writes. Each returned
+ * command is owned by the caller and must be freed with KoiCommand_Del, and
+ * the array itself with KoiCommandArray_Del.
+ */
+int32_t KoiParser_ParseAll(struct KoiParser *parser,
+                           struct KoiCommand ***out_commands,
+                           uintptr_t *out_count);
+
 /**
  * Get the last parsing error from the parser
  *
@@ -1659,6 +2050,41 @@ struct KoiCommand *KoiParser_NextCommand(struct KoiParser *parser);
  */
 struct KoiParserError *KoiParser_Error(struct KoiParser *parser);
 
+/**
+ * Get the line number the parser is at
+ *
+ * Delegates to `Parser::current_line`: the number of the next line to be
+ * read, starting at 1. Useful for showing progress.
+ *
+ * # Arguments
+ * * `parser` - Parser pointer
+ *
+ * # Returns
+ * The current line number, or 0 if parser is null
+ *
+ * # Safety
+ * The parser pointer must be a valid KoiParser created with KoiParser_New.
+ */
+uintptr_t KoiParser_CurrentLine(struct KoiParser *parser);
+
+/**
+ * Check whether the parser has reached the end of its input
+ *
+ * Becomes true once KoiParser_NextCommand has returned null at the end of the
+ * input, or KoiParser_ParseAll has read all commands. A null return caused by
+ * a parsing error does not set it, so this tells the two apart.
+ *
+ * # Arguments
+ * * `parser` - Parser pointer
+ *
+ * # Returns
+ * 1 if the end of input was reached, 0 if not, or -1 if parser is null
+ *
+ * # Safety
+ * The parser pointer must be a valid KoiParser created with KoiParser_New.
+ */
+int32_t KoiParser_IsEof(struct KoiParser *parser);
+
 /**
  * Deletes a KoiParserError object and frees its memory
  *
@@ -1885,6 +2311,35 @@ struct KoiInputSource *KoiInputSource_FromFileAndEncoding(const char *path,
                                                           const char *encoding,
                                                           enum KoiFileInputEncodingStrategy encoding_strategy);
 
+/**
+ * Creates an input source that reads several input sources one after another
+ *
+ * The sources form one logical stream. Errors name the source the failing line
+ * came from.
+ *
+ * # Arguments
+ *
+ * * `sources` - Pointer to an array of `count` KoiInputSource pointers
+ * * `count` - Number of sources in the array
+ * * `per_source_numbering` - Whether line numbers restart at 1 for each source
+ *   (true) or continue across sources (false)
+ *
+ * # Returns
+ *
+ * Pointer to the created KoiInputSource, or NULL if sources is NULL or any
+ * source pointer is NULL. Nothing is taken over on failure.
+ *
+ * # Safety
+ *
+ * `sources` must point to `count` valid KoiInputSource pointers created by the
+ * KoiInputSource_From* functions. On success, ownership of every source moves
+ * into the returned source: they must not be used or passed to
+ * KoiInputSource_Del afterwards. The array itself is not freed.
+ */
+struct KoiInputSource *KoiInputSource_Chain(struct KoiInputSource *const *sources,
+                                            uintptr_t count,
+                                            bool per_source_numbering);
+
 /**
  * Deletes a KoiInputSource object and frees its memory
  *
@@ -1907,6 +2362,19 @@ void KoiInputSource_Del(struct KoiInputSource *input);
  * - command_threshold: 1 (lines starting with # are commands)
  * - skip_annotations: false (annotation lines are included in output)
  * - convert_number_command: true (numeric commands are converted to special commands)
+ * - enable_set_literals: false (brace set literals are rejected)
+ * - command_separator: NULL (commands are separated by newlines only)
+ * - enable_percent_strings: false (`u"..."` strings are rejected)
+ * - int_overflow: Error (out-of-range integers are a parse error)
+ * - indent_continuation: false (indented lines are text)
+ * - annotation_keep_hashes: false (annotation content is stored without #)
+ * - list_delimiters, dict_delimiters: `(` and `)`
+ * - numbers_as_strings: false (number literals are parsed as numbers)
+ * - text_line_continuation: false (a trailing `\` is kept in text lines)
+ * - prefix_char: `#`
+ * - comment_marker: NULL (command lines have no comments)
+ * - record_command_indent: false (the indentation of command lines is dropped)
+ * - record_spans: false (commands have no source position)
  *
  * # Arguments
  * * `config` - Pointer to the KoiParserConfig structure to initialize
@@ -1962,9 +2430,29 @@ struct KoiWriter *KoiWriter_NewFromStringOutput(struct KoiStringOutput *output,
  *
  * * `writer` must be a valid pointer returned by one of the `KoiWriter_New*` functions.
  * * After calling this function, `writer` is invalid and must not be used.
+ *
+ * The output is flushed first; call `KoiWriter_Flush` beforehand to detect
+ * flush errors, which are ignored here.
  */
 void KoiWriter_Del(struct KoiWriter *writer);
 
+/**
+ * Flush the writer's output.
+ *
+ * Output written to a file is buffered until flushed or the writer is deleted.
+ *
+ * # Safety
+ *
+ * * `writer` must be a valid pointer to a `KoiWriter`.
+ *
+ * # Returns
+ *
+ * * 0 on success
+ * * -1 if writer is null
+ * * -2 if flushing fails
+ */
+int32_t KoiWriter_Flush(struct KoiWriter *writer);
+
 /**
  * Write a command.
  *
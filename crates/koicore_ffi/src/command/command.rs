@@ -162,6 +162,25 @@ pub unsafe extern "C" fn KoiCommand_Del(command: *mut KoiCommand) {
     }
 }
 
+/// Free an array of command pointers
+///
+/// Frees the array itself, as returned by KoiParser_ParseAll. The commands in
+/// it are not freed; free each of them with KoiCommand_Del first.
+///
+/// # Arguments
+/// * `commands` - Array pointer to free
+/// * `count` - Number of commands in the array
+///
+/// # Safety
+/// The array must have been returned by KoiParser_ParseAll together with
+/// `count`, and must not be used after this call.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn KoiCommandArray_Del(commands: *mut *mut KoiCommand, count: usize) {
+    if !commands.is_null() {
+        drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(commands, count)) });
+    }
+}
+
 /// Set command name
 ///
 /// # Arguments
@@ -7,7 +7,7 @@ use std::{
     ptr, slice,
 };
 
-use crate::command::param::KoiParamType;
+use crate::command::param::{KoiParamType, basic_param_type};
 
 use super::command::KoiCommand;
 
@@ -500,12 +500,7 @@ pub unsafe extern "C" fn KoiCompositeDict_GetValueTypeByIndex(
             if index >= entries.len() {
                 KoiParamType::Invalid as i32
             } else {
-                match &entries[index].1 {
-                    Value::Int(_) => KoiParamType::BasicInt as i32,
-                    Value::Float(_) => KoiParamType::BasicFloat as i32,
-                    Value::String(_) => KoiParamType::BasicString as i32,
-                    Value::Bool(_) => KoiParamType::BasicBool as i32,
-                }
+                basic_param_type(&entries[index].1) as i32
             }
         }
         _ => KoiParamType::Invalid as i32,
@@ -539,12 +534,7 @@ pub unsafe extern "C" fn KoiCompositeDict_GetValueType(
     match param {
         Parameter::Composite(_, CompositeValue::Dict(entries)) => {
             if let Some((_, value)) = entries.iter().find(|(k, _)| k == key_str) {
-                match value {
-                    Value::Int(_) => KoiParamType::BasicInt as i32,
-                    Value::Float(_) => KoiParamType::BasicFloat as i32,
-                    Value::String(_) => KoiParamType::BasicString as i32,
-                    Value::Bool(_) => KoiParamType::BasicBool as i32,
-                }
+                basic_param_type(value) as i32
             } else {
                 KoiParamType::Invalid as i32
             }
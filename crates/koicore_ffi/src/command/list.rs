@@ -7,7 +7,7 @@ use std::{
     ptr, slice,
 };
 
-use crate::command::param::KoiParamType;
+use crate::command::param::{KoiParamType, basic_param_type};
 
 use super::command::KoiCommand;
 
@@ -135,12 +135,7 @@ pub unsafe extern "C" fn KoiCompositeList_GetValueType(
             if index >= values.len() {
                 KoiParamType::Invalid as i32
             } else {
-                match &values[index] {
-                    Value::Int(_) => KoiParamType::BasicInt as i32,
-                    Value::Float(_) => KoiParamType::BasicFloat as i32,
-                    Value::String(_) => KoiParamType::BasicString as i32,
-                    Value::Bool(_) => KoiParamType::BasicBool as i32,
-                }
+                basic_param_type(&values[index]) as i32
             }
         }
         _ => KoiParamType::Invalid as i32,
@@ -9,6 +9,7 @@
 //! - [`KoiCommand`]: Represents a parsed KoiLang command with name and parameters
 //! - [`KoiCompositeList`]: Represents a list data structure in KoiLang
 //! - [`KoiCompositeDict`]: Represents a dictionary data structure in KoiLang
+//! - [`KoiCompositeSet`]: Represents a set data structure in KoiLang (read-only)
 //!
 //! ## Command Structure
 //!
@@ -34,9 +35,11 @@ pub(crate) mod command;
 pub(crate) mod dict;
 pub(crate) mod list;
 pub(crate) mod param;
+pub(crate) mod set;
 pub(crate) mod single;
 
 pub use command::KoiCommand;
 pub use dict::KoiCompositeDict;
 pub use list::KoiCompositeList;
+pub use set::KoiCompositeSet;
 pub use single::KoiCompositeSingle;
@@ -8,7 +8,7 @@ use super::command::KoiCommand;
 /// Unified parameter type enumeration
 ///
 /// This enumeration represents all possible parameter types in KoiLang commands.
-/// It includes both basic types (int, float, string) and composite types (single, list, dict).
+/// It includes both basic types (int, float, string) and composite types (single, list, dict, set).
 #[repr(C)]
 pub enum KoiParamType {
     /// 64-bit signed integer value
@@ -27,6 +27,23 @@ pub enum KoiParamType {
     Invalid = -1,
     /// Boolean value
     BasicBool = 6,
+    /// Set composite value
+    CompositeSet = 7,
+}
+
+/// Map a basic value to its parameter type
+///
+/// Values without a C representation (such as the timestamps and durations
+/// added by koicore's `chrono` feature) are reported as `Invalid`.
+pub(crate) fn basic_param_type(value: &Value) -> KoiParamType {
+    #[allow(unreachable_patterns)]
+    match value {
+        Value::Int(_) => KoiParamType::BasicInt,
+        Value::Float(_) => KoiParamType::BasicFloat,
+        Value::String(_) => KoiParamType::BasicString,
+        Value::Bool(_) => KoiParamType::BasicBool,
+        _ => KoiParamType::Invalid,
+    }
 }
 
 /// Get number of parameters in command
@@ -68,16 +85,12 @@ pub unsafe extern "C" fn KoiCommand_GetParamType(command: *mut KoiCommand, index
     }
 
     match &params[index] {
-        Parameter::Basic(value) => match value {
-            Value::Int(_) => KoiParamType::BasicInt as i32,
-            Value::Float(_) => KoiParamType::BasicFloat as i32,
-            Value::String(_) => KoiParamType::BasicString as i32,
-            Value::Bool(_) => KoiParamType::BasicBool as i32,
-        },
+        Parameter::Basic(value) => basic_param_type(value) as i32,
         Parameter::Composite(_, composite) => match composite {
             CompositeValue::Single(_) => KoiParamType::CompositeSingle as i32,
             CompositeValue::List(_) => KoiParamType::CompositeList as i32,
             CompositeValue::Dict(_) => KoiParamType::CompositeDict as i32,
+            CompositeValue::Set(_) => KoiParamType::CompositeSet as i32,
         },
     }
 }
@@ -0,0 +1,334 @@
+use koicore::{
+    Value,
+    command::{Command, CompositeValue, Parameter},
+};
+use std::{ffi::c_char, ptr, slice};
+
+use crate::command::param::{KoiParamType, basic_param_type};
+
+use super::command::KoiCommand;
+
+/// Opaque handle for composite set parameter
+///
+/// This structure represents a set parameter in a KoiLang command, written
+/// with braces like `tags{1, 2}`. Sets hold distinct values in source order.
+/// Sets are read-only through this interface.
+#[repr(C)]
+pub struct KoiCompositeSet {
+    _data: (),
+    _marker: core::marker::PhantomData<(*mut u8, core::marker::PhantomPinned)>,
+}
+
+/// Get the values of a set handle, or None if it is not a set
+///
+/// # Safety
+///
+/// The `set` pointer must be non-NULL and point to a valid KoiCompositeSet object.
+unsafe fn set_values<'a>(set: *mut KoiCompositeSet) -> Option<&'a [Value]> {
+    match unsafe { &*(set as *const Parameter) } {
+        Parameter::Composite(_, CompositeValue::Set(values)) => Some(values),
+        _ => None,
+    }
+}
+
+/// Get composite set parameter from command
+///
+/// This function retrieves a set parameter from a command at the specified index.
+/// The parameter must be of set type, otherwise NULL is returned.
+///
+/// # Ownership and Lifetime
+///
+/// The returned pointer is a borrowed reference to data owned by the command.
+/// It must NOT be freed. The pointer is only valid as long as the command
+/// object exists and is not modified or destroyed.
+///
+/// # Arguments
+///
+/// * `command` - Pointer to the command object
+/// * `index` - Zero-based index of the parameter to retrieve
+///
+/// # Returns
+///
+/// Pointer to the composite set parameter, or NULL if:
+/// - command is NULL
+/// - index is out of bounds
+/// - the parameter at the specified index is not a set
+///
+/// # Safety
+///
+/// The `command` pointer must be either NULL or point to a valid KoiCommand object.
+/// The returned pointer becomes invalid if the command is destroyed or modified.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn KoiCommand_GetCompositeSet(
+    command: *mut KoiCommand,
+    index: usize,
+) -> *mut KoiCompositeSet {
+    if command.is_null() {
+        return ptr::null_mut();
+    }
+
+    let command = unsafe { &*(command as *mut Command) };
+    match command.params().get(index) {
+        Some(p @ Parameter::Composite(_, CompositeValue::Set(_))) => {
+            // Cast the parameter reference to the opaque set type
+            p as *const Parameter as *mut KoiCompositeSet
+        }
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Get composite set parameter length
+///
+/// # Arguments
+///
+/// * `set` - Pointer to the composite set parameter
+///
+/// # Returns
+///
+/// Number of values in the set, or 0 if the set pointer is NULL or invalid.
+///
+/// # Safety
+///
+/// The `set` pointer must be either NULL or point to a valid KoiCompositeSet object
+/// obtained from KoiCommand_GetCompositeSet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn KoiCompositeSet_GetLength(set: *mut KoiCompositeSet) -> usize {
+    if set.is_null() {
+        return 0;
+    }
+
+    unsafe { set_values(set) }.map_or(0, <[Value]>::len)
+}
+
+/// Get value type from composite set by index
+///
+/// # Arguments
+///
+/// * `set` - Pointer to the composite set parameter
+/// * `index` - Zero-based index of the value to query
+///
+/// # Returns
+///
+/// The type of the value as a KoiParamType enum value, or KoiParamType::Invalid
+/// if the set pointer is NULL, invalid, or the index is out of bounds.
+///
+/// # Safety
+///
+/// The `set` pointer must be either NULL or point to a valid KoiCompositeSet object.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn KoiCompositeSet_GetValueType(
+    set: *mut KoiCompositeSet,
+    index: usize,
+) -> i32 {
+    if set.is_null() {
+        return KoiParamType::Invalid as i32;
+    }
+
+    match unsafe { set_values(set) }.and_then(|values| values.get(index)) {
+        Some(value) => basic_param_type(value) as i32,
+        None => KoiParamType::Invalid as i32,
+    }
+}
+
+/// Get integer value from composite set by index
+///
+/// # Arguments
+///
+/// * `set` - Pointer to the composite set parameter
+/// * `index` - Zero-based index of the value to retrieve
+/// * `out_value` - Pointer to store the retrieved integer value
+///
+/// # Returns
+///
+/// 0 on success, or a non-zero error code on failure:
+/// - -1: set pointer is NULL or out_value is NULL
+/// - -2: index is out of bounds
+/// - -3: value at the specified index is not an integer
+/// - -4: set pointer is invalid
+///
+/// # Safety
+///
+/// The `set` pointer must be either NULL or point to a valid KoiCompositeSet object.
+/// The `out_value` pointer must point to a valid i64 variable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn KoiCompositeSet_GetIntValue(
+    set: *mut KoiCompositeSet,
+    index: usize,
+    out_value: *mut i64,
+) -> i32 {
+    if set.is_null() || out_value.is_null() {
+        return -1;
+    }
+
+    let Some(values) = (unsafe { set_values(set) }) else {
+        return -4;
+    };
+    match values.get(index) {
+        None => -2,
+        Some(Value::Int(value)) => {
+            unsafe { *out_value = *value };
+            0
+        }
+        Some(_) => -3,
+    }
+}
+
+/// Get float value from composite set by index
+///
+/// # Arguments
+///
+/// * `set` - Pointer to the composite set parameter
+/// * `index` - Zero-based index of the value to retrieve
+/// * `out_value` - Pointer to store the retrieved float value
+///
+/// # Returns
+///
+/// 0 on success, or a non-zero error code on failure:
+/// - -1: set pointer is NULL or out_value is NULL
+/// - -2: index is out of bounds
+/// - -3: value at the specified index is not a float
+/// - -4: set pointer is invalid
+///
+/// # Safety
+///
+/// The `set` pointer must be either NULL or point to a valid KoiCompositeSet object.
+/// The `out_value` pointer must point to a valid f64 variable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn KoiCompositeSet_GetFloatValue(
+    set: *mut KoiCompositeSet,
+    index: usize,
+    out_value: *mut f64,
+) -> i32 {
+    if set.is_null() || out_value.is_null() {
+        return -1;
+    }
+
+    let Some(values) = (unsafe { set_values(set) }) else {
+        return -4;
+    };
+    match values.get(index) {
+        None => -2,
+        Some(Value::Float(value)) => {
+            unsafe { *out_value = *value };
+            0
+        }
+        Some(_) => -3,
+    }
+}
+
+/// Get boolean value from composite set by index
+///
+/// # Arguments
+/// * `set` - Pointer to the composite set parameter
+/// * `index` - Zero-based index of the value to retrieve
+/// * `out_value` - Pointer to store boolean value (1 for true, 0 for false)
+///
+/// # Returns
+/// 0 on success, non-zero on error, with the same codes as
+/// KoiCompositeSet_GetIntValue
+///
+/// # Safety
+///
+/// The `set` pointer must be either NULL or point to a valid KoiCompositeSet object.
+/// The `out_value` pointer must point to a valid i32 variable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn KoiCompositeSet_GetBoolValue(
+    set: *mut KoiCompositeSet,
+    index: usize,
+    out_value: *mut i32,
+) -> i32 {
+    if set.is_null() || out_value.is_null() {
+        return -1;
+    }
+
+    let Some(values) = (unsafe { set_values(set) }) else {
+        return -4;
+    };
+    match values.get(index) {
+        None => -2,
+        Some(Value::Bool(value)) => {
+            unsafe { *out_value = if *value { 1 } else { 0 } };
+            0
+        }
+        Some(_) => -3,
+    }
+}
+
+/// Get string value from composite set by index
+///
+/// # Arguments
+///
+/// * `set` - Pointer to the composite set parameter
+/// * `index` - Zero-based index of the value to retrieve
+/// * `buffer` - Buffer to store the retrieved string value
+/// * `buffer_size` - Size of the buffer in bytes
+///
+/// # Returns
+///
+/// The number of bytes required for the string including the null terminator.
+/// If the buffer is too small, no data is written and the required size is returned.
+/// Returns 0 on error or if the value at the specified index is not a string.
+///
+/// # Safety
+///
+/// The `set` pointer must be either NULL or point to a valid KoiCompositeSet object.
+/// If `buffer` is not NULL, it must point to a valid memory region of at least `buffer_size` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn KoiCompositeSet_GetStringValue(
+    set: *mut KoiCompositeSet,
+    index: usize,
+    buffer: *mut c_char,
+    buffer_size: usize,
+) -> usize {
+    if set.is_null() || buffer.is_null() {
+        return 0;
+    }
+
+    let value_str = match unsafe { set_values(set) }.and_then(|values| values.get(index)) {
+        Some(Value::String(value)) => value,
+        _ => return 0,
+    };
+
+    let value_bytes = value_str.as_bytes();
+    let value_len = value_bytes.len();
+    let required_size = value_len + 1;
+
+    if buffer_size < required_size {
+        return required_size;
+    }
+
+    let buffer_slice = unsafe { slice::from_raw_parts_mut(buffer as *mut u8, buffer_size) };
+    buffer_slice[..value_len].copy_from_slice(value_bytes);
+    buffer_slice[value_len] = 0;
+
+    required_size
+}
+
+/// Get string value length from composite set by index
+///
+/// # Arguments
+///
+/// * `set` - Pointer to the composite set parameter
+/// * `index` - Zero-based index of the value to query
+///
+/// # Returns
+///
+/// Required buffer size (including null terminator), or 0 on error or type mismatch.
+///
+/// # Safety
+///
+/// The `set` pointer must be either NULL or point to a valid KoiCompositeSet object.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn KoiCompositeSet_GetStringValueLen(
+    set: *mut KoiCompositeSet,
+    index: usize,
+) -> usize {
+    if set.is_null() {
+        return 0;
+    }
+
+    match unsafe { set_values(set) }.and_then(|values| values.get(index)) {
+        Some(Value::String(value)) => value.len() + 1,
+        _ => 0,
+    }
+}
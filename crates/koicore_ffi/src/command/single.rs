@@ -8,7 +8,7 @@ use std::{
 };
 
 use super::command::KoiCommand;
-use crate::command::param::KoiParamType;
+use crate::command::param::{KoiParamType, basic_param_type};
 
 /// Opaque handle for composite single parameter
 ///
@@ -248,12 +248,7 @@ pub unsafe extern "C" fn KoiCompositeSingle_GetValueType(single: *mut KoiComposi
 
     let param = unsafe { &*(single as *const Parameter) };
     match param {
-        Parameter::Composite(_, CompositeValue::Single(v)) => match v {
-            Value::Int(_) => KoiParamType::BasicInt as i32,
-            Value::Float(_) => KoiParamType::BasicFloat as i32,
-            Value::String(_) => KoiParamType::BasicString as i32,
-            Value::Bool(_) => KoiParamType::BasicBool as i32,
-        },
+        Parameter::Composite(_, CompositeValue::Single(v)) => basic_param_type(v) as i32,
         _ => KoiParamType::Invalid as i32,
     }
 }
@@ -112,6 +112,8 @@ mod tests {
     use crate::command::command::*;
     use crate::command::dict::*;
     use crate::command::list::*;
+    use crate::command::param::KoiParamType;
+    use crate::command::set::*;
     use crate::command::single::*;
     use koicore::command::{Command, CompositeValue, Parameter, Value};
     use std::ffi::CString;
@@ -198,4 +200,72 @@ mod tests {
             KoiCommand_Del(cmd);
         }
     }
+
+    #[test]
+    fn test_ffi_composite_set() {
+        unsafe {
+            let command = Command::new("tags", vec![
+                Parameter::from(1),
+                Parameter::Composite(
+                    "ids".to_string(),
+                    CompositeValue::Set(vec![Value::Int(3), Value::from("a"), Value::Bool(true)]),
+                ),
+            ]);
+            let cmd = Box::into_raw(Box::new(command)) as *mut KoiCommand;
+
+            assert!(KoiCommand_GetCompositeSet(cmd, 0).is_null());
+            assert!(KoiCommand_GetCompositeSet(cmd, 2).is_null());
+            let set = KoiCommand_GetCompositeSet(cmd, 1);
+            assert!(!set.is_null());
+            assert_eq!(KoiCompositeSet_GetLength(set), 3);
+            assert_eq!(KoiCompositeSet_GetValueType(set, 0), KoiParamType::BasicInt as i32);
+            assert_eq!(KoiCompositeSet_GetValueType(set, 3), KoiParamType::Invalid as i32);
+
+            let mut int_value = 0;
+            assert_eq!(KoiCompositeSet_GetIntValue(set, 0, &mut int_value), 0);
+            assert_eq!(int_value, 3);
+            assert_eq!(KoiCompositeSet_GetIntValue(set, 1, &mut int_value), -3);
+            assert_eq!(KoiCompositeSet_GetIntValue(set, 3, &mut int_value), -2);
+
+            assert_eq!(KoiCompositeSet_GetStringValueLen(set, 1), 2);
+            let mut buffer = [0 as std::ffi::c_char; 2];
+            assert_eq!(KoiCompositeSet_GetStringValue(set, 1, buffer.as_mut_ptr(), 2), 2);
+            assert_eq!(std::ffi::CStr::from_ptr(buffer.as_ptr()).to_str().unwrap(), "a");
+
+            let mut bool_value = 0;
+            assert_eq!(KoiCompositeSet_GetBoolValue(set, 2, &mut bool_value), 0);
+            assert_eq!(bool_value, 1);
+
+            KoiCommand_Del(cmd);
+        }
+    }
+
+    #[test]
+    fn test_ffi_writer_flush_file() {
+        use crate::writer::config::KoiWriterConfig_Init;
+        use crate::writer::*;
+
+        let path = std::env::temp_dir().join("koicore_ffi_test_flush.ktxt");
+        let c_path = CString::new(path.to_str().unwrap()).unwrap();
+        unsafe {
+            let mut config = std::mem::MaybeUninit::<KoiWriterConfig>::uninit();
+            KoiWriterConfig_Init(config.as_mut_ptr());
+            let config = config.assume_init();
+            let writer = KoiWriter_NewFromFile(c_path.as_ptr(), &config);
+            assert!(!writer.is_null());
+
+            let cmd_name = CString::new("test_cmd").unwrap();
+            let cmd = KoiCommand_New(cmd_name.as_ptr());
+            assert_eq!(KoiWriter_WriteCommand(writer, cmd), 0);
+            // The file output is buffered until flushed
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+            assert_eq!(KoiWriter_Flush(writer), 0);
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), "#test_cmd\n");
+
+            KoiCommand_Del(cmd);
+            KoiWriter_Del(writer);
+            assert_eq!(KoiWriter_Flush(std::ptr::null_mut()), -1);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
 }
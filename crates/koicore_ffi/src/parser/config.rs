@@ -1,4 +1,29 @@
 use koicore::ParserConfig;
+use koicore::parser::IntOverflow;
+use std::ffi::{CStr, c_char};
+use std::ptr;
+
+/// How integer literals that don't fit in a 64-bit signed integer are handled
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub enum KoiIntOverflow {
+    /// Reject the value with a parse error
+    Error = 0,
+    /// Clamp the value to the minimum or maximum 64-bit integer
+    Saturate = 1,
+    /// Keep the low 64 bits in two's complement
+    Wrap = 2,
+}
+
+impl From<KoiIntOverflow> for IntOverflow {
+    fn from(overflow: KoiIntOverflow) -> Self {
+        match overflow {
+            KoiIntOverflow::Error => IntOverflow::Error,
+            KoiIntOverflow::Saturate => IntOverflow::Saturate,
+            KoiIntOverflow::Wrap => IntOverflow::Wrap,
+        }
+    }
+}
 
 #[repr(C)]
 #[derive(Clone)]
@@ -29,6 +54,69 @@ pub struct KoiParserConfig {
     /// If set to true, empty lines will be preserved and returned as empty text commands.
     /// If set to false, empty lines will be skipped.
     pub preserve_empty_lines: bool,
+    /// Whether to accept set literals in composite parameters
+    ///
+    /// If set to true, `name{v1, v2}` is parsed as a set with duplicates removed.
+    /// If set to false, braces are a syntax error.
+    pub enable_set_literals: bool,
+    /// Additional separator between commands on the same line
+    ///
+    /// Can be NULL to separate commands by newlines only.
+    pub command_separator: *const c_char,
+    /// Whether to accept percent-encoded `u"..."` string parameters
+    ///
+    /// If set to true, `u"a%20b"` is parsed as the string `a b`.
+    pub enable_percent_strings: bool,
+    /// How to handle integer literals outside the 64-bit signed range
+    pub int_overflow: KoiIntOverflow,
+    /// Whether indented lines continue the parameters of the preceding command
+    ///
+    /// If set to true, lines without a # prefix that are indented further than the
+    /// preceding command line are parsed as more parameters of that command.
+    pub indent_continuation: bool,
+    /// Whether annotation content keeps its leading # characters
+    ///
+    /// If set to true, `### note` is stored as `### note` instead of `note`.
+    pub annotation_keep_hashes: bool,
+    /// Opening and closing characters of list composites, as Unicode code points
+    ///
+    /// Invalid code points fall back to parentheses.
+    pub list_delimiters: [u32; 2],
+    /// Opening and closing characters of dictionary composites, as Unicode code points
+    ///
+    /// Invalid code points fall back to parentheses.
+    pub dict_delimiters: [u32; 2],
+    /// Whether numbers are kept as strings
+    ///
+    /// If set to true, `007` is parsed as the string `007` instead of the integer 7.
+    pub numbers_as_strings: bool,
+    /// Whether text lines ending in `\` continue on the next line
+    ///
+    /// If set to true, the lines are joined into one text command without the backslash.
+    pub text_line_continuation: bool,
+    /// Character that starts command and annotation lines, as a Unicode code point
+    ///
+    /// Invalid code points fall back to `#`.
+    pub prefix_char: u32,
+    /// Marker starting a trailing comment on command lines, e.g. `//`
+    ///
+    /// Can be NULL for command lines without comments.
+    pub comment_marker: *const c_char,
+    /// Whether to record the indentation of command lines
+    ///
+    /// If set to true, the leading whitespace of each command line is kept
+    /// with the command and written back by format-preserving writes.
+    pub record_command_indent: bool,
+    /// Whether to record the source line and column of each command
+    pub record_spans: bool,
+}
+
+/// Convert a delimiter pair from code points, falling back to parentheses
+pub(crate) fn delimiters_from_ffi([open, close]: [u32; 2]) -> (char, char) {
+    match (char::from_u32(open), char::from_u32(close)) {
+        (Some(open), Some(close)) => (open, close),
+        _ => ('(', ')'),
+    }
 }
 
 impl From<&KoiParserConfig> for ParserConfig {
@@ -39,6 +127,30 @@ impl From<&KoiParserConfig> for ParserConfig {
             convert_number_command: config.convert_number_command,
             preserve_indent: config.preserve_indent,
             preserve_empty_lines: config.preserve_empty_lines,
+            enable_set_literals: config.enable_set_literals,
+            command_separator: if config.command_separator.is_null() {
+                None
+            } else {
+                let separator = unsafe { CStr::from_ptr(config.command_separator) };
+                Some(separator.to_string_lossy().into_owned())
+            },
+            enable_percent_strings: config.enable_percent_strings,
+            int_overflow: config.int_overflow.into(),
+            indent_continuation: config.indent_continuation,
+            annotation_keep_hashes: config.annotation_keep_hashes,
+            list_delimiters: delimiters_from_ffi(config.list_delimiters),
+            dict_delimiters: delimiters_from_ffi(config.dict_delimiters),
+            numbers_as_strings: config.numbers_as_strings,
+            text_line_continuation: config.text_line_continuation,
+            prefix_char: char::from_u32(config.prefix_char).unwrap_or('#'),
+            comment_marker: if config.comment_marker.is_null() {
+                None
+            } else {
+                let marker = unsafe { CStr::from_ptr(config.comment_marker) };
+                Some(marker.to_string_lossy().into_owned())
+            },
+            record_command_indent: config.record_command_indent,
+            record_spans: config.record_spans,
         }
     }
 }
@@ -49,6 +161,19 @@ impl From<&KoiParserConfig> for ParserConfig {
 /// - command_threshold: 1 (lines starting with # are commands)
 /// - skip_annotations: false (annotation lines are included in output)
 /// - convert_number_command: true (numeric commands are converted to special commands)
+/// - enable_set_literals: false (brace set literals are rejected)
+/// - command_separator: NULL (commands are separated by newlines only)
+/// - enable_percent_strings: false (`u"..."` strings are rejected)
+/// - int_overflow: Error (out-of-range integers are a parse error)
+/// - indent_continuation: false (indented lines are text)
+/// - annotation_keep_hashes: false (annotation content is stored without #)
+/// - list_delimiters, dict_delimiters: `(` and `)`
+/// - numbers_as_strings: false (number literals are parsed as numbers)
+/// - text_line_continuation: false (a trailing `\` is kept in text lines)
+/// - prefix_char: `#`
+/// - comment_marker: NULL (command lines have no comments)
+/// - record_command_indent: false (the indentation of command lines is dropped)
+/// - record_spans: false (commands have no source position)
 ///
 /// # Arguments
 /// * `config` - Pointer to the KoiParserConfig structure to initialize
@@ -69,6 +194,20 @@ pub unsafe extern "C" fn KoiParserConfig_Init(config: *mut KoiParserConfig) {
             convert_number_command: true,
             preserve_empty_lines: true,
             preserve_indent: true,
+            enable_set_literals: false,
+            command_separator: ptr::null(),
+            enable_percent_strings: false,
+            int_overflow: KoiIntOverflow::Error,
+            indent_continuation: false,
+            annotation_keep_hashes: false,
+            list_delimiters: ['(' as u32, ')' as u32],
+            dict_delimiters: ['(' as u32, ')' as u32],
+            numbers_as_strings: false,
+            text_line_continuation: false,
+            prefix_char: '#' as u32,
+            comment_marker: ptr::null(),
+            record_command_indent: false,
+            record_spans: false,
         }
     };
 }
@@ -3,7 +3,7 @@ use std::ptr;
 use std::io;
 
 use encoding_rs::Encoding;
-use koicore::parser::input::{ StringInputSource, FileInputSource, EncodingErrorStrategy };
+use koicore::parser::input::{ StringInputSource, FileInputSource, ChainedInputSource, EncodingErrorStrategy };
 use koicore::parser::TextInputSource;
 
 /// Opaque handle for KoiLang input sources
@@ -278,6 +278,52 @@ pub unsafe extern "C" fn KoiInputSource_FromFileAndEncoding(
     Box::into_raw(source_wrapper)
 }
 
+/// Creates an input source that reads several input sources one after another
+///
+/// The sources form one logical stream. Errors name the source the failing line
+/// came from.
+///
+/// # Arguments
+///
+/// * `sources` - Pointer to an array of `count` KoiInputSource pointers
+/// * `count` - Number of sources in the array
+/// * `per_source_numbering` - Whether line numbers restart at 1 for each source
+///   (true) or continue across sources (false)
+///
+/// # Returns
+///
+/// Pointer to the created KoiInputSource, or NULL if sources is NULL or any
+/// source pointer is NULL. Nothing is taken over on failure.
+///
+/// # Safety
+///
+/// `sources` must point to `count` valid KoiInputSource pointers created by the
+/// KoiInputSource_From* functions. On success, ownership of every source moves
+/// into the returned source: they must not be used or passed to
+/// KoiInputSource_Del afterwards. The array itself is not freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn KoiInputSource_Chain(
+    sources: *const *mut KoiInputSource,
+    count: usize,
+    per_source_numbering: bool,
+) -> *mut KoiInputSource {
+    if sources.is_null() {
+        return ptr::null_mut();
+    }
+    let sources = unsafe { std::slice::from_raw_parts(sources, count) };
+    if sources.iter().any(|source| source.is_null()) {
+        return ptr::null_mut();
+    }
+
+    let inputs: Vec<Box<dyn TextInputSource>> = sources
+        .iter()
+        .map(|&source| unsafe { Box::from_raw(source) }.inner)
+        .collect();
+    let input = ChainedInputSource::new(inputs).with_per_source_numbering(per_source_numbering);
+    let source_wrapper = Box::new(KoiInputSource { inner: Box::new(input) });
+    Box::into_raw(source_wrapper)
+}
+
 /// Deletes a KoiInputSource object and frees its memory
 ///
 /// # Arguments
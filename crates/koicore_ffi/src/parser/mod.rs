@@ -15,7 +15,8 @@
 //! 1. Create an input source from a string, file, or custom callback
 //! 2. Configure parser options using `KoiParserConfig`
 //! 3. Create a parser with `KoiParser_New`
-//! 4. Iteratively retrieve commands with `KoiParser_NextCommand`
+//! 4. Iteratively retrieve commands with `KoiParser_NextCommand`, or all at
+//!    once with `KoiParser_ParseAll`
 //! 5. Check for errors with `KoiParser_Error` if needed
 //! 6. Clean up resources with the appropriate `_Del` functions
 //!
@@ -35,6 +36,7 @@ use koicore::Parser;
 
 use crate::command::KoiCommand;
 pub use config::KoiParserConfig;
+pub(crate) use config::delimiters_from_ffi;
 pub use error::KoiParserError;
 pub use input::{KoiInputSource, KoiFileInputEncodingStrategy};
 
@@ -157,6 +159,72 @@ pub unsafe extern "C" fn KoiParser_NextCommand(
     }
 }
 
+/// Parse all remaining commands from the parser
+///
+/// Reads commands until the end of the input and returns them as an array of
+/// command pointers. On a parsing error, no commands are returned and the
+/// error can be retrieved using KoiParser_Error.
+///
+/// # Arguments
+/// * `parser` - Parser pointer
+/// * `out_commands` - Receives the array of command pointers
+/// * `out_count` - Receives the number of commands in the array
+///
+/// # Returns
+/// 0 on success, or a negative value on error:
+/// - -1 if any pointer is null
+/// - -2 if a parsing error occurred
+///
+/// On error, `*out_commands` is set to null and `*out_count` to 0.
+///
+/// # Safety
+/// The parser pointer must be a valid KoiParser created with KoiParser_New.
+/// `out_commands` and `out_count` must be valid for writes. Each returned
+/// command is owned by the caller and must be freed with KoiCommand_Del, and
+/// the array itself with KoiCommandArray_Del.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn KoiParser_ParseAll(
+    parser: *mut KoiParser,
+    out_commands: *mut *mut *mut KoiCommand,
+    out_count: *mut usize,
+) -> i32 {
+    if parser.is_null() || out_commands.is_null() || out_count.is_null() {
+        return -1;
+    }
+    unsafe {
+        *out_commands = ptr::null_mut();
+        *out_count = 0;
+    }
+
+    let parser = unsafe { &mut *parser };
+    let mut commands = Vec::new();
+    if !parser.eof {
+        loop {
+            match parser.inner.next_command() {
+                Ok(Some(command)) => commands.push(command),
+                Ok(None) => {
+                    parser.eof = true;
+                    break;
+                }
+                Err(error) => {
+                    parser.last_error = Some(error);
+                    return -2;
+                }
+            }
+        }
+    }
+
+    let array: Box<[*mut KoiCommand]> = commands
+        .into_iter()
+        .map(|command| Box::into_raw(Box::new(command)) as *mut KoiCommand)
+        .collect();
+    unsafe {
+        *out_count = array.len();
+        *out_commands = Box::into_raw(array) as *mut *mut KoiCommand;
+    }
+    0
+}
+
 /// Get the last parsing error from the parser
 ///
 /// Retrieves the last error that occurred during parsing, if any. This function
@@ -189,3 +257,161 @@ pub unsafe extern "C" fn KoiParser_Error(parser: *mut KoiParser) -> *mut KoiPars
     }
     Box::into_raw(error.unwrap()) as *mut KoiParserError
 }
+
+/// Get the line number the parser is at
+///
+/// Delegates to `Parser::current_line`: the number of the next line to be
+/// read, starting at 1. Useful for showing progress.
+///
+/// # Arguments
+/// * `parser` - Parser pointer
+///
+/// # Returns
+/// The current line number, or 0 if parser is null
+///
+/// # Safety
+/// The parser pointer must be a valid KoiParser created with KoiParser_New.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn KoiParser_CurrentLine(parser: *mut KoiParser) -> usize {
+    if parser.is_null() {
+        return 0;
+    }
+
+    let parser = unsafe { &*parser };
+    parser.inner.current_line()
+}
+
+/// Check whether the parser has reached the end of its input
+///
+/// Becomes true once KoiParser_NextCommand has returned null at the end of the
+/// input, or KoiParser_ParseAll has read all commands. A null return caused by
+/// a parsing error does not set it, so this tells the two apart.
+///
+/// # Arguments
+/// * `parser` - Parser pointer
+///
+/// # Returns
+/// 1 if the end of input was reached, 0 if not, or -1 if parser is null
+///
+/// # Safety
+/// The parser pointer must be a valid KoiParser created with KoiParser_New.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn KoiParser_IsEof(parser: *mut KoiParser) -> i32 {
+    if parser.is_null() {
+        return -1;
+    }
+
+    let parser = unsafe { &*parser };
+    parser.eof as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::command::{KoiCommandArray_Del, KoiCommand_Del};
+    use config::KoiParserConfig_Init;
+    use input::{KoiInputSource_Chain, KoiInputSource_FromString};
+    use koicore::Command;
+    use std::ffi::CString;
+
+    #[test]
+    fn test_ffi_input_source_chain() {
+        unsafe {
+            let first = CString::new("#a\n").unwrap();
+            let second = CString::new("#b\n").unwrap();
+            let sources = [
+                KoiInputSource_FromString(first.as_ptr()),
+                KoiInputSource_FromString(second.as_ptr()),
+            ];
+            let source = KoiInputSource_Chain(sources.as_ptr(), sources.len(), false);
+            assert!(!source.is_null());
+            assert!(KoiInputSource_Chain(ptr::null(), 0, false).is_null());
+
+            let mut config = std::mem::MaybeUninit::<KoiParserConfig>::uninit();
+            KoiParserConfig_Init(config.as_mut_ptr());
+            let mut config = config.assume_init();
+            let parser = KoiParser_New(source, &mut config);
+            for name in ["a", "b"] {
+                let cmd = KoiParser_NextCommand(parser);
+                assert_eq!((*(cmd as *mut Command)).name, name);
+                KoiCommand_Del(cmd);
+            }
+            assert!(KoiParser_NextCommand(parser).is_null());
+            KoiParser_Del(parser);
+        }
+    }
+
+    #[test]
+    fn test_ffi_current_line_and_eof() {
+        unsafe {
+            let mut config = std::mem::MaybeUninit::<KoiParserConfig>::uninit();
+            KoiParserConfig_Init(config.as_mut_ptr());
+            let mut config = config.assume_init();
+
+            let text = CString::new("#a\n#b\n").unwrap();
+            let parser = KoiParser_New(KoiInputSource_FromString(text.as_ptr()), &mut config);
+            assert_eq!(KoiParser_CurrentLine(parser), 1);
+            assert_eq!(KoiParser_IsEof(parser), 0);
+
+            let cmd = KoiParser_NextCommand(parser);
+            assert_eq!((*(cmd as *mut Command)).name, "a");
+            KoiCommand_Del(cmd);
+            let first_line = KoiParser_CurrentLine(parser);
+            assert_eq!(first_line, 2);
+
+            let cmd = KoiParser_NextCommand(parser);
+            assert_eq!((*(cmd as *mut Command)).name, "b");
+            KoiCommand_Del(cmd);
+            assert!(KoiParser_CurrentLine(parser) > first_line);
+            assert_eq!(KoiParser_IsEof(parser), 0);
+
+            assert!(KoiParser_NextCommand(parser).is_null());
+            assert_eq!(KoiParser_IsEof(parser), 1);
+            assert!(KoiParser_Error(parser).is_null());
+            KoiParser_Del(parser);
+
+            assert_eq!(KoiParser_CurrentLine(ptr::null_mut()), 0);
+            assert_eq!(KoiParser_IsEof(ptr::null_mut()), -1);
+        }
+    }
+
+    #[test]
+    fn test_ffi_parse_all() {
+        unsafe {
+            let mut config = std::mem::MaybeUninit::<KoiParserConfig>::uninit();
+            KoiParserConfig_Init(config.as_mut_ptr());
+            let mut config = config.assume_init();
+
+            let text = CString::new("#a 1\n#b\nHello\n").unwrap();
+            let parser = KoiParser_New(KoiInputSource_FromString(text.as_ptr()), &mut config);
+            let mut commands = ptr::null_mut();
+            let mut count = 0;
+            assert_eq!(KoiParser_ParseAll(parser, &mut commands, &mut count), 0);
+            assert_eq!(count, 3);
+            let array = std::slice::from_raw_parts(commands, count);
+            let names: Vec<_> = array.iter().map(|&cmd| (*(cmd as *mut Command)).name.clone()).collect();
+            assert_eq!(names, ["a", "b", "@text"]);
+            for &cmd in array {
+                KoiCommand_Del(cmd);
+            }
+            KoiCommandArray_Del(commands, count);
+
+            // Nothing is left after the end of input
+            assert_eq!(KoiParser_ParseAll(parser, &mut commands, &mut count), 0);
+            assert_eq!(count, 0);
+            KoiCommandArray_Del(commands, count);
+            assert_eq!(KoiParser_ParseAll(parser, ptr::null_mut(), &mut count), -1);
+            KoiParser_Del(parser);
+
+            let text = CString::new("#a\n#b (\n").unwrap();
+            let parser = KoiParser_New(KoiInputSource_FromString(text.as_ptr()), &mut config);
+            assert_eq!(KoiParser_ParseAll(parser, &mut commands, &mut count), -2);
+            assert!(commands.is_null());
+            assert_eq!(count, 0);
+            let error = KoiParser_Error(parser);
+            assert!(!error.is_null());
+            error::KoiParserError_Del(error);
+            KoiParser_Del(parser);
+        }
+    }
+}
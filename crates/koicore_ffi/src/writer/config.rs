@@ -1,9 +1,11 @@
 use koicore::WriterConfig;
-use koicore::writer::{FloatFormat, FormatterOptions, NumberFormat};
+use koicore::writer::{CommandSeparator, FloatFormat, FormatterOptions, NumberFormat};
 use std::collections::HashMap;
 use std::ffi::{CStr, c_char};
 use std::ptr;
 
+use crate::parser::delimiters_from_ffi;
+
 /// Transparent configuration struct for FFI
 #[repr(C)]
 #[derive(Clone, Copy)]
@@ -19,6 +21,32 @@ pub struct KoiFormatterOptions {
     pub newline_before_param: bool,
     pub newline_after_param: bool,
     pub should_override: bool,
+    pub type_annotations: bool,
+    pub percent_encode_strings: bool,
+    /// Space after commas in composite values: 1 = yes, 0 = no, -1 = unless compact
+    pub composite_space_after_comma: i8,
+    /// Space after colons in dictionary entries: 1 = yes, 0 = no, -1 = unless compact
+    pub composite_space_after_colon: i8,
+    pub quote_dict_string_values: bool,
+    pub canonicalize_composites: bool,
+    pub force_single_line: bool,
+    pub blank_after_composite_commands: bool,
+}
+
+fn optional_bool_from_ffi(value: i8) -> Option<bool> {
+    match value {
+        0 => Some(false),
+        v if v > 0 => Some(true),
+        _ => None,
+    }
+}
+
+fn optional_bool_to_ffi(value: Option<bool>) -> i8 {
+    match value {
+        Some(true) => 1,
+        Some(false) => 0,
+        None => -1,
+    }
 }
 
 impl From<KoiFormatterOptions> for FormatterOptions {
@@ -46,6 +74,14 @@ impl From<KoiFormatterOptions> for FormatterOptions {
             newline_before_param: opt.newline_before_param,
             newline_after_param: opt.newline_after_param,
             should_override: opt.should_override,
+            type_annotations: opt.type_annotations,
+            percent_encode_strings: opt.percent_encode_strings,
+            composite_space_after_comma: optional_bool_from_ffi(opt.composite_space_after_comma),
+            composite_space_after_colon: optional_bool_from_ffi(opt.composite_space_after_colon),
+            quote_dict_string_values: opt.quote_dict_string_values,
+            canonicalize_composites: opt.canonicalize_composites,
+            force_single_line: opt.force_single_line,
+            blank_after_composite_commands: opt.blank_after_composite_commands,
         }
     }
 }
@@ -64,6 +100,14 @@ impl From<FormatterOptions> for KoiFormatterOptions {
             newline_before_param: opt.newline_before_param,
             newline_after_param: opt.newline_after_param,
             should_override: opt.should_override,
+            type_annotations: opt.type_annotations,
+            percent_encode_strings: opt.percent_encode_strings,
+            composite_space_after_comma: optional_bool_to_ffi(opt.composite_space_after_comma),
+            composite_space_after_colon: optional_bool_to_ffi(opt.composite_space_after_colon),
+            quote_dict_string_values: opt.quote_dict_string_values,
+            canonicalize_composites: opt.canonicalize_composites,
+            force_single_line: opt.force_single_line,
+            blank_after_composite_commands: opt.blank_after_composite_commands,
         }
     }
 }
@@ -125,6 +169,27 @@ pub struct KoiWriterConfig {
     /// Pointer to array of KoiCommandOption, terminated by name=NULL.
     /// Can be NULL if no command options.
     pub command_options: *const KoiCommandOption,
+    /// Whether every command is terminated by the command separator.
+    /// If false, separators are only written between commands.
+    pub trailing_newline: bool,
+    /// Separator written between commands.
+    /// Can be NULL to separate commands with newlines.
+    pub command_separator: *const c_char,
+    /// Whether to write the # prefix before commands.
+    /// If false, commands are written bare; text and annotations are unchanged.
+    pub emit_prefix: bool,
+    /// Opening and closing characters of list composites, as Unicode code points.
+    /// Invalid code points fall back to parentheses.
+    pub list_delimiters: [u32; 2],
+    /// Opening and closing characters of dictionary composites, as Unicode code points.
+    /// Invalid code points fall back to parentheses.
+    pub dict_delimiters: [u32; 2],
+    /// Maximum width of output lines; wider commands continue on the next line.
+    /// 0 for no limit.
+    pub max_width: usize,
+    /// Character written before commands and annotations, as a Unicode code point.
+    /// Invalid code points fall back to #.
+    pub prefix_char: u32,
 }
 
 /// Helper to convert raw pointer array to HashMap
@@ -153,6 +218,22 @@ impl From<&KoiWriterConfig> for WriterConfig {
             global_options: config.global_options.into(),
             command_threshold: config.command_threshold,
             command_options: unsafe { parse_command_options(config.command_options) },
+            trailing_newline: config.trailing_newline,
+            command_separator: if config.command_separator.is_null() {
+                CommandSeparator::Newline
+            } else {
+                let separator = unsafe { CStr::from_ptr(config.command_separator) };
+                match separator.to_string_lossy().as_ref() {
+                    "\n" => CommandSeparator::Newline,
+                    ";" => CommandSeparator::Semicolon,
+                    other => CommandSeparator::Custom(other.to_string()),
+                }
+            },
+            emit_prefix: config.emit_prefix,
+            list_delimiters: delimiters_from_ffi(config.list_delimiters),
+            dict_delimiters: delimiters_from_ffi(config.dict_delimiters),
+            max_width: (config.max_width > 0).then_some(config.max_width),
+            prefix_char: char::from_u32(config.prefix_char).unwrap_or('#'),
         }
     }
 }
@@ -170,5 +251,12 @@ pub unsafe extern "C" fn KoiWriterConfig_Init(config: *mut KoiWriterConfig) {
         config.global_options = defaults.global_options.into();
         config.command_threshold = defaults.command_threshold;
         config.command_options = ptr::null();
+        config.trailing_newline = defaults.trailing_newline;
+        config.command_separator = ptr::null();
+        config.emit_prefix = defaults.emit_prefix;
+        config.list_delimiters = [defaults.list_delimiters.0 as u32, defaults.list_delimiters.1 as u32];
+        config.dict_delimiters = [defaults.dict_delimiters.0 as u32, defaults.dict_delimiters.1 as u32];
+        config.max_width = defaults.max_width.unwrap_or(0);
+        config.prefix_char = defaults.prefix_char as u32;
     }
 }
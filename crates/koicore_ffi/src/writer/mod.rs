@@ -121,12 +121,40 @@ pub unsafe extern "C" fn KoiWriter_NewFromStringOutput(
 ///
 /// * `writer` must be a valid pointer returned by one of the `KoiWriter_New*` functions.
 /// * After calling this function, `writer` is invalid and must not be used.
+///
+/// The output is flushed first; call `KoiWriter_Flush` beforehand to detect
+/// flush errors, which are ignored here.
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn KoiWriter_Del(writer: *mut KoiWriter) {
     if !writer.is_null() {
-        unsafe {
-            drop(Box::from_raw(writer));
+        let mut writer = unsafe { Box::from_raw(writer) };
+        let _ = writer.inner.flush();
+    }
+}
+
+/// Flush the writer's output.
+///
+/// Output written to a file is buffered until flushed or the writer is deleted.
+///
+/// # Safety
+///
+/// * `writer` must be a valid pointer to a `KoiWriter`.
+///
+/// # Returns
+///
+/// * 0 on success
+/// * -1 if writer is null
+/// * -2 if flushing fails
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn KoiWriter_Flush(writer: *mut KoiWriter) -> i32 {
+    if !writer.is_null() {
+        let writer = unsafe { &mut *writer };
+        match writer.inner.flush() {
+            Ok(_) => 0,
+            Err(_) => -2,
         }
+    } else {
+        -1
     }
 }
 
@@ -221,6 +221,44 @@ TEST(InputSourceTest, TestMultipleCommandsFromInput) {
     KoiParser_Del(parser);
 }
 
+TEST(InputSourceTest, TestChainedInputSource) {
+    KoiInputSource* sources[2] = {
+        KoiInputSource_FromString("#first\n"),
+        KoiInputSource_FromString("#second\n#third pos(x: 1\n"),
+    };
+    // Ownership of both sources moves into the chain
+    KoiInputSource* source = KoiInputSource_Chain(sources, 2, true);
+    EXPECT_NE(source, nullptr);
+
+    KoiParserConfig config;
+    KoiParserConfig_Init(&config);
+    KoiParser* parser = KoiParser_New(source, &config);
+    EXPECT_NE(parser, nullptr);
+
+    const char* expected[2] = {"first", "second"};
+    for (int i = 0; i < 2; i++) {
+        KoiCommand* cmd = KoiParser_NextCommand(parser);
+        EXPECT_NE(cmd, nullptr);
+        uintptr_t name_len = KoiCommand_GetNameLen(cmd);
+        char* name = new char[name_len];
+        KoiCommand_GetName(cmd, name, name_len);
+        EXPECT_STREQ(name, expected[i]);
+        delete[] name;
+        KoiCommand_Del(cmd);
+    }
+
+    // Line numbers restart in the second source
+    EXPECT_EQ(KoiParser_NextCommand(parser), nullptr);
+    KoiParserError* error = KoiParser_Error(parser);
+    EXPECT_NE(error, nullptr);
+    uintptr_t lineno = 0, column = 0;
+    EXPECT_EQ(KoiParserError_GetTracebackPosition(error, &lineno, &column), 0);
+    EXPECT_EQ(lineno, 2);
+
+    KoiParserError_Del(error);
+    KoiParser_Del(parser);
+}
+
 int main() {
     ::testing::InitGoogleTest();
     return RUN_ALL_TESTS();
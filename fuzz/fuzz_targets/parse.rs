@@ -0,0 +1,44 @@
+//! Feed arbitrary input to the parser: it must return commands or errors, never panic
+//!
+//! Run with `cargo +nightly fuzz run parse` from the repository root.
+
+#![no_main]
+
+use koicore::parser::{Parser, ParserConfig, StringInputSource};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&selector, data)) = data.split_first() else {
+        return;
+    };
+    let input = String::from_utf8_lossy(data);
+    let config = match selector % 4 {
+        0 => ParserConfig::default(),
+        1 => ParserConfig::format_preserving()
+            .with_enable_set_literals(true)
+            .with_enable_percent_strings(true),
+        2 => ParserConfig::default()
+            .with_indent_continuation(true)
+            .with_command_separator(";"),
+        _ => ParserConfig::default()
+            .with_list_delimiters('[', ']')
+            .with_dict_delimiters('{', '}'),
+    };
+
+    let mut parser = Parser::new(StringInputSource::new(&input), config);
+    // Every command consumes part of the input, so this bounds the loop
+    for _ in 0..input.len() + 2 {
+        match parser.next_command() {
+            Ok(Some(command)) => {
+                let _ = command.to_string();
+            }
+            Ok(None) => return,
+            Err(e) => {
+                let _ = e.to_string();
+                let _ = e.display_colored().to_string();
+                return;
+            }
+        }
+    }
+    panic!("parser did not finish");
+});
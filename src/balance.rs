@@ -0,0 +1,212 @@
+//! Checks for paired commands such as `#begin ... #end`
+//!
+//! A [`BalanceValidator`] is fed commands as they are parsed and reports
+//! closing commands without a matching opener, closers that don't match the
+//! innermost open command, and commands still open at the end of the input.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::command::Command;
+
+/// A balance problem found by a [`BalanceValidator`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BalanceError {
+    /// An opening command that was never closed
+    Unclosed {
+        /// Name of the opening command
+        opener: String,
+        /// Line of the opening command
+        line: usize,
+    },
+    /// A closing command with no open command to close
+    Unexpected {
+        /// Name of the closing command
+        closer: String,
+        /// Line of the closing command
+        line: usize,
+    },
+    /// A closing command that closes an outer command, leaving an inner one open
+    Mismatched {
+        /// Name of the inner opening command
+        opener: String,
+        /// Line of the inner opening command
+        opener_line: usize,
+        /// The closer the inner command expects
+        expected: String,
+        /// Name of the closing command found
+        found: String,
+        /// Line of the closing command found
+        line: usize,
+    },
+}
+
+impl fmt::Display for BalanceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BalanceError::Unclosed { opener, line } => {
+                write!(f, "line {}: #{} is never closed", line, opener)
+            }
+            BalanceError::Unexpected { closer, line } => {
+                write!(f, "line {}: #{} has no matching opener", line, closer)
+            }
+            BalanceError::Mismatched {
+                opener,
+                opener_line,
+                expected,
+                found,
+                line,
+            } => write!(
+                f,
+                "line {}: expected #{} to close #{} from line {}, found #{}",
+                line, expected, opener, opener_line, found
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BalanceError {}
+
+/// Streaming validator for paired opening and closing commands
+///
+/// # Examples
+///
+/// ```rust
+/// use koicore::balance::{BalanceError, BalanceValidator};
+/// use koicore::parser::{Parser, ParserConfig, StringInputSource};
+///
+/// let input = StringInputSource::new("#begin\n#section\n#end\n");
+/// let mut parser = Parser::new(input, ParserConfig::default());
+/// let mut validator = BalanceValidator::new([("begin", "end"), ("section", "endsection")]);
+/// while let Some((command, source)) = parser.next_command_with_source()? {
+///     validator.feed(&command, source.lineno);
+/// }
+/// let errors = validator.finish();
+/// assert!(matches!(&errors[..], [BalanceError::Mismatched { line: 3, .. }]));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct BalanceValidator {
+    /// Closing command name by opening command name
+    closers: HashMap<String, String>,
+    /// Open commands with their line numbers, innermost last
+    stack: Vec<(String, usize)>,
+    errors: Vec<BalanceError>,
+}
+
+impl BalanceValidator {
+    /// Create a validator for the given pairs of command names
+    ///
+    /// # Arguments
+    /// * `pairs` - `(opener, closer)` command names, e.g. `("begin", "end")`
+    pub fn new<O: Into<String>, C: Into<String>>(pairs: impl IntoIterator<Item = (O, C)>) -> Self {
+        Self {
+            closers: pairs
+                .into_iter()
+                .map(|(opener, closer)| (opener.into(), closer.into()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Check the next command
+    ///
+    /// Commands that are not part of a pair are ignored.
+    ///
+    /// # Arguments
+    /// * `command` - The parsed command
+    /// * `line` - Line number of the command, used in reports
+    pub fn feed(&mut self, command: &Command, line: usize) {
+        let name = command.name();
+        if self.closers.contains_key(name) {
+            self.stack.push((name.to_string(), line));
+            return;
+        }
+        if !self.closers.values().any(|closer| closer == name) {
+            return;
+        }
+
+        // Close the innermost command this closer belongs to
+        let Some(depth) = self
+            .stack
+            .iter()
+            .rposition(|(opener, _)| self.closers[opener] == name)
+        else {
+            self.errors.push(BalanceError::Unexpected {
+                closer: name.to_string(),
+                line,
+            });
+            return;
+        };
+        for (opener, opener_line) in self.stack.drain(depth + 1..).rev() {
+            self.errors.push(BalanceError::Mismatched {
+                expected: self.closers[&opener].clone(),
+                opener,
+                opener_line,
+                found: name.to_string(),
+                line,
+            });
+        }
+        self.stack.pop();
+    }
+
+    /// Finish checking at the end of the input
+    ///
+    /// Returns all problems found, in the order they were detected, followed
+    /// by the commands left open (outermost first).
+    pub fn finish(mut self) -> Vec<BalanceError> {
+        let unclosed = self
+            .stack
+            .drain(..)
+            .map(|(opener, line)| BalanceError::Unclosed { opener, line });
+        self.errors.extend(unclosed);
+        self.errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(source: &str) -> Vec<BalanceError> {
+        let mut validator = BalanceValidator::new([("begin", "end"), ("section", "endsection")]);
+        for (i, line) in source.lines().enumerate() {
+            validator.feed(&line.parse().unwrap(), i + 1);
+        }
+        validator.finish()
+    }
+
+    #[test]
+    fn test_balanced() {
+        let errors = check("#begin\n#section a\n#text\n#endsection\n#section b\n#endsection\n#end");
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn test_missing_end() {
+        assert_eq!(check("#begin\n#section\n#endsection"), vec![BalanceError::Unclosed {
+            opener: "begin".to_string(),
+            line: 1,
+        }]);
+        assert_eq!(check("#end"), vec![BalanceError::Unexpected {
+            closer: "end".to_string(),
+            line: 1,
+        }]);
+    }
+
+    #[test]
+    fn test_mismatched_pair() {
+        let errors = check("#begin\n#section\n#end");
+        assert_eq!(errors, vec![BalanceError::Mismatched {
+            opener: "section".to_string(),
+            opener_line: 2,
+            expected: "endsection".to_string(),
+            found: "end".to_string(),
+            line: 3,
+        }]);
+        assert_eq!(
+            errors[0].to_string(),
+            "line 3: expected #endsection to close #section from line 2, found #end"
+        );
+    }
+}
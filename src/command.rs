@@ -34,7 +34,10 @@
 //! let annotation_cmd = Command::new_annotation("This is an annotation");
 //! ```
 
-use std::{collections::HashMap, fmt};
+use std::{cmp::Ordering, collections::HashMap, fmt};
+
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, FixedOffset, SecondsFormat, TimeDelta};
 
 #[cfg(feature = "serde")]
 use serde::{
@@ -53,11 +56,140 @@ pub enum Value {
     /// Integer values (64-bit signed)
     Int(i64),
     /// Floating-point values (64-bit)
+    ///
+    /// With the `serde` feature, NaN and infinities are rejected with an error
+    /// when serializing, since JSON and most text formats cannot represent them
+    /// and KoiLang has no literal to read them back from.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_float"))]
     Float(f64),
     /// Boolean values
     Bool(bool),
     /// String values (UTF-8 encoded)
+    ///
+    /// Bare identifiers such as `name` and quoted strings such as `"name"`
+    /// both parse to this variant; KoiLang has no separate literal type.
     String(String),
+    /// Timestamps parsed from RFC 3339 literals (e.g., `2024-01-01T00:00:00Z`)
+    #[cfg(feature = "chrono")]
+    #[cfg_attr(
+        feature = "serde",
+        serde(serialize_with = "serialize_datetime", skip_deserializing)
+    )]
+    DateTime(DateTime<FixedOffset>),
+    /// Durations parsed from literals with a unit suffix (e.g., `10s`, `5m`)
+    ///
+    /// Supported units are `ms`, `s`, `m`, `h` and `d`.
+    #[cfg(feature = "chrono")]
+    #[cfg_attr(
+        feature = "serde",
+        serde(serialize_with = "serialize_duration", skip_deserializing)
+    )]
+    Duration(TimeDelta),
+}
+
+/// Units accepted in duration literals, from largest to smallest, in milliseconds
+#[cfg(feature = "chrono")]
+pub(crate) const DURATION_UNITS: [(&str, i64); 5] = [
+    ("d", 86_400_000),
+    ("h", 3_600_000),
+    ("m", 60_000),
+    ("s", 1_000),
+    ("ms", 1),
+];
+
+/// Format a duration as a literal in the largest unit that represents it exactly
+///
+/// Sub-millisecond precision is truncated.
+#[cfg(feature = "chrono")]
+pub(crate) fn format_duration(duration: &TimeDelta) -> String {
+    let millis = duration.num_milliseconds();
+    if millis == 0 {
+        return "0s".to_string();
+    }
+    let (unit, scale) = DURATION_UNITS
+        .iter()
+        .find(|(_, scale)| millis % scale == 0)
+        .copied()
+        .unwrap_or(("ms", 1));
+    format!("{}{}", millis / scale, unit)
+}
+
+/// Format a timestamp as canonical RFC 3339, using `Z` for UTC
+#[cfg(feature = "chrono")]
+pub(crate) fn format_datetime(datetime: &DateTime<FixedOffset>) -> String {
+    datetime.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+/// Check whether a string must be quoted to re-parse as the same string
+///
+/// KoiLang has no separate literal type: a bare identifier and a quoted string
+/// both parse to `Value::String`. A string can therefore be written bare only if
+/// it would be read back as exactly that string, i.e. it is a non-empty
+/// identifier (letters, digits and `_`, not starting with a digit) that is not a
+/// keyword. Everything else, including number-like text such as `0x10` and the
+/// keywords `true` and `false`, is quoted, which keeps its type as a string.
+pub(crate) fn string_needs_quotes(s: &str) -> bool {
+    let mut chars = s.chars();
+    let starts_like_identifier = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+    !starts_like_identifier
+        || !chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        || s == "true"
+        || s == "false"
+}
+
+impl Value {
+    /// Name of this value's type as used in type annotations
+    ///
+    /// Returns one of `"int"`, `"float"`, `"bool"` or `"string"` (plus `"datetime"`
+    /// and `"duration"` with the `chrono` feature), matching the `value:type`
+    /// annotation syntax accepted by the parser.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "int",
+            Value::Float(_) => "float",
+            Value::Bool(_) => "bool",
+            Value::String(_) => "string",
+            #[cfg(feature = "chrono")]
+            Value::DateTime(_) => "datetime",
+            #[cfg(feature = "chrono")]
+            Value::Duration(_) => "duration",
+        }
+    }
+
+    /// Compare two values in the canonical KoiLang order
+    ///
+    /// Values are ordered first by type (`Bool`, `Int`, `Float`, `String`, then
+    /// `DateTime` and `Duration` with the `chrono` feature) and then
+    /// by value. Floats use IEEE 754 total ordering, so the comparison is total even
+    /// in the presence of NaN. This is the order used when emitting sets.
+    pub fn canonical_cmp(&self, other: &Self) -> Ordering {
+        fn rank(value: &Value) -> u8 {
+            match value {
+                Value::Bool(_) => 0,
+                Value::Int(_) => 1,
+                Value::Float(_) => 2,
+                Value::String(_) => 3,
+                #[cfg(feature = "chrono")]
+                Value::DateTime(_) => 4,
+                #[cfg(feature = "chrono")]
+                Value::Duration(_) => 5,
+            }
+        }
+
+        match (self, other) {
+            (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+            (Value::Int(a), Value::Int(b)) => a.cmp(b),
+            (Value::Float(a), Value::Float(b)) => a.total_cmp(b),
+            (Value::String(a), Value::String(b)) => a.cmp(b),
+            #[cfg(feature = "chrono")]
+            (Value::DateTime(a), Value::DateTime(b)) => a.cmp(b),
+            #[cfg(feature = "chrono")]
+            (Value::Duration(a), Value::Duration(b)) => a.cmp(b),
+            _ => rank(self).cmp(&rank(other)),
+        }
+    }
 }
 
 impl From<i64> for Value {
@@ -66,12 +198,33 @@ impl From<i64> for Value {
     }
 }
 
+/// Widens to `Value::Int`
+impl From<i32> for Value {
+    fn from(i: i32) -> Self {
+        Self::Int(i.into())
+    }
+}
+
+/// Widens to `Value::Int`; every `u32` fits in an `i64`
+impl From<u32> for Value {
+    fn from(i: u32) -> Self {
+        Self::Int(i.into())
+    }
+}
+
 impl From<f64> for Value {
     fn from(f: f64) -> Self {
         Self::Float(f)
     }
 }
 
+/// Widens to `Value::Float`; the conversion to `f64` is exact
+impl From<f32> for Value {
+    fn from(f: f32) -> Self {
+        Self::Float(f.into())
+    }
+}
+
 impl From<bool> for Value {
     fn from(v: bool) -> Self {
         Value::Bool(v)
@@ -90,6 +243,27 @@ impl From<&'_ str> for Value {
     }
 }
 
+/// Converts to a one-character `Value::String`
+impl From<char> for Value {
+    fn from(c: char) -> Self {
+        Self::String(c.to_string())
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<DateTime<FixedOffset>> for Value {
+    fn from(dt: DateTime<FixedOffset>) -> Self {
+        Self::DateTime(dt)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<TimeDelta> for Value {
+    fn from(d: TimeDelta) -> Self {
+        Self::Duration(d)
+    }
+}
+
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -97,37 +271,16 @@ impl fmt::Display for Value {
             Value::Float(fl) => write!(f, "{}", fl),
             Value::Bool(b) => write!(f, "{}", b),
             Value::String(s) => {
-                // Check if the string needs to be quoted
-                // It needs quoting if:
-                // 1. It is empty
-                // 2. It contains characters that are not allowed in unquoted identifiers (alphanumeric + '_')
-                // 3. It starts with a digit (which would be parsed as a number)
-                let needs_quotes = s.is_empty()
-                    || !s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
-                    || s.chars()
-                        .next()
-                        .map(|c| c.is_ascii_digit())
-                        .unwrap_or(false);
-
-                if needs_quotes {
-                    write!(f, "\"")?;
-                    for c in s.chars() {
-                        match c {
-                            '"' => write!(f, "\\\"")?,
-                            '\\' => write!(f, "\\\\")?,
-                            '\n' => write!(f, "\\n")?,
-                            '\r' => write!(f, "\\r")?,
-                            '\t' => write!(f, "\\t")?,
-                            // We don't strictly need to escape other control chars for valid parsing,
-                            // but we could. For now, just basic text escapes.
-                            c => write!(f, "{}", c)?,
-                        }
-                    }
-                    write!(f, "\"")
+                if string_needs_quotes(s) {
+                    write!(f, "\"{}\"", crate::writer::escape_string(s))
                 } else {
                     write!(f, "{}", s)
                 }
             }
+            #[cfg(feature = "chrono")]
+            Value::DateTime(dt) => write!(f, "{}", format_datetime(dt)),
+            #[cfg(feature = "chrono")]
+            Value::Duration(d) => write!(f, "{}", format_duration(d)),
         }
     }
 }
@@ -138,12 +291,21 @@ impl fmt::Display for Value {
 /// including lists and dictionaries.
 #[derive(Debug, Clone, PartialEq)]
 pub enum CompositeValue {
-    /// Single basic value
+    /// Single basic value (e.g., `name(1)`)
     Single(Value),
-    /// List of basic values
+    /// List of basic values (e.g., `name(1, 2)`)
+    ///
+    /// A one-element list is written with a trailing comma, `name(1,)`, which
+    /// the parser reads back as a list rather than a [`CompositeValue::Single`].
     List(Vec<Value>),
     /// Dictionary mapping strings to values
     Dict(Vec<(String, Value)>),
+    /// Set of unique basic values (e.g., `name{1, 2, 3}`)
+    ///
+    /// Only produced by the parser when `ParserConfig::enable_set_literals` is set.
+    /// Duplicates are removed on parse, and the writer emits the values in
+    /// canonical order (see [`Value::canonical_cmp`]).
+    Set(Vec<Value>),
 }
 
 impl<T: Into<Value>> From<T> for CompositeValue {
@@ -176,17 +338,55 @@ impl<T: Into<Value>> FromIterator<(String, T)> for CompositeValue {
     }
 }
 
+impl CompositeValue {
+    /// Get all values stored under `key` in a dictionary, in source order
+    ///
+    /// Dictionaries keep duplicate keys as separate entries, so a key can map
+    /// to several values. Returns no values for other composite kinds.
+    ///
+    /// # Arguments
+    /// * `key` - The dictionary key
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::command::{CompositeValue, Value};
+    ///
+    /// let dict: CompositeValue = vec![
+    ///     ("x".to_string(), 1),
+    ///     ("y".to_string(), 0),
+    ///     ("x".to_string(), 2),
+    /// ].into_iter().collect();
+    /// let xs: Vec<&Value> = dict.get_all("x").collect();
+    /// assert_eq!(xs, [&Value::Int(1), &Value::Int(2)]);
+    /// ```
+    pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a Value> + 'a {
+        let entries = match self {
+            CompositeValue::Dict(entries) => entries.as_slice(),
+            _ => &[],
+        };
+        entries
+            .iter()
+            .filter(move |(k, _)| k == key)
+            .map(|(_, value)| value)
+    }
+}
+
 impl fmt::Display for CompositeValue {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             CompositeValue::Single(value) => write!(f, "{}", value),
-            CompositeValue::List(values) => {
+            CompositeValue::List(values) | CompositeValue::Set(values) => {
                 for (i, value) in values.iter().enumerate() {
                     if i > 0 {
                         write!(f, ", ")?;
                     }
                     write!(f, "{}", value)?;
                 }
+                // Keep a one-element list apart from a single value
+                if matches!(self, CompositeValue::List(values) if values.len() == 1) {
+                    write!(f, ",")?;
+                }
                 Ok(())
             }
             CompositeValue::Dict(entries) => {
@@ -226,10 +426,117 @@ impl<K: Into<String>, V: Into<CompositeValue>> From<(K, V)> for Parameter {
     }
 }
 
+impl Parameter {
+    /// Create a basic integer parameter
+    pub fn int(value: i64) -> Self {
+        Self::Basic(Value::Int(value))
+    }
+
+    /// Create a basic float parameter
+    pub fn float(value: f64) -> Self {
+        Self::Basic(Value::Float(value))
+    }
+
+    /// Create a basic boolean parameter
+    pub fn bool(value: bool) -> Self {
+        Self::Basic(Value::Bool(value))
+    }
+
+    /// Create a basic string parameter
+    pub fn string(value: impl Into<String>) -> Self {
+        Self::Basic(Value::String(value.into()))
+    }
+
+    /// Create a composite list parameter, e.g. `name(1, 2)`
+    ///
+    /// # Arguments
+    /// * `name` - The parameter name
+    /// * `values` - The list items
+    pub fn list<T: Into<Value>>(name: impl Into<String>, values: impl IntoIterator<Item = T>) -> Self {
+        Self::Composite(name.into(), CompositeValue::List(values.into_iter().map(Into::into).collect()))
+    }
+
+    /// Create a composite dictionary parameter, e.g. `name(x: 1, y: 2)`
+    ///
+    /// # Arguments
+    /// * `name` - The parameter name
+    /// * `entries` - The dictionary entries, in order
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::command::Parameter;
+    ///
+    /// let pos = Parameter::dict("pos", [("x", 1), ("y", 2)]);
+    /// assert_eq!(pos.to_string(), "pos(x: 1, y: 2)");
+    /// assert!(pos.is_composite_dict());
+    /// ```
+    pub fn dict<K: Into<String>, T: Into<Value>>(
+        name: impl Into<String>,
+        entries: impl IntoIterator<Item = (K, T)>,
+    ) -> Self {
+        let entries = entries.into_iter().map(|(k, v)| (k.into(), v.into())).collect();
+        Self::Composite(name.into(), CompositeValue::Dict(entries))
+    }
+
+    /// Whether this is a basic parameter
+    pub fn is_basic(&self) -> bool {
+        matches!(self, Parameter::Basic(_))
+    }
+
+    /// Whether this is a composite parameter of any kind
+    pub fn is_composite(&self) -> bool {
+        matches!(self, Parameter::Composite(..))
+    }
+
+    /// Whether this is a basic integer parameter
+    pub fn is_int(&self) -> bool {
+        matches!(self, Parameter::Basic(Value::Int(_)))
+    }
+
+    /// Whether this is a basic float parameter
+    pub fn is_float(&self) -> bool {
+        matches!(self, Parameter::Basic(Value::Float(_)))
+    }
+
+    /// Whether this is a basic boolean parameter
+    pub fn is_bool(&self) -> bool {
+        matches!(self, Parameter::Basic(Value::Bool(_)))
+    }
+
+    /// Whether this is a basic string parameter
+    pub fn is_string(&self) -> bool {
+        matches!(self, Parameter::Basic(Value::String(_)))
+    }
+
+    /// Whether this is a single-value composite, e.g. `name(1)`
+    pub fn is_composite_single(&self) -> bool {
+        matches!(self, Parameter::Composite(_, CompositeValue::Single(_)))
+    }
+
+    /// Whether this is a composite list, e.g. `name(1, 2)`
+    pub fn is_composite_list(&self) -> bool {
+        matches!(self, Parameter::Composite(_, CompositeValue::List(_)))
+    }
+
+    /// Whether this is a composite dictionary, e.g. `name(x: 1)`
+    pub fn is_composite_dict(&self) -> bool {
+        matches!(self, Parameter::Composite(_, CompositeValue::Dict(_)))
+    }
+
+    /// Whether this is a composite set, e.g. `name{1, 2}`
+    pub fn is_composite_set(&self) -> bool {
+        matches!(self, Parameter::Composite(_, CompositeValue::Set(_)))
+    }
+}
+
 impl fmt::Display for Parameter {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Parameter::Basic(value) => write!(f, "{}", value),
+            Parameter::Composite(name, value @ CompositeValue::Set(_)) => {
+                write!(f, "{}{{{}}}", name, value)
+            }
             Parameter::Composite(name, value) => write!(f, "{}({})", name, value),
         }
     }
@@ -239,13 +546,40 @@ impl fmt::Display for Parameter {
 ///
 /// Commands are the fundamental units of KoiLang files, consisting of a name
 /// and zero or more parameters. They can represent actions, text content, or annotations.
-#[derive(Debug, Clone, PartialEq)]
+///
+/// Two commands are equal when their names and parameters are equal; where
+/// they came from (see [`Command::span`]), the recorded `#` depth of an
+/// annotation (see [`Command::annotation_level`]) and the recorded
+/// indentation (see [`Command::indent`]) are not compared.
+#[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Command {
     /// The command name (e.g., "character", "background", "@text")
     pub name: String,
     /// List of command parameters
     pub params: Vec<Parameter>,
+    /// Source position (line, column) of a parsed command, both 1-based
+    ///
+    /// Set by the parser with `ParserConfig::record_spans`.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub span: Option<(usize, usize)>,
+    /// Number of leading `#` characters of a parsed annotation
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub annotation_level: Option<usize>,
+    /// Leading whitespace of a parsed command line, if recorded
+    ///
+    /// Set by the parser with `ParserConfig::record_command_indent`.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub indent: Option<String>,
+}
+
+impl PartialEq for Command {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.params == other.params
+    }
 }
 
 impl Command {
@@ -276,6 +610,9 @@ impl Command {
         Self {
             name: name.into(),
             params,
+            span: None,
+            annotation_level: None,
+            indent: None,
         }
     }
 
@@ -325,6 +662,31 @@ impl Command {
         Self::new("@annotation", vec![Parameter::from(content.into())])
     }
 
+    /// Create an annotation command that records its `#` depth
+    ///
+    /// The writer re-emits the annotation with the same number of `#`
+    /// characters, as long as that is still above its command threshold. The
+    /// parser creates annotations this way.
+    ///
+    /// # Arguments
+    /// * `content` - The annotation content (can be `&str` or `String`)
+    /// * `level` - Number of leading `#` characters
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::command::Command;
+    ///
+    /// let doc_comment = Command::new_annotation_with_level("Draws a line", 3);
+    /// assert_eq!(doc_comment.annotation_level(), Some(3));
+    /// assert_eq!(doc_comment, Command::new_annotation("Draws a line"));
+    /// ```
+    pub fn new_annotation_with_level(content: impl Into<String>, level: usize) -> Self {
+        let mut annotation = Self::new_annotation(content);
+        annotation.annotation_level = Some(level);
+        annotation
+    }
+
     /// Create a number command with integer value and additional parameters
     ///
     /// This is a convenience method for creating commands that start with a number.
@@ -360,6 +722,220 @@ impl Command {
     pub fn params(&self) -> &[Parameter] {
         &self.params
     }
+
+    /// Get the `#` depth of an annotation command
+    ///
+    /// Returns `None` if this is not an annotation or the level was not recorded,
+    /// e.g. for annotations created with [`Command::new_annotation`].
+    pub fn annotation_level(&self) -> Option<usize> {
+        if self.name != "@annotation" {
+            return None;
+        }
+        self.annotation_level
+    }
+
+    /// Compare two commands by content
+    ///
+    /// Text commands are equal when their text is equal, and annotations when
+    /// their content is equal. Other commands, including number commands, are
+    /// compared by name and parameters.
+    ///
+    /// # Arguments
+    /// * `other` - The command to compare with
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::command::Command;
+    ///
+    /// let parsed: Command = "## note".parse()?;
+    /// assert!(parsed.content_eq(&Command::new_annotation("note")));
+    /// assert!(!parsed.content_eq(&Command::new_text("note")));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn content_eq(&self, other: &Self) -> bool {
+        match (self.special_content(), other.special_content()) {
+            (Some(content), Some(other_content)) => {
+                self.name == other.name && content == other_content
+            }
+            _ => self == other,
+        }
+    }
+
+    /// Get the content of a text or annotation command
+    fn special_content(&self) -> Option<&str> {
+        if self.name != "@text" && self.name != "@annotation" {
+            return None;
+        }
+        match self.params.first() {
+            Some(Parameter::Basic(Value::String(content))) => Some(content),
+            _ => None,
+        }
+    }
+
+    /// Return this command with a different name
+    ///
+    /// # Arguments
+    /// * `name` - The new command name
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::command::Command;
+    ///
+    /// let cmd = Command::new("draw", vec![]).renamed("paint");
+    /// assert_eq!(cmd.name(), "paint");
+    /// ```
+    pub fn renamed(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Return this command with a parameter appended
+    ///
+    /// # Arguments
+    /// * `param` - The parameter to append
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::command::{Command, Parameter};
+    ///
+    /// let cmd = Command::new("draw", vec![]).with_param(Parameter::from("Line"));
+    /// assert_eq!(cmd.params().len(), 1);
+    /// ```
+    pub fn with_param(mut self, param: Parameter) -> Self {
+        self.params.push(param);
+        self
+    }
+
+    /// Return this command keeping only the parameters matching `predicate`
+    ///
+    /// # Arguments
+    /// * `predicate` - Returns `true` for parameters to keep
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::command::{Command, Parameter};
+    ///
+    /// let cmd = Command::new("draw", vec![Parameter::from("Line"), Parameter::from(2)])
+    ///     .filter_params(|param| !param.is_int());
+    /// assert_eq!(cmd.params().len(), 1);
+    /// ```
+    pub fn filter_params(mut self, predicate: impl Fn(&Parameter) -> bool) -> Self {
+        self.params.retain(predicate);
+        self
+    }
+
+    /// Merge the parameters of another command into this one
+    ///
+    /// Useful for layering, e.g. a base command plus overrides. Parameters of
+    /// `other` are merged in order:
+    /// * basic parameters are appended
+    /// * a composite whose name matches a composite of this command is merged
+    ///   into the first one: if both are dictionaries, entries of `other`
+    ///   replace the first entry with the same key and new keys are appended;
+    ///   otherwise the value of `other` replaces the existing value
+    /// * other composites are appended
+    ///
+    /// # Arguments
+    /// * `other` - The command whose parameters take precedence
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::command::Command;
+    ///
+    /// let mut cmd: Command = "#config opts(width: 80, tabs: false) strict".parse()?;
+    /// cmd.merge_params(&"#config opts(tabs: true, color: auto) size(2)".parse()?);
+    /// assert_eq!(cmd.to_string(), "config opts(width: 80, tabs: true, color: auto) strict size(2)");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn merge_params(&mut self, other: &Command) {
+        for param in &other.params {
+            let Parameter::Composite(name, value) = param else {
+                self.params.push(param.clone());
+                continue;
+            };
+            let existing = self.params.iter_mut().find_map(|param| match param {
+                Parameter::Composite(existing, value) if existing == name => Some(value),
+                _ => None,
+            });
+            match (existing, value) {
+                (Some(CompositeValue::Dict(entries)), CompositeValue::Dict(overrides)) => {
+                    for (key, value) in overrides {
+                        match entries.iter_mut().find(|(existing, _)| existing == key) {
+                            Some(entry) => entry.1 = value.clone(),
+                            None => entries.push((key.clone(), value.clone())),
+                        }
+                    }
+                }
+                (Some(existing), value) => *existing = value.clone(),
+                (None, _) => self.params.push(param.clone()),
+            }
+        }
+    }
+
+    /// Get the source position of this command
+    ///
+    /// Returns the 1-based line and byte column where a command read by a
+    /// [`Parser`](crate::parser::Parser) starts, if the parser records spans
+    /// (see `ParserConfig::record_spans`), or `None` for a command built in
+    /// code.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::{Parser, ParserConfig, StringInputSource};
+    /// use koicore::Command;
+    ///
+    /// let input = StringInputSource::new("Hello\n  #wave 1\n");
+    /// let config = ParserConfig::default().with_record_spans(true);
+    /// let mut parser = Parser::new(input, config);
+    /// assert_eq!(parser.next_command()?.unwrap().span(), Some((1, 1)));
+    /// assert_eq!(parser.next_command()?.unwrap().span(), Some((2, 3)));
+    /// assert_eq!(Command::new_text("Hello").span(), None);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn span(&self) -> Option<(usize, usize)> {
+        self.span
+    }
+
+    /// Get the shape signature of this command
+    ///
+    /// The signature combines the command name with the type of each parameter,
+    /// ignoring the actual values. Basic parameters use [`Value::type_name`] and
+    /// composite parameters use their kind (`single`, `list`, `dict` or `set`).
+    /// Commands with the same shape share a signature, which makes it usable as
+    /// a key for grouping commands.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::command::{Command, Parameter};
+    ///
+    /// let cmd = Command::new("draw", vec![
+    ///     Parameter::from("Line"),
+    ///     Parameter::from(2),
+    ///     Parameter::from(("pos", vec![0, 0])),
+    /// ]);
+    /// assert_eq!(cmd.signature(), "draw(string,int,list)");
+    /// ```
+    pub fn signature(&self) -> String {
+        let params: Vec<&str> = self
+            .params
+            .iter()
+            .map(|param| match param {
+                Parameter::Basic(value) => value.type_name(),
+                Parameter::Composite(_, CompositeValue::Single(_)) => "single",
+                Parameter::Composite(_, CompositeValue::List(_)) => "list",
+                Parameter::Composite(_, CompositeValue::Dict(_)) => "dict",
+                Parameter::Composite(_, CompositeValue::Set(_)) => "set",
+            })
+            .collect();
+        format!("{}({})", self.name, params.join(","))
+    }
 }
 
 impl fmt::Display for Command {
@@ -373,6 +949,48 @@ impl fmt::Display for Command {
     }
 }
 
+/// Compact positional JSON, e.g. for sending many commands over the network
+#[cfg(feature = "json")]
+impl Command {
+    /// Serialize this command as a compact JSON array
+    ///
+    /// The command is written as `[name, params]`, with parameters in the same
+    /// form as the default serde representation. This drops the `name` and
+    /// `params` keys of the default form, which adds up for large batches.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::Command;
+    ///
+    /// let cmd: Command = "#draw Line 2 pos(x: 0)".parse()?;
+    /// assert_eq!(cmd.to_compact_json()?, r#"["draw",["Line",2,{"pos":{"x":0}}]]"#);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn to_compact_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(&(&self.name, &self.params))
+    }
+
+    /// Parse a command from the compact JSON form of [`Command::to_compact_json`]
+    ///
+    /// # Arguments
+    /// * `json` - A `[name, params]` JSON array
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::{Command, Parameter};
+    ///
+    /// let cmd = Command::from_compact_json(r#"["move",[1,"fast"]]"#)?;
+    /// assert_eq!(cmd, Command::new("move", vec![Parameter::from(1), Parameter::from("fast")]));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_compact_json(json: &str) -> serde_json::Result<Self> {
+        let (name, params): (String, Vec<Parameter>) = serde_json::from_str(json)?;
+        Ok(Self::new(name, params))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -383,6 +1001,27 @@ mod tests {
         assert_eq!(format!("{}", cmd), "hello world");
     }
 
+    #[test]
+    fn test_content_eq() {
+        let input = crate::parser::StringInputSource::new("Hello there.");
+        let mut parser = crate::parser::Parser::new(input, Default::default());
+        let text = parser.next_command().unwrap().unwrap();
+        assert!(text.content_eq(&Command::new_text("Hello there.")));
+        assert!(!text.content_eq(&Command::new_text("Hello.")));
+        assert!(!text.content_eq(&Command::new_annotation("Hello there.")));
+
+        let annotation: Command = "### doc".parse().unwrap();
+        assert!(annotation.content_eq(&Command::new_annotation("doc")));
+        assert_eq!(annotation, Command::new_annotation("doc"));
+        assert!(annotation.content_eq(&Command::new_annotation_with_level("doc", 2)));
+        assert!(!annotation.content_eq(&Command::new_annotation("other")));
+
+        let number: Command = "#114 514".parse().unwrap();
+        assert!(number.content_eq(&Command::new_number(114, vec![Parameter::from(514)])));
+        assert!(!number.content_eq(&Command::new_number(114, vec![])));
+        assert!(!number.content_eq(&Command::new_text("114 514")));
+    }
+
     #[test]
     fn test_command_display_text() {
         let cmd = Command::new_text("hello world");
@@ -401,6 +1040,43 @@ mod tests {
         assert_eq!(format!("{}", cv), "10");
         let cv = Parameter::from(("a", 10));
         assert_eq!(format!("{}", cv), "a(10)");
+        let cv = Parameter::from(("s", CompositeValue::Set(vec![Value::Int(1), Value::Int(2)])));
+        assert_eq!(format!("{}", cv), "s{1, 2}");
+    }
+
+    #[test]
+    fn test_value_canonical_cmp() {
+        let mut values = vec![
+            Value::String("b".to_string()),
+            Value::Float(1.5),
+            Value::Int(3),
+            Value::String("a".to_string()),
+            Value::Bool(true),
+            Value::Int(-1),
+        ];
+        values.sort_by(Value::canonical_cmp);
+        assert_eq!(
+            values,
+            vec![
+                Value::Bool(true),
+                Value::Int(-1),
+                Value::Int(3),
+                Value::Float(1.5),
+                Value::String("a".to_string()),
+                Value::String("b".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_value_display_ambiguous_literals() {
+        // Strings that would re-parse as another type are quoted
+        assert_eq!(Value::from("0x10").to_string(), "\"0x10\"");
+        assert_eq!(Value::from("true").to_string(), "\"true\"");
+        assert_eq!(Value::from("false").to_string(), "\"false\"");
+        assert_eq!(Value::from("").to_string(), "\"\"");
+        assert_eq!(Value::from("truthy").to_string(), "truthy");
+        assert_eq!(Value::from("_x10").to_string(), "_x10");
     }
 
     #[test]
@@ -418,6 +1094,23 @@ mod tests {
         assert_eq!(format!("{}", v), "1.23");
     }
 
+    #[test]
+    fn test_value_conversions() {
+        assert_eq!(Value::from(-7i32), Value::Int(-7));
+        assert_eq!(Value::from(u32::MAX), Value::Int(4294967295));
+        assert_eq!(Value::from(1i64 << 40), Value::Int(1 << 40));
+        assert_eq!(Value::from(0.5f32), Value::Float(0.5));
+        assert_eq!(Value::from(0.1f64), Value::Float(0.1));
+        assert_eq!(Value::from(true), Value::Bool(true));
+        assert_eq!(Value::from("text"), Value::String("text".to_string()));
+        assert_eq!(Value::from(String::from("owned")), Value::String("owned".to_string()));
+        assert_eq!(Value::from('k'), Value::String("k".to_string()));
+
+        // Unsuffixed literals still convert as before
+        assert_eq!(Parameter::from(2), Parameter::Basic(Value::Int(2)));
+        assert_eq!(Parameter::from(2.5), Parameter::Basic(Value::Float(2.5)));
+    }
+
     #[test]
     fn test_composite_value_conversions() {
         // Test From<Vec<T>>
@@ -463,6 +1156,20 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_composite_get_all() {
+        let cmd: Command = "#p opts(x: 1, y: 0, x: 2)".parse().unwrap();
+        let Parameter::Composite(_, dict) = &cmd.params[0] else {
+            panic!("Expected composite parameter");
+        };
+        assert_eq!(dict.get_all("x").collect::<Vec<_>>(), [&Value::Int(1), &Value::Int(2)]);
+        assert_eq!(dict.get_all("y").count(), 1);
+        assert_eq!(dict.get_all("z").count(), 0);
+        // Duplicate keys are written back as they were
+        assert_eq!(cmd.to_string(), "p opts(x: 1, y: 0, x: 2)");
+        assert_eq!(CompositeValue::from(vec![1]).get_all("x").count(), 0);
+    }
+
     #[test]
     fn test_composite_value_display() {
         // Test List display
@@ -480,6 +1187,139 @@ mod tests {
         let single = CompositeValue::Single(Value::Int(42));
         assert_eq!(format!("{}", single), "42");
     }
+
+    #[test]
+    fn test_parameter_constructors() {
+        assert_eq!(Parameter::int(-3), Parameter::Basic(Value::Int(-3)));
+        assert_eq!(Parameter::float(0.5), Parameter::Basic(Value::Float(0.5)));
+        assert_eq!(Parameter::bool(true), Parameter::Basic(Value::Bool(true)));
+        assert_eq!(Parameter::string("a b"), Parameter::Basic(Value::String("a b".to_string())));
+        assert_eq!(
+            Parameter::list("tags", ["a", "b"]),
+            Parameter::Composite("tags".to_string(), CompositeValue::List(vec!["a".into(), "b".into()]))
+        );
+        assert_eq!(
+            Parameter::dict("pos", [("x", 1), ("x", 2)]),
+            Parameter::Composite(
+                "pos".to_string(),
+                CompositeValue::Dict(vec![("x".to_string(), 1.into()), ("x".to_string(), 2.into())])
+            )
+        );
+        assert_eq!(Parameter::list("empty", Vec::<i64>::new()).to_string(), "empty()");
+    }
+
+    #[test]
+    fn test_parameter_predicates() {
+        let params = [
+            Parameter::int(1),
+            Parameter::float(1.0),
+            Parameter::bool(false),
+            Parameter::string("s"),
+            Parameter::from(("one", 1)),
+            Parameter::list("list", [1, 2]),
+            Parameter::dict("dict", [("k", 1)]),
+            Parameter::Composite("set".to_string(), CompositeValue::Set(vec![1.into()])),
+        ];
+        let predicates: [fn(&Parameter) -> bool; 8] = [
+            Parameter::is_int,
+            Parameter::is_float,
+            Parameter::is_bool,
+            Parameter::is_string,
+            Parameter::is_composite_single,
+            Parameter::is_composite_list,
+            Parameter::is_composite_dict,
+            Parameter::is_composite_set,
+        ];
+        // Each predicate matches exactly its own kind of parameter
+        for (i, predicate) in predicates.iter().enumerate() {
+            for (j, param) in params.iter().enumerate() {
+                assert_eq!(predicate(param), i == j, "{param}");
+            }
+        }
+        for (i, param) in params.iter().enumerate() {
+            assert_eq!(param.is_basic(), i < 4);
+            assert_eq!(param.is_composite(), i >= 4);
+        }
+    }
+
+    #[test]
+    fn test_command_combinators() {
+        let cmd: Command = "#draw Line 2 pos(0, 0) color(red)".parse().unwrap();
+        let cmd = cmd
+            .renamed("paint")
+            .filter_params(Parameter::is_composite)
+            .with_param(Parameter::from(("width", 3)));
+
+        assert_eq!(cmd.name(), "paint");
+        assert_eq!(cmd.to_string(), "paint pos(0, 0) color(red) width(3)");
+    }
+
+    #[test]
+    fn test_merge_params() {
+        let mut cmd: Command = "#config opts(width: 80, tabs: false, width: 100) name(base) 1".parse().unwrap();
+        let overrides: Command = "#config opts(tabs: true, color: auto, tabs: false) name(site) 2 extra(a, b)".parse().unwrap();
+        cmd.merge_params(&overrides);
+
+        let expected: Command = "#config opts(width: 80, tabs: false, width: 100, color: auto) name(site) 1 2 extra(a, b)"
+            .parse()
+            .unwrap();
+        assert_eq!(cmd, expected);
+
+        // A dictionary replaces a composite of another kind, and vice versa
+        let mut cmd: Command = "#config opts(1, 2) size(w: 1)".parse().unwrap();
+        cmd.merge_params(&"#config opts(x: 1) size(3)".parse().unwrap());
+        assert_eq!(cmd.to_string(), "config opts(x: 1) size(3)");
+    }
+
+    #[test]
+    fn test_command_signature() {
+        let a = Command::new("draw", vec![
+            Parameter::from("Line"),
+            Parameter::from(1),
+            Parameter::from(("style", CompositeValue::Dict(vec![("w".to_string(), Value::Float(1.5))]))),
+        ]);
+        let b = Command::new("draw", vec![
+            Parameter::from("Circle"),
+            Parameter::from(-7),
+            Parameter::from(("color", CompositeValue::Dict(vec![]))),
+        ]);
+        assert_eq!(a.signature(), "draw(string,int,dict)");
+        assert_eq!(a.signature(), b.signature());
+
+        let c = Command::new("draw", vec![Parameter::from("Line"), Parameter::from(1.0)]);
+        assert_ne!(a.signature(), c.signature());
+        assert_eq!(Command::new("end", vec![]).signature(), "end()");
+    }
+}
+
+#[cfg(feature = "serde")]
+fn serialize_float<S>(f: &f64, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    if !f.is_finite() {
+        return Err(serde::ser::Error::custom(format_args!(
+            "cannot serialize non-finite float {}, only finite floats are supported",
+            f
+        )));
+    }
+    serializer.serialize_f64(*f)
+}
+
+#[cfg(all(feature = "serde", feature = "chrono"))]
+fn serialize_datetime<S>(dt: &DateTime<FixedOffset>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&format_datetime(dt))
+}
+
+#[cfg(all(feature = "serde", feature = "chrono"))]
+fn serialize_duration<S>(d: &TimeDelta, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&format_duration(d))
 }
 
 #[cfg(feature = "serde")]
@@ -490,7 +1330,7 @@ impl Serialize for CompositeValue {
     {
         match self {
             CompositeValue::Single(v) => v.serialize(serializer),
-            CompositeValue::List(l) => l.serialize(serializer),
+            CompositeValue::List(l) | CompositeValue::Set(l) => l.serialize(serializer),
             CompositeValue::Dict(d) => {
                 use serde::ser::SerializeMap;
                 let mut map = serializer.serialize_map(Some(d.len()))?;
@@ -525,6 +1365,14 @@ impl<'de> Deserialize<'de> for CompositeValue {
                 Ok(CompositeValue::Single(Value::Int(v)))
             }
 
+            fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                let v = i64::try_from(v).map_err(|_| E::custom("integer out of range"))?;
+                Ok(CompositeValue::Single(Value::Int(v)))
+            }
+
             fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
             where
                 E: de::Error,
@@ -620,6 +1468,14 @@ impl<'de> Deserialize<'de> for Parameter {
                 Ok(Parameter::Basic(Value::Int(v)))
             }
 
+            fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                let v = i64::try_from(v).map_err(|_| E::custom("integer out of range"))?;
+                Ok(Parameter::Basic(Value::Int(v)))
+            }
+
             fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
             where
                 E: de::Error,
@@ -0,0 +1,219 @@
+//! Conversion between KoiLang and other formats
+//!
+//! [`convert`] reads a whole document in one [`Format`] and writes it in
+//! another, e.g. KoiLang to JSON. JSON documents are arrays of commands in
+//! the serde representation of [`Command`].
+
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use crate::command::Command;
+use crate::parser::{BufReadWrapper, ParseError, Parser, ParserConfig, TextInputSource};
+use crate::writer::{Writer, WriterConfig};
+
+/// A document format supported by [`convert`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Format {
+    /// KoiLang source text
+    Koi,
+    /// A JSON array of commands
+    Json,
+}
+
+/// Options for [`convert`]
+#[derive(Debug, Clone, Default)]
+pub struct ConvertConfig {
+    /// Configuration for reading KoiLang input
+    pub parser: ParserConfig,
+    /// Configuration for writing KoiLang output
+    pub writer: WriterConfig,
+    /// Whether to pretty-print JSON output
+    pub pretty: bool,
+}
+
+/// An error while converting a document
+#[derive(Debug)]
+pub enum ConvertError {
+    /// Reading the input or writing the output failed
+    Io(io::Error),
+    /// The KoiLang input is invalid
+    Parse(Box<ParseError>),
+    /// The JSON input is invalid, or the output could not be serialized
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConvertError::Io(e) => write!(f, "ConvertError: {}", e),
+            ConvertError::Parse(e) => write!(f, "ConvertError: {}", e),
+            ConvertError::Json(e) => write!(f, "ConvertError: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConvertError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConvertError::Io(e) => Some(e),
+            ConvertError::Parse(e) => Some(e.as_ref()),
+            ConvertError::Json(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for ConvertError {
+    fn from(e: io::Error) -> Self {
+        ConvertError::Io(e)
+    }
+}
+
+impl From<Box<ParseError>> for ConvertError {
+    fn from(e: Box<ParseError>) -> Self {
+        ConvertError::Parse(e)
+    }
+}
+
+impl From<serde_json::Error> for ConvertError {
+    fn from(e: serde_json::Error) -> Self {
+        ConvertError::Json(e)
+    }
+}
+
+/// Convert a document from one format to another
+///
+/// # Arguments
+/// * `input` - The document to read
+/// * `input_fmt` - Format of `input`
+/// * `output` - Where to write the converted document
+/// * `output_fmt` - Format to write
+/// * `config` - Parser, writer and JSON options
+///
+/// # Examples
+///
+/// ```rust
+/// use koicore::convert::{ConvertConfig, Format};
+///
+/// let mut json = Vec::new();
+/// koicore::convert(&b"#move 1 2"[..], Format::Koi, &mut json, Format::Json, &ConvertConfig::default())?;
+/// assert_eq!(
+///     String::from_utf8(json).unwrap(),
+///     r#"[{"name":"move","params":[1,2]}]"#
+/// );
+/// # Ok::<(), koicore::convert::ConvertError>(())
+/// ```
+pub fn convert(
+    input: impl BufRead,
+    input_fmt: Format,
+    output: impl Write,
+    output_fmt: Format,
+    config: &ConvertConfig,
+) -> Result<(), ConvertError> {
+    match input_fmt {
+        Format::Koi => convert_source(BufReadWrapper(input), output, output_fmt, config),
+        Format::Json => {
+            let commands: Vec<Command> = serde_json::from_reader(input)?;
+            write_commands(&commands, output, output_fmt, config)
+        }
+    }
+}
+
+/// Convert a KoiLang document read from an input source
+///
+/// Like [`convert`] with KoiLang input, for sources that are not plain
+/// readers, such as a [`FileInputSource`](crate::parser::FileInputSource)
+/// with encoding detection.
+///
+/// # Arguments
+/// * `source` - The KoiLang source to read
+/// * `output` - Where to write the converted document
+/// * `output_fmt` - Format to write
+/// * `config` - Parser, writer and JSON options
+pub fn convert_source<T: TextInputSource>(
+    source: T,
+    output: impl Write,
+    output_fmt: Format,
+    config: &ConvertConfig,
+) -> Result<(), ConvertError> {
+    let mut parser = Parser::new(source, config.parser.clone());
+    let mut commands = Vec::new();
+    while let Some(command) = parser.next_command()? {
+        commands.push(command);
+    }
+    write_commands(&commands, output, output_fmt, config)
+}
+
+/// Write already parsed commands in the given format
+///
+/// Used by [`convert`] for its output, and useful when the commands are
+/// processed between reading and writing.
+///
+/// # Arguments
+/// * `commands` - The commands to write
+/// * `output` - Where to write the document
+/// * `output_fmt` - Format to write
+/// * `config` - Writer and JSON options
+pub fn write_commands(
+    commands: &[Command],
+    mut output: impl Write,
+    output_fmt: Format,
+    config: &ConvertConfig,
+) -> Result<(), ConvertError> {
+    match output_fmt {
+        Format::Koi => {
+            let mut writer = Writer::new(&mut output, config.writer.clone());
+            for command in commands {
+                writer.write_command(command)?;
+            }
+        }
+        Format::Json if config.pretty => serde_json::to_writer_pretty(&mut output, commands)?,
+        Format::Json => serde_json::to_writer(&mut output, commands)?,
+    }
+    output.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "#draw Line 2 pos(x: 0x10, y: -1.5) tags(a, \"b c\")\nSome text\n## note\n#end\n";
+
+    #[test]
+    fn test_convert_round_trip() {
+        let config = ConvertConfig::default();
+        let mut json = Vec::new();
+        convert(SOURCE.as_bytes(), Format::Koi, &mut json, Format::Json, &config).unwrap();
+        let mut koi = Vec::new();
+        convert(&json[..], Format::Json, &mut koi, Format::Koi, &config).unwrap();
+
+        let parse = |text: &str| {
+            let input = crate::parser::StringInputSource::new(text);
+            Parser::new(input, ParserConfig::default())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap()
+        };
+        let koi = String::from_utf8(koi).unwrap();
+        assert_eq!(parse(&koi), parse(SOURCE));
+        assert_eq!(parse(&koi).len(), 4);
+
+        // Pretty output holds the same commands
+        let pretty_config = ConvertConfig { pretty: true, ..Default::default() };
+        let mut pretty = Vec::new();
+        convert(SOURCE.as_bytes(), Format::Koi, &mut pretty, Format::Json, &pretty_config).unwrap();
+        assert!(pretty.contains(&b'\n'));
+        let from_pretty: Vec<Command> = serde_json::from_slice(&pretty).unwrap();
+        let from_compact: Vec<Command> = serde_json::from_slice(&json).unwrap();
+        assert_eq!(from_pretty, from_compact);
+    }
+
+    #[test]
+    fn test_convert_errors() {
+        let config = ConvertConfig::default();
+        let result = convert(&b"#draw pos(x: 1"[..], Format::Koi, io::sink(), Format::Json, &config);
+        assert!(matches!(result, Err(ConvertError::Parse(_))));
+        let result = convert(&b"[{\"name\": 1}]"[..], Format::Json, io::sink(), Format::Koi, &config);
+        assert!(matches!(result, Err(ConvertError::Json(_))));
+    }
+}
@@ -0,0 +1,332 @@
+//! Structural diff between command lists
+//!
+//! This module compares two lists of commands, e.g. the output of a generator
+//! before and after a change, and reports which commands were added, removed
+//! or changed.
+
+use std::fmt;
+
+use crate::command::{Command, Parameter};
+
+/// A single difference between two command lists
+///
+/// Indices are 0-based positions in the old or new list.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandDiff {
+    /// A command only present in the new list
+    Added {
+        /// Position in the new list
+        index: usize,
+        /// The added command
+        command: Command,
+    },
+    /// A command only present in the old list
+    Removed {
+        /// Position in the old list
+        index: usize,
+        /// The removed command
+        command: Command,
+    },
+    /// A command with the same name whose parameters differ
+    Changed {
+        /// Position in the old list
+        old_index: usize,
+        /// Position in the new list
+        new_index: usize,
+        /// Command name
+        name: String,
+        /// Parameters that differ, by position
+        params: Vec<ParamChange>,
+    },
+}
+
+/// A parameter that differs between two versions of a command
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamChange {
+    /// Position of the parameter (0-based)
+    pub position: usize,
+    /// Old parameter, or `None` if it was added
+    pub old: Option<Parameter>,
+    /// New parameter, or `None` if it was removed
+    pub new: Option<Parameter>,
+}
+
+/// Compute the structural diff between two command lists
+///
+/// Commands equal in both lists are matched first (as a longest common
+/// subsequence) and are not reported. Between matched commands, a removed and
+/// an added command with the same name are paired into a
+/// [`CommandDiff::Changed`] entry listing the differing parameters; the rest
+/// are reported as added or removed. Entries are ordered by position.
+///
+/// # Examples
+///
+/// ```rust
+/// use koicore::{Command, diff};
+/// use koicore::diff::CommandDiff;
+///
+/// let old = vec![Command::new("draw", vec![2.into()]), Command::new("end", vec![])];
+/// let new = vec![Command::new("draw", vec![3.into()]), Command::new("end", vec![])];
+///
+/// let changes = diff(&old, &new);
+/// assert_eq!(changes.len(), 1);
+/// assert!(matches!(&changes[0], CommandDiff::Changed { name, .. } if name == "draw"));
+/// ```
+pub fn diff(old: &[Command], new: &[Command]) -> Vec<CommandDiff> {
+    let mut matches = Vec::new();
+    common_subsequence(old, new, (0, 0), &mut matches);
+
+    let mut diffs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    for (old_index, new_index) in matches.into_iter().chain([(old.len(), new.len())]) {
+        let removed: Vec<usize> = (i..old_index).collect();
+        let added: Vec<usize> = (j..new_index).collect();
+        pair_changes(old, new, &removed, &added, &mut diffs);
+        (i, j) = (old_index + 1, new_index + 1);
+    }
+    diffs
+}
+
+/// Find a longest common subsequence, as matched `(old, new)` index pairs
+///
+/// Uses Hirschberg's divide and conquer, so memory stays linear in the list
+/// lengths. `start` is the position of the slices in the full lists.
+fn common_subsequence(
+    old: &[Command],
+    new: &[Command],
+    start: (usize, usize),
+    matches: &mut Vec<(usize, usize)>,
+) {
+    if old.is_empty() || new.is_empty() {
+        return;
+    }
+    if let [command] = old {
+        if let Some(j) = new.iter().position(|other| other == command) {
+            matches.push((start.0, start.1 + j));
+        }
+        return;
+    }
+
+    // Split the new list where the halves of the old list share the most
+    let mid = old.len() / 2;
+    let front = lcs_lengths(old[..mid].iter(), new.iter());
+    let back = lcs_lengths(old[mid..].iter().rev(), new.iter().rev());
+    let split = (0..=new.len())
+        .max_by_key(|&k| (front[k] + back[new.len() - k], std::cmp::Reverse(k)))
+        .unwrap();
+
+    common_subsequence(&old[..mid], &new[..split], start, matches);
+    common_subsequence(
+        &old[mid..],
+        &new[split..],
+        (start.0 + mid, start.1 + split),
+        matches,
+    );
+}
+
+/// Length of the longest common subsequence of `old` and each prefix of `new`
+///
+/// Entry `j` of the result is for the first `j` commands of `new`.
+fn lcs_lengths<'a>(
+    old: impl Iterator<Item = &'a Command>,
+    new: impl Iterator<Item = &'a Command> + Clone,
+) -> Vec<usize> {
+    let mut row = vec![0; new.clone().count() + 1];
+    for command in old {
+        // The entry of the previous row left of the one being updated
+        let mut diagonal = 0;
+        for (j, other) in new.clone().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if command == other {
+                diagonal + 1
+            } else {
+                above.max(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row
+}
+
+/// Report a run of unmatched commands, pairing commands with the same name
+fn pair_changes(
+    old: &[Command],
+    new: &[Command],
+    removed: &[usize],
+    added: &[usize],
+    diffs: &mut Vec<CommandDiff>,
+) {
+    let mut added = added.iter().copied();
+    for &old_index in removed {
+        // Added commands before the next same-named one are reported as is
+        let paired = added
+            .clone()
+            .position(|new_index| new[new_index].name == old[old_index].name);
+        let Some(skip) = paired else {
+            diffs.push(CommandDiff::Removed {
+                index: old_index,
+                command: old[old_index].clone(),
+            });
+            continue;
+        };
+        for new_index in added.by_ref().take(skip) {
+            diffs.push(CommandDiff::Added {
+                index: new_index,
+                command: new[new_index].clone(),
+            });
+        }
+        let new_index = added.next().unwrap();
+        diffs.push(CommandDiff::Changed {
+            old_index,
+            new_index,
+            name: old[old_index].name.clone(),
+            params: param_changes(&old[old_index].params, &new[new_index].params),
+        });
+    }
+    for new_index in added {
+        diffs.push(CommandDiff::Added {
+            index: new_index,
+            command: new[new_index].clone(),
+        });
+    }
+}
+
+/// Compare two parameter lists by position
+fn param_changes(old: &[Parameter], new: &[Parameter]) -> Vec<ParamChange> {
+    (0..old.len().max(new.len()))
+        .filter(|&position| old.get(position) != new.get(position))
+        .map(|position| ParamChange {
+            position,
+            old: old.get(position).cloned(),
+            new: new.get(position).cloned(),
+        })
+        .collect()
+}
+
+impl fmt::Display for CommandDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandDiff::Added { index, command } => write!(f, "+ [{}] {}", index, command),
+            CommandDiff::Removed { index, command } => write!(f, "- [{}] {}", index, command),
+            CommandDiff::Changed {
+                old_index,
+                new_index,
+                name,
+                params,
+            } => {
+                write!(f, "~ [{} -> {}] {}", old_index, new_index, name)?;
+                for change in params {
+                    write!(f, "\n    param {}: ", change.position)?;
+                    match &change.old {
+                        Some(param) => write!(f, "{}", param)?,
+                        None => write!(f, "(none)")?,
+                    }
+                    write!(f, " -> ")?;
+                    match &change.new {
+                        Some(param) => write!(f, "{}", param)?,
+                        None => write!(f, "(none)")?,
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_all(source: &str) -> Vec<Command> {
+        source.lines().map(|line| line.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_diff_identical() {
+        let commands = parse_all("#a 1\n#b 2");
+        assert!(diff(&commands, &commands).is_empty());
+    }
+
+    #[test]
+    fn test_diff_param_change() {
+        let old = parse_all("#begin\n#draw Line 2 pos(0, 0)\n#end");
+        let new = parse_all("#begin\n#draw Line 3 pos(0, 0) thick\n#end");
+
+        let changes = diff(&old, &new);
+        assert_eq!(changes, vec![CommandDiff::Changed {
+            old_index: 1,
+            new_index: 1,
+            name: "draw".to_string(),
+            params: vec![
+                ParamChange {
+                    position: 1,
+                    old: Some(Parameter::from(2)),
+                    new: Some(Parameter::from(3)),
+                },
+                ParamChange {
+                    position: 3,
+                    old: None,
+                    new: Some(Parameter::from("thick")),
+                },
+            ],
+        }]);
+        assert_eq!(
+            changes[0].to_string(),
+            "~ [1 -> 1] draw\n    param 1: 2 -> 3\n    param 3: (none) -> thick"
+        );
+    }
+
+    #[test]
+    fn test_diff_repeated_commands() {
+        let old = parse_all("#a\n#b\n#a\n#b\n#a");
+        let new = parse_all("#b\n#a\n#c\n#b");
+
+        // The longest common subsequence is `b a b`
+        let changes = diff(&old, &new);
+        assert_eq!(changes, vec![
+            CommandDiff::Removed {
+                index: 0,
+                command: Command::new("a", vec![]),
+            },
+            CommandDiff::Added {
+                index: 2,
+                command: Command::new("c", vec![]),
+            },
+            CommandDiff::Removed {
+                index: 4,
+                command: Command::new("a", vec![]),
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_diff_added_and_removed() {
+        let old = parse_all("#a\n#b 1\n#c");
+        let new = parse_all("#a\n#x\n#b 2\n#d");
+
+        assert_eq!(diff(&old, &new), vec![
+            CommandDiff::Added {
+                index: 1,
+                command: Command::new("x", vec![]),
+            },
+            CommandDiff::Changed {
+                old_index: 1,
+                new_index: 2,
+                name: "b".to_string(),
+                params: vec![ParamChange {
+                    position: 0,
+                    old: Some(Parameter::from(1)),
+                    new: Some(Parameter::from(2)),
+                }],
+            },
+            CommandDiff::Removed {
+                index: 2,
+                command: Command::new("c", vec![]),
+            },
+            CommandDiff::Added {
+                index: 3,
+                command: Command::new("d", vec![]),
+            },
+        ]);
+    }
+}
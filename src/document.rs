@@ -0,0 +1,501 @@
+//! Whole-document operations over command lists
+//!
+//! A [`Document`] holds the commands of a KoiLang source in order, for
+//! passes that need to see every command, such as value transformations.
+
+use std::collections::HashMap;
+
+use crate::command::{Command, CompositeValue, Parameter, Value};
+use crate::parser::{Parser, ParserConfig, TextInputSource};
+
+/// An ordered list of commands
+///
+/// With the `serde` feature, a document serializes as `{"commands": [...]}`.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Document {
+    /// The commands, in source order
+    pub commands: Vec<Command>,
+}
+
+/// Information about how a document was read
+///
+/// Saved next to the commands by [`Document::to_json_with_meta`], so a
+/// pipeline can tell where a document came from and parse it again the
+/// same way.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct DocumentMeta {
+    /// Name of the source, e.g. a file path
+    pub filename: Option<String>,
+    /// Name of the text encoding of the source, e.g. `"UTF-8"`
+    pub encoding: Option<String>,
+    /// The parser configuration the document was parsed with
+    pub config: Option<ParserConfig>,
+}
+
+impl DocumentMeta {
+    /// Record the source name, encoding and configuration of a parser
+    ///
+    /// The encoding is left unset for sources that don't decode bytes
+    /// themselves, such as in-memory strings.
+    ///
+    /// # Arguments
+    /// * `parser` - The parser the document is read with
+    pub fn from_parser<T: TextInputSource>(parser: &Parser<T>) -> Self {
+        Self {
+            filename: Some(parser.source_name()),
+            encoding: parser.encoding().map(|encoding| encoding.name().to_string()),
+            config: Some(parser.config().clone()),
+        }
+    }
+}
+
+/// A document with its metadata, as written by [`Document::to_json_with_meta`]
+#[cfg(feature = "json")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct DocumentWithMeta<M, C> {
+    #[serde(default)]
+    meta: M,
+    commands: C,
+}
+
+/// Location of a value visited by a [`ValueTransformer`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValuePath<'a> {
+    /// Name of the command holding the value (e.g. `"draw"`, `"@text"`)
+    pub command: &'a str,
+    /// Position of the parameter in the command (0-based)
+    pub position: usize,
+    /// Name of the composite parameter, or `None` for a basic parameter
+    pub composite: Option<&'a str>,
+    /// Dictionary key of the value, or `None` outside dictionaries
+    pub key: Option<&'a str>,
+}
+
+/// A pass that rewrites values in place, e.g. for redaction or normalization
+///
+/// Closures taking a [`ValuePath`] and a `&mut Value` implement this trait.
+///
+/// # Examples
+///
+/// ```rust
+/// use koicore::{Command, Value};
+/// use koicore::document::{Document, ValuePath};
+///
+/// let mut document = Document::new(vec![Command::new("move", vec![2.into()])]);
+/// document.transform(&mut |_: &ValuePath<'_>, value: &mut Value| {
+///     if let Value::Int(n) = value {
+///         *n *= 10;
+///     }
+/// });
+/// assert_eq!(document.commands[0], Command::new("move", vec![20.into()]));
+/// ```
+pub trait ValueTransformer {
+    /// Transform a single value
+    ///
+    /// # Arguments
+    /// * `path` - Where the value is in the document
+    /// * `value` - The value to rewrite in place
+    fn transform_value(&mut self, path: &ValuePath<'_>, value: &mut Value);
+}
+
+impl<F: FnMut(&ValuePath<'_>, &mut Value)> ValueTransformer for F {
+    fn transform_value(&mut self, path: &ValuePath<'_>, value: &mut Value) {
+        self(path, value)
+    }
+}
+
+/// Transformer replacing every string value with a fixed mask
+///
+/// This covers string parameters, strings inside composites and the content
+/// of text and annotation lines. Command names, composite names and
+/// dictionary keys are kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedactStrings {
+    /// The replacement for every string
+    pub mask: String,
+}
+
+impl RedactStrings {
+    /// Create a redaction transformer with the given mask
+    ///
+    /// # Arguments
+    /// * `mask` - The replacement for every string
+    pub fn new(mask: impl Into<String>) -> Self {
+        Self { mask: mask.into() }
+    }
+}
+
+impl Default for RedactStrings {
+    /// Mask strings with `***`
+    fn default() -> Self {
+        Self::new("***")
+    }
+}
+
+impl ValueTransformer for RedactStrings {
+    fn transform_value(&mut self, _path: &ValuePath<'_>, value: &mut Value) {
+        if let Value::String(s) = value {
+            s.clone_from(&self.mask);
+        }
+    }
+}
+
+impl Document {
+    /// Create a document from a list of commands
+    ///
+    /// # Arguments
+    /// * `commands` - The commands, in source order
+    pub fn new(commands: Vec<Command>) -> Self {
+        Self { commands }
+    }
+
+    /// Count the commands of each name
+    ///
+    /// Text and annotation lines are counted under `@text` and `@annotation`.
+    pub fn count_by_name(&self) -> HashMap<&str, usize> {
+        let mut counts = HashMap::new();
+        for command in &self.commands {
+            *counts.entry(command.name()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Check that each of the given commands appears at most once
+    ///
+    /// # Arguments
+    /// * `names` - Names of the commands that must be unique, e.g. `["title"]`
+    ///
+    /// # Returns
+    /// `Err` with each duplicated name and its number of occurrences, in the
+    /// order of `names`
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::document::Document;
+    ///
+    /// let document: Document = ["#title A", "#title B", "#author C"]
+    ///     .iter()
+    ///     .map(|line| line.parse().unwrap())
+    ///     .collect();
+    /// assert_eq!(
+    ///     document.assert_unique(&["title", "author"]),
+    ///     Err(vec![("title".to_string(), 2)])
+    /// );
+    /// ```
+    pub fn assert_unique(&self, names: &[&str]) -> Result<(), Vec<(String, usize)>> {
+        let counts = self.count_by_name();
+        let duplicates: Vec<_> = names
+            .iter()
+            .filter_map(|&name| match counts.get(name) {
+                Some(&count) if count > 1 => Some((name.to_string(), count)),
+                _ => None,
+            })
+            .collect();
+        if duplicates.is_empty() {
+            Ok(())
+        } else {
+            Err(duplicates)
+        }
+    }
+
+    /// Get the first command with the given name
+    ///
+    /// # Arguments
+    /// * `name` - Command name
+    pub fn first(&self, name: &str) -> Option<&Command> {
+        self.commands.iter().find(|command| command.name() == name)
+    }
+
+    /// Get the last command with the given name
+    ///
+    /// # Arguments
+    /// * `name` - Command name
+    pub fn last(&self, name: &str) -> Option<&Command> {
+        self.commands.iter().rev().find(|command| command.name() == name)
+    }
+
+    /// Join the content of all text lines, in order
+    ///
+    /// Each text command contributes one line, so lines are separated by
+    /// `\n`, without a trailing newline. Empty text commands (kept with
+    /// `ParserConfig::preserve_empty_lines`) give empty lines.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::Command;
+    /// use koicore::document::Document;
+    ///
+    /// let document = Document::new(vec![
+    ///     Command::new_text("Hello"),
+    ///     "#pause 1".parse()?,
+    ///     Command::new_text("world"),
+    /// ]);
+    /// assert_eq!(document.collect_text(), "Hello\nworld");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn collect_text(&self) -> String {
+        self.special_contents("@text").collect::<Vec<_>>().join("\n")
+    }
+
+    /// Get the content of all annotations, in order
+    ///
+    /// The content is stored as parsed, so it excludes the leading `#`
+    /// characters unless the parser was configured to keep them.
+    pub fn collect_annotations(&self) -> Vec<String> {
+        self.special_contents("@annotation").map(str::to_string).collect()
+    }
+
+    /// Get the string content of every command with a special name
+    fn special_contents<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.commands
+            .iter()
+            .filter(move |command| command.name() == name)
+            .filter_map(|command| match command.params().first() {
+                Some(Parameter::Basic(Value::String(content))) => Some(content.as_str()),
+                _ => None,
+            })
+    }
+
+    /// Apply a transformer to every value in the document
+    ///
+    /// Values are visited in order: command by command, parameter by
+    /// parameter, and element by element within composites.
+    ///
+    /// # Arguments
+    /// * `transformer` - The transformation to apply
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::document::{Document, RedactStrings};
+    ///
+    /// let mut document = Document::new(vec!["#login user \"hunter2\"".parse()?]);
+    /// document.transform(&mut RedactStrings::default());
+    /// assert_eq!(document.commands[0].to_string(), r#"login "***" "***""#);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn transform(&mut self, transformer: &mut impl ValueTransformer) {
+        for command in &mut self.commands {
+            let Command { name, params, .. } = command;
+            for (position, param) in params.iter_mut().enumerate() {
+                let mut path = ValuePath {
+                    command: name,
+                    position,
+                    composite: None,
+                    key: None,
+                };
+                match param {
+                    Parameter::Basic(value) => transformer.transform_value(&path, value),
+                    Parameter::Composite(composite, value) => {
+                        path.composite = Some(composite);
+                        match value {
+                            CompositeValue::Single(value) => {
+                                transformer.transform_value(&path, value)
+                            }
+                            CompositeValue::List(values) | CompositeValue::Set(values) => {
+                                for value in values {
+                                    transformer.transform_value(&path, value);
+                                }
+                            }
+                            CompositeValue::Dict(entries) => {
+                                for (key, value) in entries {
+                                    path.key = Some(key);
+                                    transformer.transform_value(&path, value);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// JSON with document-level metadata
+#[cfg(feature = "json")]
+impl Document {
+    /// Serialize this document as JSON, together with its metadata
+    ///
+    /// The document is written as `{"meta": {...}, "commands": [...]}`, with
+    /// commands in the default serde representation.
+    ///
+    /// # Arguments
+    /// * `meta` - Information about how the document was read
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::document::{Document, DocumentMeta};
+    ///
+    /// let document = Document::new(vec!["#draw Line 2".parse()?]);
+    /// let meta = DocumentMeta {
+    ///     filename: Some("scene.ktxt".to_string()),
+    ///     ..Default::default()
+    /// };
+    /// let json = document.to_json_with_meta(&meta)?;
+    /// assert!(json.starts_with(r#"{"meta":{"filename":"scene.ktxt","#));
+    /// assert_eq!(Document::from_json_with_meta(&json)?, (document, meta));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn to_json_with_meta(&self, meta: &DocumentMeta) -> serde_json::Result<String> {
+        serde_json::to_string(&DocumentWithMeta {
+            meta,
+            commands: &self.commands,
+        })
+    }
+
+    /// Parse a document and its metadata from the JSON of [`Document::to_json_with_meta`]
+    ///
+    /// A missing `meta` object, as in the plain serde representation of a
+    /// document, gives empty metadata.
+    ///
+    /// # Arguments
+    /// * `json` - A `{"meta": {...}, "commands": [...]}` JSON object
+    pub fn from_json_with_meta(json: &str) -> serde_json::Result<(Self, DocumentMeta)> {
+        let document: DocumentWithMeta<DocumentMeta, Vec<Command>> = serde_json::from_str(json)?;
+        Ok((Self::new(document.commands), document.meta))
+    }
+}
+
+impl From<Vec<Command>> for Document {
+    fn from(commands: Vec<Command>) -> Self {
+        Self::new(commands)
+    }
+}
+
+impl FromIterator<Command> for Document {
+    fn from_iter<I: IntoIterator<Item = Command>>(iter: I) -> Self {
+        Self::new(iter.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_all(source: &str) -> Document {
+        source.lines().map(|line| line.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_redact_strings() {
+        let mut document = parse_all(concat!(
+            "#login user \"hunter2\" 42\n",
+            "#connect host(\"db.local\") opts(user: admin, port: 5432, tls: true)\n",
+            "#tags list(a, 1, \"b c\")\n",
+            "## secret note",
+        ));
+        document.transform(&mut RedactStrings::new("REDACTED"));
+
+        let expected = parse_all(concat!(
+            "#login REDACTED REDACTED 42\n",
+            "#connect host(REDACTED) opts(user: REDACTED, port: 5432, tls: true)\n",
+            "#tags list(REDACTED, 1, REDACTED)",
+        ));
+        assert_eq!(document.commands[..3], expected.commands[..]);
+        assert_eq!(document.commands[3], Command::new_annotation_with_level("REDACTED", 2));
+    }
+
+    #[test]
+    fn test_unique_names() {
+        let document = parse_all("#title A\n#author X\n#title B\n#chapter 1\n#chapter 2\n#title C");
+
+        let counts = document.count_by_name();
+        assert_eq!(counts["title"], 3);
+        assert_eq!(counts["chapter"], 2);
+        assert_eq!(counts["author"], 1);
+        assert!(!counts.contains_key("missing"));
+
+        assert_eq!(document.assert_unique(&["author", "missing"]), Ok(()));
+        assert_eq!(
+            document.assert_unique(&["chapter", "author", "title"]),
+            Err(vec![("chapter".to_string(), 2), ("title".to_string(), 3)])
+        );
+
+        assert_eq!(document.first("title").unwrap().to_string(), "title A");
+        assert_eq!(document.last("title").unwrap().to_string(), "title C");
+        assert!(document.first("missing").is_none());
+        assert!(document.last("missing").is_none());
+    }
+
+    #[test]
+    fn test_collect_text_and_annotations() {
+        let source = concat!(
+            "## Chapter one\n",
+            "#scene forest\n",
+            "It was dark.\n",
+            "#pause 2\n",
+            "### aside\n",
+            "The wind   howled.\n",
+            "#end\n",
+        );
+        let input = crate::parser::StringInputSource::new(source);
+        let config = crate::ParserConfig::default();
+        let document: Document = crate::Parser::new(input, config)
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(document.collect_text(), "It was dark.\nThe wind   howled.");
+        assert_eq!(document.collect_annotations(), ["Chapter one", "aside"]);
+
+        let empty = parse_all("#a 1\n#b 2");
+        assert_eq!(empty.collect_text(), "");
+        assert!(empty.collect_annotations().is_empty());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json_with_meta() {
+        let source = "#title \"A\"\n##note\nSome text\n#pos p(x: 1, y: 2.5) l[1, true]";
+        let config = crate::ParserConfig::default()
+            .with_annotation_keep_hashes(true)
+            .with_int_overflow(crate::parser::IntOverflow::Wrap)
+            .with_list_delimiters('[', ']');
+        let mut parser = Parser::new(crate::parser::StringInputSource::new(source), config);
+        let mut meta = DocumentMeta::from_parser(&parser);
+        let document: Document = (&mut parser).collect::<Result<_, _>>().unwrap();
+        assert_eq!(meta.filename.as_deref(), Some("<string>"));
+        assert_eq!(meta.encoding, None);
+        meta.encoding = Some("UTF-8".to_string());
+
+        let json = document.to_json_with_meta(&meta).unwrap();
+        let (restored, restored_meta) = Document::from_json_with_meta(&json).unwrap();
+        assert_eq!(restored, document);
+        assert_eq!(restored_meta, meta);
+
+        // The plain representation reads back with empty metadata
+        let plain = serde_json::to_string(&document).unwrap();
+        let (restored, restored_meta) = Document::from_json_with_meta(&plain).unwrap();
+        assert_eq!(restored, document);
+        assert_eq!(restored_meta, DocumentMeta::default());
+    }
+
+    #[test]
+    fn test_transform_paths() {
+        let mut document = parse_all("#a 1 p(x: 2, y: 3)\n#b l(4, 5)");
+        let mut seen = Vec::new();
+        document.transform(&mut |path: &ValuePath<'_>, value: &mut Value| {
+            seen.push(format!(
+                "{} {} {:?} {:?} {}",
+                path.command, path.position, path.composite, path.key, value
+            ));
+            if let Value::Int(n) = value {
+                *n *= 2;
+            }
+        });
+
+        assert_eq!(seen, vec![
+            "a 0 None None 1",
+            "a 1 Some(\"p\") Some(\"x\") 2",
+            "a 1 Some(\"p\") Some(\"y\") 3",
+            "b 0 Some(\"l\") None 4",
+            "b 0 Some(\"l\") None 5",
+        ]);
+        assert_eq!(document, parse_all("#a 2 p(x: 4, y: 6)\n#b l(8, 10)"));
+    }
+}
@@ -0,0 +1,241 @@
+//! Typed extraction of command parameters
+//!
+//! [`Command::extract`] converts the basic parameters of a command into a
+//! tuple of Rust values, checking the number and types of the parameters:
+//!
+//! ```rust
+//! use koicore::Command;
+//!
+//! let cmd: Command = "#move 3 \"east\"".parse()?;
+//! let (steps, direction): (i64, String) = cmd.extract()?;
+//! assert_eq!((steps, direction.as_str()), (3, "east"));
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::fmt;
+
+use crate::command::{Command, Parameter, Value};
+
+/// Why the parameters of a command could not be extracted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExtractError {
+    /// The command has another number of parameters
+    Arity {
+        /// Number of parameters expected
+        expected: usize,
+        /// Number of parameters found
+        found: usize,
+    },
+    /// A parameter has another type
+    Type {
+        /// Position of the parameter (0-based)
+        position: usize,
+        /// Name of the expected type, e.g. `"int"`
+        expected: &'static str,
+        /// Name of the type found, e.g. `"string"` or `"composite"`
+        found: &'static str,
+    },
+}
+
+impl fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtractError::Arity { expected: 1, found } => {
+                write!(f, "expected 1 parameter, found {}", found)
+            }
+            ExtractError::Arity { expected, found } => {
+                write!(f, "expected {} parameters, found {}", expected, found)
+            }
+            ExtractError::Type {
+                position,
+                expected,
+                found,
+            } => write!(
+                f,
+                "expected {} for parameter {}, found {}",
+                expected, position, found
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ExtractError {}
+
+/// A Rust type a basic parameter value can be extracted as
+pub trait FromValue: Sized {
+    /// Name of the type in error messages, e.g. `"int"`
+    const TYPE_NAME: &'static str;
+
+    /// Convert the value, or return `None` if it has another type
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+impl FromValue for i64 {
+    const TYPE_NAME: &'static str = "int";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Int(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+/// Integers are accepted too, since `1` and `1.0` both read as numbers
+impl FromValue for f64 {
+    const TYPE_NAME: &'static str = "float";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Float(f) => Some(*f),
+            Value::Int(n) => Some(*n as f64),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for bool {
+    const TYPE_NAME: &'static str = "bool";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for String {
+    const TYPE_NAME: &'static str = "string";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::String(s) => Some(s.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Any basic value, whatever its type
+impl FromValue for Value {
+    const TYPE_NAME: &'static str = "value";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+/// A tuple the parameters of a command can be extracted as
+///
+/// Implemented for tuples of up to 8 [`FromValue`] types.
+pub trait FromParams: Sized {
+    /// Convert the parameters, checking their number and types
+    fn from_params(params: &[Parameter]) -> Result<Self, ExtractError>;
+}
+
+/// Extract the basic parameter at `position` as `T`
+fn extract_param<T: FromValue>(params: &[Parameter], position: usize) -> Result<T, ExtractError> {
+    let found = match &params[position] {
+        Parameter::Basic(value) => match T::from_value(value) {
+            Some(value) => return Ok(value),
+            None => value.type_name(),
+        },
+        Parameter::Composite(..) => "composite",
+    };
+    Err(ExtractError::Type {
+        position,
+        expected: T::TYPE_NAME,
+        found,
+    })
+}
+
+macro_rules! impl_from_params {
+    ($len:expr; $($ty:ident $position:tt),*) => {
+        impl<$($ty: FromValue),*> FromParams for ($($ty,)*) {
+            fn from_params(params: &[Parameter]) -> Result<Self, ExtractError> {
+                if params.len() != $len {
+                    return Err(ExtractError::Arity {
+                        expected: $len,
+                        found: params.len(),
+                    });
+                }
+                Ok(($(extract_param::<$ty>(params, $position)?,)*))
+            }
+        }
+    };
+}
+
+impl_from_params!(0;);
+impl_from_params!(1; A 0);
+impl_from_params!(2; A 0, B 1);
+impl_from_params!(3; A 0, B 1, C 2);
+impl_from_params!(4; A 0, B 1, C 2, D 3);
+impl_from_params!(5; A 0, B 1, C 2, D 3, E 4);
+impl_from_params!(6; A 0, B 1, C 2, D 3, E 4, F 5);
+impl_from_params!(7; A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_from_params!(8; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+impl Command {
+    /// Extract the parameters of this command as a tuple of Rust values
+    ///
+    /// Every parameter must be a basic value of the corresponding tuple
+    /// element's type (see [`FromValue`]), and the number of parameters must
+    /// match the tuple's length.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::Command;
+    /// use koicore::extract::ExtractError;
+    ///
+    /// let cmd: Command = "#move 3 \"east\"".parse()?;
+    /// assert_eq!(cmd.extract::<(i64, String)>()?, (3, "east".to_string()));
+    /// assert_eq!(
+    ///     cmd.extract::<(String, String)>(),
+    ///     Err(ExtractError::Type { position: 0, expected: "string", found: "int" })
+    /// );
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn extract<T: FromParams>(&self) -> Result<T, ExtractError> {
+        T::from_params(&self.params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract() {
+        let cmd: Command = "#move 3 \"east\"".parse().unwrap();
+        let (steps, direction): (i64, String) = cmd.extract().unwrap();
+        assert_eq!(steps, 3);
+        assert_eq!(direction, "east");
+
+        let err = cmd.extract::<(i64, i64)>().unwrap_err();
+        assert_eq!(err, ExtractError::Type {
+            position: 1,
+            expected: "int",
+            found: "string",
+        });
+        assert_eq!(err.to_string(), "expected int for parameter 1, found string");
+
+        let err = cmd.extract::<(i64,)>().unwrap_err();
+        assert_eq!(err.to_string(), "expected 1 parameter, found 2");
+
+        let err = cmd.extract::<(i64, String, bool)>().unwrap_err();
+        assert_eq!(err.to_string(), "expected 3 parameters, found 2");
+
+        let cmd: Command = "#set 2 0.5 true name".parse().unwrap();
+        let values: (f64, f64, bool, Value) = cmd.extract().unwrap();
+        assert_eq!(values, (2.0, 0.5, true, Value::from("name")));
+
+        let cmd: Command = "#set 1 pos(x: 1)".parse().unwrap();
+        assert_eq!(cmd.extract::<(i64, Value)>(), Err(ExtractError::Type {
+            position: 1,
+            expected: "value",
+            found: "composite",
+        }));
+        assert_eq!(Command::new("end", vec![]).extract::<()>(), Ok(()));
+    }
+}
@@ -0,0 +1,324 @@
+//! Structural hashing of commands and documents
+//!
+//! [`Command::content_hash`] and [`Document::content_hash`] hash the content
+//! of commands rather than how they were built or written, for deduplication,
+//! caching and change detection. The hash is stable: it does not depend on the
+//! platform, the process or the Rust version, so it can be stored.
+
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
+use crate::command::{Command, CompositeValue, Parameter, Value};
+use crate::document::Document;
+
+/// 64-bit FNV-1a hasher
+///
+/// Integers are always hashed in little-endian byte order, unlike the
+/// defaults of [`Hasher`], so the result is the same on every platform.
+struct StableHasher(u64);
+
+impl StableHasher {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    fn new() -> Self {
+        Self(Self::OFFSET_BASIS)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+}
+
+/// Hash a length or count as a little-endian `u64`
+fn write_len<H: Hasher>(len: usize, state: &mut H) {
+    state.write(&(len as u64).to_le_bytes());
+}
+
+/// Hash a string as its byte length followed by its bytes
+///
+/// Unlike `str::hash`, this does not depend on the terminator the standard
+/// library happens to use.
+fn write_str<H: Hasher>(s: &str, state: &mut H) {
+    write_len(s.len(), state);
+    state.write(s.as_bytes());
+}
+
+/// Normalized view of a value, command or document for hashing
+///
+/// Every string is hashed with [`write_str`] and every integer as fixed-width
+/// little-endian bytes, so the hash does not depend on [`Hash`] impls of the
+/// standard library.
+///
+/// Values that compare equal hash the same: `-0.0` and `0.0` are one float,
+/// all NaNs are one float, and timestamps are hashed as instants regardless
+/// of their offset. Sets are hashed in canonical order and dictionary entries
+/// sorted by key, so the order they were built in does not matter.
+struct Canonical<'a, T>(&'a T);
+
+impl Hash for Canonical<'_, Value> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self.0 {
+            Value::Int(n) => {
+                state.write_u8(0);
+                state.write(&n.to_le_bytes());
+            }
+            Value::Float(f) => {
+                state.write_u8(1);
+                let f = if *f == 0.0 {
+                    0.0
+                } else if f.is_nan() {
+                    f64::NAN
+                } else {
+                    *f
+                };
+                state.write(&f.to_bits().to_le_bytes());
+            }
+            Value::Bool(b) => {
+                state.write_u8(2);
+                state.write_u8(u8::from(*b));
+            }
+            Value::String(s) => {
+                state.write_u8(3);
+                write_str(s, state);
+            }
+            #[cfg(feature = "chrono")]
+            Value::DateTime(datetime) => {
+                state.write_u8(4);
+                state.write(&datetime.timestamp().to_le_bytes());
+                state.write(&datetime.timestamp_subsec_nanos().to_le_bytes());
+            }
+            #[cfg(feature = "chrono")]
+            Value::Duration(duration) => {
+                state.write_u8(5);
+                state.write(&duration.num_seconds().to_le_bytes());
+                state.write(&duration.subsec_nanos().to_le_bytes());
+            }
+        }
+    }
+}
+
+/// Hash a sequence of values, prefixed with its length
+fn hash_values<'a, H: Hasher>(values: impl ExactSizeIterator<Item = &'a Value>, state: &mut H) {
+    write_len(values.len(), state);
+    for value in values {
+        Canonical(value).hash(state);
+    }
+}
+
+impl Hash for Canonical<'_, CompositeValue> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self.0 {
+            CompositeValue::Single(value) => {
+                state.write_u8(0);
+                Canonical(value).hash(state);
+            }
+            CompositeValue::List(values) => {
+                state.write_u8(1);
+                hash_values(values.iter(), state);
+            }
+            CompositeValue::Dict(entries) => {
+                state.write_u8(2);
+                let mut entries: Vec<_> = entries.iter().collect();
+                entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+                write_len(entries.len(), state);
+                for (key, value) in entries {
+                    write_str(key, state);
+                    Canonical(value).hash(state);
+                }
+            }
+            CompositeValue::Set(values) => {
+                state.write_u8(3);
+                let mut values: Vec<_> = values.iter().collect();
+                values.sort_by(|a, b| a.canonical_cmp(b));
+                values.dedup_by(|a, b| a.canonical_cmp(b) == Ordering::Equal);
+                hash_values(values.into_iter(), state);
+            }
+        }
+    }
+}
+
+impl Hash for Canonical<'_, Parameter> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self.0 {
+            Parameter::Basic(value) => {
+                state.write_u8(0);
+                Canonical(value).hash(state);
+            }
+            Parameter::Composite(name, value) => {
+                state.write_u8(1);
+                write_str(name, state);
+                Canonical(value).hash(state);
+            }
+        }
+    }
+}
+
+impl Hash for Canonical<'_, Command> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let command = self.0;
+        write_str(&command.name, state);
+        write_len(command.params.len(), state);
+        for param in &command.params {
+            Canonical(param).hash(state);
+        }
+    }
+}
+
+impl Hash for Canonical<'_, Document> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        write_len(self.0.commands.len(), state);
+        for command in &self.0.commands {
+            Canonical(command).hash(state);
+        }
+    }
+}
+
+impl Command {
+    /// Get a stable hash of the content of this command
+    ///
+    /// Commands with the same name and parameters hash the same, however they
+    /// were built: from parsed text, with constructors or after editing.
+    /// Dictionary entry order, set order and the recorded `#` depth of
+    /// annotations are ignored. The hash is stable across platforms and
+    /// versions of Rust, so it can be used as a storage key.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::{Command, Parameter};
+    /// use koicore::command::CompositeValue;
+    ///
+    /// let parsed: Command = "#pos at(x: 1, y: 2.0)".parse()?;
+    /// let mut built: Command = "#pos at(y: 2.)".parse()?;
+    /// if let Parameter::Composite(_, CompositeValue::Dict(entries)) = &mut built.params[0] {
+    ///     entries.push(("x".to_string(), 1.into()));
+    /// }
+    /// assert_ne!(parsed, built);
+    /// assert_eq!(parsed.content_hash(), built.content_hash());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = StableHasher::new();
+        Canonical(self).hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+impl Document {
+    /// Get a stable hash of the content of this document
+    ///
+    /// Combines the [`Command::content_hash`] rules over all commands, in
+    /// order.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::{Command, Document};
+    ///
+    /// let parse = |lines: &[&str]| {
+    ///     lines.iter().map(|line| line.parse()).collect::<Result<Vec<Command>, _>>()
+    /// };
+    /// let a = Document::new(parse(&["#scene forest", "#light 0.5"])?);
+    /// let b = Document::new(parse(&["#scene   forest", "#light 5e-1"])?);
+    /// assert_eq!(a.content_hash(), b.content_hash());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = StableHasher::new();
+        Canonical(self).hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_hash() {
+        let parsed: Command = "#draw Line pos(x: 1, y: -0.0) 0x10".parse().unwrap();
+        let built = Command::new("draw", vec![
+            Parameter::from("Line"),
+            Parameter::Composite(
+                "pos".to_string(),
+                CompositeValue::Dict(vec![
+                    ("y".to_string(), Value::Float(0.0)),
+                    ("x".to_string(), Value::Int(1)),
+                ]),
+            ),
+            Parameter::from(16),
+        ]);
+        assert_eq!(parsed.content_hash(), built.content_hash());
+
+        // Sets ignore order and duplicates, annotations their recorded depth
+        let set = |values: Vec<Value>| {
+            Command::new("tags", vec![Parameter::Composite("t".to_string(), CompositeValue::Set(values))])
+        };
+        assert_eq!(
+            set(vec![Value::from("b"), Value::from(1), Value::from("b")]).content_hash(),
+            set(vec![Value::from(1), Value::from("b")]).content_hash()
+        );
+        assert_eq!(
+            Command::new_annotation_with_level("note", 4).content_hash(),
+            Command::new_annotation("note").content_hash()
+        );
+
+        // Different content hashes differently
+        let distinct = [
+            "#draw Line".parse::<Command>().unwrap(),
+            "#draw Line 1.0".parse().unwrap(),
+            "#draw Line 1".parse().unwrap(),
+            "#draw Line true".parse().unwrap(),
+            "#draw Line(1)".parse().unwrap(),
+            "#draw Line(1,)".parse().unwrap(),
+            "#draw Line(1, 2)".parse().unwrap(),
+            "#draw Line(2, 1)".parse().unwrap(),
+            "#draw Line(x: 1)".parse().unwrap(),
+            "#paint Line".parse().unwrap(),
+            Command::new_text("draw Line"),
+            Command::new_annotation("draw Line"),
+        ];
+        for (i, a) in distinct.iter().enumerate() {
+            for b in &distinct[i + 1..] {
+                assert_ne!(a.content_hash(), b.content_hash(), "{:?} and {:?}", a, b);
+            }
+        }
+
+        // The hash is stable, so it can be stored
+        assert_eq!(Command::new("end", vec![]).content_hash(), 0x9df0_93a5_942d_ed83);
+
+        let doc = Document::new(vec![parsed.clone(), Command::new_text("Hello")]);
+        let reordered = Document::new(vec![Command::new_text("Hello"), parsed]);
+        assert_ne!(doc.content_hash(), reordered.content_hash());
+        assert_eq!(doc.content_hash(), Document::new(vec![built, Command::new_text("Hello")]).content_hash());
+    }
+}
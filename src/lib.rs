@@ -1,9 +1,24 @@
 #![doc = include_str!("../README.md")]
 
+pub mod balance;
 pub mod command;
+#[cfg(feature = "json")]
+pub mod convert;
+pub mod diff;
+pub mod document;
+pub mod extract;
+mod hash;
 pub mod parser;
+#[cfg(feature = "schema")]
+pub mod schema;
 pub mod writer;
 
 pub use command::{Command, Parameter, Value};
-pub use parser::{Parser, ParserConfig, ParseError};
-pub use writer::{Writer, WriterConfig, FormatterOptions};
\ No newline at end of file
+#[cfg(feature = "json")]
+pub use convert::convert;
+pub use diff::diff;
+pub use document::Document;
+#[cfg(feature = "schema")]
+pub use schema::json_schema;
+pub use parser::{Parser, ParserConfig, ParseError, parse_one, unescape_string};
+pub use writer::{Writer, WriterConfig, FormatterOptions, escape_string};
\ No newline at end of file
@@ -7,16 +7,22 @@ use nom::{
     IResult, Parser,
     branch::alt,
     bytes::complete::{is_not, tag, take_while, take_while_m_n, take_while1},
-    character::complete::{char, digit1, multispace1},
-    combinator::{cut, map, map_opt, map_res, opt, recognize, value, verify},
+    character::complete::{char, digit1, multispace1, none_of},
+    combinator::{consumed, cut, map, map_opt, map_res, opt, peek, recognize, value, verify},
     error::{ContextError, FromExternalError, ParseError, context},
     multi::{fold_many0, many0, many1, separated_list1},
-    sequence::{delimited, pair, preceded, separated_pair},
+    sequence::{delimited, pair, preceded, separated_pair, terminated},
 };
+use std::num::IntErrorKind;
 use std::str::FromStr;
 
+use super::hint::{ParamHint, Spellings};
+use super::{IntOverflow, ParserConfig};
 use crate::command::{Command, CompositeValue, Parameter, Value};
 
+/// Key-value pairs of a dictionary composite
+type DictEntries = Vec<(String, Value)>;
+
 /// Parse a Python-style escaped character: \n, \t, \r, \x41, \u0041, etc.
 /// Also handles line continuation where \\\n should be ignored.
 fn parse_escaped_char<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, char, E> {
@@ -105,11 +111,11 @@ fn parse_string_fragment<'a, E: ParseError<&'a str>>(
     .parse(input)
 }
 
-/// Parse a quoted string
-fn parse_string<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+/// Parse the contents of a quoted string up to the closing quote, resolving escapes
+pub fn parse_string_content<'a, E: ParseError<&'a str>>(
     input: &'a str,
-) -> IResult<&'a str, Value, E> {
-    let build_string = fold_many0(
+) -> IResult<&'a str, String, E> {
+    fold_many0(
         parse_string_fragment,
         String::new,
         |mut string, fragment| {
@@ -122,25 +128,144 @@ fn parse_string<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
             }
             string
         },
-    );
+    )
+    .parse(input)
+}
 
+/// Parse a quoted string
+fn parse_string<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, Value, E> {
     context(
         "string",
-        delimited(char('"'), map(build_string, Value::String), char('"')),
+        delimited(char('"'), map(parse_string_content, Value::String), char('"')),
+    )
+    .parse(input)
+}
+
+/// Parse a single-character literal: `'A'`, `'\n'`, `'\x41'`, `'\u00e9'`, etc.
+///
+/// There is no character value type, so the literal produces a
+/// [`Value::Int`] holding the Unicode code point of the character. Escapes
+/// are the same as in quoted strings.
+fn parse_char<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, Value, E> {
+    context(
+        "char",
+        delimited(
+            char('\''),
+            map(
+                alt((parse_escaped_char, none_of("\\'\n"))),
+                |c| Value::Int(c as i64),
+            ),
+            char('\''),
+        ),
+    )
+    .parse(input)
+}
+
+/// Decode `%XX` escapes in a percent-encoded string
+///
+/// Returns `None` for malformed escapes or if the decoded bytes are not valid UTF-8.
+fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            if !hex.bytes().all(|c| c.is_ascii_hexdigit()) {
+                return None;
+            }
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Parse a percent-encoded string: u"https://example.com/?q=a%20b"
+///
+/// The content is taken verbatim up to the closing quote and then percent-decoded.
+fn parse_percent_string<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, Value, E> {
+    context(
+        "percent_string",
+        preceded(
+            tag("u\""),
+            cut(terminated(
+                map_opt(take_while(|c| c != '"'), |s: &str| {
+                    percent_decode(s).map(Value::String)
+                }),
+                char('"'),
+            )),
+        ),
     )
     .parse(input)
 }
 
+/// Convert integer digits to `i64`, applying the overflow policy if out of range
+fn int_from_str_radix(
+    s: &str,
+    radix: u32,
+    overflow: IntOverflow,
+) -> Result<i64, std::num::ParseIntError> {
+    let err = match i64::from_str_radix(s, radix) {
+        Ok(n) => return Ok(n),
+        Err(e) => e,
+    };
+    match (overflow, err.kind()) {
+        (IntOverflow::Saturate, IntErrorKind::PosOverflow) => Ok(i64::MAX),
+        (IntOverflow::Saturate, IntErrorKind::NegOverflow) => Ok(i64::MIN),
+        (IntOverflow::Wrap, IntErrorKind::PosOverflow | IntErrorKind::NegOverflow) => {
+            let magnitude = s.trim_start_matches('-').chars().fold(0u64, |acc, c| {
+                let digit = c.to_digit(radix).unwrap_or(0) as u64;
+                acc.wrapping_mul(radix as u64).wrapping_add(digit)
+            }) as i64;
+            Ok(if s.starts_with('-') { magnitude.wrapping_neg() } else { magnitude })
+        }
+        _ => Err(err),
+    }
+}
+
+/// Recognize digits with single underscores between them: `1_000`
+///
+/// An underscore must sit between two digits, so recognition stops before the
+/// underscore in `1_`, `1__0` and `1_x`, and fails on `_1`.
+fn separated_digits<'a, E: ParseError<&'a str>>(
+    is_digit: impl Fn(char) -> bool + Copy,
+) -> impl Parser<&'a str, Output = &'a str, Error = E> {
+    recognize(pair(
+        take_while1(is_digit),
+        many0(preceded(char('_'), take_while1(is_digit))),
+    ))
+}
+
+/// Remove the digit separators recognized by [`separated_digits`]
+fn strip_separators(s: &str) -> std::borrow::Cow<'_, str> {
+    if s.contains('_') {
+        s.replace('_', "").into()
+    } else {
+        s.into()
+    }
+}
+
 /// Parse a decimal integer
 fn parse_decimal_int<
     'a,
     E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
 >(
     input: &'a str,
+    overflow: IntOverflow,
 ) -> IResult<&'a str, i64, E> {
-    map_res(recognize(pair(opt(char('-')), digit1)), |s: &str| {
-        i64::from_str(s)
-    })
+    map_res(
+        recognize(pair(opt(char('-')), separated_digits(|c| c.is_ascii_digit()))),
+        |s: &str| int_from_str_radix(&strip_separators(s), 10, overflow),
+    )
     .parse(input)
 }
 
@@ -150,11 +275,12 @@ fn parse_hex_int<
     E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
 >(
     input: &'a str,
+    overflow: IntOverflow,
 ) -> IResult<&'a str, i64, E> {
     preceded(
         tag("0x"),
-        map_res(take_while1(|c: char| c.is_ascii_hexdigit()), |s: &str| {
-            i64::from_str_radix(s, 16)
+        map_res(separated_digits(|c| c.is_ascii_hexdigit()), |s: &str| {
+            int_from_str_radix(&strip_separators(s), 16, overflow)
         }),
     )
     .parse(input)
@@ -166,11 +292,12 @@ fn parse_bin_int<
     E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
 >(
     input: &'a str,
+    overflow: IntOverflow,
 ) -> IResult<&'a str, i64, E> {
     preceded(
         tag("0b"),
-        map_res(take_while1(|c: char| c == '0' || c == '1'), |s: &str| {
-            i64::from_str_radix(s, 2)
+        map_res(separated_digits(|c| c == '0' || c == '1'), |s: &str| {
+            int_from_str_radix(&strip_separators(s), 2, overflow)
         }),
     )
     .parse(input)
@@ -181,11 +308,12 @@ fn parse_oct_int<
     E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
 >(
     input: &'a str,
+    overflow: IntOverflow,
 ) -> IResult<&'a str, i64, E> {
     preceded(
         tag("0o"),
-        map_res(take_while1(|c: char| c.is_ascii_digit()), |s: &str| {
-            i64::from_str_radix(s, 8)
+        map_res(separated_digits(|c| c.is_ascii_digit()), |s: &str| {
+            int_from_str_radix(&strip_separators(s), 8, overflow)
         }),
     )
     .parse(input)
@@ -199,20 +327,25 @@ fn parse_integer<
         + FromExternalError<&'a str, std::num::ParseIntError>,
 >(
     input: &'a str,
+    config: &ParserConfig,
 ) -> IResult<&'a str, Value, E> {
+    let overflow = config.int_overflow;
     context(
         "integer",
         alt((
-            map(parse_hex_int, Value::Int),
-            map(parse_bin_int, Value::Int),
-            map(parse_oct_int, Value::Int),
-            map(parse_decimal_int, Value::Int),
+            map(|i| parse_hex_int(i, overflow), Value::Int),
+            map(|i| parse_bin_int(i, overflow), Value::Int),
+            map(|i| parse_oct_int(i, overflow), Value::Int),
+            map(|i| parse_decimal_int(i, overflow), Value::Int),
         )),
     )
     .parse(input)
 }
 
 /// Parse a float number
+///
+/// The digits before and after the `.` may be grouped with underscores, like
+/// integers; the exponent may not.
 fn parse_float<
     'a,
     E: ParseError<&'a str>
@@ -227,20 +360,48 @@ fn parse_float<
             recognize((
                 opt(char('-')),
                 alt((
-                    recognize((digit1, char('.'), digit0, opt(float_exp))),
-                    recognize((char('.'), digit1, opt(float_exp))),
-                    recognize((digit1, float_exp)),
+                    recognize((decimal_digits, char('.'), opt(decimal_digits), opt(float_exp))),
+                    recognize((char('.'), decimal_digits, opt(float_exp))),
+                    recognize((decimal_digits, float_exp)),
                 )),
             )),
-            |s: &str| f64::from_str(s).map(Value::Float),
+            |s: &str| f64::from_str(&strip_separators(s)).map(Value::Float),
+        ),
+    )
+    .parse(input)
+}
+
+/// Parse a number, keeping its source text as a string
+fn parse_number_text<
+    'a,
+    E: ParseError<&'a str>
+        + ContextError<&'a str>
+        + FromExternalError<&'a str, std::num::ParseIntError>
+        + FromExternalError<&'a str, std::num::ParseFloatError>,
+>(
+    input: &'a str,
+) -> IResult<&'a str, Value, E> {
+    // Wrapping never fails, so out-of-range integers are kept as text too
+    let overflow = IntOverflow::Wrap;
+    context(
+        "number",
+        map(
+            recognize(alt((
+                parse_float,
+                map(|i| parse_hex_int(i, overflow), Value::Int),
+                map(|i| parse_bin_int(i, overflow), Value::Int),
+                map(|i| parse_oct_int(i, overflow), Value::Int),
+                map(|i| parse_decimal_int(i, overflow), Value::Int),
+            ))),
+            |s: &str| Value::String(s.to_string()),
         ),
     )
     .parse(input)
 }
 
-/// Helper for float parsing - digits or empty
-fn digit0<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
-    take_while(|c: char| c.is_ascii_digit())(input)
+/// Helper for float parsing - decimal digits, possibly grouped with underscores
+fn decimal_digits<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
+    separated_digits(|c| c.is_ascii_digit()).parse(input)
 }
 
 /// Helper for float parsing - exponent part
@@ -274,15 +435,75 @@ fn parse_literal<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
 }
 
 /// Parse a boolean literal (true or false)
+///
+/// Only whole words match, so identifiers such as `trueish` or `false_flag`
+/// are left to [`parse_literal`].
 fn parse_bool<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
     input: &'a str,
 ) -> IResult<&'a str, Value, E> {
     context(
         "boolean",
-        alt((
-            map(tag("true"), |_| Value::Bool(true)),
-            map(tag("false"), |_| Value::Bool(false)),
-        )),
+        map_opt(parse_literal_str, |word: &str| match word {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            _ => None,
+        }),
+    )
+    .parse(input)
+}
+
+/// Parse an RFC 3339 timestamp: 2024-01-01T00:00:00Z
+#[cfg(feature = "chrono")]
+fn parse_datetime<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, Value, E> {
+    let digits = |n| take_while_m_n(n, n, |c: char| c.is_ascii_digit());
+    context(
+        "datetime",
+        map_opt(
+            // The shape is matched exactly, so a following `:datetime`
+            // annotation is not taken for part of the offset
+            recognize((
+                (digits(4), char('-'), digits(2), char('-'), digits(2)),
+                alt((char('T'), char('t'))),
+                (digits(2), char(':'), digits(2), char(':'), digits(2)),
+                opt((char('.'), digit1)),
+                alt((
+                    recognize(alt((char('Z'), char('z')))),
+                    recognize((alt((char('+'), char('-'))), digits(2), char(':'), digits(2))),
+                )),
+            )),
+            |s: &str| chrono::DateTime::parse_from_rfc3339(s).ok().map(Value::DateTime),
+        ),
+    )
+    .parse(input)
+}
+
+/// Parse a duration literal: 500ms, 10s, 5m, 2h, 1d
+#[cfg(feature = "chrono")]
+fn parse_duration<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, Value, E> {
+    use crate::command::DURATION_UNITS;
+    use nom::{character::complete::satisfy, combinator::not};
+
+    context(
+        "duration",
+        map_opt(
+            (
+                opt(char('-')),
+                digit1,
+                // "ms" must be tried before "m"
+                alt((tag("ms"), tag("d"), tag("h"), tag("m"), tag("s"))),
+                not(satisfy(|c: char| c.is_ascii_alphanumeric() || c == '_')),
+            ),
+            |(sign, digits, unit, _): (Option<char>, &str, &str, ())| {
+                let (_, scale) = DURATION_UNITS.iter().find(|(u, _)| *u == unit)?;
+                let millis = digits.parse::<i64>().ok()?.checked_mul(*scale)?;
+                let millis = if sign.is_some() { -millis } else { millis };
+                chrono::TimeDelta::try_milliseconds(millis).map(Value::Duration)
+            },
+        ),
     )
     .parse(input)
 }
@@ -296,21 +517,92 @@ fn parse_basic_value<
         + FromExternalError<&'a str, std::num::ParseFloatError>,
 >(
     input: &'a str,
+    config: &ParserConfig,
 ) -> IResult<&'a str, Value, E> {
+    let parse_number = |i| {
+        if config.numbers_as_strings {
+            parse_number_text(i)
+        } else {
+            alt((parse_float, |i| parse_integer(i, config))).parse(i)
+        }
+    };
+    // Try string first since it starts with a quote. Timestamps and durations
+    // start with digits, so they must be tried before numbers.
+    #[cfg(feature = "chrono")]
+    let values = alt((
+        parse_string,
+        parse_char,
+        parse_bool,
+        parse_datetime,
+        parse_duration,
+        parse_number,
+        parse_literal,
+    ));
+    #[cfg(not(feature = "chrono"))]
+    let values = alt((
+        parse_string,
+        parse_char,
+        parse_bool,
+        parse_number,
+        parse_literal,
+    ));
+
+    context("basic_value", values).parse(input)
+}
+
+/// Parse a basic value, noting how it was spelled
+fn parse_spelled_value<
+    'a,
+    E: ParseError<&'a str>
+        + ContextError<&'a str>
+        + FromExternalError<&'a str, std::num::ParseIntError>
+        + FromExternalError<&'a str, std::num::ParseFloatError>,
+>(
+    input: &'a str,
+    config: &ParserConfig,
+) -> IResult<&'a str, (Value, Spellings), E> {
+    map(consumed(|i| parse_basic_value(i, config)), |(source, value)| {
+        let spellings = Spellings::of(&value, source);
+        (value, spellings)
+    })
+    .parse(input)
+}
+
+/// Split parsed values from their combined spellings
+fn unzip_spelled<T>(items: Vec<(T, Spellings)>) -> (Vec<T>, Spellings) {
+    let mut spellings = Spellings::default();
+    let values = items
+        .into_iter()
+        .map(|(value, s)| {
+            spellings = spellings.merge(s);
+            value
+        })
+        .collect();
+    (values, spellings)
+}
+
+/// Parse a type annotation suffix: `:int`, `:float`, `:bool` or `:string`
+fn parse_type_annotation<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, &'a str, E> {
     context(
-        "basic_value",
-        alt((
-            parse_string, // Try string first since it starts with a quote
-            parse_bool,
-            parse_float,
-            parse_integer,
-            parse_literal,
-        )),
+        "type_annotation",
+        preceded(
+            char(':'),
+            cut(verify(parse_literal_str, |ty: &str| match ty {
+                "int" | "float" | "bool" | "string" => true,
+                #[cfg(feature = "chrono")]
+                "datetime" | "duration" => true,
+                _ => false,
+            })),
+        ),
     )
     .parse(input)
 }
 
 /// Parse a single parameter value (not composite)
+///
+/// The value may carry a type annotation (`5:int`), which must match the parsed type.
 fn parse_single_param<
     'a,
     E: ParseError<&'a str>
@@ -319,11 +611,42 @@ fn parse_single_param<
         + FromExternalError<&'a str, std::num::ParseFloatError>,
 >(
     input: &'a str,
-) -> IResult<&'a str, Parameter, E> {
-    map(parse_basic_value, Parameter::Basic).parse(input)
+    config: &ParserConfig,
+) -> IResult<&'a str, (Parameter, Spellings), E> {
+    map(
+        verify(
+            (|i| parse_spelled_value(i, config), opt(parse_type_annotation)),
+            |((value, _), ty): &((Value, Spellings), Option<&str>)| {
+                ty.is_none_or(|ty| ty == value.type_name())
+            },
+        ),
+        |((value, spellings), _)| (Parameter::Basic(value), spellings),
+    )
+    .parse(input)
+}
+
+/// Parse a percent-encoded string parameter, optionally annotated as `:string`
+fn parse_percent_param<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    input: &'a str,
+) -> IResult<&'a str, (Parameter, Spellings), E> {
+    map(
+        (
+            consumed(parse_percent_string),
+            cut(verify(opt(parse_type_annotation), |ty: &Option<&str>| {
+                ty.is_none_or(|ty| ty == "string")
+            })),
+        ),
+        |((source, value), _)| {
+            let spellings = Spellings::of(&value, source);
+            (Parameter::Basic(value), spellings)
+        },
+    )
+    .parse(input)
 }
 
 /// Parse a list of values in parentheses: (item1, item2, ...)
+///
+/// A trailing comma after the last item is allowed.
 fn parse_value_list<
     'a,
     E: ParseError<&'a str>
@@ -332,18 +655,29 @@ fn parse_value_list<
         + FromExternalError<&'a str, std::num::ParseFloatError>,
 >(
     input: &'a str,
-) -> IResult<&'a str, Vec<Value>, E> {
+    config: &ParserConfig,
+) -> IResult<&'a str, (Vec<Value>, Spellings), E> {
     context(
         "list",
-        separated_list1(
-            preceded(parse_whitespace_with_continuation, char(',')),
-            preceded(parse_whitespace_with_continuation, parse_basic_value),
+        map(
+            terminated(
+                separated_list1(
+                    preceded(parse_whitespace_with_continuation, char(',')),
+                    preceded(parse_whitespace_with_continuation, |i| {
+                        parse_spelled_value(i, config)
+                    }),
+                ),
+                opt(preceded(parse_whitespace_with_continuation, char(','))),
+            ),
+            unzip_spelled,
         ),
     )
     .parse(input)
 }
 
 /// Parse a dictionary in parentheses: (key1: value1, key2: value2, ...)
+///
+/// A trailing comma after the last entry is allowed.
 fn parse_dict<
     'a,
     E: ParseError<&'a str>
@@ -352,26 +686,38 @@ fn parse_dict<
         + FromExternalError<&'a str, std::num::ParseFloatError>,
 >(
     input: &'a str,
-) -> IResult<&'a str, Vec<(String, Value)>, E> {
+    config: &ParserConfig,
+) -> IResult<&'a str, (DictEntries, Spellings), E> {
     context(
         "dictionary",
-        separated_list1(
-            preceded(parse_whitespace_with_continuation, char(',')),
-            preceded(
-                parse_whitespace_with_continuation,
-                separated_pair(
-                    map(parse_literal_str, |v| v.to_string()),
-                    preceded(parse_whitespace_with_continuation, char(':')),
-                    preceded(parse_whitespace_with_continuation, cut(parse_basic_value)),
+        map(
+            terminated(
+                separated_list1(
+                    preceded(parse_whitespace_with_continuation, char(',')),
+                    preceded(
+                        parse_whitespace_with_continuation,
+                        map(
+                            separated_pair(
+                                map(parse_literal_str, |v| v.to_string()),
+                                preceded(parse_whitespace_with_continuation, char(':')),
+                                preceded(parse_whitespace_with_continuation, cut(|i| parse_spelled_value(i, config))),
+                            ),
+                            |(key, (value, spellings))| ((key, value), spellings),
+                        ),
+                    ),
                 ),
+                opt(preceded(parse_whitespace_with_continuation, char(','))),
             ),
+            unzip_spelled,
         ),
     )
     .parse(input)
 }
 
-/// Parse composite parameters: key(value), key(item1, item2), key(x: 1, y: 2)
-fn parse_composite_param<
+/// Parse a set in braces: {item1, item2, ...}
+///
+/// Duplicate values are dropped, keeping the first occurrence.
+fn parse_set<
     'a,
     E: ParseError<&'a str>
         + ContextError<&'a str>
@@ -379,31 +725,145 @@ fn parse_composite_param<
         + FromExternalError<&'a str, std::num::ParseFloatError>,
 >(
     input: &'a str,
-) -> IResult<&'a str, Parameter, E> {
+    config: &ParserConfig,
+) -> IResult<&'a str, (CompositeValue, Spellings), E> {
     context(
-        "composite_parameter",
-        (
-            parse_literal_str,
-            delimited(
-                (char('('), parse_whitespace_with_continuation),
-                cut(alt((
-                    map(parse_dict, CompositeValue::Dict),
-                    map(parse_value_list, |values| {
-                        if values.len() == 1 {
-                            CompositeValue::Single(values[0].clone())
-                        } else {
-                            CompositeValue::List(values)
-                        }
-                    }),
-                ))),
-                cut((parse_whitespace_with_continuation, char(')'))),
-            ),
+        "set",
+        delimited(
+            (char('{'), parse_whitespace_with_continuation),
+            cut(map(|i| parse_value_list(i, config), |(values, spellings)| {
+                let mut unique: Vec<Value> = Vec::with_capacity(values.len());
+                for value in values {
+                    if !unique.iter().any(|v| v.canonical_cmp(&value).is_eq()) {
+                        unique.push(value);
+                    }
+                }
+                (CompositeValue::Set(unique), spellings)
+            })),
+            cut((parse_whitespace_with_continuation, char('}'))),
         ),
     )
     .parse(input)
-    .map(|(remaining, (key, composite))| {
-        (remaining, Parameter::Composite(key.to_string(), composite))
-    })
+}
+
+/// Parse the delimited part of a composite: (value), (item1, item2), (x: 1, y: 2)
+///
+/// A single value with a trailing comma, (value,), is a one-element list.
+/// `lists` and `dicts` select which forms are accepted between `delimiters`.
+fn parse_delimited_composite<
+    'a,
+    E: ParseError<&'a str>
+        + ContextError<&'a str>
+        + FromExternalError<&'a str, std::num::ParseIntError>
+        + FromExternalError<&'a str, std::num::ParseFloatError>,
+>(
+    input: &'a str,
+    config: &ParserConfig,
+    (open, close): (char, char),
+    lists: bool,
+    dicts: bool,
+) -> IResult<&'a str, (CompositeValue, Spellings), E> {
+    let dict = |i| {
+        map(|i| parse_dict(i, config), |(entries, spellings)| {
+            (CompositeValue::Dict(entries), spellings)
+        })
+        .parse(i)
+    };
+    let values = |i| {
+        alt((
+            map(
+                terminated(
+                    |i| parse_spelled_value(i, config),
+                    (
+                        parse_whitespace_with_continuation,
+                        char(','),
+                        parse_whitespace_with_continuation,
+                        peek(char(close)),
+                    ),
+                ),
+                |(value, spellings)| (CompositeValue::List(vec![value]), spellings),
+            ),
+            map(|i| parse_value_list(i, config), |(mut values, spellings)| {
+                let composite = if values.len() == 1 {
+                    CompositeValue::Single(values.remove(0))
+                } else {
+                    CompositeValue::List(values)
+                };
+                (composite, spellings)
+            }),
+        ))
+        .parse(i)
+    };
+    let body = |i| match (lists, dicts) {
+        (true, true) => alt((dict, values)).parse(i),
+        (true, false) => values(i),
+        _ => dict(i),
+    };
+
+    delimited(
+        (char(open), parse_whitespace_with_continuation),
+        cut(body),
+        cut((parse_whitespace_with_continuation, char(close))),
+    )
+    .parse(input)
+}
+
+/// Parse the delimited part of a composite with the configured delimiters
+///
+/// Lists and dictionaries share the parser when their delimiters are the same.
+fn parse_configured_composite<
+    'a,
+    E: ParseError<&'a str>
+        + ContextError<&'a str>
+        + FromExternalError<&'a str, std::num::ParseIntError>
+        + FromExternalError<&'a str, std::num::ParseFloatError>,
+>(
+    input: &'a str,
+    config: &ParserConfig,
+) -> IResult<&'a str, (CompositeValue, Spellings), E> {
+    let (list, dict) = (config.list_delimiters, config.dict_delimiters);
+    if list == dict {
+        parse_delimited_composite(input, config, list, true, true)
+    } else {
+        alt((
+            |i| parse_delimited_composite(i, config, list, true, false),
+            |i| parse_delimited_composite(i, config, dict, false, true),
+        ))
+        .parse(input)
+    }
+}
+
+/// Parse composite parameters: key(value), key(item1, item2), key(x: 1, y: 2)
+///
+/// Lists and dictionaries use `list_delimiters` and `dict_delimiters`, which
+/// are both parentheses by default. With `enable_set_literals`, also accepts
+/// key{item1, item2}. The key may be left out for an anonymous composite such
+/// as `(1, 2)`, which gets an empty name.
+fn parse_composite_param<
+    'a,
+    E: ParseError<&'a str>
+        + ContextError<&'a str>
+        + FromExternalError<&'a str, std::num::ParseIntError>
+        + FromExternalError<&'a str, std::num::ParseFloatError>,
+>(
+    input: &'a str,
+    config: &ParserConfig,
+) -> IResult<&'a str, (Parameter, Spellings), E> {
+    let delimited_composite = |i| parse_configured_composite(i, config);
+    let composite = |i| {
+        if config.enable_set_literals {
+            alt((|i| parse_set(i, config), delimited_composite)).parse(i)
+        } else {
+            delimited_composite(i)
+        }
+    };
+
+    context("composite_parameter", (opt(parse_literal_str), composite))
+        .parse(input)
+        .map(|(remaining, (key, (composite, spellings)))| {
+            let name = key.unwrap_or_default().to_string();
+            (remaining, (Parameter::Composite(name, composite), spellings))
+        })
 }
 
 /// Parse any parameter type (basic or composite)
@@ -415,10 +875,36 @@ fn parse_parameter<
         + FromExternalError<&'a str, std::num::ParseFloatError>,
 >(
     input: &'a str,
+    config: &ParserConfig,
 ) -> IResult<&'a str, Parameter, E> {
+    map(|i| parse_spelled_parameter(i, config), |(param, _)| param).parse(input)
+}
+
+/// Parse any parameter type, noting how its values were spelled
+///
+/// With `enable_percent_strings`, basic parameters may also be `u"..."` strings.
+fn parse_spelled_parameter<
+    'a,
+    E: ParseError<&'a str>
+        + ContextError<&'a str>
+        + FromExternalError<&'a str, std::num::ParseIntError>
+        + FromExternalError<&'a str, std::num::ParseFloatError>,
+>(
+    input: &'a str,
+    config: &ParserConfig,
+) -> IResult<&'a str, (Parameter, Spellings), E> {
+    let basic = |i| parse_single_param(i, config);
+    let single = |i| {
+        if config.enable_percent_strings {
+            alt((parse_percent_param, basic)).parse(i)
+        } else {
+            basic(i)
+        }
+    };
+
     context(
         "parameter",
-        alt((parse_composite_param, parse_single_param)),
+        alt((|i| parse_composite_param(i, config), single)),
     )
     .parse(input)
 }
@@ -436,7 +922,7 @@ fn parse_command_name<
         "command_name",
         cut(alt((
             map(parse_literal_str, |v| v.to_string()),
-            map(parse_decimal_int, |n| n.to_string()),
+            map(|i| parse_decimal_int(i, IntOverflow::Error), |n| n.to_string()),
         ))),
     )
     .parse(input)
@@ -465,18 +951,125 @@ pub fn parse_command_line<
         + FromExternalError<&'a str, std::num::ParseFloatError>,
 >(
     input: &'a str,
+) -> IResult<&'a str, Command, E> {
+    parse_command_line_with_config(input, &ParserConfig::default())
+}
+
+/// Parse a complete command line, honoring syntax options from `config`
+pub fn parse_command_line_with_config<
+    'a,
+    E: ParseError<&'a str>
+        + ContextError<&'a str>
+        + FromExternalError<&'a str, std::num::ParseIntError>
+        + FromExternalError<&'a str, std::num::ParseFloatError>,
+>(
+    input: &'a str,
+    config: &ParserConfig,
 ) -> IResult<&'a str, Command, E> {
     (
         parse_command_name,
         many0(preceded(
             parse_whitespace_with_continuation1,
-            cut(parse_parameter),
+            cut(|i| parse_parameter(i, config)),
         )),
     )
         .parse(input)
         .map(|(remaining, (name, params))| (remaining, Command::new(name, params)))
 }
 
+/// Parse a command line, returning the source text of each parameter
+pub fn parse_command_line_sources_with_config<
+    'a,
+    E: ParseError<&'a str>
+        + ContextError<&'a str>
+        + FromExternalError<&'a str, std::num::ParseIntError>
+        + FromExternalError<&'a str, std::num::ParseFloatError>,
+>(
+    input: &'a str,
+    config: &ParserConfig,
+) -> IResult<&'a str, Vec<&'a str>, E> {
+    preceded(parse_command_name, |i| {
+        parse_parameter_sources_with_config(i, config)
+    })
+    .parse(input)
+}
+
+/// Parse whitespace-separated parameters, returning the source text of each
+pub fn parse_parameter_sources_with_config<
+    'a,
+    E: ParseError<&'a str>
+        + ContextError<&'a str>
+        + FromExternalError<&'a str, std::num::ParseIntError>
+        + FromExternalError<&'a str, std::num::ParseFloatError>,
+>(
+    input: &'a str,
+    config: &ParserConfig,
+) -> IResult<&'a str, Vec<&'a str>, E> {
+    many0(preceded(
+        parse_whitespace_with_continuation,
+        recognize(|i| parse_parameter(i, config)),
+    ))
+    .parse(input)
+}
+
+/// Parse a single parameter, returning how its values were written
+///
+/// See [`ParamHint`].
+pub fn parse_parameter_hint_with_config<
+    'a,
+    E: ParseError<&'a str>
+        + ContextError<&'a str>
+        + FromExternalError<&'a str, std::num::ParseIntError>
+        + FromExternalError<&'a str, std::num::ParseFloatError>,
+>(
+    input: &'a str,
+    config: &ParserConfig,
+) -> IResult<&'a str, ParamHint, E> {
+    map(|i| parse_spelled_parameter(i, config), |(_, spellings)| spellings.into_hint())
+        .parse(input)
+}
+
+/// Parse a single basic value: a number, boolean, string or literal
+pub fn parse_value_with_config<
+    'a,
+    E: ParseError<&'a str>
+        + ContextError<&'a str>
+        + FromExternalError<&'a str, std::num::ParseIntError>
+        + FromExternalError<&'a str, std::num::ParseFloatError>,
+>(
+    input: &'a str,
+    config: &ParserConfig,
+) -> IResult<&'a str, Value, E> {
+    parse_basic_value(input, config)
+}
+
+/// Parse one or more whitespace-separated parameters without a command name
+///
+/// Used for continuation lines that add parameters to the preceding command.
+pub fn parse_parameters_with_config<
+    'a,
+    E: ParseError<&'a str>
+        + ContextError<&'a str>
+        + FromExternalError<&'a str, std::num::ParseIntError>
+        + FromExternalError<&'a str, std::num::ParseFloatError>,
+>(
+    input: &'a str,
+    config: &ParserConfig,
+) -> IResult<&'a str, Vec<Parameter>, E> {
+    (
+        cut(|i| parse_parameter(i, config)),
+        many0(preceded(
+            parse_whitespace_with_continuation1,
+            cut(|i| parse_parameter(i, config)),
+        )),
+    )
+        .parse(input)
+        .map(|(remaining, (first, mut rest))| {
+            rest.insert(0, first);
+            (remaining, rest)
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -484,19 +1077,19 @@ mod tests {
     #[test]
     fn test_parse_integer() {
         assert_eq!(
-            parse_integer::<nom::error::Error<&str>>("123"),
+            parse_integer::<nom::error::Error<&str>>("123", &ParserConfig::default()),
             Ok(("", Value::Int(123)))
         );
         assert_eq!(
-            parse_integer::<nom::error::Error<&str>>("-456"),
+            parse_integer::<nom::error::Error<&str>>("-456", &ParserConfig::default()),
             Ok(("", Value::Int(-456)))
         );
         assert_eq!(
-            parse_integer::<nom::error::Error<&str>>("0x1A"),
+            parse_integer::<nom::error::Error<&str>>("0x1A", &ParserConfig::default()),
             Ok(("", Value::Int(26)))
         );
         assert_eq!(
-            parse_integer::<nom::error::Error<&str>>("0b101"),
+            parse_integer::<nom::error::Error<&str>>("0b101", &ParserConfig::default()),
             Ok(("", Value::Int(5)))
         );
     }
@@ -517,6 +1110,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_digit_separators() {
+        let config = ParserConfig::default();
+        let int = |s| parse_integer::<nom::error::Error<&str>>(s, &config);
+        assert_eq!(int("1_000"), Ok(("", Value::Int(1000))));
+        assert_eq!(int("-1_000_000"), Ok(("", Value::Int(-1_000_000))));
+        assert_eq!(int("0xFF_FF"), Ok(("", Value::Int(0xFFFF))));
+        assert_eq!(int("0b1010_0101"), Ok(("", Value::Int(0b1010_0101))));
+        assert_eq!(int("0o7_7"), Ok(("", Value::Int(0o77))));
+        assert_eq!(
+            parse_float::<nom::error::Error<&str>>("1.234_56"),
+            Ok(("", Value::Float(1.23456)))
+        );
+        assert_eq!(
+            parse_float::<nom::error::Error<&str>>("1_000.5e1_0"),
+            Ok(("_0", Value::Float(1000.5e1)))
+        );
+
+        // Separators must sit between two digits
+        assert!(int("_100").is_err());
+        assert_eq!(int("100_"), Ok(("_", Value::Int(100))));
+        assert_eq!(int("1__0"), Ok(("__0", Value::Int(1))));
+        assert_eq!(int("0x_FF"), Ok(("x_FF", Value::Int(0))));
+        for text in ["100_", "1__0", "0x_FF", "3._14"] {
+            assert!(format!("#set n {}", text).parse::<Command>().is_err(), "{} parsed", text);
+        }
+        // `_100` is an identifier, not a number
+        let (_, cmd) = parse_command_line::<nom::error::Error<&str>>("set n _100").unwrap();
+        assert_eq!(cmd.params()[1], Parameter::from("_100"));
+    }
+
     #[test]
     fn test_parse_literal() {
         assert_eq!(
@@ -529,6 +1153,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_bool() {
+        let parse = |input| {
+            parse_command_line::<nom::error::Error<&str>>(input)
+                .map(|(remaining, cmd)| (remaining, cmd.params))
+        };
+        assert_eq!(parse("x true"), Ok(("", vec![Value::Bool(true).into()])));
+        assert_eq!(parse("x false"), Ok(("", vec![Value::Bool(false).into()])));
+        // Identifiers starting with a keyword stay literals
+        assert_eq!(parse("x truthy"), Ok(("", vec![Value::from("truthy").into()])));
+        assert_eq!(parse("x false_flag true1"), Ok(("", vec![
+            Value::from("false_flag").into(),
+            Value::from("true1").into(),
+        ])));
+        assert_eq!(parse("x l(trueish, false)"), Ok(("", vec![
+            Parameter::from(("l", vec![Value::from("trueish"), Value::Bool(false)])),
+        ])));
+        assert!(parse_bool::<nom::error::Error<&str>>("trueish").is_err());
+    }
+
     #[test]
     fn test_parse_command_simple() {
         let result = parse_command_line::<nom::error::Error<&str>>("command");
@@ -552,10 +1196,49 @@ mod tests {
         assert_eq!(cmd.params()[1], Value::from(2).into());
     }
 
+    #[test]
+    fn test_parse_anonymous_composite() {
+        let (remaining, cmd) =
+            parse_command_line::<nom::error::Error<&str>>("point (1, 2) (x: 0) (3,) label(a)").unwrap();
+        assert_eq!(remaining, "");
+        assert_eq!(cmd.params(), &[
+            Parameter::Composite(String::new(), CompositeValue::List(vec![1.into(), 2.into()])),
+            Parameter::Composite(String::new(), CompositeValue::Dict(vec![("x".to_string(), 0.into())])),
+            Parameter::Composite(String::new(), CompositeValue::List(vec![3.into()])),
+            Parameter::Composite("label".to_string(), CompositeValue::Single("a".into())),
+        ]);
+
+        // A space after a name starts a separate anonymous composite
+        let (_, cmd) = parse_command_line::<nom::error::Error<&str>>("point p (1, 2)").unwrap();
+        assert_eq!(cmd.params()[0], Parameter::from("p"));
+        assert!(cmd.params()[1].is_composite_list());
+
+        assert!(parse_command_line::<nom::error::Error<&str>>("point (1, 2").is_err());
+    }
+
+    #[test]
+    fn test_parse_char() {
+        let parse = |input| parse_basic_value::<nom::error::Error<&str>>(input, &ParserConfig::default());
+        assert_eq!(parse("'A'"), Ok(("", Value::Int(65))));
+        assert_eq!(parse("'\\n'"), Ok(("", Value::Int(10))));
+        assert_eq!(parse("'\\x41'"), Ok(("", Value::Int(65))));
+        assert_eq!(parse("'\\u00e9'"), Ok(("", Value::Int(0xE9))));
+        assert_eq!(parse("'é'"), Ok(("", Value::Int(0xE9))));
+        assert_eq!(parse("'\\''"), Ok(("", Value::Int(39))));
+
+        for bad in ["''", "'AB'", "'A", "'\\'", "'\\x4'"] {
+            assert!(parse(bad).is_err(), "{bad}");
+        }
+
+        let (_, cmd) = parse_command_line::<nom::error::Error<&str>>("key 'q':int keys('a', 'b')").unwrap();
+        assert_eq!(cmd.params()[0], Parameter::from(113));
+        assert_eq!(cmd.params()[1].to_string(), "keys(97, 98)");
+    }
+
     #[test]
     fn test_parse_string_parameter() {
         // Test basic value parsing with string
-        let basic_result = parse_basic_value::<nom::error::Error<&str>>("\"Hello World\"");
+        let basic_result = parse_basic_value::<nom::error::Error<&str>>("\"Hello World\"", &ParserConfig::default());
         println!("Basic value parse result: {:?}", basic_result);
         assert!(basic_result.is_ok());
 
@@ -570,7 +1253,7 @@ mod tests {
         assert_eq!(cmd.params()[0], Parameter::from("Hello World"));
 
         // Test escape sequences
-        let escape_result = parse_basic_value::<nom::error::Error<&str>>("\"Hello\\nWorld\"");
+        let escape_result = parse_basic_value::<nom::error::Error<&str>>("\"Hello\\nWorld\"", &ParserConfig::default());
         println!("Escape parse result: {:?}", escape_result);
         assert!(escape_result.is_ok());
         if let Ok((_, Value::String(s))) = escape_result {
@@ -578,7 +1261,7 @@ mod tests {
         }
 
         // Test unicode escape
-        let unicode_result = parse_basic_value::<nom::error::Error<&str>>("\"Emoji: \\U0001F602\"");
+        let unicode_result = parse_basic_value::<nom::error::Error<&str>>("\"Emoji: \\U0001F602\"", &ParserConfig::default());
         println!("Unicode parse result: {:?}", unicode_result);
         assert!(unicode_result.is_ok());
         if let Ok((_, Value::String(s))) = unicode_result {
@@ -586,7 +1269,7 @@ mod tests {
         }
 
         // Test hex escape
-        let hex_result = parse_basic_value::<nom::error::Error<&str>>("\"Hex: \\x41\"");
+        let hex_result = parse_basic_value::<nom::error::Error<&str>>("\"Hex: \\x41\"", &ParserConfig::default());
         println!("Hex parse result: {:?}", hex_result);
         assert!(hex_result.is_ok());
         if let Ok((_, Value::String(s))) = hex_result {
@@ -594,7 +1277,7 @@ mod tests {
         }
 
         // Test octal escape
-        let octal_result = parse_basic_value::<nom::error::Error<&str>>("\"Octal: \\101\"");
+        let octal_result = parse_basic_value::<nom::error::Error<&str>>("\"Octal: \\101\"", &ParserConfig::default());
         println!("Octal parse result: {:?}", octal_result);
         assert!(octal_result.is_ok());
         if let Ok((_, Value::String(s))) = octal_result {
@@ -636,6 +1319,26 @@ mod tests {
         assert_eq!(cmd.params().len(), 1);
     }
 
+    #[test]
+    fn test_parse_composite_trailing_comma() {
+        let parse = |text| parse_command_line::<nom::error::Error<&str>>(text).map(|(_, cmd)| cmd);
+        let cmd = parse("color rgb(1,2,3,)").unwrap();
+        assert_eq!(cmd, parse("color rgb(1, 2, 3)").unwrap());
+        let cmd = parse("draw pos(x:1, y:2,)").unwrap();
+        assert_eq!(cmd, parse("draw pos(x: 1, y: 2)").unwrap());
+        let cmd = parse("color palette(255,\\\n 128,\\\n 0,\\\n)").unwrap();
+        assert_eq!(cmd, parse("color palette(255, 128, 0)").unwrap());
+
+        // A single value with a trailing comma stays a one-element list
+        assert_eq!(
+            parse("draw at(1,)").unwrap().params()[0],
+            Parameter::Composite("at".to_string(), CompositeValue::List(vec![Value::Int(1)]))
+        );
+        assert!(parse("color rgb(,)").is_err());
+        assert!(parse("color rgb(1,,)").is_err());
+        assert!(parse("draw pos(x: 1,,)").is_err());
+    }
+
     #[test]
     fn test_parse_mixed_parameters() {
         let result = parse_command_line::<nom::error::Error<&str>>(
@@ -700,7 +1403,7 @@ mod tests {
 
     #[test]
     fn test_escapes_newline() {
-        let result = parse_basic_value::<nom::error::Error<&str>>("\"Hello\\\nWorld\"");
+        let result = parse_basic_value::<nom::error::Error<&str>>("\"Hello\\\nWorld\"", &ParserConfig::default());
         println!("Escape parse result: {:?}", result);
         assert!(result.is_ok());
         if let Ok((_, Value::String(s))) = result {
@@ -762,4 +1465,226 @@ mod tests {
         assert_eq!(cmd.name(), "draw");
         assert_eq!(cmd.params().len(), 3);
     }
+
+    #[test]
+    fn test_parse_composite_set() {
+        let config = ParserConfig::default().with_enable_set_literals(true);
+        let result = parse_command_line_with_config::<nom::error::Error<&str>>(
+            "tags t{b, a, b, 1, 1}",
+            &config,
+        );
+        let (remaining, cmd) = result.unwrap();
+        assert_eq!(remaining, "");
+        assert_eq!(
+            cmd.params()[0],
+            Parameter::Composite(
+                "t".to_string(),
+                CompositeValue::Set(vec![
+                    Value::String("b".to_string()),
+                    Value::String("a".to_string()),
+                    Value::Int(1),
+                ])
+            )
+        );
+
+        // Parentheses keep their meaning when sets are enabled
+        let (_, cmd) = parse_command_line_with_config::<nom::error::Error<&str>>(
+            "draw pos(x: 1) l(1, 2)",
+            &config,
+        )
+        .unwrap();
+        assert!(matches!(cmd.params()[0], Parameter::Composite(_, CompositeValue::Dict(_))));
+        assert!(matches!(cmd.params()[1], Parameter::Composite(_, CompositeValue::List(_))));
+
+        // Empty sets are rejected
+        assert!(
+            parse_command_line_with_config::<nom::error::Error<&str>>("tags t{}", &config)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_composite_set_disabled() {
+        let (remaining, _) = parse_command_line::<nom::error::Error<&str>>("tags t{a}").unwrap();
+        assert_eq!(remaining, "{a}");
+    }
+
+    #[test]
+    fn test_parse_composite_delimiters() {
+        let config = ParserConfig::default()
+            .with_list_delimiters('[', ']')
+            .with_dict_delimiters('{', '}');
+        let (remaining, cmd) = parse_command_line_with_config::<nom::error::Error<&str>>(
+            "set color[255, 255, 255] pos{x: 0, y: 0} one[1] pair[1,]",
+            &config,
+        )
+        .unwrap();
+        assert_eq!(remaining, "");
+        assert_eq!(cmd, Command::new("set", vec![
+            Parameter::from(("color", vec![255, 255, 255])),
+            Parameter::Composite(
+                "pos".to_string(),
+                CompositeValue::Dict(vec![("x".to_string(), 0.into()), ("y".to_string(), 0.into())]),
+            ),
+            Parameter::Composite("one".to_string(), CompositeValue::Single(1.into())),
+            Parameter::Composite("pair".to_string(), CompositeValue::List(vec![1.into()])),
+        ]));
+
+        // Each form only accepts its own delimiters
+        for input in ["set pos[x: 0]", "set color{1, 2}", "set color(1, 2)"] {
+            let result = parse_command_line_with_config::<nom::error::Error<&str>>(input, &config);
+            assert!(!matches!(result, Ok(("", _))), "{}", input);
+        }
+
+        // Shared delimiters still accept both forms
+        let config = ParserConfig::default()
+            .with_list_delimiters('[', ']')
+            .with_dict_delimiters('[', ']');
+        let (_, cmd) = parse_command_line_with_config::<nom::error::Error<&str>>(
+            "set l[1, 2] d[k: v]",
+            &config,
+        )
+        .unwrap();
+        assert!(matches!(cmd.params()[0], Parameter::Composite(_, CompositeValue::List(_))));
+        assert!(matches!(cmd.params()[1], Parameter::Composite(_, CompositeValue::Dict(_))));
+    }
+
+    #[test]
+    fn test_parse_int_overflow() {
+        let parse = |input, overflow| {
+            let config = ParserConfig::default().with_int_overflow(overflow);
+            parse_command_line_with_config::<nom::error::Error<&str>>(input, &config)
+                .map(|(_, cmd)| cmd.params)
+        };
+        let ints = |values: &[i64]| values.iter().map(|&v| Parameter::from(v)).collect::<Vec<_>>();
+        let input = "n 9223372036854775808 -9223372036854775809 0x1ffffffffffffffff 5";
+
+        assert!(parse(input, IntOverflow::Error).is_err());
+        assert_eq!(
+            parse(input, IntOverflow::Saturate).unwrap(),
+            ints(&[i64::MAX, i64::MIN, i64::MAX, 5])
+        );
+        assert_eq!(
+            parse(input, IntOverflow::Wrap).unwrap(),
+            ints(&[i64::MIN, i64::MAX, -1, 5])
+        );
+    }
+
+    #[test]
+    fn test_parse_percent_string() {
+        let config = ParserConfig::default().with_enable_percent_strings(true);
+        let (_, cmd) = parse_command_line_with_config::<nom::error::Error<&str>>(
+            r#"link u"https://example.com/?q=a%20b&x=100%25" u"%22%5C":string plain"#,
+            &config,
+        )
+        .unwrap();
+        assert_eq!(cmd.params, vec![
+            Parameter::from("https://example.com/?q=a b&x=100%"),
+            Parameter::from("\"\\"),
+            Parameter::from("plain"),
+        ]);
+
+        for bad in [r#"link u"%2""#, r#"link u"%zz""#, r#"link u"%FF""#, r#"link u"x":int"#] {
+            assert!(
+                parse_command_line_with_config::<nom::error::Error<&str>>(bad, &config).is_err(),
+                "{bad}"
+            );
+        }
+
+        // Disabled by default: `u` is read as a literal
+        let (remaining, _) =
+            parse_command_line::<nom::error::Error<&str>>(r#"link u"a""#).unwrap();
+        assert_eq!(remaining, r#""a""#);
+    }
+
+    #[test]
+    fn test_parse_numbers_as_strings() {
+        let config = ParserConfig::default().with_numbers_as_strings(true);
+        let (remaining, cmd) = parse_command_line_with_config::<nom::error::Error<&str>>(
+            "id 007 1.0 -2e3 0xFF 99999999999999999999 true pos(x: 01, y: 1.50) l(1, 2.0)",
+            &config,
+        )
+        .unwrap();
+        assert_eq!(remaining, "");
+        assert_eq!(cmd.params, vec![
+            Parameter::from("007"),
+            Parameter::from("1.0"),
+            Parameter::from("-2e3"),
+            Parameter::from("0xFF"),
+            Parameter::from("99999999999999999999"),
+            Parameter::from(true),
+            Parameter::from(("pos", CompositeValue::Dict(vec![
+                ("x".to_string(), Value::from("01")),
+                ("y".to_string(), Value::from("1.50")),
+            ]))),
+            Parameter::from(("l", vec!["1", "2.0"])),
+        ]);
+        assert!(
+            parse_command_line_with_config::<nom::error::Error<&str>>("id 7:int", &config).is_err()
+        );
+
+        // The writer quotes them, so they read back as the same strings
+        let written = cmd.to_string();
+        let (_, reparsed) =
+            parse_command_line::<nom::error::Error<&str>>(&written).unwrap();
+        assert_eq!(reparsed, cmd);
+    }
+
+    #[test]
+    fn test_parse_type_annotation() {
+        let (remaining, cmd) = parse_command_line::<nom::error::Error<&str>>(
+            "set 5:int 0.5:float true:bool \"x\":string name:string",
+        )
+        .unwrap();
+        assert_eq!(remaining, "");
+        assert_eq!(
+            cmd.params(),
+            &[
+                Value::Int(5).into(),
+                Value::Float(0.5).into(),
+                Value::Bool(true).into(),
+                Value::String("x".to_string()).into(),
+                Value::String("name".to_string()).into(),
+            ]
+        );
+
+        // Mismatching and unknown annotations are errors
+        assert!(parse_command_line::<nom::error::Error<&str>>("set 5:float").is_err());
+        assert!(parse_command_line::<nom::error::Error<&str>>("set 0.5:int").is_err());
+        assert!(parse_command_line::<nom::error::Error<&str>>("set count:int").is_err());
+        assert!(parse_command_line::<nom::error::Error<&str>>("set 5:number").is_err());
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_parse_datetime_and_duration() {
+        use chrono::{DateTime, TimeDelta};
+
+        let (remaining, cmd) = parse_command_line::<nom::error::Error<&str>>(
+            "at 2024-01-01T00:00:00Z 2024-06-01T12:30:00+08:00 10s 5m 250ms -1h 2024 10",
+        )
+        .unwrap();
+        assert_eq!(remaining, "");
+        assert_eq!(
+            cmd.params(),
+            &[
+                Value::DateTime(DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z").unwrap()).into(),
+                Value::DateTime(DateTime::parse_from_rfc3339("2024-06-01T12:30:00+08:00").unwrap())
+                    .into(),
+                Value::Duration(TimeDelta::seconds(10)).into(),
+                Value::Duration(TimeDelta::minutes(5)).into(),
+                Value::Duration(TimeDelta::milliseconds(250)).into(),
+                Value::Duration(TimeDelta::hours(-1)).into(),
+                Value::Int(2024).into(),
+                Value::Int(10).into(),
+            ]
+        );
+
+        // Unknown units and malformed timestamps are left unparsed
+        let (remaining, _) = parse_command_line::<nom::error::Error<&str>>("at 10sec").unwrap();
+        assert_eq!(remaining, "sec");
+        let (remaining, _) =
+            parse_command_line::<nom::error::Error<&str>>("at 2024-13-01T00:00:00Z").unwrap();
+        assert_eq!(remaining, "-13-01T00:00:00Z");
+    }
 }
@@ -294,6 +294,13 @@ impl<R: Read> DecodeBufReader<R> {
         self.finished && self.buffer_pos >= self.buffer_len
     }
 
+    /// Get the encoding being decoded
+    ///
+    /// Once a byte order mark has been read, this is the encoding it names.
+    pub fn encoding(&self) -> &'static Encoding {
+        self.decoder.encoding()
+    }
+
     /// Get a reference to the underlying reader
     pub fn get_ref(&self) -> &R {
         &self.reader
@@ -35,6 +35,12 @@ pub enum ErrorInfo {
     SyntaxError {
         /// Error message describing the syntax issue
         message: String,
+        /// Grammar contexts active when the error occurred, outermost first
+        ///
+        /// Each entry is a context label such as `"dictionary"` or `"integer"`
+        /// and the byte offset in the command text where that rule started.
+        /// Empty unless the error came from the command grammar.
+        nom_context: Vec<(&'static str, usize)>,
     },
 
     /// Unexpected input
@@ -53,6 +59,16 @@ pub enum ErrorInfo {
         expected: String,
     },
 
+    /// A command other than the expected one
+    ///
+    /// Used by `Parser::expect_command` when the next command has another name.
+    UnexpectedCommand {
+        /// Name of the expected command
+        expected: String,
+        /// Name of the command found, e.g. `"draw"` or `"@text"`
+        found: String,
+    },
+
     /// IO error (for file-based parsing)
     ///
     /// Used when file operations or other IO operations fail during parsing.
@@ -96,6 +112,11 @@ pub struct ParseError {
     pub traceback: Option<TracebackEntry>,
     /// Optional source information including filename, line number, and text content
     pub source: Option<ParserLineSource>,
+    /// Optional hint on how to fix the input, shown after the source line
+    ///
+    /// Filled in heuristically for common mistakes in command lines, such as
+    /// an unterminated string.
+    pub suggestion: Option<String>,
 }
 
 impl ParseError {
@@ -110,9 +131,13 @@ impl ParseError {
     /// A boxed ParseError with syntax error information
     pub fn syntax(message: String) -> Box<Self> {
         Box::new(ParseError {
-            error_info: ErrorInfo::SyntaxError { message },
+            error_info: ErrorInfo::SyntaxError {
+                message,
+                nom_context: Vec::new(),
+            },
             traceback: None,
             source: None,
+            suggestion: None,
         })
     }
 
@@ -135,9 +160,13 @@ impl ParseError {
         context: String,
     ) -> Box<Self> {
         Box::new(ParseError {
-            error_info: ErrorInfo::SyntaxError { message },
+            error_info: ErrorInfo::SyntaxError {
+                message,
+                nom_context: Vec::new(),
+            },
             traceback: Some(TracebackEntry::new(line, (column, column + 1), context)),
             source: None,
+            suggestion: None,
         })
     }
     /// Create a new unexpected input error
@@ -174,6 +203,7 @@ impl ParseError {
             )),
             error_info: ErrorInfo::UnexpectedInput { remaining },
             source: None,
+            suggestion: None,
         })
     }
 
@@ -197,6 +227,33 @@ impl ParseError {
                 "".to_string(),
             )),
             source: None,
+            suggestion: None,
+        })
+    }
+
+    /// Create a new unexpected command error
+    ///
+    /// This error is used when a specific command was expected but another one
+    /// was found.
+    ///
+    /// # Arguments
+    /// * `expected` - Name of the expected command
+    /// * `found` - Name of the command found
+    /// * `source` - Source of the command found
+    ///
+    /// # Returns
+    /// A boxed ParseError pointing at the command found
+    pub fn unexpected_command(expected: String, found: String, source: ParserLineSource) -> Box<Self> {
+        let width = source.text.lines().next().unwrap_or_default().trim_end().len();
+        Box::new(ParseError {
+            error_info: ErrorInfo::UnexpectedCommand { expected, found },
+            traceback: Some(TracebackEntry::new(
+                source.lineno,
+                (1, width.max(1) + 1),
+                "".to_string(),
+            )),
+            source: Some(source),
+            suggestion: None,
         })
     }
 
@@ -214,6 +271,7 @@ impl ParseError {
             error_info: ErrorInfo::IoError { error },
             traceback: None,
             source: None,
+            suggestion: None,
         })
     }
 
@@ -224,6 +282,7 @@ impl ParseError {
     /// * `original_input` - The complete input that was being parsed
     /// * `lineno` - The line number where the error occurred
     /// * `column` - The column number where the error occurred
+    /// * `offset` - The byte offset of `original_input` in the whole input
     /// * `nom_error` - The nom error node containing detailed parsing information
     ///
     /// # Returns
@@ -233,14 +292,20 @@ impl ParseError {
         original_input: I,
         lineno: usize,
         column: usize,
+        offset: usize,
         nom_error: NomErrorNode<I>,
     ) -> Box<Self> {
+        let nom_context = nom_error.context_labels(&original_input);
         let traceback =
-            TracebackEntry::build_error_trace(original_input, lineno, column, &nom_error);
+            TracebackEntry::build_error_trace(original_input, lineno, column, offset, &nom_error);
         Box::new(ParseError {
-            error_info: ErrorInfo::SyntaxError { message },
+            error_info: ErrorInfo::SyntaxError {
+                message,
+                nom_context,
+            },
             traceback: Some(traceback),
             source: None,
+            suggestion: None,
         })
     }
 
@@ -256,6 +321,45 @@ impl ParseError {
         self
     }
 
+    /// Attach a suggestion on how to fix the input to this error
+    ///
+    /// # Arguments
+    /// * `suggestion` - The suggestion, or `None` to keep the error unchanged
+    ///
+    /// # Returns
+    /// The error with the suggestion attached
+    pub(crate) fn with_suggestion(mut self: Box<Self>, suggestion: Option<String>) -> Box<Self> {
+        if suggestion.is_some() {
+            self.suggestion = suggestion;
+        }
+        self
+    }
+
+    /// Set the byte offset of the top traceback entry
+    ///
+    /// Used for errors whose constructor doesn't know where its text is in
+    /// the whole input.
+    pub(crate) fn with_offset(mut self: Box<Self>, offset: usize) -> Box<Self> {
+        if let Some(traceback) = self.traceback.as_mut() {
+            traceback.offset = Some(offset);
+        }
+        self
+    }
+
+    /// Get the suggestion on how to fix the input, if any
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::Command;
+    ///
+    /// let err = "#say \"hello".parse::<Command>().unwrap_err();
+    /// assert_eq!(err.suggestion(), Some("add a closing `\"` to end the string"));
+    /// ```
+    pub fn suggestion(&self) -> Option<&str> {
+        self.suggestion.as_deref()
+    }
+
     /// Get the position (line, column) associated with this error, if any
     ///
     /// # Returns
@@ -276,6 +380,16 @@ impl ParseError {
         self.traceback.as_ref().map(|tb| tb.lineno)
     }
 
+    /// Get the byte offset associated with this error, if any
+    ///
+    /// # Returns
+    /// An Option containing the byte offset of the error from the start of
+    /// the input, or None if the error has no position or its offset is not
+    /// known, e.g. for errors raised by a preprocessor.
+    pub fn offset(&self) -> Option<usize> {
+        self.traceback.as_ref().and_then(|tb| tb.offset)
+    }
+
     /// Get the error message
     ///
     /// Extracts the error message from the underlying ErrorInfo enum.
@@ -283,6 +397,7 @@ impl ParseError {
     /// - SyntaxError: Returns the original message
     /// - UnexpectedInput: Returns "Unexpected input: '<remaining>'"
     /// - UnexpectedEof: Returns "Unexpected end of input, expected <expected>"
+    /// - UnexpectedCommand: Returns "expected `#<expected>`, found `#<found>` at line <line>"
     /// - IoError: Returns the IO error message
     ///
     /// # Returns
@@ -296,33 +411,134 @@ impl ParseError {
             ErrorInfo::UnexpectedEof { expected, .. } => {
                 format!("Unexpected end of input, expected {}", expected)
             }
+            ErrorInfo::UnexpectedCommand { expected, found } => {
+                let mut message = format!(
+                    "expected {}, found {}",
+                    describe_command(expected),
+                    describe_command(found)
+                );
+                if let Some(line) = self.line() {
+                    message.push_str(&format!(" at line {}", line));
+                }
+                message
+            }
             ErrorInfo::IoError { error, .. } => error.to_string(),
         }
     }
+
+    /// Get the grammar context labels of a syntax error, outermost first
+    ///
+    /// Each entry names a rule of the command grammar (e.g. `"parameter"`,
+    /// `"dictionary"`, `"integer"`) that was being parsed when the error
+    /// occurred, with the byte offset in the command text where it started.
+    /// Alternatives that were tried at the same position are all included.
+    ///
+    /// # Returns
+    /// The labels, or an empty slice for errors that did not come from the
+    /// command grammar
+    pub fn context_labels(&self) -> &[(&'static str, usize)] {
+        match &self.error_info {
+            ErrorInfo::SyntaxError { nom_context, .. } => nom_context,
+            _ => &[],
+        }
+    }
+
+    /// Get a concise one-line message naming the innermost parsing context
+    ///
+    /// Combines [`message`](Self::message) with the innermost grammar rule
+    /// (see [`TracebackEntry::root_context`]) and the position of the deepest
+    /// traceback frame (see [`TracebackEntry::root_cause`]). Falls back to the plain message when no
+    /// traceback is available.
+    ///
+    /// # Returns
+    /// A String such as `"Command parsing error: in basic_value at 1:20"`
+    pub fn root_cause_message(&self) -> String {
+        match &self.traceback {
+            Some(traceback) => {
+                let root = traceback.root_cause();
+                let context = traceback.root_context().unwrap_or(&root.context);
+                format!(
+                    "{}: in {} at {}:{}",
+                    self.message(),
+                    context,
+                    root.lineno,
+                    root.column_range.0
+                )
+            }
+            None => self.message(),
+        }
+    }
 }
 
-/// Implementation for displaying ParseError in a user-friendly format
-///
-/// This implementation provides a formatted error output that includes:
-/// - The error type and message
-/// - Source file location (filename, line number, column) if available
-/// - The line of code where the error occurred with visual indicators
-/// - A traceback tree showing the parsing context
-impl fmt::Display for ParseError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+/// Describe a command by name for error messages, e.g. `` `#draw` `` or `a text line`
+fn describe_command(name: &str) -> String {
+    match name {
+        "@text" => "a text line".to_string(),
+        "@annotation" => "an annotation".to_string(),
+        "@number" => "a number command".to_string(),
+        name => format!("`#{}`", name),
+    }
+}
+
+/// ANSI escape sequences used when rendering a [`ParseError`]
+struct Style {
+    /// Error message and caret
+    error: &'static str,
+    /// Source line
+    source: &'static str,
+    /// Reset to the default style
+    reset: &'static str,
+}
+
+impl Style {
+    const PLAIN: Style = Style {
+        error: "",
+        source: "",
+        reset: "",
+    };
+
+    #[cfg(feature = "color")]
+    const COLORED: Style = Style {
+        error: "\x1b[1;31m",
+        source: "\x1b[2m",
+        reset: "\x1b[0m",
+    };
+}
+
+impl ParseError {
+    /// Write the error report shared by the plain and colored displays
+    fn write_report(&self, f: &mut fmt::Formatter<'_>, style: &Style) -> fmt::Result {
         // Display error type and message based on error_info
         match &self.error_info {
-            ErrorInfo::SyntaxError { message } => {
-                write!(f, "SyntaxError: {}", message)?;
+            ErrorInfo::SyntaxError { message, .. } => {
+                write!(f, "{}SyntaxError: {}{}", style.error, message, style.reset)?;
             }
             ErrorInfo::UnexpectedInput { remaining, .. } => {
-                write!(f, "UnexpectedInputError: '{}'", remaining)?;
+                write!(
+                    f,
+                    "{}UnexpectedInputError: '{}'{}",
+                    style.error, remaining, style.reset
+                )?;
             }
             ErrorInfo::UnexpectedEof { expected } => {
-                write!(f, "UnexpectedEofError: '{}'", expected)?;
+                write!(
+                    f,
+                    "{}UnexpectedEofError: '{}'{}",
+                    style.error, expected, style.reset
+                )?;
+            }
+            ErrorInfo::UnexpectedCommand { expected, found } => {
+                write!(
+                    f,
+                    "{}UnexpectedCommandError: expected {}, found {}{}",
+                    style.error,
+                    describe_command(expected),
+                    describe_command(found),
+                    style.reset
+                )?;
             }
             ErrorInfo::IoError { error } => {
-                write!(f, "IOError: {}", error)?;
+                write!(f, "{}IOError: {}{}", style.error, error, style.reset)?;
             }
         }
 
@@ -363,44 +579,39 @@ impl fmt::Display for ParseError {
             // Display line number and content
             write!(
                 f,
-                "\n{: ^4}│    {}",
+                "\n{: ^4}│    {}{}{}",
                 traceback.lineno,
-                current_line_content.trim_end()
+                style.source,
+                current_line_content.trim_end(),
+                style.reset
             )?;
 
             // Show arrow pointing to error location
             // The column range (start, end) is byte-based, but we need character positions for display
             // Convert byte indices to character indices
-            let mut char_start = 0;
-            let mut char_end = 0;
-
-            for (char_idx, (i, _)) in current_line_content.char_indices().enumerate() {
-                // Adjust start by -1 because start/end are 1-based columns
-                if i >= start.saturating_sub(1) && char_start == 0 {
-                    char_start = char_idx;
-                }
-                if i >= end.saturating_sub(1) {
-                    char_end = char_idx;
-                    break;
-                }
-            }
-
-            // If we didn't find the end, use the total character count
-            if char_end == 0 || char_end < char_start {
-                char_end = (char_start + (end as isize - start as isize).unsigned_abs())
-                    .max(char_start + 1);
-            }
-            // Clamp char_end to line length if it's way out of bounds, but keep at least 1 caret
+            // Adjust by -1 because start/end are 1-based columns
+            let char_index = |column: usize| {
+                current_line_content
+                    .char_indices()
+                    .position(|(i, _)| i >= column.saturating_sub(1))
+            };
             let line_char_count = current_line_content.chars().count();
-            if char_start >= line_char_count {
-                char_start = line_char_count;
-                char_end = line_char_count + 1;
-            } else {
-                char_end = char_end.min(line_char_count);
-            }
+            let char_start = char_index(start).unwrap_or(line_char_count);
+            // If we didn't find the end, extend the range by its byte length
+            let char_end = match char_index(end) {
+                Some(char_end) if char_end > char_start => char_end,
+                _ => char_start + end.saturating_sub(start).max(1),
+            };
+            // Clamp char_end to line length if it's way out of bounds, but keep at least 1 caret
+            let char_end = char_end.min(line_char_count).max(char_start + 1);
 
-            let arrow = " ".repeat(char_start + 4) + &"^".repeat((char_end - char_start).max(1));
-            write!(f, "\n    │{}", arrow)?;
+            let padding = " ".repeat(char_start + 4);
+            let arrow = "^".repeat((char_end - char_start).max(1));
+            write!(f, "\n    │{}{}{}{}", padding, style.error, arrow, style.reset)?;
+        }
+
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "\n  help: {}", suggestion)?;
         }
 
         writeln!(f)?;
@@ -413,17 +624,194 @@ impl fmt::Display for ParseError {
 
         Ok(())
     }
+
+    /// Get a `Display` adapter rendering this error with ANSI colors
+    ///
+    /// The message and caret are shown in red and the source line is dimmed.
+    /// The plain `Display` implementation is left uncolored.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::ParseError;
+    ///
+    /// let err = ParseError::syntax("bad input".to_string());
+    /// assert!(err.display_colored().to_string().contains("\x1b["));
+    /// ```
+    #[cfg(feature = "color")]
+    pub fn display_colored(&self) -> ColoredParseError<'_> {
+        ColoredParseError(self)
+    }
+}
+
+/// `Display` adapter returned by [`ParseError::display_colored`]
+#[cfg(feature = "color")]
+pub struct ColoredParseError<'a>(&'a ParseError);
+
+#[cfg(feature = "color")]
+impl fmt::Display for ColoredParseError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.write_report(f, &Style::COLORED)
+    }
+}
+
+/// Implementation for displaying ParseError in a user-friendly format
+///
+/// This implementation provides a formatted error output that includes:
+/// - The error type and message
+/// - Source file location (filename, line number, column) if available
+/// - The line of code where the error occurred with visual indicators
+/// - A traceback tree showing the parsing context
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_report(f, &Style::PLAIN)
+    }
 }
 
 /// Implementation of the standard Error trait for ParseError
 ///
 /// This allows ParseError to be used with the standard error handling mechanisms in Rust.
-impl std::error::Error for ParseError {}
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.error_info {
+            ErrorInfo::IoError { error } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Invalid parser configuration
+///
+/// Returned by [`ParserConfig::validate`] and [`Parser::try_new`] for settings
+/// that would make the parser silently misbehave.
+///
+/// [`ParserConfig::validate`]: crate::parser::ParserConfig::validate
+/// [`Parser::try_new`]: crate::parser::Parser::try_new
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// `command_threshold` is 0, so every line would be a command
+    ZeroCommandThreshold,
+    /// `command_separator` is an empty string
+    EmptyCommandSeparator,
+    /// `comment_marker` is an empty string
+    EmptyCommentMarker,
+    /// `command_separator` contains the command prefix or a line break
+    InvalidCommandSeparator {
+        /// The rejected separator
+        separator: String,
+    },
+    /// `list_delimiters` or `dict_delimiters` can't be told apart from other syntax
+    InvalidDelimiters {
+        /// The rejected opening character
+        open: char,
+        /// The rejected closing character
+        close: char,
+    },
+    /// `prefix_char` is whitespace, a character of identifiers, or used by other syntax
+    InvalidPrefixChar {
+        /// The rejected prefix character
+        prefix: char,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::ZeroCommandThreshold => {
+                write!(f, "ConfigError: command_threshold must be at least 1")
+            }
+            ConfigError::EmptyCommandSeparator => {
+                write!(f, "ConfigError: command_separator must not be empty")
+            }
+            ConfigError::EmptyCommentMarker => {
+                write!(f, "ConfigError: comment_marker must not be empty")
+            }
+            ConfigError::InvalidCommandSeparator { separator } => write!(
+                f,
+                "ConfigError: command_separator {:?} must not contain the command prefix or a line break",
+                separator
+            ),
+            ConfigError::InvalidDelimiters { open, close } => write!(
+                f,
+                "ConfigError: composite delimiters {:?} and {:?} are ambiguous",
+                open, close
+            ),
+            ConfigError::InvalidPrefixChar { prefix } => write!(
+                f,
+                "ConfigError: prefix_char {:?} can't be told apart from other syntax",
+                prefix
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// A problem found while parsing that doesn't stop the parser
+///
+/// Collected by the parser and returned by `Parser::take_warnings`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseWarning {
+    /// A numeric command name was normalized, losing how it was written
+    ///
+    /// Leading zeros and the sign of `-0` are dropped, so `#007` is read as the
+    /// number command `7` and written back as `#7`.
+    LossyNumberCommand {
+        /// Line of the command
+        lineno: usize,
+        /// The command name as written
+        original: String,
+        /// The number it was read as
+        value: i64,
+    },
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseWarning::LossyNumberCommand {
+                lineno,
+                original,
+                value,
+            } => write!(
+                f,
+                "line {}: number command #{} is read as #{}",
+                lineno, original, value
+            ),
+        }
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_error_caret_position() {
+        let caret_line = |column: usize, end: usize, text: &str| {
+            let mut err = ParseError::syntax_with_context("bad".to_string(), 1, column, String::new());
+            err.traceback.as_mut().unwrap().column_range = (column, end);
+            let err = err.with_line_source(ParserLineSource {
+                filename: "<test>".to_string(),
+                lineno: 1,
+                text: text.to_string(),
+            });
+            let display = err.to_string();
+            display.lines().nth(4).unwrap().to_string()
+        };
+
+        // The first column used to be mistaken for "not found"
+        assert_eq!(caret_line(1, 2, "x y"), "    │    ^");
+        assert_eq!(caret_line(3, 4, "x y"), "    │      ^");
+        // Byte columns after multibyte characters
+        assert_eq!(caret_line(8, 10, "é😂 ab"), "    │       ^^");
+        // Columns in the middle of a character point at the next one
+        assert_eq!(caret_line(2, 3, "éa"), "    │     ^");
+        // Past the end of the line, and an empty line
+        assert_eq!(caret_line(10, 12, "ab"), "    │      ^");
+        assert_eq!(caret_line(1, 1, ""), "    │    ^");
+    }
+
     #[test]
     fn test_error_display() {
         // Test SyntaxError display
@@ -446,6 +834,13 @@ mod tests {
         let err = ParseError::io(io_err);
         let display = format!("{}", err);
         assert!(display.contains("IOError: file not found"));
+
+        // Test ConfigError display
+        let err = ConfigError::InvalidCommandSeparator { separator: "#".to_string() };
+        assert_eq!(
+            err.to_string(),
+            "ConfigError: command_separator \"#\" must not contain the command prefix or a line break"
+        );
     }
 
     #[test]
@@ -498,4 +893,72 @@ mod tests {
         // Column 5 should point to 't' (5th character), not somewhere in the middle of a Chinese character
         assert!(display.contains("^"));
     }
+
+    #[test]
+    fn test_root_cause_message() {
+        use crate::parser::{Parser, ParserConfig, StringInputSource};
+
+        // Missing closing parenthesis: the deepest frame is the expected ')'
+        let input = StringInputSource::new("#draw pos(x: 1, y: 2");
+        let mut parser = Parser::new(input, ParserConfig::default());
+        let err = parser.next_command().unwrap_err();
+        let root = err.traceback.as_ref().unwrap().root_cause();
+        assert_eq!(root.context, "nom.char<')'>");
+        assert!(root.children.is_empty());
+        assert_eq!(
+            err.root_cause_message(),
+            format!("{}: in composite_parameter at 1:21", err.message())
+        );
+
+        // Missing dict value: every basic value alternative failed at the same spot
+        let input = StringInputSource::new("#draw pos(x: 1, y: )");
+        let mut parser = Parser::new(input, ParserConfig::default());
+        let err = parser.next_command().unwrap_err();
+        let traceback = err.traceback.as_ref().unwrap();
+        assert_eq!(traceback.root_cause().context, "nom.Alt");
+        assert_eq!(traceback.root_context(), Some("basic_value"));
+        assert_eq!(
+            err.root_cause_message(),
+            format!("{}: in basic_value at 1:20", err.message())
+        );
+
+        let err = ParseError::syntax("msg".to_string());
+        assert_eq!(err.root_cause_message(), "msg");
+    }
+
+    #[cfg(feature = "color")]
+    #[test]
+    fn test_error_display_colored() {
+        let mut err = ParseError::syntax_with_context("error".to_string(), 1, 1, "ctx".to_string());
+        err.source = Some(ParserLineSource {
+            filename: "test.koi".to_string(),
+            lineno: 1,
+            text: "line content".to_string(),
+        });
+
+        let plain = format!("{}", err);
+        let colored = format!("{}", err.display_colored());
+        assert!(!plain.contains('\x1b'));
+        assert!(colored.contains("\x1b[1;31mSyntaxError: error\x1b[0m"));
+        assert!(colored.contains("\x1b[2mline content\x1b[0m"));
+
+        // Stripping the escape codes gives back the plain output
+        let stripped = colored
+            .replace("\x1b[1;31m", "")
+            .replace("\x1b[2m", "")
+            .replace("\x1b[0m", "");
+        assert_eq!(stripped, plain);
+    }
+
+    #[test]
+    fn test_error_source() {
+        use std::error::Error;
+
+        let err = ParseError::io(io::Error::new(io::ErrorKind::NotFound, "missing.koi"));
+        let source = err.source().expect("IO error should have a source");
+        assert_eq!(source.to_string(), "missing.koi");
+
+        let err = ParseError::syntax("error".to_string());
+        assert!(err.source().is_none());
+    }
 }
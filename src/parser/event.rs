@@ -0,0 +1,128 @@
+//! Event-based parsing for the parser.
+//!
+//! This module defines the events emitted by `Parser::parse_events`. The
+//! events of a line are produced from its parsed `Command`, so they follow the
+//! same syntax rules as `Parser::next_command`.
+
+use crate::command::{Command, CompositeValue, Parameter, Value};
+
+/// Event emitted while parsing, in input order
+///
+/// Every regular command produces `CommandStart`, one event group per parameter
+/// and `CommandEnd`. A composite parameter is wrapped in `CompositeStart` and
+/// `CompositeEnd`, with a `Key` before each dictionary value. Text and
+/// annotation lines produce a single `Text` or `Annotation` event.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseEvent {
+    /// Start of a command, with its name
+    CommandStart(String),
+    /// A basic parameter, or a value inside a composite parameter
+    Param(Value),
+    /// Start of a composite parameter, with its name
+    CompositeStart(String),
+    /// Key of the following dictionary value
+    Key(String),
+    /// End of a composite parameter
+    CompositeEnd,
+    /// End of a command
+    CommandEnd,
+    /// A text line
+    Text(String),
+    /// An annotation line
+    Annotation(String),
+}
+
+/// Emit the events for a parsed command, moving its contents into the events
+///
+/// Events are only emitted for complete commands, so a consumer never sees a
+/// partial command followed by a parse error.
+pub(super) fn emit_command<F: FnMut(ParseEvent)>(command: Command, handler: &mut F) {
+    let Command { name, params, .. } = command;
+    match name.as_str() {
+        "@text" | "@annotation" => {
+            let content = match params.into_iter().next() {
+                Some(Parameter::Basic(Value::String(content))) => content,
+                _ => String::new(),
+            };
+            if name == "@text" {
+                handler(ParseEvent::Text(content));
+            } else {
+                handler(ParseEvent::Annotation(content));
+            }
+        }
+        _ => {
+            handler(ParseEvent::CommandStart(name));
+            for param in params {
+                match param {
+                    Parameter::Basic(value) => handler(ParseEvent::Param(value)),
+                    Parameter::Composite(name, value) => {
+                        handler(ParseEvent::CompositeStart(name));
+                        match value {
+                            CompositeValue::Single(value) => handler(ParseEvent::Param(value)),
+                            CompositeValue::List(values) | CompositeValue::Set(values) => {
+                                values
+                                    .into_iter()
+                                    .for_each(|value| handler(ParseEvent::Param(value)));
+                            }
+                            CompositeValue::Dict(entries) => {
+                                for (key, value) in entries {
+                                    handler(ParseEvent::Key(key));
+                                    handler(ParseEvent::Param(value));
+                                }
+                            }
+                        }
+                        handler(ParseEvent::CompositeEnd);
+                    }
+                }
+            }
+            handler(ParseEvent::CommandEnd);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Parser, ParserConfig, StringInputSource};
+
+    #[test]
+    fn test_parse_events() {
+        let input = StringInputSource::new("#draw Line pos(x: 0, y: 1) color(255, 0)\nHello\n## note");
+        let mut parser = Parser::new(input, ParserConfig::default());
+        let mut events = Vec::new();
+        parser.parse_events(|event| events.push(event)).unwrap();
+
+        assert_eq!(events, vec![
+            ParseEvent::CommandStart("draw".to_string()),
+            ParseEvent::Param(Value::from("Line")),
+            ParseEvent::CompositeStart("pos".to_string()),
+            ParseEvent::Key("x".to_string()),
+            ParseEvent::Param(Value::Int(0)),
+            ParseEvent::Key("y".to_string()),
+            ParseEvent::Param(Value::Int(1)),
+            ParseEvent::CompositeEnd,
+            ParseEvent::CompositeStart("color".to_string()),
+            ParseEvent::Param(Value::Int(255)),
+            ParseEvent::Param(Value::Int(0)),
+            ParseEvent::CompositeEnd,
+            ParseEvent::CommandEnd,
+            ParseEvent::Text("Hello".to_string()),
+            ParseEvent::Annotation("note".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_events_error() {
+        // No events are emitted for a line that fails to parse
+        let input = StringInputSource::new("#a 1\n#b 2 pos(3, ]\n#c\n");
+        let mut parser = Parser::new(input, ParserConfig::default());
+        let mut events = Vec::new();
+        assert!(parser.parse_events(|event| events.push(event)).is_err());
+
+        assert_eq!(events, vec![
+            ParseEvent::CommandStart("a".to_string()),
+            ParseEvent::Param(Value::Int(1)),
+            ParseEvent::CommandEnd,
+        ]);
+    }
+}
@@ -0,0 +1,199 @@
+//! Formatting hints recovered from the source text of parameters
+//!
+//! Parsing drops how a value was written: `0xFF` and `255` give the same
+//! [`Value::Int`](crate::command::Value::Int), and `"Alice"` and `Alice` the
+//! same string. A [`ParamHint`] records this per parameter, so a writer can
+//! reproduce the original spelling (see `Writer::write_command_with_hints`).
+
+use nom::error::Error;
+
+use super::ParserConfig;
+use super::command_parser::parse_parameter_hint_with_config;
+use crate::command::Value;
+use crate::writer::{FormatterOptions, NumberFormat};
+
+/// How a parameter was written in the source
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParamHint {
+    /// Base the integers of the parameter are written in
+    ///
+    /// `Unknown` if the parameter has no integer or its integers are written in
+    /// different bases. Character literals are not counted.
+    pub number_format: NumberFormat,
+    /// Whether the strings of the parameter were all quoted
+    ///
+    /// Only set if the parameter has at least one quoted string and no bare
+    /// string literal.
+    pub quoted: bool,
+}
+
+impl ParamHint {
+    /// Recover the hint from the source text of a single parameter
+    ///
+    /// The text is parsed with the default [`ParserConfig`]; use
+    /// [`ParamHint::from_source_with_config`] for other syntax options.
+    /// Text that is not a single parameter gives the default hint.
+    ///
+    /// # Arguments
+    /// * `text` - The parameter as written, e.g. `0xFF` or `pos(x: 0b1, y: 2)`
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::ParamHint;
+    /// use koicore::writer::NumberFormat;
+    ///
+    /// assert_eq!(ParamHint::from_source("0xFF").number_format, NumberFormat::Hex);
+    /// assert!(ParamHint::from_source("names(\"Alice\", \"Bob\")").quoted);
+    /// assert!(!ParamHint::from_source("names(\"Alice\", Bob)").quoted);
+    /// ```
+    pub fn from_source(text: &str) -> Self {
+        Self::from_source_with_config(text, &ParserConfig::default())
+    }
+
+    /// Recover the hint from the source text of a single parameter, parsed
+    /// with the syntax options of `config`
+    pub fn from_source_with_config(text: &str, config: &ParserConfig) -> Self {
+        match parse_parameter_hint_with_config::<Error<&str>>(text, config) {
+            Ok(("", hint)) => hint,
+            _ => Self::default(),
+        }
+    }
+
+    /// Get formatter options reproducing this hint
+    ///
+    /// Only `number_format` and `force_quotes_for_vars` are set, so the result
+    /// can be merged over other options.
+    pub fn to_options(&self) -> FormatterOptions {
+        FormatterOptions {
+            number_format: self.number_format.clone(),
+            force_quotes_for_vars: self.quoted,
+            ..Default::default()
+        }
+    }
+}
+
+/// How the values of a parameter were spelled, collected while parsing it
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Spellings {
+    /// Radix of the integers, if they all share one
+    radix: Option<u32>,
+    /// Whether the integers are written in different bases
+    mixed_radix: bool,
+    /// Whether a string was quoted
+    quoted: bool,
+    /// Whether a string was a bare literal
+    bare: bool,
+}
+
+impl Spellings {
+    /// Get the spelling of a basic value parsed from `source`
+    pub(crate) fn of(value: &Value, source: &str) -> Self {
+        let mut spellings = Self::default();
+        match value {
+            // Character literals also give integers, but have no base
+            Value::Int(_) if !source.starts_with('\'') => {
+                let digits = source.strip_prefix('-').unwrap_or(source);
+                spellings.radix = Some(match digits.get(..2) {
+                    Some("0x") => 16,
+                    Some("0b") => 2,
+                    Some("0o") => 8,
+                    _ => 10,
+                });
+            }
+            Value::String(_) if source.starts_with('"') || source.starts_with("u\"") => {
+                spellings.quoted = true;
+            }
+            // Numbers kept as text are strings too, but start with a digit
+            Value::String(_) if source.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') => {
+                spellings.bare = true;
+            }
+            _ => {}
+        }
+        spellings
+    }
+
+    /// Combine the spellings of two values of the same parameter
+    pub(crate) fn merge(self, other: Self) -> Self {
+        let (radix, mixed_radix) = match (self.radix, other.radix) {
+            (Some(a), Some(b)) if a != b => (None, true),
+            (a, b) => (a.or(b), self.mixed_radix || other.mixed_radix),
+        };
+        Self {
+            radix: if mixed_radix { None } else { radix },
+            mixed_radix,
+            quoted: self.quoted || other.quoted,
+            bare: self.bare || other.bare,
+        }
+    }
+
+    /// Get the hint for a parameter with these spellings
+    pub(crate) fn into_hint(self) -> ParamHint {
+        ParamHint {
+            number_format: match self.radix {
+                Some(16) => NumberFormat::Hex,
+                Some(8) => NumberFormat::Octal,
+                Some(2) => NumberFormat::Binary,
+                Some(_) => NumberFormat::Decimal,
+                None => NumberFormat::Unknown,
+            },
+            quoted: self.quoted && !self.bare,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hint_from_source() {
+        let hint = ParamHint::from_source("0b101");
+        assert_eq!(hint.number_format, NumberFormat::Binary);
+        assert!(!hint.quoted);
+
+        let hint = ParamHint::from_source("\"double \\\" quote\"");
+        assert_eq!(hint, ParamHint {
+            number_format: NumberFormat::Unknown,
+            quoted: true,
+        });
+
+        // Keys, names and type annotations are not values
+        let hint = ParamHint::from_source("opts(key: 0o17, name: \"x\")");
+        assert_eq!(hint.number_format, NumberFormat::Octal);
+        assert!(hint.quoted);
+        let hint = ParamHint::from_source("255:int");
+        assert_eq!(hint.number_format, NumberFormat::Decimal);
+
+        // Composites are read with the configured delimiters
+        let config = ParserConfig::default().with_dict_delimiters('{', '}');
+        let hint = ParamHint::from_source_with_config("pos{x: 0x0, y: \"a\"}", &config);
+        assert_eq!(hint, ParamHint {
+            number_format: NumberFormat::Hex,
+            quoted: true,
+        });
+
+        let hint = ParamHint::from_source("list(1.5, Line)");
+        assert_eq!(hint, ParamHint::default());
+
+        // Character literals are neither integers nor strings
+        let hint = ParamHint::from_source("list('\\'', \"a\", ')')");
+        assert_eq!(hint, ParamHint {
+            number_format: NumberFormat::Unknown,
+            quoted: true,
+        });
+
+        // Integers in different bases have no common format
+        let hint = ParamHint::from_source("list(0x1, 0x2)");
+        assert_eq!(hint.number_format, NumberFormat::Hex);
+        let hint = ParamHint::from_source("list(0x1, 2)");
+        assert_eq!(hint.number_format, NumberFormat::Unknown);
+        let hint = ParamHint::from_source("list(0x1, 0b1, 0x2)");
+        assert_eq!(hint.number_format, NumberFormat::Unknown);
+
+        // Percent strings are quoted if enabled, and not a parameter otherwise
+        let config = ParserConfig::default().with_enable_percent_strings(true);
+        assert!(ParamHint::from_source_with_config("u\"a%20b\"", &config).quoted);
+        assert_eq!(ParamHint::from_source("u\"a%20b\""), ParamHint::default());
+    }
+}
@@ -5,11 +5,17 @@
 
 use super::decode_buf_reader::DecodeBufReader;
 use encoding_rs::Encoding;
+use std::collections::VecDeque;
 use std::fmt::Debug;
 use std::fs::File;
-use std::io::{self, BufRead};
+#[cfg(feature = "compression")]
+use std::io::Read;
+use std::io::{self, BufRead, BufReader};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
 
 /// Encoding error handling strategy
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -40,6 +46,23 @@ pub trait TextInputSource {
     fn source_name(&self) -> String {
         "<string>".into()
     }
+
+    /// Get the line number of the line last returned by `next_line`
+    ///
+    /// Sources that number their lines themselves, such as a
+    /// [`ChainedInputSource`] restarting at each source, return `Some`.
+    /// By default the parser counts lines itself.
+    fn line_number(&self) -> Option<usize> {
+        None
+    }
+
+    /// Get the text encoding the source is decoded from
+    ///
+    /// Sources that decode bytes themselves, such as a [`FileInputSource`],
+    /// return `Some`. By default the encoding is unknown.
+    fn encoding(&self) -> Option<&'static Encoding> {
+        None
+    }
 }
 
 impl<T: TextInputSource + ?Sized> TextInputSource for Box<T> {
@@ -50,6 +73,14 @@ impl<T: TextInputSource + ?Sized> TextInputSource for Box<T> {
     fn source_name(&self) -> String {
         self.as_ref().source_name()
     }
+
+    fn line_number(&self) -> Option<usize> {
+        self.as_ref().line_number()
+    }
+
+    fn encoding(&self) -> Option<&'static Encoding> {
+        self.as_ref().encoding()
+    }
 }
 
 impl<T: TextInputSource + ?Sized> TextInputSource for Arc<Mutex<T>> {
@@ -66,11 +97,76 @@ impl<T: TextInputSource + ?Sized> TextInputSource for Arc<Mutex<T>> {
             .map(|s| s.source_name())
             .unwrap_or("<string>".into())
     }
+
+    fn line_number(&self) -> Option<usize> {
+        self.as_ref().lock().ok().and_then(|s| s.line_number())
+    }
+
+    fn encoding(&self) -> Option<&'static Encoding> {
+        self.as_ref().lock().ok().and_then(|s| s.encoding())
+    }
+}
+
+/// Compression format of an input file
+#[cfg(feature = "compression")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Uncompressed text
+    None,
+    /// gzip compressed (`.gz`)
+    Gzip,
+    /// Zstandard compressed (`.zst`)
+    Zstd,
+}
+
+#[cfg(feature = "compression")]
+impl Compression {
+    /// Detect the compression of a file
+    ///
+    /// The `.gz` and `.zst` extensions are recognized first. Otherwise the
+    /// first bytes of the file are checked for the gzip or zstd magic number.
+    ///
+    /// # Arguments
+    /// * `path` - Path to the file to inspect
+    pub fn detect<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        match Self::from_extension(path.as_ref()) {
+            Some(compression) => Ok(compression),
+            None => Self::from_magic(&mut BufReader::new(File::open(path)?)),
+        }
+    }
+
+    /// Get the compression named by the extension of a path, if any
+    fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("gz") => Some(Compression::Gzip),
+            Some("zst") => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Check the first buffered bytes of a reader for a magic number
+    ///
+    /// The bytes are not consumed, so the reader can still be decoded from
+    /// the start, even if it is a pipe that cannot seek back.
+    fn from_magic<R: BufRead>(reader: &mut R) -> io::Result<Self> {
+        Ok(match reader.fill_buf()? {
+            [0x1f, 0x8b, ..] => Compression::Gzip,
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Compression::Zstd,
+            _ => Compression::None,
+        })
+    }
 }
 
+/// Reader of a [`FileInputSource`]: a decompressor or the file itself
+#[cfg(feature = "compression")]
+type FileReader = Box<dyn Read + Send>;
+/// Reader of a [`FileInputSource`]
+#[cfg(not(feature = "compression"))]
+type FileReader = File;
+
 /// Input source that reads from a file with encoding support
 pub struct FileInputSource {
-    reader: DecodeBufReader<File>,
+    reader: DecodeBufReader<FileReader>,
     encoding_strategy: EncodingErrorStrategy,
     filename: PathBuf,
 }
@@ -78,6 +174,9 @@ pub struct FileInputSource {
 impl FileInputSource {
     /// Create a new file input source with automatic encoding detection
     ///
+    /// With the `compression` feature, gzip and zstd compressed files are
+    /// detected (see [`Compression::detect`]) and decompressed transparently.
+    ///
     /// # Arguments
     /// * `path` - Path to the file to read
     ///
@@ -85,9 +184,54 @@ impl FileInputSource {
     /// * `Ok(FileInputSource)` if the file was opened successfully
     /// * `Err(io::Error)` if there was an error opening the file
     pub fn new<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        #[cfg(feature = "compression")]
+        {
+            // Open once and peek, so pipes and other unseekable files work
+            let filename = path.as_ref().to_path_buf();
+            let mut file = BufReader::new(File::open(path)?);
+            let compression = match Compression::from_extension(&filename) {
+                Some(compression) => compression,
+                None => Compression::from_magic(&mut file)?,
+            };
+            Self::from_compressed(file, filename, compression)
+        }
+        #[cfg(not(feature = "compression"))]
         Self::with_encoding(path, None, EncodingErrorStrategy::Replace)
     }
 
+    /// Create a new file input source with explicit compression
+    ///
+    /// The decompressed text is read as UTF-8, replacing invalid sequences.
+    ///
+    /// # Arguments
+    /// * `path` - Path to the file to read
+    /// * `compression` - Compression format of the file
+    ///
+    /// # Returns
+    /// * `Ok(FileInputSource)` if the file was opened successfully
+    /// * `Err(io::Error)` if there was an error opening the file
+    #[cfg(feature = "compression")]
+    pub fn with_compression<P: AsRef<Path>>(path: P, compression: Compression) -> io::Result<Self> {
+        let filename = path.as_ref().to_path_buf();
+        let file = BufReader::new(File::open(path)?);
+        Self::from_compressed(file, filename, compression)
+    }
+
+    /// Decompress an opened file, read as UTF-8
+    #[cfg(feature = "compression")]
+    fn from_compressed(
+        file: BufReader<File>,
+        filename: PathBuf,
+        compression: Compression,
+    ) -> io::Result<Self> {
+        let reader: FileReader = match compression {
+            Compression::None => Box::new(file),
+            Compression::Gzip => Box::new(flate2::bufread::MultiGzDecoder::new(file)),
+            Compression::Zstd => Box::new(zstd::stream::read::Decoder::with_buffer(file)?),
+        };
+        Ok(Self::from_reader(reader, filename, None, EncodingErrorStrategy::Replace))
+    }
+
     /// Create a new file input source with specified encoding
     ///
     /// # Arguments
@@ -105,16 +249,23 @@ impl FileInputSource {
     ) -> io::Result<Self> {
         let filename = path.as_ref().to_path_buf();
         let file = File::open(path)?;
-        let reader = if let Some(enc) = encoding {
-            DecodeBufReader::with_encoding_and_strategy(file, enc, strategy)
-        } else {
-            DecodeBufReader::with_encoding_and_strategy(file, encoding_rs::UTF_8, strategy)
-        };
-        Ok(Self {
-            reader,
+        #[cfg(feature = "compression")]
+        let file = Box::new(file);
+        Ok(Self::from_reader(file, filename, encoding, strategy))
+    }
+
+    fn from_reader(
+        reader: FileReader,
+        filename: PathBuf,
+        encoding: Option<&'static Encoding>,
+        strategy: EncodingErrorStrategy,
+    ) -> Self {
+        let encoding = encoding.unwrap_or(encoding_rs::UTF_8);
+        Self {
+            reader: DecodeBufReader::with_encoding_and_strategy(reader, encoding, strategy),
             filename,
             encoding_strategy: strategy,
-        })
+        }
     }
 }
 
@@ -151,11 +302,111 @@ impl TextInputSource for FileInputSource {
         // We can enhance this to return the actual filename if needed
         self.filename.to_str().unwrap_or("<unknown>").to_owned()
     }
+
+    fn encoding(&self) -> Option<&'static Encoding> {
+        Some(self.reader.encoding())
+    }
+}
+
+/// Input source that follows a growing file, like `tail -f`
+///
+/// Instead of ending at end of file, [`next_line`](TextInputSource::next_line)
+/// waits for more data to be appended, polling at a fixed interval. A line is
+/// only returned once its newline has been written, so commands appended in
+/// several writes are not split. Reading ends once [`FollowStopHandle::stop`]
+/// is called; any unterminated last line is returned first.
+///
+/// Input is read as UTF-8, replacing invalid sequences.
+pub struct FollowingInputSource {
+    reader: BufReader<File>,
+    partial: Vec<u8>,
+    filename: PathBuf,
+    poll_interval: Duration,
+    stopped: Arc<AtomicBool>,
+}
+
+/// Handle to stop a [`FollowingInputSource`] from another thread
+#[derive(Debug, Clone)]
+pub struct FollowStopHandle(Arc<AtomicBool>);
+
+impl FollowStopHandle {
+    /// Stop following; the source returns end of input once it has no complete line left
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether [`stop`](Self::stop) has been called
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+impl FollowingInputSource {
+    /// Create a new following input source, polling every 100 ms
+    ///
+    /// Reading starts at the beginning of the file.
+    ///
+    /// # Arguments
+    /// * `path` - Path to the file to follow
+    ///
+    /// # Returns
+    /// * `Ok(FollowingInputSource)` if the file was opened successfully
+    /// * `Err(io::Error)` if there was an error opening the file
+    pub fn new<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let filename = path.as_ref().to_path_buf();
+        let file = File::open(path)?;
+        Ok(Self {
+            reader: BufReader::new(file),
+            partial: Vec::new(),
+            filename,
+            poll_interval: Duration::from_millis(100),
+            stopped: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Set how long to sleep between checks for new data
+    ///
+    /// # Arguments
+    /// * `interval` - Time to wait after reaching end of file
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Get a handle that stops following when [`FollowStopHandle::stop`] is called
+    pub fn stop_handle(&self) -> FollowStopHandle {
+        FollowStopHandle(self.stopped.clone())
+    }
+}
+
+impl TextInputSource for FollowingInputSource {
+    fn next_line(&mut self) -> io::Result<Option<String>> {
+        loop {
+            // Check before reading so data written before `stop` is still returned
+            let stopped = self.stopped.load(Ordering::SeqCst);
+            self.reader.read_until(b'\n', &mut self.partial)?;
+            if self.partial.ends_with(b"\n") || (stopped && !self.partial.is_empty()) {
+                let line = String::from_utf8_lossy(&self.partial).replace("\r\n", "\n");
+                self.partial.clear();
+                return Ok(Some(line));
+            }
+            if stopped {
+                return Ok(None);
+            }
+            thread::sleep(self.poll_interval);
+        }
+    }
+
+    fn source_name(&self) -> String {
+        self.filename.to_str().unwrap_or("<unknown>").to_owned()
+    }
 }
 
 /// Input source that reads from a string
 pub struct StringInputSource {
     lines: std::vec::IntoIter<String>,
+    line_offsets: Vec<usize>,
+    name: Option<String>,
 }
 
 impl StringInputSource {
@@ -164,20 +415,267 @@ impl StringInputSource {
     /// # Arguments
     /// * `content` - The string content to parse
     pub fn new(content: &str) -> Self {
+        let mut line_offsets = Vec::new();
+        let mut offset = 0;
         let lines: Vec<String> = content
             .split_inclusive('\n')
-            .map(|s| s.to_string())
+            .map(|s| {
+                line_offsets.push(offset);
+                offset += s.len();
+                s.to_string()
+            })
             .collect();
         Self {
             lines: lines.into_iter(),
+            line_offsets,
+            name: None,
+        }
+    }
+
+    /// Create a new string input source with a name for error reporting
+    ///
+    /// # Arguments
+    /// * `content` - The string content to parse
+    /// * `name` - The source name reported instead of `<string>`
+    pub fn with_name(content: &str, name: &str) -> Self {
+        Self {
+            name: Some(name.to_string()),
+            ..Self::new(content)
         }
     }
+
+    /// Get the byte offset of the start of each line in the content
+    ///
+    /// Line `n` (1-based) starts at `line_offsets()[n - 1]`.
+    pub fn line_offsets(&self) -> &[usize] {
+        &self.line_offsets
+    }
 }
 
 impl TextInputSource for StringInputSource {
     fn next_line(&mut self) -> io::Result<Option<String>> {
         Ok(self.lines.next())
     }
+
+    fn source_name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| "<string>".into())
+    }
+}
+
+/// Input source that reads several sources one after another
+///
+/// The sources form one logical stream, e.g. to parse a directory of files as
+/// one document. The source name is that of the source the last line came from,
+/// so errors name the originating file. Line numbers continue across sources
+/// by default; see [`ChainedInputSource::with_per_source_numbering`].
+pub struct ChainedInputSource {
+    sources: Vec<Box<dyn TextInputSource>>,
+    current: usize,
+    per_source_numbering: bool,
+    line_number: usize,
+}
+
+impl ChainedInputSource {
+    /// Create a new chained input source
+    ///
+    /// # Arguments
+    /// * `sources` - The sources to read, in order
+    pub fn new(sources: Vec<Box<dyn TextInputSource>>) -> Self {
+        Self {
+            sources,
+            current: 0,
+            per_source_numbering: false,
+            line_number: 0,
+        }
+    }
+
+    /// Set whether line numbers restart at 1 for each source
+    ///
+    /// # Arguments
+    /// * `enable` - Number lines per source (true) or across all sources (false)
+    pub fn with_per_source_numbering(mut self, enable: bool) -> Self {
+        self.per_source_numbering = enable;
+        self
+    }
+}
+
+impl TextInputSource for ChainedInputSource {
+    fn next_line(&mut self) -> io::Result<Option<String>> {
+        while let Some(source) = self.sources.get_mut(self.current) {
+            if let Some(line) = source.next_line()? {
+                self.line_number += 1;
+                return Ok(Some(line));
+            }
+            if self.current + 1 == self.sources.len() {
+                // Keep naming the last source once all are exhausted
+                break;
+            }
+            self.current += 1;
+            self.line_number = 0;
+        }
+        Ok(None)
+    }
+
+    fn source_name(&self) -> String {
+        match self.sources.get(self.current) {
+            Some(source) => source.source_name(),
+            None => "<string>".into(),
+        }
+    }
+
+    fn line_number(&self) -> Option<usize> {
+        self.per_source_numbering.then_some(self.line_number)
+    }
+
+    fn encoding(&self) -> Option<&'static Encoding> {
+        self.sources.get(self.current)?.encoding()
+    }
+}
+
+/// Input source that reads only the fenced code blocks of another source
+///
+/// Used to parse KoiLang embedded in Markdown: everything outside code blocks
+/// opened with the given info string (e.g. ```` ```koi ````) is skipped.
+/// A block opens with three or more backticks or tildes followed by the
+/// marker, and closes with a line of at least as many of the same character.
+/// Line numbers are those of the underlying document.
+pub struct FencedInputSource<T: TextInputSource> {
+    source: T,
+    marker: String,
+    /// The opening fence (character and length) of the current block
+    fence: Option<(char, usize)>,
+    line_number: usize,
+}
+
+impl<T: TextInputSource> FencedInputSource<T> {
+    /// Create a new fenced input source
+    ///
+    /// # Arguments
+    /// * `source` - The document containing the fenced blocks
+    /// * `marker` - The info string of the blocks to read, e.g. `"koi"`
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::{FencedInputSource, Parser, ParserConfig, StringInputSource};
+    ///
+    /// let markdown = "# Intro\n\n```koi\n#hello\n```\n";
+    /// let input = FencedInputSource::new(StringInputSource::new(markdown), "koi");
+    /// let mut parser = Parser::new(input, ParserConfig::default());
+    /// let (command, source) = parser.next_command_with_source()?.unwrap();
+    /// assert_eq!(command.name, "hello");
+    /// assert_eq!(source.lineno, 4);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn new(source: T, marker: &str) -> Self {
+        Self {
+            source,
+            marker: marker.to_string(),
+            fence: None,
+            line_number: 0,
+        }
+    }
+
+    /// Get the fence character and length of a fence line
+    fn fence_of(line: &str) -> Option<(char, usize, &str)> {
+        let line = line.trim();
+        let fence_char = line.chars().next().filter(|c| *c == '`' || *c == '~')?;
+        let len = line.chars().take_while(|c| *c == fence_char).count();
+        (len >= 3).then(|| (fence_char, len, line[len..].trim()))
+    }
+}
+
+impl<T: TextInputSource> TextInputSource for FencedInputSource<T> {
+    fn next_line(&mut self) -> io::Result<Option<String>> {
+        while let Some(line) = self.source.next_line()? {
+            self.line_number = self.source.line_number().unwrap_or(self.line_number + 1);
+            let fence = Self::fence_of(&line);
+            match (self.fence, fence) {
+                (None, Some((fence_char, len, info))) if info == self.marker => {
+                    self.fence = Some((fence_char, len));
+                }
+                (None, _) => {}
+                (Some((open_char, open_len)), Some((fence_char, len, "")))
+                    if fence_char == open_char && len >= open_len =>
+                {
+                    self.fence = None;
+                }
+                (Some(_), _) => return Ok(Some(line)),
+            }
+        }
+        Ok(None)
+    }
+
+    fn source_name(&self) -> String {
+        self.source.source_name()
+    }
+
+    fn line_number(&self) -> Option<usize> {
+        Some(self.line_number)
+    }
+
+    fn encoding(&self) -> Option<&'static Encoding> {
+        self.source.encoding()
+    }
+}
+
+/// Input source that returns some lines before those of another source
+///
+/// Returned by [`Parser::into_parts`](super::Parser::into_parts) for lines
+/// the parser had read ahead, so a parser rebuilt from the parts continues
+/// where the old one stopped. Replayed lines have no line number of their
+/// own; the parser counts them.
+pub struct ReplayInputSource<T: TextInputSource> {
+    lines: VecDeque<String>,
+    source: T,
+    /// Whether the line last returned was replayed
+    replayed: bool,
+}
+
+impl<T: TextInputSource> ReplayInputSource<T> {
+    /// Create a new replay input source
+    ///
+    /// # Arguments
+    /// * `lines` - The lines to return first, in order
+    /// * `source` - The source to read once the lines are exhausted
+    pub fn new(lines: impl IntoIterator<Item = String>, source: T) -> Self {
+        Self {
+            lines: lines.into_iter().collect(),
+            source,
+            replayed: false,
+        }
+    }
+
+    /// Get the inner source, dropping any lines not yet replayed
+    pub fn into_inner(self) -> T {
+        self.source
+    }
+}
+
+impl<T: TextInputSource> TextInputSource for ReplayInputSource<T> {
+    fn next_line(&mut self) -> io::Result<Option<String>> {
+        self.replayed = !self.lines.is_empty();
+        match self.lines.pop_front() {
+            Some(line) => Ok(Some(line)),
+            None => self.source.next_line(),
+        }
+    }
+
+    fn source_name(&self) -> String {
+        self.source.source_name()
+    }
+
+    fn line_number(&self) -> Option<usize> {
+        if self.replayed {
+            None
+        } else {
+            self.source.line_number()
+        }
+    }
+
+    fn encoding(&self) -> Option<&'static Encoding> {
+        self.source.encoding()
+    }
 }
 
 pub struct BufReadWrapper<R: BufRead>(pub R);
@@ -197,6 +695,8 @@ impl<R: BufRead> TextInputSource for BufReadWrapper<R> {
 pub(crate) struct Input<T: TextInputSource> {
     pub source: T,
     pub line_number: usize,
+    /// Bytes of text read from the source so far
+    pub offset: usize,
 }
 
 impl<T: TextInputSource> Input<T> {
@@ -204,16 +704,41 @@ impl<T: TextInputSource> Input<T> {
         Self {
             source,
             line_number: 1,
+            offset: 0,
         }
     }
 
-    pub fn next_line(&mut self) -> io::Result<Option<(usize, String)>> {
+    /// Replace the source and restart counting lines and offsets
+    ///
+    /// Returns the previous source.
+    pub fn reset(&mut self, source: T) -> T {
+        self.line_number = 1;
+        self.offset = 0;
+        std::mem::replace(&mut self.source, source)
+    }
+
+    /// Read the next logical line, rewriting each physical line with `transform`
+    ///
+    /// `transform` runs before lines ending in `\` are joined, so it sees
+    /// every line as read from the source.
+    pub fn next_line(
+        &mut self,
+        mut transform: impl FnMut(&mut String),
+    ) -> io::Result<Option<(usize, String)>> {
         let mut line_cache = String::new();
-        let start_line_number = self.line_number;
+        let mut start_line_number = self.line_number;
         loop {
             match self.source.next_line() {
-                Ok(Some(line)) => {
+                Ok(Some(mut line)) => {
+                    if let Some(line_number) = self.source.line_number() {
+                        self.line_number = line_number;
+                    }
+                    if line_cache.is_empty() {
+                        start_line_number = self.line_number;
+                    }
                     self.line_number += 1;
+                    self.offset += line.len();
+                    transform(&mut line);
                     line_cache.push_str(&line);
                     if !line.ends_with("\\\n") {
                         break Ok(Some((start_line_number, line_cache)));
@@ -271,7 +796,7 @@ mod tests {
         // It accumulates lines ending with \\\n
 
         // First call should return combined line
-        let (lineno, text) = input.next_line().unwrap().unwrap();
+        let (lineno, text) = input.next_line(|_| {}).unwrap().unwrap();
         assert_eq!(lineno, 1);
         assert_eq!(text, "line1\\\n continued\n");
         assert_eq!(input.line_number, 3); // 1 + 1 (continued) + 1 (next) -> next will be 3?
@@ -285,11 +810,11 @@ mod tests {
         // ends_with("\\\n") false -> break.
         // Returns (original line_number 1, accumulated text).
 
-        let (lineno, text) = input.next_line().unwrap().unwrap();
+        let (lineno, text) = input.next_line(|_| {}).unwrap().unwrap();
         assert_eq!(lineno, 3);
         assert_eq!(text, "line2");
 
-        assert!(input.next_line().unwrap().is_none());
+        assert!(input.next_line(|_| {}).unwrap().is_none());
     }
 
     #[test]
@@ -337,6 +862,52 @@ mod tests {
         let _ = fs::remove_file(path);
     }
 
+    #[test]
+    fn test_file_input_source_reports_encoding() {
+        use std::env;
+        use std::fs;
+
+        let mut path = env::temp_dir();
+        path.push("koi_test_reported_encoding.txt");
+        fs::write(&path, b"\xEF\xBB\xBF#hello\n").unwrap();
+
+        let source = FileInputSource::new(&path).unwrap();
+        assert_eq!(source.encoding(), Some(encoding_rs::UTF_8));
+
+        let source = FileInputSource::with_encoding(
+            &path,
+            Some(encoding_rs::SHIFT_JIS),
+            EncodingErrorStrategy::Replace,
+        )
+        .unwrap();
+        assert_eq!(source.encoding(), Some(encoding_rs::SHIFT_JIS));
+
+        // A byte order mark overrides the configured encoding once read
+        let mut boxed: Box<dyn TextInputSource> = Box::new(source);
+        assert_eq!(boxed.next_line().unwrap(), Some("#hello\n".to_string()));
+        assert_eq!(boxed.encoding(), Some(encoding_rs::UTF_8));
+
+        assert_eq!(StringInputSource::new("#hello").encoding(), None);
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_replay_input_source() {
+        let lines = ["#a\n".to_string(), "#b".to_string()];
+        let inner = ChainedInputSource::new(vec![Box::new(StringInputSource::new("#c\n#d"))])
+            .with_per_source_numbering(true);
+        let mut source = ReplayInputSource::new(lines, inner);
+
+        assert_eq!(source.next_line().unwrap(), Some("#a\n".to_string()));
+        assert_eq!(source.line_number(), None);
+        assert_eq!(source.next_line().unwrap(), Some("#b".to_string()));
+        assert_eq!(source.next_line().unwrap(), Some("#c\n".to_string()));
+        assert_eq!(source.line_number(), Some(1));
+        assert_eq!(source.next_line().unwrap(), Some("#d".to_string()));
+        assert_eq!(source.next_line().unwrap(), None);
+    }
+
     #[test]
     fn test_box_text_input_source() {
         let source = StringInputSource::new("line1\nline2");
@@ -358,4 +929,49 @@ mod tests {
         assert_eq!(arc_source.next_line().unwrap(), Some("line2".to_string()));
         assert_eq!(arc_source.next_line().unwrap(), None);
     }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_file_input_source_zstd_magic() {
+        use std::env;
+
+        // No extension, so the format must be detected from the magic bytes
+        let mut path = env::temp_dir();
+        path.push("koi_test_compression");
+        let compressed = zstd::encode_all(&b"#draw Line\nHello\n"[..], 0).unwrap();
+        std::fs::write(&path, compressed).unwrap();
+
+        assert_eq!(Compression::detect(&path).unwrap(), Compression::Zstd);
+        let mut source = FileInputSource::new(&path).unwrap();
+        assert_eq!(source.next_line().unwrap().unwrap(), "#draw Line\n");
+        assert_eq!(source.next_line().unwrap().unwrap(), "Hello\n");
+        assert!(source.next_line().unwrap().is_none());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(all(feature = "compression", target_os = "linux"))]
+    #[test]
+    fn test_file_input_source_pipe() {
+        use std::io::Write;
+        use std::os::fd::AsRawFd;
+
+        // A pipe can't seek back, so the magic bytes must not be read twice
+        let text = b"#draw Line 1\n#end\n";
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(text).unwrap();
+        let compressed = gzip.finish().unwrap();
+
+        for data in [&text[..], &compressed[..]] {
+            let (reader, mut writer) = io::pipe().unwrap();
+            writer.write_all(data).unwrap();
+            drop(writer);
+
+            let path = format!("/proc/self/fd/{}", reader.as_raw_fd());
+            let mut source = FileInputSource::new(&path).unwrap();
+            assert_eq!(source.next_line().unwrap().unwrap(), "#draw Line 1\n");
+            assert_eq!(source.next_line().unwrap().unwrap(), "#end\n");
+            assert!(source.next_line().unwrap().is_none());
+        }
+    }
 }
@@ -28,22 +28,59 @@
 pub mod command_parser;
 pub mod decode_buf_reader;
 pub mod error;
+pub mod event;
+pub mod hint;
 pub mod input;
+pub mod preprocess;
+pub mod span;
 pub mod traceback;
 
-use super::command::Command;
-pub use error::{ErrorInfo, ParseError, ParseResult, ParserLineSource};
-pub use input::{BufReadWrapper, FileInputSource, StringInputSource, TextInputSource};
+use std::collections::{HashSet, VecDeque};
+use std::ops::Range;
+use std::str::FromStr;
+
+use super::command::{Command, Value};
+pub use error::{
+    ConfigError, ErrorInfo, ParseError, ParseResult, ParseWarning, ParserLineSource,
+};
+pub use event::ParseEvent;
+pub use hint::ParamHint;
+pub use preprocess::{ConditionalPreprocessor, Preprocessor};
+pub use span::SourcedCommand;
+pub use input::{
+    BufReadWrapper, ChainedInputSource, FencedInputSource, FileInputSource, FollowStopHandle,
+    FollowingInputSource, ReplayInputSource, StringInputSource, TextInputSource,
+};
+#[cfg(feature = "compression")]
+pub use input::Compression;
 use nom::Offset;
 pub use traceback::TracebackEntry;
 
 use input::Input;
 use traceback::NomErrorNode;
 
+/// How integer literals that don't fit in `i64` are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IntOverflow {
+    /// Reject the value with a parse error (default)
+    #[default]
+    Error,
+    /// Clamp the value to `i64::MIN` or `i64::MAX`
+    Saturate,
+    /// Keep the low 64 bits in two's complement
+    Wrap,
+}
+
 /// Configuration for the line processor
 ///
 /// Controls how the parser interprets different types of lines in the input.
+///
+/// With the `serde` feature, the configuration can be serialized; fields
+/// missing when deserializing take their default values.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct ParserConfig {
     /// The command threshold (number of # required for commands)
     ///
@@ -71,6 +108,96 @@ pub struct ParserConfig {
     /// If set to true, empty lines will be preserved and returned as empty text commands.
     /// If set to false, empty lines will be skipped.
     pub preserve_empty_lines: bool,
+    /// Whether to accept set literals in composite parameters
+    ///
+    /// If set to true, `name{v1, v2}` is parsed as a [`CompositeValue::Set`] with
+    /// duplicate values removed. If set to false, braces are a syntax error.
+    ///
+    /// [`CompositeValue::Set`]: crate::command::CompositeValue::Set
+    pub enable_set_literals: bool,
+    /// Additional separator between commands on the same line
+    ///
+    /// If set, each command line is split on this separator (outside of quoted
+    /// strings) and every piece is processed as its own line; text and
+    /// annotation lines are kept whole. This reads back output
+    /// written with a matching `WriterConfig::command_separator`. If `None`,
+    /// only newlines separate commands.
+    pub command_separator: Option<String>,
+    /// Whether to accept percent-encoded `u"..."` string parameters
+    ///
+    /// If set to true, a basic parameter like `u"a%20b"` is percent-decoded
+    /// into the string `a b`. Useful for URLs and query strings, which would
+    /// otherwise need heavy escaping. Not accepted inside composite values.
+    pub enable_percent_strings: bool,
+    /// How to handle integer literals outside the `i64` range
+    pub int_overflow: IntOverflow,
+    /// Whether indented lines continue the parameters of the preceding command
+    ///
+    /// If set to true, lines without a `#` prefix that are indented further than
+    /// the preceding command line are parsed as more parameters of that command
+    /// instead of becoming text. Indentation is counted in whitespace characters.
+    /// If set to false, such lines are text.
+    pub indent_continuation: bool,
+    /// Whether annotation content keeps its leading `#` characters
+    ///
+    /// If set to true, an annotation like `###  note` is stored as `###  note`
+    /// instead of `note`. Surrounding whitespace is still trimmed unless
+    /// `preserve_indent` is set, which keeps the whole line anyway.
+    pub annotation_keep_hashes: bool,
+    /// Opening and closing characters of list composites
+    ///
+    /// Defaults to parentheses, as in `color(255, 255, 255)`. Single-value
+    /// composites use the same delimiters.
+    pub list_delimiters: (char, char),
+    /// Opening and closing characters of dictionary composites
+    ///
+    /// Defaults to parentheses, as in `pos(x: 0, y: 0)`. Can be the same as
+    /// `list_delimiters`, in which case the contents tell the two apart.
+    pub dict_delimiters: (char, char),
+    /// Whether numbers are kept as strings
+    ///
+    /// If set to true, number literals such as `007`, `1.0` and `0xFF` are
+    /// parsed as strings holding their exact source text, in basic parameters
+    /// and in composites. Number type annotations like `7:int` are then
+    /// rejected. Number commands such as `#7` are not affected.
+    pub numbers_as_strings: bool,
+    /// Whether text lines ending in `\` continue on the next line
+    ///
+    /// If set to true, a text line ending in a backslash is joined with the
+    /// next line into one text command. The backslash, the line break and the
+    /// indentation of the next line are removed, so `dark \` followed by
+    /// `night` becomes `dark night`. If set to false, the backslash and line
+    /// break are kept in the text.
+    pub text_line_continuation: bool,
+    /// Character that starts command and annotation lines
+    ///
+    /// Defaults to `#`. `command_threshold` counts this character, so with
+    /// `%` and a threshold of 1, `%draw` is a command and `%% note` an
+    /// annotation. Useful to embed KoiLang in formats where `#` already has a
+    /// meaning. Read output back with a matching `WriterConfig::prefix_char`.
+    pub prefix_char: char,
+    /// Marker starting a trailing comment on command lines
+    ///
+    /// If set, everything from the first occurrence of the marker outside a
+    /// quoted string to the end of a command line is discarded, e.g. with
+    /// `"//"`, `#draw Line 2  // set up` is read as `#draw Line 2`. Command
+    /// lines holding only a comment are skipped. Text and annotation lines
+    /// are not affected. A comment ends at the `command_separator`, if any.
+    /// If `None`, command lines have no comments.
+    pub comment_marker: Option<String>,
+    /// Whether to record the indentation of command lines
+    ///
+    /// If set to true, the leading whitespace of each command line is stored
+    /// in [`Command::indent`], and `Writer::write_command_with_hints` writes it
+    /// back instead of its own indentation. Text and annotation lines keep
+    /// their indentation with `preserve_indent` instead.
+    pub record_command_indent: bool,
+    /// Whether to record the source position of each command
+    ///
+    /// If set to true, every command read by the parser has its line and
+    /// column in [`Command::span`], which is also serialized. If set to false,
+    /// commands have no span.
+    pub record_spans: bool,
 }
 
 impl Default for ParserConfig {
@@ -81,6 +208,20 @@ impl Default for ParserConfig {
             convert_number_command: true,
             preserve_indent: false,
             preserve_empty_lines: false,
+            enable_set_literals: false,
+            command_separator: None,
+            enable_percent_strings: false,
+            int_overflow: IntOverflow::Error,
+            indent_continuation: false,
+            annotation_keep_hashes: false,
+            list_delimiters: ('(', ')'),
+            dict_delimiters: ('(', ')'),
+            numbers_as_strings: false,
+            text_line_continuation: false,
+            prefix_char: '#',
+            comment_marker: None,
+            record_command_indent: false,
+            record_spans: false,
         }
     }
 }
@@ -119,6 +260,79 @@ impl ParserConfig {
             convert_number_command,
             preserve_indent,
             preserve_empty_lines,
+            enable_set_literals: false,
+            command_separator: None,
+            enable_percent_strings: false,
+            int_overflow: IntOverflow::Error,
+            indent_continuation: false,
+            annotation_keep_hashes: false,
+            list_delimiters: ('(', ')'),
+            dict_delimiters: ('(', ')'),
+            numbers_as_strings: false,
+            text_line_continuation: false,
+            prefix_char: '#',
+            comment_marker: None,
+            record_command_indent: false,
+            record_spans: false,
+        }
+    }
+
+    /// Create a configuration that keeps as much of the source as possible
+    ///
+    /// Intended for formatters that rewrite a document with minimal changes.
+    /// This turns on every option that records how the source was written:
+    /// `preserve_indent`, `preserve_empty_lines`, `record_command_indent` and
+    /// `record_spans`.
+    /// Number bases and quoting are recorded per parameter by
+    /// [`Parser::next_command_with_hints`], and the exact source text of each
+    /// command and parameter by [`Parser::next_command_with_spans`]. Combined
+    /// with `Writer::write_command_with_hints`, the output matches the input
+    /// except for whitespace normalization.
+    ///
+    /// Preserved:
+    /// * text and annotation lines, byte for byte, including indentation and
+    ///   the `#` characters of annotations
+    /// * the indentation of command lines
+    /// * empty lines
+    /// * the case of command names
+    /// * with hints, the base of integers and whether strings were quoted;
+    ///   both are recorded per parameter, so a parameter mixing bases is
+    ///   written in decimal
+    ///
+    /// Normalized:
+    /// * whitespace between parameters, inside composites and at the end of
+    ///   command lines
+    /// * the case of hex digits, leading zeros of number commands (`#007`
+    ///   becomes `#7`), float spelling (`1.50` becomes `1.5`) and string escapes
+    /// * type annotations, which are dropped
+    /// * line continuations, which are joined into one line
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::{Parser, ParserConfig, StringInputSource};
+    /// use koicore::writer::{Writer, WriterConfig};
+    ///
+    /// let source = "#Color 0xff \"red\"\n\n  indented text\n    #size 0b101\n";
+    /// let input = StringInputSource::new(source);
+    /// let mut parser = Parser::new(input, ParserConfig::format_preserving());
+    ///
+    /// let mut buffer = Vec::new();
+    /// let mut writer = Writer::new(&mut buffer, WriterConfig::default());
+    /// while let Some((command, hints)) = parser.next_command_with_hints()? {
+    ///     writer.write_command_with_hints(&command, &hints)?;
+    /// }
+    /// drop(writer);
+    /// assert_eq!(String::from_utf8(buffer)?, source);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn format_preserving() -> Self {
+        Self {
+            preserve_indent: true,
+            preserve_empty_lines: true,
+            record_command_indent: true,
+            record_spans: true,
+            ..Default::default()
         }
     }
 
@@ -206,8 +420,315 @@ impl ParserConfig {
         self.preserve_empty_lines = preserve;
         self
     }
+
+    /// Set whether to accept set literals in composite parameters
+    ///
+    /// # Arguments
+    /// * `enable` - Whether `name{v1, v2}` is parsed as a set
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::ParserConfig;
+    ///
+    /// let config = ParserConfig::default().with_enable_set_literals(true);
+    /// ```
+    pub fn with_enable_set_literals(mut self, enable: bool) -> Self {
+        self.enable_set_literals = enable;
+        self
+    }
+
+    /// Set an additional separator between commands on the same line
+    ///
+    /// # Arguments
+    /// * `separator` - Separator text, e.g. `";"`
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::ParserConfig;
+    ///
+    /// let config = ParserConfig::default().with_command_separator(";");
+    /// ```
+    pub fn with_command_separator(mut self, separator: &str) -> Self {
+        self.command_separator = Some(separator.to_string());
+        self
+    }
+
+    /// Set whether to accept percent-encoded `u"..."` string parameters
+    ///
+    /// # Arguments
+    /// * `enable` - Whether to percent-decode `u"..."` strings
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::ParserConfig;
+    ///
+    /// let config = ParserConfig::default().with_enable_percent_strings(true);
+    /// ```
+    pub fn with_enable_percent_strings(mut self, enable: bool) -> Self {
+        self.enable_percent_strings = enable;
+        self
+    }
+
+    /// Set how integer literals outside the `i64` range are handled
+    ///
+    /// # Arguments
+    /// * `overflow` - Overflow policy
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::{IntOverflow, ParserConfig};
+    ///
+    /// let config = ParserConfig::default().with_int_overflow(IntOverflow::Saturate);
+    /// ```
+    pub fn with_int_overflow(mut self, overflow: IntOverflow) -> Self {
+        self.int_overflow = overflow;
+        self
+    }
+
+    /// Set whether indented lines continue the preceding command
+    ///
+    /// # Arguments
+    /// * `enable` - Whether to append indented lines as parameters (true) or read them as text (false)
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::ParserConfig;
+    ///
+    /// let config = ParserConfig::default().with_indent_continuation(true);
+    /// ```
+    pub fn with_indent_continuation(mut self, enable: bool) -> Self {
+        self.indent_continuation = enable;
+        self
+    }
+
+    /// Set whether annotation content keeps its leading `#` characters
+    ///
+    /// # Arguments
+    /// * `enable` - Whether to keep the hashes (true) or strip them (false)
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::ParserConfig;
+    ///
+    /// let config = ParserConfig::default().with_annotation_keep_hashes(true);
+    /// ```
+    pub fn with_annotation_keep_hashes(mut self, enable: bool) -> Self {
+        self.annotation_keep_hashes = enable;
+        self
+    }
+
+    /// Set the opening and closing characters of list composites
+    ///
+    /// # Arguments
+    /// * `open` - Opening character, e.g. `'['`
+    /// * `close` - Closing character, e.g. `']'`
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::ParserConfig;
+    ///
+    /// let config = ParserConfig::default().with_list_delimiters('[', ']');
+    /// ```
+    pub fn with_list_delimiters(mut self, open: char, close: char) -> Self {
+        self.list_delimiters = (open, close);
+        self
+    }
+
+    /// Set the opening and closing characters of dictionary composites
+    ///
+    /// # Arguments
+    /// * `open` - Opening character, e.g. `'{'`
+    /// * `close` - Closing character, e.g. `'}'`
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::ParserConfig;
+    ///
+    /// let config = ParserConfig::default().with_dict_delimiters('{', '}');
+    /// ```
+    pub fn with_dict_delimiters(mut self, open: char, close: char) -> Self {
+        self.dict_delimiters = (open, close);
+        self
+    }
+
+    /// Set whether numbers are kept as strings
+    ///
+    /// # Arguments
+    /// * `enable` - Whether to parse number literals as their source text
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::ParserConfig;
+    ///
+    /// let config = ParserConfig::default().with_numbers_as_strings(true);
+    /// ```
+    pub fn with_numbers_as_strings(mut self, enable: bool) -> Self {
+        self.numbers_as_strings = enable;
+        self
+    }
+
+    /// Set whether text lines ending in `\` continue on the next line
+    ///
+    /// # Arguments
+    /// * `enable` - Whether to join continued text lines
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::ParserConfig;
+    ///
+    /// let config = ParserConfig::default().with_text_line_continuation(true);
+    /// ```
+    pub fn with_text_line_continuation(mut self, enable: bool) -> Self {
+        self.text_line_continuation = enable;
+        self
+    }
+
+    /// Set the character that starts command and annotation lines
+    ///
+    /// # Arguments
+    /// * `prefix` - The prefix character, `#` by default
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::ParserConfig;
+    ///
+    /// let config = ParserConfig::default().with_prefix_char('%');
+    /// ```
+    pub fn with_prefix_char(mut self, prefix: char) -> Self {
+        self.prefix_char = prefix;
+        self
+    }
+
+    /// Set the marker starting a trailing comment on command lines
+    ///
+    /// # Arguments
+    /// * `marker` - Comment marker, e.g. `"//"`
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::ParserConfig;
+    ///
+    /// let config = ParserConfig::default().with_comment_marker("//");
+    /// ```
+    pub fn with_comment_marker(mut self, marker: &str) -> Self {
+        self.comment_marker = Some(marker.to_string());
+        self
+    }
+
+    /// Set whether to record the indentation of command lines
+    ///
+    /// # Arguments
+    /// * `enable` - Whether to store the indentation in each command
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::ParserConfig;
+    ///
+    /// let config = ParserConfig::default().with_record_command_indent(true);
+    /// ```
+    pub fn with_record_command_indent(mut self, enable: bool) -> Self {
+        self.record_command_indent = enable;
+        self
+    }
+
+    /// Set whether to record the source position of each command
+    ///
+    /// # Arguments
+    /// * `enable` - Whether to store the line and column in each command
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::ParserConfig;
+    ///
+    /// let config = ParserConfig::default().with_record_spans(true);
+    /// ```
+    pub fn with_record_spans(mut self, enable: bool) -> Self {
+        self.record_spans = enable;
+        self
+    }
+
+    /// Check this configuration for settings the parser cannot honour
+    ///
+    /// The following are rejected:
+    /// * `command_threshold` of 0, which would leave no room for text lines
+    /// * an empty `command_separator` or `comment_marker`
+    /// * a `command_separator` containing the `prefix_char` or a line break,
+    ///   which would split command prefixes or never match
+    /// * composite delimiters that are not distinct ASCII punctuation, that
+    ///   are used by other syntax, or that open lists and dictionaries alike
+    ///   but close them differently
+    /// * braces as composite delimiters while `enable_set_literals` is set
+    /// * a `prefix_char` that is whitespace, alphanumeric, `_`, a quote, a
+    ///   backslash or a composite delimiter
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::{ConfigError, ParserConfig};
+    ///
+    /// assert!(ParserConfig::default().validate().is_ok());
+    ///
+    /// let config = ParserConfig::default().with_command_threshold(0);
+    /// assert_eq!(config.validate(), Err(ConfigError::ZeroCommandThreshold));
+    /// ```
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.command_threshold == 0 {
+            return Err(ConfigError::ZeroCommandThreshold);
+        }
+        if let Some(separator) = &self.command_separator {
+            if separator.is_empty() {
+                return Err(ConfigError::EmptyCommandSeparator);
+            }
+            if separator.contains([self.prefix_char, '\n', '\r']) {
+                return Err(ConfigError::InvalidCommandSeparator {
+                    separator: separator.clone(),
+                });
+            }
+        }
+        if self.comment_marker.as_deref() == Some("") {
+            return Err(ConfigError::EmptyCommentMarker);
+        }
+        let (list, dict) = (self.list_delimiters, self.dict_delimiters);
+        for (open, close) in [list, dict] {
+            let usable = |c: char| c.is_ascii_punctuation() && !"\"#,:\\.-+_".contains(c);
+            if open == close
+                || !usable(open)
+                || !usable(close)
+                || (self.enable_set_literals && (open == '{' || close == '}'))
+                || (list.0 == dict.0 && list.1 != dict.1)
+            {
+                return Err(ConfigError::InvalidDelimiters { open, close });
+            }
+        }
+        let prefix = self.prefix_char;
+        if prefix.is_whitespace()
+            || prefix.is_alphanumeric()
+            || "_\"'\\".contains(prefix)
+            || [list.0, list.1, dict.0, dict.1].contains(&prefix)
+        {
+            return Err(ConfigError::InvalidPrefixChar { prefix });
+        }
+        Ok(())
+    }
 }
 
+/// Transform of raw input lines, see [`Parser::set_line_preprocessor`]
+type LinePreprocessor = dyn FnMut(&mut String) + Send;
+
 /// Core KoiLang parser
 ///
 /// The parser processes input line by line and produces `ParsedCommand` structures
@@ -216,6 +737,20 @@ impl ParserConfig {
 pub struct Parser<T: TextInputSource> {
     input: Input<T>,
     config: ParserConfig,
+    last_line: Option<String>,
+    /// Split segments and put back lines: (line number, byte offset, text)
+    pending: VecDeque<(usize, usize, String)>,
+    /// Byte offset of the start of the last line returned by `next_line`
+    line_offset: usize,
+    /// Byte offset of the start of the last line read from the input
+    input_line_offset: usize,
+    /// Byte offset of the start of the line of the last command read
+    command_offset: usize,
+    ended_with_newline: bool,
+    warnings: Vec<ParseWarning>,
+    preprocessor: Option<Box<dyn Preprocessor + Send>>,
+    line_preprocessor: Option<Box<LinePreprocessor>>,
+    peeked: Option<SourcedCommand>,
 }
 
 impl<T: TextInputSource> Parser<T> {
@@ -238,9 +773,37 @@ impl<T: TextInputSource> Parser<T> {
         Self {
             input: Input::new(input_source),
             config,
+            last_line: None,
+            pending: VecDeque::new(),
+            line_offset: 0,
+            input_line_offset: 0,
+            command_offset: 0,
+            ended_with_newline: false,
+            warnings: Vec::new(),
+            preprocessor: None,
+            line_preprocessor: None,
+            peeked: None,
         }
     }
 
+    /// Create a new parser after validating the configuration
+    ///
+    /// Unlike [`Parser::new`], this rejects configurations that
+    /// [`ParserConfig::validate`] reports as invalid.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::{Parser, ParserConfig, StringInputSource};
+    ///
+    /// let config = ParserConfig::default().with_command_separator("");
+    /// assert!(Parser::try_new(StringInputSource::new("#a"), config).is_err());
+    /// ```
+    pub fn try_new(input_source: T, config: ParserConfig) -> Result<Self, ConfigError> {
+        config.validate()?;
+        Ok(Self::new(input_source, config))
+    }
+
     /// Get the next command from the input stream
     ///
     /// Returns `Ok(None)` when end of input is reached.
@@ -290,64 +853,533 @@ impl<T: TextInputSource> Parser<T> {
     /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
     pub fn next_command_with_source(&mut self) -> ParseResult<Option<(Command, ParserLineSource)>> {
-        loop {
-            let (lineno, line_text) = match self.input.next_line() {
-                Ok(Some(line_info)) => line_info,
-                Ok(None) => {
-                    return Ok(None);
-                }
-                Err(e) => {
-                    let source = ParserLineSource {
-                        filename: self.input.as_ref().source_name().to_string(),
-                        lineno: self.input.line_number,
-                        text: String::new(),
-                    };
-                    return Err(ParseError::io(e).with_line_source(source));
-                }
-            };
-            let source = ParserLineSource {
-                filename: self.input.as_ref().source_name().to_string(),
-                lineno,
-                text: line_text.clone(),
-            };
-            let trimmed = line_text.trim();
-            if trimmed.is_empty() {
-                if self.config.preserve_empty_lines {
-                    break Ok(Some((Command::new_text(""), source)));
-                }
-                continue;
-            }
-
-            // Count leading # characters
-            let hash_count = trimmed.chars().take_while(|&c| c == '#').count();
+        let result = self.read_command(None);
+        self.last_line = match &result {
+            Ok(Some((_, source))) => Some(source.text.clone()),
+            _ => None,
+        };
+        result
+    }
 
-            if hash_count < self.config.command_threshold {
-                let text_content = if self.config.preserve_indent {
-                    line_text.trim_end().to_string()
-                } else {
-                    trimmed.to_string()
+    /// Look at the next command without consuming it
+    ///
+    /// The command is parsed and kept, and the next call to `next_command` or
+    /// any of its variants returns it without reading the input again.
+    /// Peeking repeatedly returns the same command. While a command is
+    /// peeked, [`Parser::current_line`] reports the line it starts on.
+    ///
+    /// A parse error is returned by the peek only; the erroneous line is
+    /// consumed, as with `next_command`, so the following call continues
+    /// after it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::{Parser, ParserConfig, StringInputSource};
+    ///
+    /// let input = StringInputSource::new("#begin\n#item 1\n#end\n");
+    /// let mut parser = Parser::new(input, ParserConfig::default());
+    ///
+    /// parser.expect_command("begin")?;
+    /// let mut items = Vec::new();
+    /// while parser.peek_command()?.is_some_and(|command| command.name() != "end") {
+    ///     items.push(parser.next_command()?.unwrap());
+    /// }
+    /// assert_eq!(items.len(), 1);
+    /// assert_eq!(parser.next_command()?.unwrap().name(), "end");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn peek_command(&mut self) -> ParseResult<Option<&Command>> {
+        if self.peeked.is_none() {
+            let mut param_spans = Vec::new();
+            self.peeked = self
+                .read_command(Some(&mut param_spans))?
+                .map(|(command, source)| SourcedCommand {
+                    command,
+                    source,
+                    param_spans,
+                });
+        }
+        Ok(self.peeked.as_ref().map(|sourced| &sourced.command))
+    }
+
+    /// Get the next command, which must be named `name`
+    ///
+    /// For protocol-style input where the next command is known in advance.
+    /// Text and annotation lines count as commands named `@text` and
+    /// `@annotation`.
+    ///
+    /// # Arguments
+    /// * `name` - Name of the expected command, without the `#` prefix
+    ///
+    /// # Errors
+    /// Besides parse errors, returns [`ErrorInfo::UnexpectedCommand`] if the
+    /// next command has another name, and [`ErrorInfo::UnexpectedEof`] at the
+    /// end of the input.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::{Parser, ParserConfig, StringInputSource};
+    ///
+    /// let input = StringInputSource::new("#begin\n#draw Line\n");
+    /// let mut parser = Parser::new(input, ParserConfig::default());
+    ///
+    /// let begin = parser.expect_command("begin")?;
+    /// assert_eq!(begin.name(), "begin");
+    /// let err = parser.expect_command("end").unwrap_err();
+    /// assert_eq!(err.message(), "expected `#end`, found `#draw` at line 2");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn expect_command(&mut self, name: &str) -> ParseResult<Command> {
+        match self.next_command_with_source()? {
+            Some((command, _)) if command.name == name => Ok(command),
+            Some((command, source)) => Err(ParseError::unexpected_command(
+                name.to_string(),
+                command.name,
+                source,
+            )
+            .with_offset(self.command_offset)),
+            None => Err(ParseError::unexpected_eof(
+                format!("`#{}`", name),
+                self.input.line_number,
+                1,
+            )
+            .with_offset(self.input.offset)),
+        }
+    }
+
+    /// Get the next command with a formatting hint for each parameter
+    ///
+    /// Similar to `next_command()`, but also records how each parameter was
+    /// written (integer base, quoting), so that `Writer::write_command_with_hints`
+    /// can reproduce the original spelling. The hints are in parameter order;
+    /// text, annotation and number-name parameters get default hints.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::{Parser, ParserConfig, StringInputSource};
+    /// use koicore::writer::NumberFormat;
+    ///
+    /// let input = StringInputSource::new("#color 0xFF \"red\"");
+    /// let mut parser = Parser::new(input, ParserConfig::default());
+    ///
+    /// let (command, hints) = parser.next_command_with_hints()?.unwrap();
+    /// assert_eq!(command.params().len(), hints.len());
+    /// assert_eq!(hints[0].number_format, NumberFormat::Hex);
+    /// assert!(hints[1].quoted);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn next_command_with_hints(&mut self) -> ParseResult<Option<(Command, Vec<ParamHint>)>> {
+        let sourced = self.next_command_with_spans()?;
+        Ok(sourced.map(|sourced| {
+            let mut hints: Vec<ParamHint> = (0..sourced.command.params.len())
+                .map(|index| {
+                    sourced
+                        .param_source(index)
+                        .map(|text| ParamHint::from_source_with_config(text, &self.config))
+                        .unwrap_or_default()
+                })
+                .collect();
+            if sourced.command.name == "@number" {
+                hints[0] = ParamHint::default();
+            }
+            (sourced.command, hints)
+        }))
+    }
+
+    /// Get the next command with the source span of each parameter
+    ///
+    /// Similar to `next_command_with_source()`, but also records where each
+    /// parameter is in the source text, for tools that need the exact spelling
+    /// of a value. Spans are byte ranges into `source.text`, which includes
+    /// line continuations and continuation lines. The number of a number
+    /// command spans its name; text and annotation commands have no spans.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::{Parser, ParserConfig, StringInputSource};
+    ///
+    /// let input = StringInputSource::new("#color 0xFF \\\n  red");
+    /// let mut parser = Parser::new(input, ParserConfig::default());
+    ///
+    /// let sourced = parser.next_command_with_spans()?.unwrap();
+    /// assert_eq!(sourced.param_source(0), Some("0xFF"));
+    /// assert_eq!(sourced.param_source(1), Some("red"));
+    /// assert_eq!(sourced.param_spans[1], 16..19);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn next_command_with_spans(&mut self) -> ParseResult<Option<SourcedCommand>> {
+        let mut param_spans = Vec::new();
+        let result = self.read_command(Some(&mut param_spans));
+        self.last_line = match &result {
+            Ok(Some((_, source))) => Some(source.text.clone()),
+            _ => None,
+        };
+        Ok(result?.map(|(command, source)| SourcedCommand {
+            command,
+            source,
+            param_spans,
+        }))
+    }
+
+    /// Read the next line without interpreting it as a command
+    ///
+    /// Returns the line number and the raw text (including any line continuations
+    /// and the trailing newline), or `Ok(None)` at end of input. This advances the
+    /// same cursor as `next_command()`, so raw and parsed reads can be interleaved,
+    /// e.g. to handle a custom embedded section. If the current line was split on
+    /// `command_separator`, its remaining pieces are returned first. A peeked
+    /// command is returned as its source text and dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::{Parser, ParserConfig, StringInputSource};
+    ///
+    /// let input = StringInputSource::new("#raw\n#not a command\n#end");
+    /// let mut parser = Parser::new(input, ParserConfig::default());
+    ///
+    /// assert_eq!(parser.next_command()?.unwrap().name(), "raw");
+    /// assert_eq!(parser.next_raw_line()?, Some((2, "#not a command\n".to_string())));
+    /// assert_eq!(parser.next_command()?.unwrap().name(), "end");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn next_raw_line(&mut self) -> ParseResult<Option<(usize, String)>> {
+        if let Some(sourced) = self.peeked.take() {
+            return Ok(Some((sourced.source.lineno, sourced.source.text)));
+        }
+        if let Some((lineno, offset, segment)) = self.pending.pop_front() {
+            self.line_offset = offset;
+            return Ok(Some((lineno, segment)));
+        }
+        self.read_input_line().map_err(|e| self.io_error(e))
+    }
+
+    /// Whether the last line read from the input ended with a newline
+    ///
+    /// Once the input is exhausted, this tells whether the source ended with a
+    /// newline, which a writer can reproduce with `Writer::finish_line`.
+    /// Returns `false` before any line has been read.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::{Parser, ParserConfig, StringInputSource};
+    ///
+    /// let input = StringInputSource::new("#a\n#b");
+    /// let mut parser = Parser::new(input, ParserConfig::default());
+    /// while parser.next_command()?.is_some() {}
+    /// assert!(!parser.ended_with_newline());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn ended_with_newline(&self) -> bool {
+        self.ended_with_newline
+    }
+
+    /// Read the next logical line from the input, tracking its line ending
+    fn read_input_line(&mut self) -> std::io::Result<Option<(usize, String)>> {
+        self.line_offset = self.input.offset;
+        self.input_line_offset = self.input.offset;
+        let mut ended_with_newline = false;
+        let line_preprocessor = &mut self.line_preprocessor;
+        let line = self.input.next_line(|line| {
+            ended_with_newline = line.ends_with('\n');
+            if let Some(line_preprocessor) = line_preprocessor.as_mut() {
+                line_preprocessor(line);
+            }
+        })?;
+        if line.is_some() {
+            self.ended_with_newline = ended_with_newline;
+        }
+        Ok(line)
+    }
+
+    /// Wrap an input error with the current position
+    fn io_error(&self, e: std::io::Error) -> Box<ParseError> {
+        let source = ParserLineSource {
+            filename: self.input.as_ref().source_name().to_string(),
+            lineno: self.input.line_number,
+            text: String::new(),
+        };
+        ParseError::io(e).with_line_source(source)
+    }
+
+    /// Read the next command that passes the preprocessor
+    ///
+    /// If `spans` is given, the source spans of the returned command's
+    /// parameters are appended to it.
+    fn read_command(
+        &mut self,
+        mut spans: Option<&mut Vec<Range<usize>>>,
+    ) -> ParseResult<Option<(Command, ParserLineSource)>> {
+        if let Some(sourced) = self.peeked.take() {
+            if let Some(spans) = spans {
+                spans.extend(sourced.param_spans);
+            }
+            return Ok(Some((sourced.command, sourced.source)));
+        }
+        let Some(mut preprocessor) = self.preprocessor.take() else {
+            return self.read_raw_command(spans);
+        };
+        let spans_len = spans.as_ref().map_or(0, |spans| spans.len());
+        let result = loop {
+            match self.read_raw_command(spans.as_deref_mut()) {
+                Ok(Some((command, source))) => match preprocessor.process(command, &source) {
+                    Ok(Some(command)) => break Ok(Some((command, source))),
+                    Ok(None) => {
+                        if let Some(spans) = spans.as_deref_mut() {
+                            spans.truncate(spans_len);
+                        }
+                    }
+                    Err(e) => break Err(e),
+                },
+                Ok(None) => break preprocessor.finish(self.input.line_number).map(|_| None),
+                Err(e) => break Err(e),
+            }
+        };
+        self.preprocessor = Some(preprocessor);
+        result
+    }
+
+    /// Read and classify the next logical line from the input
+    ///
+    /// If `spans` is given, the source spans of the command's parameters are
+    /// appended to it.
+    fn read_raw_command(
+        &mut self,
+        mut spans: Option<&mut Vec<Range<usize>>>,
+    ) -> ParseResult<Option<(Command, ParserLineSource)>> {
+        loop {
+            let (lineno, line_text) = match self.next_line() {
+                Ok(Some(line_info)) => line_info,
+                Ok(None) => {
+                    return Ok(None);
+                }
+                Err(e) => {
+                    return Err(self.io_error(e));
+                }
+            };
+            self.command_offset = self.line_offset;
+            let mut source = ParserLineSource {
+                filename: self.input.as_ref().source_name().to_string(),
+                lineno,
+                text: line_text.clone(),
+            };
+            let trimmed = line_text.trim();
+            // Split segments start within their line
+            let start = self.line_offset - self.input_line_offset + line_text.offset(trimmed);
+            let span = self.config.record_spans.then_some((lineno, start + 1));
+            if trimmed.is_empty() {
+                if self.config.preserve_empty_lines {
+                    let mut command = Command::new_text("");
+                    command.span = span;
+                    break Ok(Some((command, source)));
+                }
+                continue;
+            }
+
+            let hash_count = self.prefix_count(trimmed);
+            let prefix_len = hash_count * self.config.prefix_char.len_utf8();
+
+            if hash_count < self.config.command_threshold {
+                let text_content = if self.config.preserve_indent {
+                    line_text.trim_end()
+                } else {
+                    trimmed
+                };
+                let text_content = if self.config.text_line_continuation {
+                    join_text_continuations(text_content)
+                } else {
+                    text_content.to_string()
                 };
-                break Ok(Some((Command::new_text(text_content), source)));
+                let mut command = Command::new_text(text_content);
+                command.span = span;
+                break Ok(Some((command, source)));
             } else if hash_count > self.config.command_threshold {
                 if self.config.skip_annotations {
                     continue;
                 }
                 let annotation_content = if self.config.preserve_indent {
                     line_text.trim_end().to_string()
+                } else if self.config.annotation_keep_hashes {
+                    trimmed.to_string()
                 } else {
-                    let content: String = trimmed.chars().skip(hash_count).collect();
-                    content.trim().to_string()
+                    trimmed[prefix_len..].trim().to_string()
                 };
-                break Ok(Some((Command::new_annotation(annotation_content), source)));
+                let mut annotation =
+                    Command::new_annotation_with_level(annotation_content, hash_count);
+                annotation.span = span;
+                break Ok(Some((annotation, source)));
             } else {
                 // hash_count == self.config.command_threshold
-                let column = line_text.offset(trimmed) + hash_count;
-                let command_str: String = trimmed.chars().skip(hash_count).collect();
-                break self
-                    .parse_command_line(command_str, lineno, column)
-                    .map_err(|e| e.with_line_source(source.clone()))
-                    .map(|opt| opt.map(|cmd| (cmd, source)));
+                let column = line_text.offset(trimmed) + prefix_len;
+                let command_text = self.strip_comment(&trimmed[prefix_len..]);
+                if command_text.is_empty() && trimmed.len() > prefix_len {
+                    // A comment-only command line
+                    continue;
+                }
+                let mut command = self
+                    .parse_command_text(command_text, lineno, column)
+                    .map_err(|e| e.with_line_source(source.clone()))?;
+                command.span = span;
+                if self.config.record_command_indent {
+                    command.indent = Some(line_text[..line_text.offset(trimmed)].to_string());
+                }
+                self.check_number_name(command_text, lineno);
+                if let Some(spans) = spans.as_deref_mut() {
+                    if command.name == "@number" {
+                        let name = Self::command_name_text(command_text);
+                        let start = line_text.offset(name);
+                        spans.push(start..start + name.len());
+                    }
+                    let sources = command_parser::parse_command_line_sources_with_config::<
+                        nom::error::Error<&str>,
+                    >(command_text, &self.config);
+                    Self::push_spans(&line_text, 0, sources, spans);
+                }
+                if self.config.indent_continuation {
+                    let indent = line_text.len() - line_text.trim_start().len();
+                    self.read_continuations(indent, &mut command, &mut source, spans)?;
+                }
+                break Ok(Some((command, source)));
+            }
+        }
+    }
+
+    /// Count the leading prefix characters of a trimmed line
+    fn prefix_count(&self, trimmed: &str) -> usize {
+        // A byte scan is enough for the usual ASCII prefix
+        let prefix = self.config.prefix_char;
+        if prefix.is_ascii() {
+            trimmed.bytes().take_while(|&b| b == prefix as u8).count()
+        } else {
+            trimmed.chars().take_while(|&c| c == prefix).count()
+        }
+    }
+
+    /// Append the parameters of indented continuation lines to `command`
+    ///
+    /// The first line that is not a continuation is put back for the next read.
+    fn read_continuations(
+        &mut self,
+        indent: usize,
+        command: &mut Command,
+        source: &mut ParserLineSource,
+        mut spans: Option<&mut Vec<Range<usize>>>,
+    ) -> ParseResult<()> {
+        while let Some((lineno, line_text)) = self.next_line().map_err(|e| self.io_error(e))? {
+            let trimmed = line_text.trim();
+            let line_indent = line_text.len() - line_text.trim_start().len();
+            if trimmed.is_empty()
+                || trimmed.starts_with(self.config.prefix_char)
+                || line_indent <= indent
+            {
+                self.pending.push_front((lineno, self.line_offset, line_text));
+                break;
+            }
+
+            let column = line_text.offset(trimmed);
+            let trimmed = self.strip_comment(trimmed);
+            if trimmed.is_empty() {
+                source.text.push_str(&line_text);
+                continue;
+            }
+            let line_source = ParserLineSource {
+                filename: source.filename.clone(),
+                lineno,
+                text: line_text.clone(),
+            };
+            let result = command_parser::parse_parameters_with_config::<NomErrorNode<&str>>(
+                trimmed,
+                &self.config,
+            );
+            let params = Self::finish_parse(result, trimmed, lineno, column, self.line_offset + column)
+                .map_err(|e| e.with_line_source(line_source))?;
+            command.params.extend(params);
+            if let Some(spans) = spans.as_deref_mut() {
+                let sources = command_parser::parse_parameter_sources_with_config::<
+                    nom::error::Error<&str>,
+                >(trimmed, &self.config);
+                Self::push_spans(&line_text, source.text.len(), sources, spans);
+            }
+            source.text.push_str(&line_text);
+        }
+        Ok(())
+    }
+
+    /// Get the command name as written at the start of `command_text`
+    fn command_name_text(command_text: &str) -> &str {
+        command_text
+            .split(|c: char| c.is_whitespace() || c == '\\')
+            .next()
+            .unwrap_or_default()
+    }
+
+    /// Warn if a numeric command name doesn't survive normalization
+    fn check_number_name(&mut self, command_text: &str, lineno: usize) {
+        let original = Self::command_name_text(command_text);
+        if let Ok(value) = original.parse::<i64>()
+            && value.to_string() != original
+        {
+            self.warnings.push(ParseWarning::LossyNumberCommand {
+                lineno,
+                original: original.to_string(),
+                value,
+            });
+        }
+    }
+
+    /// Append the spans of parameter sources found in `line`
+    ///
+    /// `base` is the offset of `line` in the text the spans refer to.
+    fn push_spans(
+        line: &str,
+        base: usize,
+        sources: nom::IResult<&str, Vec<&str>, nom::error::Error<&str>>,
+        spans: &mut Vec<Range<usize>>,
+    ) {
+        if let Ok((_, sources)) = sources {
+            spans.extend(sources.into_iter().map(|param| {
+                let start = base + line.offset(param);
+                start..start + param.len()
+            }));
+        }
+    }
+
+    /// Get the next logical line, split on the configured command separator
+    fn next_line(&mut self) -> std::io::Result<Option<(usize, String)>> {
+        loop {
+            if let Some((lineno, offset, segment)) = self.pending.pop_front() {
+                self.line_offset = offset;
+                return Ok(Some((lineno, segment)));
+            }
+
+            let Some((lineno, line)) = self.read_input_line()? else {
+                return Ok(None);
+            };
+            let separator = match &self.config.command_separator {
+                Some(separator) if !separator.is_empty() => separator,
+                _ => return Ok(Some((lineno, line))),
+            };
+            // Only command lines are split; text and annotations are kept whole
+            if self.prefix_count(line.trim_start()) != self.config.command_threshold {
+                return Ok(Some((lineno, line)));
+            }
+
+            let segments = split_outside_strings(&line, separator);
+            if segments.len() == 1 {
+                return Ok(Some((lineno, line)));
             }
+            // Separators leave empty pieces behind, e.g. after a trailing `;`
+            self.pending.extend(
+                segments
+                    .into_iter()
+                    .filter(|segment| !segment.trim().is_empty())
+                    .map(|segment| (lineno, self.line_offset + line.offset(segment), segment.to_string())),
+            );
         }
     }
 
@@ -367,49 +1399,130 @@ impl<T: TextInputSource> Parser<T> {
         lineno: usize,
         column: usize,
     ) -> ParseResult<Option<Command>> {
+        self.parse_command_text(self.strip_comment(&command_text), lineno, column)
+            .map(Some)
+    }
+
+    /// Parse the text of a command line whose comment is already stripped
+    fn parse_command_text(&self, command_text: &str, lineno: usize, column: usize) -> ParseResult<Command> {
+        let offset = self.line_offset + column;
         if command_text.is_empty() {
             return Err(ParseError::syntax_with_context(
                 "Empty command line".to_string(),
                 lineno,
                 column,
-                command_text,
-            ));
+                command_text.to_string(),
+            )
+            .with_offset(offset));
         }
 
-        let result = command_parser::parse_command_line::<NomErrorNode<&str>>(&command_text);
+        let result = command_parser::parse_command_line_with_config::<NomErrorNode<&str>>(
+            command_text,
+            &self.config,
+        );
+        let command = Self::finish_parse(result, command_text, lineno, column, offset)
+            .map_err(|e| {
+                let suggestion = self.suggest_fix(command_text, &e);
+                e.with_suggestion(suggestion)
+            })?;
 
-        match result {
-            Ok(("", command)) => {
-                let num_name = command.name().parse();
-                match num_name {
-                    Result::Err(_) => Ok(Some(command)),
-                    Result::Ok(num) => {
-                        if !self.config.convert_number_command {
-                            Ok(Some(command))
-                        } else {
-                            Ok(Some(Command::new_number(num, command.params)))
-                        }
-                    }
-                }
+        match command.name().parse() {
+            Ok(num) if self.config.convert_number_command => {
+                Ok(Command::new_number(num, command.params))
+            }
+            _ => Ok(command),
+        }
+    }
+
+    /// Remove a trailing comment and the whitespace before it
+    ///
+    /// Returns `text` unchanged if no `comment_marker` is configured.
+    fn strip_comment<'a>(&self, text: &'a str) -> &'a str {
+        match &self.config.comment_marker {
+            Some(marker) if !marker.is_empty() => {
+                let end = OutsideQuotes::new(text)
+                    .find(|&(i, _)| text[i..].starts_with(marker.as_str()))
+                    .map_or(text.len(), |(i, _)| i);
+                text[..end].trim_end()
+            }
+            _ => text,
+        }
+    }
+
+    /// Guess how to fix a command line that failed to parse
+    ///
+    /// Recognizes unterminated strings, unclosed composites, `key: value`
+    /// pairs outside of composites and commas between parameters.
+    fn suggest_fix(&self, text: &str, error: &ParseError) -> Option<String> {
+        let mut closers = vec![self.config.list_delimiters, self.config.dict_delimiters];
+        if self.config.enable_set_literals {
+            closers.push(('{', '}'));
+        }
+        let mut open = Vec::new();
+        let mut chars = OutsideQuotes::new(text);
+        for (_, c) in chars.by_ref() {
+            if let Some((_, close)) = closers.iter().find(|(o, _)| *o == c) {
+                open.push(*close);
+            } else if open.last() == Some(&c) {
+                open.pop();
+            }
+        }
+        if chars.in_string() {
+            return Some("add a closing `\"` to end the string".to_string());
+        }
+        if let Some(close) = open.last() {
+            return Some(format!("add a closing `{}` to end the composite", close));
+        }
+        match &error.error_info {
+            ErrorInfo::UnexpectedInput { remaining } if remaining.starts_with(',') => Some(
+                "separate parameters with spaces, or group values in a composite like `name(a, b)`"
+                    .to_string(),
+            ),
+            ErrorInfo::SyntaxError { nom_context, .. }
+                if nom_context.iter().any(|(label, _)| *label == "type_annotation") =>
+            {
+                Some(
+                    "use `name(key: value)` for key-value pairs; \
+                     a `:` after a value starts a type annotation like `:int`"
+                        .to_string(),
+                )
             }
+            _ => None,
+        }
+    }
+
+    /// Convert the result of a nom parser over the whole of `text` into a `ParseResult`
+    ///
+    /// `text` starts at `column` of line `lineno`, and at byte `offset` of the input.
+    fn finish_parse<'a, O>(
+        result: nom::IResult<&'a str, O, NomErrorNode<&'a str>>,
+        text: &'a str,
+        lineno: usize,
+        column: usize,
+        offset: usize,
+    ) -> ParseResult<O> {
+        match result {
+            Ok(("", output)) => Ok(output),
             Ok((remaining, _)) => Err(ParseError::unexpected_input(
                 remaining.to_string(),
                 lineno,
                 column,
-                command_text,
-            )),
+                text.to_string(),
+            )
+            .with_offset(offset + text.offset(remaining))),
             Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
                 // Create a simple nom error for compatibility
                 Err(ParseError::from_nom_error(
                     "Command parsing error".to_string(),
-                    command_text.as_str(),
+                    text,
                     lineno,
                     column,
+                    offset,
                     e,
                 ))
             }
             Err(nom::Err::Incomplete(_)) => {
-                Err(ParseError::unexpected_eof(command_text, lineno, column))
+                Err(ParseError::unexpected_eof(text.to_string(), lineno, column).with_offset(offset))
             }
         }
     }
@@ -483,12 +1596,441 @@ impl<T: TextInputSource> Parser<T> {
         }
     }
 
+    /// Parse the rest of the input, reporting each element as a [`ParseEvent`]
+    ///
+    /// This is a lower-level alternative to `process_with` for consumers that
+    /// build their own structures. Each line is still parsed into a `Command`
+    /// first, with the same options and post-processing as `next_command`, and
+    /// its contents are then moved into the events. So the events of a line are
+    /// only emitted once the whole line has parsed: a line with a syntax error
+    /// emits nothing before the error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::{ParseEvent, Parser, ParserConfig, StringInputSource};
+    ///
+    /// let input = StringInputSource::new("#draw Line pos(0, 1)");
+    /// let mut parser = Parser::new(input, ParserConfig::default());
+    ///
+    /// let mut composites = 0;
+    /// parser.parse_events(|event| {
+    ///     if let ParseEvent::CompositeStart(_) = event {
+    ///         composites += 1;
+    ///     }
+    /// })?;
+    /// assert_eq!(composites, 1);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn parse_events<F: FnMut(ParseEvent)>(&mut self, mut handler: F) -> ParseResult<()> {
+        while let Some(command) = self.next_command()? {
+            event::emit_command(command, &mut handler);
+        }
+        Ok(())
+    }
+
+    /// Parse the rest of the input and collect all commands
+    ///
+    /// Stops at the first parse error. The parser can be used further, e.g.
+    /// after [`Parser::reset`]; use [`Parser::into_commands`] to consume it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::{Parser, ParserConfig, StringInputSource};
+    ///
+    /// let input = StringInputSource::new("#draw 1\ntext");
+    /// let mut parser = Parser::new(input, ParserConfig::default());
+    ///
+    /// let commands = parser.collect_commands()?;
+    /// assert_eq!(commands.len(), 2);
+    /// assert!(parser.next_command()?.is_none());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn collect_commands(&mut self) -> ParseResult<Vec<Command>> {
+        let mut commands = Vec::new();
+        while let Some(command) = self.next_command()? {
+            commands.push(command);
+        }
+        Ok(commands)
+    }
+
+    /// Parse the whole input and return all commands
+    ///
+    /// Stops at the first parse error. Convenient for small inputs that are
+    /// loaded at once; use [`Parser::next_command`] to process large inputs
+    /// as a stream.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::{Parser, ParserConfig, StringInputSource};
+    ///
+    /// let input = StringInputSource::new("#draw 1\ntext");
+    /// let commands = Parser::new(input, ParserConfig::default()).into_commands()?;
+    /// assert_eq!(commands[0].name(), "draw");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn into_commands(mut self) -> ParseResult<Vec<Command>> {
+        self.collect_commands()
+    }
+
+    /// Parse the rest of the input and collect commands with unknown names
+    ///
+    /// Returns the line number and name of every command whose name is not in
+    /// `known`, in document order. Text, annotation and number commands (names
+    /// starting with `@`) are never reported.
+    ///
+    /// # Arguments
+    /// * `known` - Names of the commands understood by the caller
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::collections::HashSet;
+    /// use koicore::parser::{Parser, ParserConfig, StringInputSource};
+    ///
+    /// let input = StringInputSource::new("#draw 1\n#unknown\ntext");
+    /// let mut parser = Parser::new(input, ParserConfig::default());
+    ///
+    /// let known = HashSet::from(["draw".to_string()]);
+    /// let unknown = parser.collect_unknown(&known)?;
+    /// assert_eq!(unknown, vec![(2, "unknown".to_string())]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn collect_unknown(
+        &mut self,
+        known: &HashSet<String>,
+    ) -> ParseResult<Vec<(usize, String)>> {
+        let mut unknown = Vec::new();
+        while let Some((command, source)) = self.next_command_with_source()? {
+            if !command.name.starts_with('@') && !known.contains(&command.name) {
+                unknown.push((source.lineno, command.name));
+            }
+        }
+        Ok(unknown)
+    }
+
     /// Get the current line number
     ///
     /// Returns the line number that the parser is currently processing.
-    /// This is useful for error reporting and progress tracking.
+    /// This is useful for error reporting and progress tracking. While a
+    /// command is peeked (see [`Parser::peek_command`]), this is the line the
+    /// peeked command starts on.
     pub fn current_line(&self) -> usize {
-        self.input.line_number
+        match &self.peeked {
+            Some(sourced) => sourced.source.lineno,
+            None => self.input.line_number,
+        }
+    }
+
+    /// Get the current byte offset
+    ///
+    /// Returns the number of bytes read from the input so far. While a command
+    /// is peeked (see [`Parser::peek_command`]), this is the offset of the
+    /// line the peeked command starts on, like [`Parser::current_line`].
+    /// Error offsets (see [`ParseError::offset`]) are counted the same way.
+    ///
+    /// Offsets count the input as read from the source. If a line
+    /// preprocessor (see [`Parser::set_line_preprocessor`]) changes the length
+    /// of a line, offsets within that line are measured in the rewritten text.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::{Parser, ParserConfig, StringInputSource};
+    ///
+    /// let input = StringInputSource::new("#a 1\n#b 2\n");
+    /// let mut parser = Parser::new(input, ParserConfig::default());
+    /// assert_eq!(parser.current_offset(), 0);
+    /// parser.next_command()?;
+    /// assert_eq!(parser.current_offset(), 5);
+    /// parser.peek_command()?;
+    /// assert_eq!(parser.current_offset(), 5);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn current_offset(&self) -> usize {
+        match &self.peeked {
+            Some(_) => self.command_offset,
+            None => self.input.offset,
+        }
+    }
+
+    /// Get the raw source text of the most recently returned command
+    ///
+    /// Returns the original line (including any line continuations) that produced
+    /// the command returned by the previous `next_command` call. This is useful for
+    /// logging context without switching to `next_command_with_source`.
+    ///
+    /// Returns `None` before the first command, after EOF has been reached, or
+    /// after a parse error.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::{Parser, ParserConfig, StringInputSource};
+    ///
+    /// let input = StringInputSource::new("#name \"Test\"\nHello");
+    /// let mut parser = Parser::new(input, ParserConfig::default());
+    ///
+    /// parser.next_command()?;
+    /// assert_eq!(parser.last_command_line(), Some("#name \"Test\"\n"));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn last_command_line(&self) -> Option<&str> {
+        self.last_line.as_deref()
+    }
+
+    /// Get the name of the input source, e.g. a file path, as used in errors
+    pub fn source_name(&self) -> String {
+        self.input.as_ref().source_name()
+    }
+
+    /// Get the text encoding of the input source, if the source knows it
+    ///
+    /// See [`TextInputSource::encoding`].
+    pub fn encoding(&self) -> Option<&'static encoding_rs::Encoding> {
+        self.input.as_ref().encoding()
+    }
+
+    /// Get the configuration used by this parser
+    pub fn config(&self) -> &ParserConfig {
+        &self.config
+    }
+
+    /// Replace the configuration used by this parser
+    ///
+    /// Subsequent `next_command` calls use the new configuration; commands that
+    /// were already returned are not affected. This supports documents whose
+    /// dialect changes after a header directive.
+    ///
+    /// # Arguments
+    /// * `config` - The new parser configuration
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::{Parser, ParserConfig, StringInputSource};
+    ///
+    /// let input = StringInputSource::new("#dialect 2\n##cmd");
+    /// let mut parser = Parser::new(input, ParserConfig::default());
+    ///
+    /// parser.next_command()?;
+    /// let config = parser.config().clone().with_command_threshold(2);
+    /// parser.set_config(config);
+    /// assert_eq!(parser.next_command()?.unwrap().name(), "cmd");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn set_config(&mut self, config: ParserConfig) {
+        self.config = config;
+    }
+
+    /// Install a preprocessor for the parsed commands
+    ///
+    /// Every command is passed to the preprocessor before it is returned, and
+    /// commands it drops are skipped. This replaces any previous preprocessor.
+    /// See [`ConditionalPreprocessor`] for `#if`/`#endif` blocks.
+    ///
+    /// # Arguments
+    /// * `preprocessor` - The preprocessor to install
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::{ConditionalPreprocessor, Parser, ParserConfig, StringInputSource};
+    ///
+    /// let input = StringInputSource::new("#if\n#hidden\n#endif\n#shown");
+    /// let mut parser = Parser::new(input, ParserConfig::default());
+    /// parser.set_preprocessor(ConditionalPreprocessor::new(|_| false));
+    /// assert_eq!(parser.next_command()?.unwrap().name(), "shown");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn set_preprocessor(&mut self, preprocessor: impl Preprocessor + Send + 'static) {
+        self.preprocessor = Some(Box::new(preprocessor));
+    }
+
+    /// Remove the preprocessor, if any
+    pub fn clear_preprocessor(&mut self) {
+        self.preprocessor = None;
+    }
+
+    /// Install a transform for the raw input lines
+    ///
+    /// The function can rewrite each line before it is classified, e.g. to
+    /// strip a fixed gutter, expand tabs or remove comment markers. It is
+    /// called once per physical line read from the input, with the line ending
+    /// still attached and before lines ending in `\` are joined with the next
+    /// one. Everything else happens on its result: splitting on the command
+    /// separator, trimming, indentation handling for `preserve_indent` and
+    /// `indent_continuation`, and the positions in errors and spans. Byte
+    /// offsets still count the original input up to the start of each line
+    /// (see [`Parser::current_offset`]). This replaces any previous line
+    /// preprocessor.
+    ///
+    /// # Arguments
+    /// * `f` - Function rewriting a line in place
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::{Parser, ParserConfig, StringInputSource};
+    ///
+    /// let input = StringInputSource::new("#draw Line  // legacy comment\n");
+    /// let mut parser = Parser::new(input, ParserConfig::default());
+    /// parser.set_line_preprocessor(|line: &mut String| {
+    ///     if let Some(pos) = line.find("//") {
+    ///         line.truncate(pos);
+    ///     }
+    /// });
+    /// assert_eq!(parser.next_command()?.unwrap().to_string(), "draw Line");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn set_line_preprocessor(&mut self, f: impl FnMut(&mut String) + Send + 'static) {
+        self.line_preprocessor = Some(Box::new(f));
+    }
+
+    /// Remove the line preprocessor, if any
+    pub fn clear_line_preprocessor(&mut self) {
+        self.line_preprocessor = None;
+    }
+
+    /// Rebuild a parser from an input source and configuration
+    ///
+    /// The counterpart of [`Parser::into_parts`]; equivalent to [`Parser::new`].
+    /// Line numbers restart at 1, so a source taken from another parser
+    /// reports lines relative to where that parser stopped.
+    ///
+    /// # Arguments
+    /// * `input_source` - The source of text input
+    /// * `config` - Parser configuration
+    pub fn from_parts(input_source: T, config: ParserConfig) -> Self {
+        Self::new(input_source, config)
+    }
+
+    /// Take the parser apart into its input source and configuration
+    ///
+    /// Lines the parser has read but not yet returned as commands, such as a
+    /// command looked at with [`Parser::peek_command`], commands split by
+    /// [`ParserConfig::command_separator`] or the line read ahead to look for
+    /// an [indented continuation](ParserConfig::indent_continuation), are
+    /// replayed by the returned source before the rest of the input.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::{Parser, ParserConfig, StringInputSource};
+    ///
+    /// let input = StringInputSource::new("#a\n#b");
+    /// let mut parser = Parser::new(input, ParserConfig::default());
+    /// parser.next_command()?;
+    ///
+    /// let (input, config) = parser.into_parts();
+    /// let mut parser = Parser::from_parts(input, config);
+    /// assert_eq!(parser.next_command()?.unwrap().name(), "b");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn into_parts(self) -> (ReplayInputSource<T>, ParserConfig) {
+        // A peeked command may span several physical lines
+        let peeked = self.peeked.map(|sourced| sourced.source.text).unwrap_or_default();
+        let lines = peeked
+            .split_inclusive('\n')
+            .map(str::to_string)
+            .chain(self.pending.into_iter().map(|(_, _, line)| line))
+            .collect::<Vec<_>>();
+        (ReplayInputSource::new(lines, self.input.source), self.config)
+    }
+
+    /// Take the warnings collected since the last call
+    ///
+    /// Warnings report input that was parsed but not exactly as written, such
+    /// as a number command with leading zeros. Linters can surface them while
+    /// other consumers ignore them.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::{ParseWarning, Parser, ParserConfig, StringInputSource};
+    ///
+    /// let input = StringInputSource::new("#007 x");
+    /// let mut parser = Parser::new(input, ParserConfig::default());
+    /// let command = parser.next_command()?.unwrap();
+    /// assert_eq!(command.to_string(), "@number 7 x");
+    /// assert_eq!(parser.take_warnings(), vec![ParseWarning::LossyNumberCommand {
+    ///     lineno: 1,
+    ///     original: "007".to_string(),
+    ///     value: 7,
+    /// }]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn take_warnings(&mut self) -> Vec<ParseWarning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Start parsing a new input, keeping the configuration
+    ///
+    /// Line numbers restart at 1 and any state left from the previous input,
+    /// such as commands split by `command_separator` but not yet returned, is
+    /// dropped, as are warnings not yet taken. The queue of split commands and
+    /// the warning list are cleared in place, so reusing a parser for many
+    /// small inputs avoids reallocating them. Returns the previous input source.
+    ///
+    /// # Arguments
+    /// * `source` - The new input source
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::{Parser, ParserConfig, StringInputSource};
+    ///
+    /// let mut parser = Parser::new(StringInputSource::new("#a"), ParserConfig::default());
+    /// assert_eq!(parser.next_command()?.unwrap().name(), "a");
+    ///
+    /// parser.reset(StringInputSource::new("#b"));
+    /// assert_eq!(parser.next_command()?.unwrap().name(), "b");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn reset(&mut self, source: T) -> T {
+        let previous = self.input.reset(source);
+        self.last_line = None;
+        self.pending.clear();
+        self.line_offset = 0;
+        self.input_line_offset = 0;
+        self.command_offset = 0;
+        self.peeked = None;
+        self.ended_with_newline = false;
+        self.warnings.clear();
+        if let Some(preprocessor) = self.preprocessor.as_mut() {
+            preprocessor.reset();
+        }
+        previous
+    }
+}
+
+impl Parser<StringInputSource> {
+    /// Get the byte offset of the start of each line in the input
+    ///
+    /// Line `n` (1-based) starts at `line_offsets()[n - 1]`. Error columns are
+    /// 1-based byte columns, so an error at `(line, column)` is at byte
+    /// `line_offsets()[line - 1] + column - 1` of the original string.
+    ///
+    /// Offsets are only known for fully-buffered input, so this is only
+    /// available when parsing a [`StringInputSource`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::{Parser, ParserConfig, StringInputSource};
+    ///
+    /// let text = "#a 1\n#b (\n";
+    /// let mut parser = Parser::new(StringInputSource::new(text), ParserConfig::default());
+    /// let _ = parser.next_command();
+    /// let err = parser.next_command().unwrap_err();
+    /// let (line, column) = err.position().unwrap();
+    /// let offset = parser.line_offsets()[line - 1] + column - 1;
+    /// assert_eq!(&text[offset..offset + 1], "(");
+    /// ```
+    pub fn line_offsets(&self) -> &[usize] {
+        self.input.source.line_offsets()
     }
 }
 
@@ -516,9 +2058,235 @@ impl<T: TextInputSource> Iterator for Parser<T> {
     }
 }
 
+/// Characters of a line outside of quoted strings and char literals
+///
+/// Yields `(index, char)` like [`str::char_indices`], skipping `"..."` strings
+/// (with their escapes) and `'x'` char literals, quotes included. A `'` that
+/// doesn't start a char literal is yielded as is. After the iterator ends,
+/// [`OutsideQuotes::in_string`] tells whether the text ended inside a string.
+struct OutsideQuotes<'a> {
+    text: &'a str,
+    pos: usize,
+    in_string: bool,
+}
+
+impl<'a> OutsideQuotes<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            pos: 0,
+            in_string: false,
+        }
+    }
+
+    /// Whether the scan stopped inside an unterminated `"` string
+    fn in_string(&self) -> bool {
+        self.in_string
+    }
+
+    /// Get the length in bytes of the char literal at the start of `text`, if any
+    fn char_literal_len(text: &str) -> Option<usize> {
+        let body = text.strip_prefix('\'')?;
+        let mut chars = body.char_indices();
+        let end = match chars.next()? {
+            // An escape: `\n`, `\'`, `\x41`, `\u00e9`, ...
+            (_, '\\') => {
+                let (i, escaped) = chars.next()?;
+                let rest = &body[i + escaped.len_utf8()..];
+                i + escaped.len_utf8() + rest.bytes().take_while(u8::is_ascii_alphanumeric).count()
+            }
+            (_, '\'' | '\n') => return None,
+            (_, c) => c.len_utf8(),
+        };
+        body[end..].starts_with('\'').then_some(end + 2)
+    }
+}
+
+impl Iterator for OutsideQuotes<'_> {
+    type Item = (usize, char);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let i = self.pos;
+            let c = self.text[i..].chars().next()?;
+            self.pos = i + c.len_utf8();
+            if c == '\'' {
+                match Self::char_literal_len(&self.text[i..]) {
+                    Some(len) => self.pos = i + len,
+                    None => return Some((i, c)),
+                }
+            } else if c == '"' {
+                let mut escaped = false;
+                let end = self.text[self.pos..].char_indices().find(|&(_, c)| match c {
+                    _ if escaped => {
+                        escaped = false;
+                        false
+                    }
+                    '\\' => {
+                        escaped = true;
+                        false
+                    }
+                    _ => c == '"',
+                });
+                match end {
+                    Some((j, _)) => self.pos += j + 1,
+                    None => {
+                        self.pos = self.text.len();
+                        self.in_string = true;
+                        return None;
+                    }
+                }
+            } else {
+                return Some((i, c));
+            }
+        }
+    }
+}
+
+/// Split a line on `separator`, ignoring separators inside strings and char literals
+fn split_outside_strings<'a>(line: &'a str, separator: &str) -> Vec<&'a str> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    for (i, _) in OutsideQuotes::new(line) {
+        // Skip the rest of a multi-character separator
+        if i >= start && line[i..].starts_with(separator) {
+            segments.push(&line[start..i]);
+            start = i + separator.len();
+        }
+    }
+    segments.push(&line[start..]);
+    segments
+}
+
+/// Parse exactly one line of KoiLang into a command
+///
+/// The line is classified like any other input line, so it may be a command,
+/// a text line or an annotation. Line continuations are allowed, but input
+/// that yields no command or more than one command is an error.
+///
+/// # Arguments
+/// * `line` - The line to parse, including its `#` prefix for commands
+/// * `config` - Parser configuration
+///
+/// # Examples
+///
+/// ```rust
+/// use koicore::{ParserConfig, parse_one};
+///
+/// let command = parse_one("#draw Line 2", &ParserConfig::default())?;
+/// assert_eq!(command.name(), "draw");
+///
+/// let text = parse_one("Hello", &ParserConfig::default())?;
+/// assert_eq!(text.name(), "@text");
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn parse_one(line: &str, config: &ParserConfig) -> ParseResult<Command> {
+    let mut parser = Parser::new(StringInputSource::new(line), config.clone());
+    let command = parser
+        .next_command()?
+        .ok_or_else(|| ParseError::syntax("Expected a line to parse".to_string()))?;
+    if parser.next_command()?.is_some() {
+        return Err(ParseError::syntax(
+            "Expected a single line, found more input".to_string(),
+        ));
+    }
+    Ok(command)
+}
+
+/// Join the lines of a continued text line, removing each `\`, line break
+/// and the indentation after it
+fn join_text_continuations(text: &str) -> String {
+    let mut lines = text.split("\\\n");
+    let mut joined = lines.next().unwrap_or_default().to_string();
+    for line in lines {
+        joined.push_str(line.trim_start());
+    }
+    joined
+}
+
+/// Parse a single command with the default configuration
+///
+/// The leading `#` is optional, so `"draw Line 2"` and `"#draw Line 2"` give the
+/// same command. Since the input is always read as a command, a plain text line
+/// either parses as a command or fails. Lines starting with `##` are annotations.
+/// Trailing content or more than one line is an error.
+///
+/// # Examples
+///
+/// ```rust
+/// use koicore::Command;
+///
+/// let cmd: Command = "#draw Line 2".parse()?;
+/// assert_eq!(cmd.name(), "draw");
+/// assert_eq!(cmd, "draw Line 2".parse()?);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+impl FromStr for Command {
+    type Err = Box<ParseError>;
+
+    fn from_str(s: &str) -> ParseResult<Self> {
+        let body = s.strip_prefix('#').unwrap_or(s);
+        parse_one(&format!("#{}", body), &ParserConfig::default())
+    }
+}
+
+/// Parse a single basic value with the default configuration
+///
+/// The whole input must be one value: an integer (in any base), a float, a
+/// boolean, a quoted string or a literal. Trailing content is an error.
+/// Since `Value` converts infallibly from `&str` as a string, use `parse`
+/// rather than `try_from` to interpret the text.
+///
+/// # Examples
+///
+/// ```rust
+/// use koicore::Value;
+///
+/// assert_eq!("0xFF".parse::<Value>()?, Value::Int(255));
+/// assert_eq!("\"a b\"".parse::<Value>()?, Value::from("a b"));
+/// assert!("1 2".parse::<Value>().is_err());
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+impl FromStr for Value {
+    type Err = Box<ParseError>;
+
+    fn from_str(s: &str) -> ParseResult<Self> {
+        let result = command_parser::parse_value_with_config::<NomErrorNode<&str>>(
+            s,
+            &ParserConfig::default(),
+        );
+        Parser::<StringInputSource>::finish_parse(result, s, 1, 0, 0)
+    }
+}
+
+/// Resolve the escapes of a string written between double quotes
+///
+/// This is the inverse of [`escape_string`](crate::writer::escape_string) and
+/// accepts every escape of quoted string parameters: `\n`, `\x41`, `\u00e9`,
+/// line continuations and so on. The input is the text between the quotes; an
+/// unescaped `"` or an invalid escape is an error.
+///
+/// # Arguments
+/// * `s` - The escaped string, without the surrounding quotes
+///
+/// # Examples
+///
+/// ```rust
+/// use koicore::unescape_string;
+///
+/// assert_eq!(unescape_string(r#"say \"hi\"\x21"#)?, "say \"hi\"!");
+/// assert!(unescape_string(r#"bad \q"#).is_err());
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn unescape_string(s: &str) -> ParseResult<String> {
+    let result = command_parser::parse_string_content::<NomErrorNode<&str>>(s);
+    Parser::<StringInputSource>::finish_parse(result, s, 1, 0, 0)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::command::Parameter;
     use crate::parser::error::ParseError;
 
     #[test]
@@ -664,6 +2432,580 @@ mod tests {
         assert_eq!(parser.current_line(), 2);
     }
 
+    #[test]
+    fn test_collect_unknown() {
+        let input = StringInputSource::new(
+            "#draw 1\n#fill red\ntext\n## note\n#42\n#erase \\\n  all\n#draw 2\n",
+        );
+        let mut parser = Parser::new(input, ParserConfig::default());
+        let known = HashSet::from(["draw".to_string()]);
+
+        let unknown = parser.collect_unknown(&known).unwrap();
+        assert_eq!(
+            unknown,
+            vec![(2, "fill".to_string()), (6, "erase".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_escape_round_trip() {
+        let tricky = "say \"hi\"\n\tback\\slash\r\n'é 😂' \\\"";
+        let escaped = crate::writer::escape_string(tricky);
+        assert!(!escaped.contains('\n'));
+        assert_eq!(unescape_string(&escaped).unwrap(), tricky);
+
+        // The escaped form is what the writer puts between quotes
+        let value = format!("\"{}\"", escaped).parse::<Value>().unwrap();
+        assert_eq!(value, Value::from(tricky));
+
+        assert_eq!(unescape_string("\\x41\\u00e9\\U0001F602").unwrap(), "Aé😂");
+        assert!(unescape_string("unescaped \" quote").is_err());
+        assert!(unescape_string("trailing \\").is_err());
+    }
+
+    #[test]
+    fn test_parse_one() {
+        let config = ParserConfig::default();
+
+        let cmd = parse_one("#draw Line 2", &config).unwrap();
+        assert_eq!(
+            cmd,
+            Command::new("draw", vec!["Line".into(), 2.into()])
+        );
+
+        let cmd = parse_one("Hello world\n", &config).unwrap();
+        assert_eq!(cmd, Command::new_text("Hello world"));
+
+        let err = parse_one("#draw pos(x: 1", &config).unwrap_err();
+        assert!(matches!(err.error_info, ErrorInfo::SyntaxError { .. }));
+        assert!(err.traceback.is_some());
+
+        assert!(parse_one("", &config).is_err());
+        assert!(parse_one("#a\n#b", &config).is_err());
+    }
+
+    #[test]
+    fn test_error_context_labels() {
+        let config = ParserConfig::default();
+        let err = parse_one("#draw pos(x: 1, y: )", &config).unwrap_err();
+        let labels = err.context_labels();
+        assert_eq!(labels[..4], [
+            ("parameter", 5),
+            ("composite_parameter", 5),
+            ("dictionary", 9),
+            ("basic_value", 18),
+        ]);
+        assert!(labels.contains(&("integer", 18)));
+
+        let err = ParseError::syntax("msg".to_string());
+        assert!(err.context_labels().is_empty());
+    }
+
+    #[test]
+    fn test_annotation_level() {
+        let input = StringInputSource::new("## meta\n### doc comment\n#cmd");
+        let mut parser = Parser::new(input, ParserConfig::default());
+
+        let cmd = parser.next_command().unwrap().unwrap();
+        assert_eq!(cmd, Command::new_annotation_with_level("meta", 2));
+        assert_eq!(cmd.annotation_level(), Some(2));
+        let cmd = parser.next_command().unwrap().unwrap();
+        assert_eq!(cmd.annotation_level(), Some(3));
+        assert_eq!(cmd.params()[0].to_string(), "\"doc comment\"");
+        let cmd = parser.next_command().unwrap().unwrap();
+        assert_eq!(cmd.annotation_level(), None);
+    }
+
+    #[test]
+    fn test_next_raw_line() {
+        let input = StringInputSource::new("@@@ raw \"unbalanced\n#draw 1\n");
+        let mut parser = Parser::new(input, ParserConfig::default());
+
+        let (lineno, line) = parser.next_raw_line().unwrap().unwrap();
+        assert_eq!(lineno, 1);
+        assert_eq!(line, "@@@ raw \"unbalanced\n");
+
+        let (cmd, source) = parser.next_command_with_source().unwrap().unwrap();
+        assert_eq!(cmd.name(), "draw");
+        assert_eq!(source.lineno, 2);
+        assert!(parser.next_raw_line().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_command_separator() {
+        let input = StringInputSource::new("#a 1; #b \"x;\\\"y\";;\n#c;");
+        let config = ParserConfig::default().with_command_separator(";");
+        let mut parser = Parser::new(input, config);
+
+        let (cmd, source) = parser.next_command_with_source().unwrap().unwrap();
+        assert_eq!(cmd.name(), "a");
+        assert_eq!(source.lineno, 1);
+        let (cmd, source) = parser.next_command_with_source().unwrap().unwrap();
+        assert_eq!(cmd.to_string(), "b \"x;\\\"y\"");
+        assert_eq!(source.lineno, 1);
+        let (cmd, source) = parser.next_command_with_source().unwrap().unwrap();
+        assert_eq!(cmd.name(), "c");
+        assert_eq!(source.lineno, 2);
+        assert!(parser.next_command().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_command_separator_skips_text() {
+        let input = StringInputSource::new("Hello; world\n## note; more\n#a; #b\n");
+        let config = ParserConfig::default().with_command_separator(";");
+        let commands = Parser::new(input, config).into_commands().unwrap();
+        assert_eq!(commands, vec![
+            Command::new_text("Hello; world"),
+            Command::new_annotation("note; more"),
+            Command::new("a", vec![]),
+            Command::new("b", vec![]),
+        ]);
+    }
+
+    #[test]
+    fn test_parser_into_parts() {
+        let input = StringInputSource::new("#a 1\n#b 2\n#c 3");
+        let config = ParserConfig::default().with_command_threshold(1);
+        let mut parser = Parser::new(input, config.clone());
+        assert_eq!(parser.next_command().unwrap().unwrap().name(), "a");
+
+        let (mut input, returned_config) = parser.into_parts();
+        assert_eq!(returned_config, config);
+        assert_eq!(input.next_line().unwrap(), Some("#b 2\n".to_string()));
+
+        let mut parser = Parser::from_parts(input, returned_config);
+        let (cmd, source) = parser.next_command_with_source().unwrap().unwrap();
+        assert_eq!(cmd, Command::new("c", vec![3.into()]));
+        assert_eq!(source.lineno, 1);
+        assert!(parser.next_command().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_parser_into_parts_buffered_lines() {
+        // The line read ahead to look for a continuation is handed back
+        let input = StringInputSource::new("#a 1\n#b 2\n#c 3");
+        let config = ParserConfig::default().with_indent_continuation(true);
+        let mut parser = Parser::new(input, config);
+        assert_eq!(parser.next_command().unwrap().unwrap().name(), "a");
+
+        let (input, config) = parser.into_parts();
+        let mut parser = Parser::from_parts(input, config);
+        let (cmd, source) = parser.next_command_with_source().unwrap().unwrap();
+        assert_eq!(cmd, Command::new("b", vec![2.into()]));
+        assert_eq!(source.lineno, 1);
+        assert_eq!(parser.next_command().unwrap().unwrap(), Command::new("c", vec![3.into()]));
+        assert!(parser.next_command().unwrap().is_none());
+
+        // So are the pieces of a split line
+        let input = StringInputSource::new("#a; #b; #c\n#d");
+        let config = ParserConfig::default().with_command_separator(";");
+        let mut parser = Parser::new(input, config);
+        assert_eq!(parser.next_command().unwrap().unwrap().name(), "a");
+
+        let (input, config) = parser.into_parts();
+        let mut parser = Parser::from_parts(input, config);
+        let names: Vec<_> = std::iter::from_fn(|| parser.next_command().unwrap())
+            .map(|command| command.name)
+            .collect();
+        assert_eq!(names, ["b", "c", "d"]);
+    }
+
+    #[test]
+    fn test_indent_continuation() {
+        let input = StringInputSource::new(
+            "#draw Line\n    2 pos(x: 0, y: 0)\n    thick\nHello\n  #fill red\n      blue\n  text\n",
+        );
+        let config = ParserConfig::default().with_indent_continuation(true);
+        let mut parser = Parser::new(input, config);
+
+        let (cmd, source) = parser.next_command_with_source().unwrap().unwrap();
+        assert_eq!(cmd, "#draw Line 2 pos(x: 0, y: 0) thick".parse().unwrap());
+        assert_eq!(source.lineno, 1);
+        assert_eq!(source.text, "#draw Line\n    2 pos(x: 0, y: 0)\n    thick\n");
+        assert_eq!(parser.next_command().unwrap().unwrap(), Command::new_text("Hello"));
+        // Continuation is relative to the indentation of the command line
+        let cmd = parser.next_command().unwrap().unwrap();
+        assert_eq!(cmd, Command::new("fill", vec!["red".into(), "blue".into()]));
+        assert_eq!(parser.next_command().unwrap().unwrap(), Command::new_text("text"));
+        assert!(parser.next_command().unwrap().is_none());
+
+        // Without the option, indented lines are text
+        let input = StringInputSource::new("#draw Line\n    2\n");
+        let mut parser = Parser::new(input, ParserConfig::default());
+        assert_eq!(parser.next_command().unwrap().unwrap(), Command::new("draw", vec!["Line".into()]));
+        assert_eq!(parser.next_command().unwrap().unwrap(), Command::new_text("2"));
+
+        // Errors point at the continuation line
+        let input = StringInputSource::new("#draw Line\n    pos(x: 0\n");
+        let config = ParserConfig::default().with_indent_continuation(true);
+        let mut parser = Parser::new(input, config);
+        let err = parser.next_command().unwrap_err();
+        assert_eq!(err.source.as_ref().unwrap().lineno, 2);
+    }
+
+    #[test]
+    fn test_expect_command() {
+        let input = StringInputSource::new("#begin 1\n#draw Line\nHello\n");
+        let mut parser = Parser::new(input, ParserConfig::default());
+
+        let cmd = parser.expect_command("begin").unwrap();
+        assert_eq!(cmd, Command::new("begin", vec![1.into()]));
+
+        let err = parser.expect_command("end").unwrap_err();
+        assert!(matches!(
+            &err.error_info,
+            ErrorInfo::UnexpectedCommand { expected, found } if expected == "end" && found == "draw"
+        ));
+        assert_eq!(err.message(), "expected `#end`, found `#draw` at line 2");
+        assert_eq!(err.source.as_ref().unwrap().text, "#draw Line\n");
+        assert!(err.to_string().starts_with("UnexpectedCommandError: expected `#end`, found `#draw`"));
+
+        let err = parser.expect_command("end").unwrap_err();
+        assert_eq!(err.message(), "expected `#end`, found a text line at line 3");
+
+        let err = parser.expect_command("end").unwrap_err();
+        assert!(matches!(
+            &err.error_info,
+            ErrorInfo::UnexpectedEof { expected } if expected == "`#end`"
+        ));
+        assert_eq!(err.message(), "Unexpected end of input, expected `#end`");
+    }
+
+    #[test]
+    fn test_next_command_with_spans() {
+        let input = StringInputSource::new("#color 0xFF \"dark red\"\n#007 0o17\n#draw Line\n    2 pos(x: 0x1, y: 0)\n");
+        let config = ParserConfig::default().with_indent_continuation(true);
+        let mut parser = Parser::new(input, config);
+
+        let sourced = parser.next_command_with_spans().unwrap().unwrap();
+        assert_eq!(sourced.command.params[0], 0xFF.into());
+        assert_eq!(sourced.param_source(0), Some("0xFF"));
+        assert_eq!(sourced.param_spans[0], 7..11);
+        assert_eq!(sourced.param_source(1), Some("\"dark red\""));
+        assert_eq!(sourced.param_source(2), None);
+
+        // The number of a number command spans its name
+        let sourced = parser.next_command_with_spans().unwrap().unwrap();
+        assert_eq!(sourced.command.name(), "@number");
+        assert_eq!(sourced.param_source(0), Some("007"));
+        assert_eq!(sourced.param_source(1), Some("0o17"));
+
+        // Spans of continuation lines are within the assembled text
+        let sourced = parser.next_command_with_spans().unwrap().unwrap();
+        assert_eq!(sourced.param_source(0), Some("Line"));
+        assert_eq!(sourced.param_source(1), Some("2"));
+        assert_eq!(sourced.param_source(2), Some("pos(x: 0x1, y: 0)"));
+        assert_eq!(sourced.param_spans[2], 17..34);
+        assert!(parser.next_command_with_spans().unwrap().is_none());
+
+        let input = StringInputSource::new("Hello\n## note\n");
+        let mut parser = Parser::new(input, ParserConfig::default());
+        for _ in 0..2 {
+            let sourced = parser.next_command_with_spans().unwrap().unwrap();
+            assert!(sourced.param_spans.is_empty());
+            assert_eq!(sourced.param_source(0), None);
+        }
+    }
+
+    #[test]
+    fn test_line_offsets() {
+        let text = "#name \"名前 café\"\nüber\n\n#draw \"ü\" pos(x: 1, y: )\n";
+        let mut parser = Parser::new(StringInputSource::new(text), ParserConfig::default());
+        assert_eq!(parser.line_offsets(), &[0, 21, 27, 28]);
+
+        assert_eq!(parser.next_command().unwrap().unwrap().name(), "name");
+        assert_eq!(parser.next_command().unwrap().unwrap(), Command::new_text("über"));
+        let err = parser.next_command().unwrap_err();
+        let (line, column) = err.position().unwrap();
+        assert_eq!(line, 4);
+        let offset = parser.line_offsets()[line - 1] + column - 1;
+        assert!(text[offset..].starts_with("pos("));
+
+        let parser = Parser::new(StringInputSource::new(""), ParserConfig::default());
+        assert!(parser.line_offsets().is_empty());
+    }
+
+    #[test]
+    fn test_command_span() {
+        let text = "#title \"Intro\"\n\n    #draw Line 1\n## note\n";
+        let mut parser = Parser::new(StringInputSource::new(text), ParserConfig::default());
+        assert_eq!(parser.next_command().unwrap().unwrap().span(), None);
+
+        let config = ParserConfig::default().with_record_spans(true);
+        let mut parser = Parser::new(StringInputSource::new(text), config);
+        assert_eq!(parser.next_command().unwrap().unwrap().span(), Some((1, 1)));
+        let command = parser.next_command().unwrap().unwrap();
+        assert_eq!(command.name(), "draw");
+        assert_eq!(command.span(), Some((3, 5)));
+        assert_eq!(command, "#draw Line 1".parse().unwrap());
+        assert_eq!(parser.next_command().unwrap().unwrap().span(), Some((4, 1)));
+
+        let config = ParserConfig::default()
+            .with_command_separator(";")
+            .with_record_spans(true);
+        let mut parser = Parser::new(StringInputSource::new("#a 1;  #b 2\n#c"), config);
+        let spans: Vec<_> = std::iter::from_fn(|| parser.next_command().unwrap())
+            .map(|command| command.span())
+            .collect();
+        assert_eq!(spans, vec![Some((1, 1)), Some((1, 8)), Some((2, 1))]);
+    }
+
+    #[test]
+    fn test_error_offset() {
+        let text = "#a 1\n#c \"é\"\n#b 3 x(1, ]\n";
+        let mut parser = Parser::new(StringInputSource::new(text), ParserConfig::default());
+        assert_eq!(parser.next_command().unwrap().unwrap().name(), "a");
+        assert_eq!(parser.current_offset(), 5);
+        assert_eq!(parser.next_command().unwrap().unwrap().name(), "c");
+        // "#a 1\n" is 5 bytes, "#c \"é\"\n" is 8
+        assert_eq!(parser.current_offset(), 13);
+        let err = parser.next_command().unwrap_err();
+        assert_eq!(err.line(), Some(3));
+        let offset = err.offset().unwrap();
+        let (_, column) = err.position().unwrap();
+        assert_eq!(offset, 13 + column - 1);
+        assert_eq!(&text[offset..offset + 1], "x");
+        let root = err.traceback.as_ref().unwrap().root_cause();
+        assert_eq!(root.offset, Some(13 + root.column_range.0 - 1));
+        assert_eq!(parser.current_offset(), text.len());
+
+        // Offsets of split segments and continuation lines
+        let text = "#a 1; #b )\n";
+        let config = ParserConfig::default().with_command_separator(";");
+        let mut parser = Parser::new(StringInputSource::new(text), config);
+        parser.next_command().unwrap();
+        let offset = parser.next_command().unwrap_err().offset().unwrap();
+        assert_eq!(&text[offset..], ")\n");
+
+        let text = "#a\n#b 1\n  2 )\n";
+        let config = ParserConfig::default().with_indent_continuation(true);
+        let mut parser = Parser::new(StringInputSource::new(text), config);
+        parser.next_command().unwrap();
+        let offset = parser.next_command().unwrap_err().offset().unwrap();
+        assert_eq!(&text[offset..], ")\n");
+
+        // A peeked command is reported at the start of its line
+        let text = "#a 1\n#b 2\n#c 3\n";
+        let mut parser = Parser::new(StringInputSource::new(text), ParserConfig::default());
+        parser.next_command().unwrap();
+        parser.peek_command().unwrap();
+        assert_eq!(parser.current_line(), 2);
+        assert_eq!(parser.current_offset(), 5);
+        let err = parser.expect_command("x").unwrap_err();
+        assert_eq!(err.offset(), Some(5));
+        parser.next_command().unwrap();
+        let err = parser.expect_command("x").unwrap_err();
+        assert_eq!(err.offset(), Some(text.len()));
+
+        // Errors without a position have no offset
+        assert_eq!(ParseError::syntax("bad".to_string()).offset(), None);
+    }
+
+    #[test]
+    fn test_lossy_number_command_warning() {
+        let input = StringInputSource::new("#007 x\n#7\n#-007\n#-0\n#-1\n#a007\n");
+        let mut parser = Parser::new(input, ParserConfig::default());
+        let commands: Vec<_> = (&mut parser).collect::<ParseResult<_>>().unwrap();
+        assert_eq!(commands.len(), 6);
+
+        let warnings = parser.take_warnings();
+        let lossy: Vec<_> = warnings
+            .iter()
+            .map(|warning| match warning {
+                ParseWarning::LossyNumberCommand { lineno, original, value } => {
+                    (*lineno, original.as_str(), *value)
+                }
+            })
+            .collect();
+        assert_eq!(lossy, vec![(1, "007", 7), (3, "-007", -7), (4, "-0", 0)]);
+        assert_eq!(warnings[0].to_string(), "line 1: number command #007 is read as #7");
+        assert!(parser.take_warnings().is_empty());
+
+        // Names are normalized even without number conversion
+        let config = ParserConfig {
+            convert_number_command: false,
+            ..Default::default()
+        };
+        let mut parser = Parser::new(StringInputSource::new("#01"), config);
+        assert_eq!(parser.next_command().unwrap().unwrap().name(), "1");
+        assert_eq!(parser.take_warnings().len(), 1);
+    }
+
+    #[test]
+    fn test_reset() {
+        let config = ParserConfig::default().with_command_separator(";");
+        let mut parser = Parser::new(StringInputSource::new("#a 1; #b 2\n#c\n"), config);
+        assert_eq!(parser.next_command().unwrap().unwrap().name(), "a");
+        assert_eq!(parser.last_command_line(), Some("#a 1"));
+
+        parser.reset(StringInputSource::new("\n#x pos(0\n"));
+        assert_eq!(parser.last_command_line(), None);
+        assert_eq!(parser.current_line(), 1);
+        let err = parser.next_command().unwrap_err();
+        assert_eq!(err.source.as_ref().unwrap().lineno, 2);
+
+        parser.reset(StringInputSource::new("#y"));
+        assert_eq!(parser.next_command().unwrap().unwrap().name(), "y");
+        assert!(parser.next_command().unwrap().is_none());
+        assert!(!parser.ended_with_newline());
+    }
+
+    #[test]
+    fn test_annotation_keep_hashes() {
+        let parse = |config: ParserConfig| {
+            let mut parser = Parser::new(StringInputSource::new("  ###  note  \n"), config);
+            parser.next_command().unwrap().unwrap()
+        };
+
+        let cmd = parse(ParserConfig::default());
+        assert_eq!(cmd, Command::new_annotation_with_level("note", 3));
+        let cmd = parse(ParserConfig::default().with_annotation_keep_hashes(true));
+        assert_eq!(cmd, Command::new_annotation_with_level("###  note", 3));
+        let cmd = parse(ParserConfig::default().with_preserve_indent(true));
+        assert_eq!(cmd, Command::new_annotation_with_level("  ###  note", 3));
+
+        // The writer re-emits kept hashes as they were
+        let mut buffer = Vec::new();
+        let mut writer = crate::Writer::new(&mut buffer, Default::default());
+        writer
+            .write_command(&Command::new_annotation_with_level("###  note", 3))
+            .unwrap();
+        drop(writer);
+        assert_eq!(String::from_utf8(buffer).unwrap(), "###  note\n");
+    }
+
+    #[test]
+    fn test_config_validate() {
+        assert_eq!(ParserConfig::default().validate(), Ok(()));
+        assert_eq!(
+            ParserConfig::default().with_command_separator(" | ").validate(),
+            Ok(())
+        );
+
+        let config = ParserConfig::default().with_command_threshold(0);
+        assert_eq!(config.validate(), Err(ConfigError::ZeroCommandThreshold));
+        let config = ParserConfig::default().with_command_separator("");
+        assert_eq!(config.validate(), Err(ConfigError::EmptyCommandSeparator));
+        for separator in ["#", ";#", "\n", "\r\n"] {
+            let config = ParserConfig::default().with_command_separator(separator);
+            assert_eq!(config.validate(), Err(ConfigError::InvalidCommandSeparator {
+                separator: separator.to_string(),
+            }));
+        }
+
+        let config = ParserConfig::default()
+            .with_list_delimiters('[', ']')
+            .with_dict_delimiters('{', '}');
+        assert_eq!(config.validate(), Ok(()));
+        for (list, dict) in [(('[', '['), ('(', ')')), (('(', ')'), ('(', ']')), (('"', '"'), ('(', ')'))] {
+            let config = ParserConfig::default()
+                .with_list_delimiters(list.0, list.1)
+                .with_dict_delimiters(dict.0, dict.1);
+            assert!(matches!(config.validate(), Err(ConfigError::InvalidDelimiters { .. })));
+        }
+        let config = config.with_enable_set_literals(true);
+        assert_eq!(config.validate(), Err(ConfigError::InvalidDelimiters { open: '{', close: '}' }));
+
+        // The prefix must not be mistaken for other syntax, nor appear in the separator
+        for prefix in ['%', '@', '§'] {
+            assert_eq!(ParserConfig::default().with_prefix_char(prefix).validate(), Ok(()));
+        }
+        for prefix in [' ', 'a', '7', '_', '"', '\\', '('] {
+            let config = ParserConfig::default().with_prefix_char(prefix);
+            assert_eq!(config.validate(), Err(ConfigError::InvalidPrefixChar { prefix }));
+        }
+        let config = ParserConfig::default()
+            .with_prefix_char('%')
+            .with_command_separator(";%");
+        assert!(matches!(config.validate(), Err(ConfigError::InvalidCommandSeparator { .. })));
+        assert_eq!(config.with_command_separator(";#").validate(), Ok(()));
+
+        let config = ParserConfig::default().with_command_threshold(0);
+        let result = Parser::try_new(StringInputSource::new("#a"), config);
+        assert!(matches!(result, Err(ConfigError::ZeroCommandThreshold)));
+        let result = Parser::try_new(StringInputSource::new("#a"), ParserConfig::default());
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_command_from_str() {
+        let cmd: Command = "#draw Line 2".parse().unwrap();
+        let expected = Command::new("draw", vec!["Line".into(), 2.into()]);
+        assert_eq!(cmd, expected);
+        assert_eq!("draw Line 2".parse::<Command>().unwrap(), cmd);
+
+        // Trailing content and multiple lines are rejected
+        assert!("#draw Line 2)".parse::<Command>().is_err());
+        assert!("#draw Line\n#draw Circle".parse::<Command>().is_err());
+        assert!("".parse::<Command>().is_err());
+
+        // Text is read as a command, so prose doesn't parse
+        assert!("Hello, world!".parse::<Command>().is_err());
+        let cmd: Command = "## note".parse().unwrap();
+        assert_eq!(cmd.name(), "@annotation");
+    }
+
+    #[test]
+    fn test_value_from_str() {
+        assert_eq!("42".parse::<Value>().unwrap(), Value::Int(42));
+        assert_eq!("0xFF".parse::<Value>().unwrap(), Value::Int(255));
+        assert_eq!("0b101".parse::<Value>().unwrap(), Value::Int(5));
+        assert_eq!("1.5e2".parse::<Value>().unwrap(), Value::Float(150.0));
+        assert_eq!("true".parse::<Value>().unwrap(), Value::Bool(true));
+        assert_eq!(r#""a\tb""#.parse::<Value>().unwrap(), Value::from("a\tb"));
+        assert_eq!("Line".parse::<Value>().unwrap(), Value::from("Line"));
+
+        // Trailing garbage and empty input are rejected
+        let err = "12 34".parse::<Value>().unwrap_err();
+        assert!(matches!(err.error_info, ErrorInfo::UnexpectedInput { .. }));
+        assert!(r#""open"x"#.parse::<Value>().is_err());
+        assert!("".parse::<Value>().is_err());
+        assert!("(1, 2)".parse::<Value>().is_err());
+    }
+
+    #[test]
+    fn test_set_config() {
+        let input = StringInputSource::new("#threshold 2\n#text\n##cmd a\n###note\n");
+        let mut parser = Parser::new(input, ParserConfig::default());
+
+        let cmd = parser.next_command().unwrap().unwrap();
+        assert_eq!(cmd.name(), "threshold");
+        assert_eq!(parser.config().command_threshold, 1);
+
+        parser.set_config(parser.config().clone().with_command_threshold(2));
+        assert_eq!(parser.config().command_threshold, 2);
+
+        let cmd = parser.next_command().unwrap().unwrap();
+        assert_eq!(cmd, Command::new_text("#text"));
+        let cmd = parser.next_command().unwrap().unwrap();
+        assert_eq!(cmd.name(), "cmd");
+        let cmd = parser.next_command().unwrap().unwrap();
+        assert_eq!(cmd.name(), "@annotation");
+        assert!(parser.next_command().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_last_command_line() {
+        let input = StringInputSource::new("#cmd1 a\ntext line\n#cmd2 \\\n  b\n");
+        let config = ParserConfig::default();
+        let mut parser = Parser::new(input, config);
+
+        assert_eq!(parser.last_command_line(), None);
+
+        parser.next_command().unwrap();
+        assert_eq!(parser.last_command_line(), Some("#cmd1 a\n"));
+
+        parser.next_command().unwrap();
+        assert_eq!(parser.last_command_line(), Some("text line\n"));
+
+        let cmd = parser.next_command().unwrap().unwrap();
+        assert_eq!(cmd.name(), "cmd2");
+        assert_eq!(parser.last_command_line(), Some("#cmd2 \\\n  b\n"));
+
+        assert!(parser.next_command().unwrap().is_none());
+        assert_eq!(parser.last_command_line(), None);
+    }
+
     #[test]
     fn test_next_command_with_source_command() {
         let input = StringInputSource::new("#name \"Test\"\n#draw Line");
@@ -777,4 +3119,289 @@ mod tests {
         assert_eq!(cmd.name(), "cmd2");
         assert_eq!(source.lineno, 3);
     }
+
+    #[test]
+    fn test_multibyte_after_hashes() {
+        let input = StringInputSource::new("##中文注释\n###  😂 note\n#say \"é\" \"中\"\n中#文\n#é");
+        let mut parser = Parser::new(input, ParserConfig::default());
+
+        let annotation = parser.next_command().unwrap().unwrap();
+        assert_eq!(annotation, Command::new_annotation_with_level("中文注释", 2));
+        let annotation = parser.next_command().unwrap().unwrap();
+        assert_eq!(annotation, Command::new_annotation_with_level("😂 note", 3));
+        let command = parser.next_command().unwrap().unwrap();
+        assert_eq!(command, Command::new("say", vec!["é".into(), "中".into()]));
+        assert_eq!(parser.next_command().unwrap().unwrap(), Command::new_text("中#文"));
+        let err = parser.next_command().unwrap_err();
+        assert_eq!(err.position(), Some((5, 2)));
+    }
+
+    #[test]
+    fn test_error_suggestions() {
+        let suggestion = |line: &str| line.parse::<Command>().unwrap_err().suggestion().map(str::to_string);
+
+        assert_eq!(
+            suggestion("#say \"hello \\\" world").as_deref(),
+            Some("add a closing `\"` to end the string")
+        );
+        assert_eq!(
+            suggestion("#move pos(x: 1, y: 2").as_deref(),
+            Some("add a closing `)` to end the composite")
+        );
+        assert!(suggestion("#move x: 1").unwrap().starts_with("use `name(key: value)`"));
+        assert!(suggestion("#move 1, 2").unwrap().contains("`name(a, b)`"));
+        assert_eq!(suggestion("#move \"(\" )"), None);
+        // Quotes and brackets in char literals are not counted
+        assert_eq!(suggestion("#move '\"' ')' )"), None);
+        assert_eq!(
+            suggestion("#move '(' pos(x: '\"'").as_deref(),
+            Some("add a closing `)` to end the composite")
+        );
+        let config = ParserConfig::default().with_enable_set_literals(true);
+        let err = parse_one("#tag names{1, 2", &config).unwrap_err();
+        assert_eq!(err.suggestion(), Some("add a closing `}` to end the composite"));
+
+        let err = "#say \"hello".parse::<Command>().unwrap_err();
+        assert!(err.to_string().contains("\n  help: add a closing `\"` to end the string"));
+    }
+
+    #[test]
+    fn test_text_line_continuation() {
+        let source = "It was a dark \\\n   and stormy \\\nnight.\nNext line\n#cmd a \\\n  b\nlast \\";
+        let texts = |config: ParserConfig| -> Vec<Command> {
+            Parser::new(StringInputSource::new(source), config)
+                .collect::<Result<_, _>>()
+                .unwrap()
+        };
+
+        let joined = texts(ParserConfig::default().with_text_line_continuation(true));
+        assert_eq!(joined, vec![
+            Command::new_text("It was a dark and stormy night."),
+            Command::new_text("Next line"),
+            Command::new("cmd", vec!["a".into(), "b".into()]),
+            Command::new_text("last \\"),
+        ]);
+
+        // Without the flag the backslashes and line breaks stay in the text
+        let kept = texts(ParserConfig::default());
+        assert_eq!(kept[0], Command::new_text("It was a dark \\\n   and stormy \\\nnight."));
+        assert_eq!(kept[1..], joined[1..]);
+    }
+
+    #[test]
+    fn test_line_preprocessor() {
+        let source = "> #scene forest\n>   Indented text\n> #draw pos(x: 1)\n>\n#raw";
+        let mut parser = Parser::new(
+            StringInputSource::new(source),
+            ParserConfig::default()
+                .with_preserve_indent(true)
+                .with_preserve_empty_lines(true),
+        );
+        parser.set_line_preprocessor(|line: &mut String| {
+            if let Some(rest) = line.strip_prefix("> ").or_else(|| line.strip_prefix('>')) {
+                *line = rest.to_string();
+            }
+        });
+
+        // Runs before classification, trimming and indentation handling
+        let commands: Vec<Command> = parser.by_ref().collect::<Result<_, _>>().unwrap();
+        assert_eq!(commands, vec![
+            Command::new("scene", vec!["forest".into()]),
+            Command::new_text("  Indented text"),
+            "#draw pos(x: 1)".parse().unwrap(),
+            Command::new_text(""),
+            Command::new("raw", vec![]),
+        ]);
+
+        // Runs on each physical line, before continuations are joined
+        let source = "> #draw a \\\n> b\n> c";
+        let mut parser = Parser::new(StringInputSource::new(source), ParserConfig::default());
+        parser.set_line_preprocessor(|line: &mut String| {
+            if let Some(rest) = line.strip_prefix("> ") {
+                *line = rest.to_string();
+            }
+        });
+        let commands: Vec<Command> = parser.by_ref().collect::<Result<_, _>>().unwrap();
+        assert_eq!(commands, vec![
+            Command::new("draw", vec!["a".into(), "b".into()]),
+            Command::new_text("c"),
+        ]);
+
+        // Errors point into the transformed line
+        let mut parser = Parser::new(StringInputSource::new("> #draw )"), ParserConfig::default());
+        parser.set_line_preprocessor(|line: &mut String| line.replace_range(..2, ""));
+        let err = parser.next_command().unwrap_err();
+        assert_eq!(err.position(), Some((1, 7)));
+
+        parser.clear_line_preprocessor();
+        let _ = parser.reset(StringInputSource::new("> #draw"));
+        assert_eq!(parser.next_command().unwrap(), Some(Command::new_text("> #draw")));
+    }
+
+    #[test]
+    fn test_peek_command() {
+        let source = "#begin\n\n#item 1 \\\n  2\nText\n";
+        let mut parser = Parser::new(StringInputSource::new(source), ParserConfig::default());
+
+        // Peek then next
+        assert_eq!(parser.current_line(), 1);
+        assert_eq!(parser.peek_command().unwrap().unwrap().name(), "begin");
+        assert_eq!(parser.current_line(), 1);
+        assert_eq!(parser.last_command_line(), None);
+        assert_eq!(parser.next_command().unwrap().unwrap().name(), "begin");
+        assert_eq!(parser.current_line(), 2);
+
+        // Double peek returns the same command, with its spans kept
+        let item = parser.peek_command().unwrap().cloned();
+        assert_eq!(parser.current_line(), 3);
+        assert_eq!(parser.peek_command().unwrap().cloned(), item);
+        let sourced = parser.next_command_with_spans().unwrap().unwrap();
+        assert_eq!(sourced.param_source(1), Some("2"));
+        assert_eq!(Some(sourced.command), item);
+        assert_eq!(parser.current_line(), 5);
+
+        // Peek at EOF
+        assert_eq!(parser.next_command().unwrap(), Some(Command::new_text("Text")));
+        assert_eq!(parser.peek_command().unwrap(), None);
+        assert_eq!(parser.peek_command().unwrap(), None);
+        assert_eq!(parser.next_command().unwrap(), None);
+
+        // Errors are returned once, by the peek
+        let source = "#a )\n#b\n";
+        let mut parser = Parser::new(StringInputSource::new(source), ParserConfig::default());
+        assert!(parser.peek_command().is_err());
+        assert_eq!(parser.next_command().unwrap().unwrap().name(), "b");
+
+        // A raw read returns the peeked line
+        let mut parser = Parser::new(StringInputSource::new("#a\n#b\n"), ParserConfig::default());
+        parser.peek_command().unwrap();
+        assert_eq!(parser.next_raw_line().unwrap(), Some((1, "#a\n".to_string())));
+        assert_eq!(parser.next_command().unwrap().unwrap().name(), "b");
+    }
+
+    #[test]
+    fn test_peek_command_into_parts() {
+        let input = StringInputSource::new("#a 1\n#b 2\n#c 3");
+        let mut parser = Parser::new(input, ParserConfig::default());
+        assert_eq!(parser.next_command().unwrap().unwrap().name(), "a");
+        assert_eq!(parser.peek_command().unwrap().unwrap().name(), "b");
+
+        let (input, config) = parser.into_parts();
+        let mut parser = Parser::from_parts(input, config);
+        assert_eq!(parser.next_command().unwrap().unwrap(), Command::new("b", vec![2.into()]));
+        assert_eq!(parser.next_command().unwrap().unwrap(), Command::new("c", vec![3.into()]));
+        assert!(parser.next_command().unwrap().is_none());
+
+        // A peeked command spanning several lines is read again whole
+        let input = StringInputSource::new("#a\n#b 1 \\\n  2\n#c\n    3\n#d");
+        let config = ParserConfig::default().with_indent_continuation(true);
+        let mut parser = Parser::new(input, config);
+        parser.next_command().unwrap();
+        assert_eq!(parser.peek_command().unwrap().unwrap().params().len(), 2);
+
+        let (input, config) = parser.into_parts();
+        let mut parser = Parser::from_parts(input, config);
+        assert_eq!(parser.next_command().unwrap().unwrap(), Command::new("b", vec![1.into(), 2.into()]));
+        assert_eq!(parser.next_command().unwrap().unwrap(), Command::new("c", vec![3.into()]));
+        assert_eq!(parser.next_command().unwrap().unwrap().name(), "d");
+        assert!(parser.next_command().unwrap().is_none());
+
+        let input = StringInputSource::new("#a\n#c\n    3\n#d");
+        let config = ParserConfig::default().with_indent_continuation(true);
+        let mut parser = Parser::new(input, config);
+        parser.next_command().unwrap();
+        parser.peek_command().unwrap();
+
+        let (input, config) = parser.into_parts();
+        let mut parser = Parser::from_parts(input, config);
+        assert_eq!(parser.next_command().unwrap().unwrap(), Command::new("c", vec![3.into()]));
+        assert_eq!(parser.next_command().unwrap().unwrap().name(), "d");
+    }
+
+    #[test]
+    fn test_collect_commands() {
+        let source = "#scene forest\nOnce upon a time\n#end\n";
+        let commands = Parser::new(StringInputSource::new(source), ParserConfig::default())
+            .into_commands()
+            .unwrap();
+        assert_eq!(commands.len(), 3);
+        let names: Vec<&str> = commands.iter().map(Command::name).collect();
+        assert_eq!(names, ["scene", "@text", "end"]);
+
+        // The first error stops collection
+        let mut parser = Parser::new(StringInputSource::new("#a\n#b )\n#c\n"), ParserConfig::default());
+        let err = parser.collect_commands().unwrap_err();
+        assert_eq!(err.line(), Some(2));
+        assert_eq!(parser.collect_commands().unwrap(), vec![Command::new("c", vec![])]);
+    }
+
+    #[test]
+    fn test_quotes_in_char_literals() {
+        let quote = Parameter::from(Value::Int('"' as i64));
+        let config = ParserConfig::default().with_comment_marker("//");
+        let text = "#say '\"' // c\n#say '\\'' \"//\" // c\n#say '/' 1 //'\n";
+        let commands = Parser::new(StringInputSource::new(text), config).into_commands().unwrap();
+        assert_eq!(commands, vec![
+            Command::new("say", vec![quote.clone()]),
+            Command::new("say", vec![Parameter::from(Value::Int('\'' as i64)), Parameter::from("//")]),
+            Command::new("say", vec![Parameter::from(Value::Int('/' as i64)), Parameter::from(1)]),
+        ]);
+
+        let config = ParserConfig::default().with_command_separator(";");
+        let text = "#say '\"';#b ';'";
+        let commands = Parser::new(StringInputSource::new(text), config).into_commands().unwrap();
+        assert_eq!(commands, vec![
+            Command::new("say", vec![quote]),
+            Command::new("b", vec![Parameter::from(Value::Int(';' as i64))]),
+        ]);
+
+        // A lone `'` doesn't hide what follows it
+        assert_eq!(split_outside_strings("a ' b; c", ";"), vec!["a ' b", " c"]);
+        assert_eq!(split_outside_strings("\"a;\\\";b\";c", ";"), vec!["\"a;\\\";b\"", "c"]);
+    }
+
+    #[test]
+    fn test_comment_marker() {
+        let source = concat!(
+            "#draw Line 2  // set up the line\n",
+            "#say \"http://example.com\" url(\"a//b\")//trailing\n",
+            "#   // only a comment\n",
+            "Text // keeps its marker\n",
+            "## Annotation // too\n",
+            "#end//\n",
+        );
+        let config = ParserConfig::default().with_comment_marker("//");
+        let commands = Parser::new(StringInputSource::new(source), config.clone())
+            .into_commands()
+            .unwrap();
+        assert_eq!(commands, vec![
+            Command::new("draw", vec!["Line".into(), 2.into()]),
+            Command::new("say", vec![
+                "http://example.com".into(),
+                crate::command::Parameter::from(("url", "a//b")),
+            ]),
+            Command::new_text("Text // keeps its marker"),
+            Command::new_annotation_with_level("Annotation // too", 2),
+            Command::new("end", vec![]),
+        ]);
+
+        // Spans and continuation lines see the command without its comment
+        let config = config.with_indent_continuation(true);
+        let mut parser = Parser::new(
+            StringInputSource::new("#draw 1 // a\n    2 // b\n    // c\n    3\n"),
+            config.clone(),
+        );
+        let sourced = parser.next_command_with_spans().unwrap().unwrap();
+        assert_eq!(sourced.command, Command::new("draw", vec![1.into(), 2.into(), 3.into()]));
+        assert_eq!(sourced.param_source(1), Some("2"));
+
+        // An empty command line is still an error, and without a marker `//` is a syntax error
+        let mut parser = Parser::new(StringInputSource::new("#\n"), config);
+        assert!(parser.next_command().is_err());
+        let mut parser = Parser::new(StringInputSource::new(source), ParserConfig::default());
+        assert!(parser.next_command().is_err());
+
+        let config = ParserConfig::default().with_comment_marker("");
+        assert_eq!(config.validate(), Err(ConfigError::EmptyCommentMarker));
+    }
 }
@@ -0,0 +1,292 @@
+//! Preprocessing of parsed commands
+//!
+//! A [`Preprocessor`] installed with [`Parser::set_preprocessor`] sees every
+//! command before it is returned, and may drop it. [`ConditionalPreprocessor`]
+//! uses this for `#if`/`#elif`/`#else`/`#endif` blocks, so templated KoiLang
+//! can include commands depending on a user-supplied condition.
+//!
+//! [`Parser::set_preprocessor`]: super::Parser::set_preprocessor
+
+use crate::command::Command;
+
+use super::{ParseError, ParseResult, ParserLineSource};
+
+/// A stage between parsing and the consumer of the commands
+pub trait Preprocessor {
+    /// Process the next parsed command
+    ///
+    /// # Arguments
+    /// * `command` - The parsed command
+    /// * `source` - Where the command was read from
+    ///
+    /// # Returns
+    /// `Some` with the command to return from the parser, or `None` to drop it
+    fn process(
+        &mut self,
+        command: Command,
+        source: &ParserLineSource,
+    ) -> ParseResult<Option<Command>>;
+
+    /// Check the state at the end of the input
+    ///
+    /// # Arguments
+    /// * `lineno` - Number of the last line of the input
+    fn finish(&mut self, lineno: usize) -> ParseResult<()> {
+        let _ = lineno;
+        Ok(())
+    }
+
+    /// Forget any state, when the parser starts a new input
+    fn reset(&mut self) {}
+}
+
+/// An open conditional block
+#[derive(Debug, Clone)]
+struct Block {
+    /// Line of the opening directive
+    lineno: usize,
+    /// Whether the enclosing block is included
+    parent_active: bool,
+    /// Whether a branch of this block was already included
+    taken: bool,
+    /// Whether the current branch is included
+    active: bool,
+    /// Whether the else branch has started
+    seen_else: bool,
+}
+
+/// Preprocessor for conditional blocks of commands
+///
+/// A block starts with `#if`, may have any number of `#elif` branches and an
+/// `#else` branch, and ends with `#endif`. The condition function is called
+/// with the `#if` or `#elif` command and decides from its parameters whether
+/// that branch is included. Only the first true branch is included; blocks
+/// nest. The directives themselves are never returned.
+///
+/// Directives without a matching `#if`, branches after `#else` and blocks
+/// left open at the end of the input are errors.
+///
+/// # Examples
+///
+/// ```rust
+/// use koicore::Value;
+/// use koicore::parser::{ConditionalPreprocessor, Parser, ParserConfig, StringInputSource};
+///
+/// let input = StringInputSource::new("#if debug\n#log verbose\n#else\n#log quiet\n#endif\n");
+/// let mut parser = Parser::new(input, ParserConfig::default());
+/// parser.set_preprocessor(ConditionalPreprocessor::new(|command| {
+///     command.params().first() == Some(&Value::from("debug").into())
+/// }));
+///
+/// let commands = parser.collect::<Result<Vec<_>, _>>()?;
+/// assert_eq!(commands.len(), 1);
+/// assert_eq!(commands[0].to_string(), "log verbose");
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConditionalPreprocessor<F> {
+    condition: F,
+    if_name: String,
+    elif_name: String,
+    else_name: String,
+    endif_name: String,
+    blocks: Vec<Block>,
+}
+
+impl<F: FnMut(&Command) -> bool> ConditionalPreprocessor<F> {
+    /// Create a preprocessor for `#if`/`#elif`/`#else`/`#endif` blocks
+    ///
+    /// # Arguments
+    /// * `condition` - Decides whether the branch of an `#if` or `#elif` command is included
+    pub fn new(condition: F) -> Self {
+        Self {
+            condition,
+            if_name: "if".to_string(),
+            elif_name: "elif".to_string(),
+            else_name: "else".to_string(),
+            endif_name: "endif".to_string(),
+            blocks: Vec::new(),
+        }
+    }
+
+    /// Use other command names for the directives
+    ///
+    /// # Arguments
+    /// * `if_name` - Name of the command opening a block
+    /// * `elif_name` - Name of the command starting a conditional branch
+    /// * `else_name` - Name of the command starting the final branch
+    /// * `endif_name` - Name of the command closing a block
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::ConditionalPreprocessor;
+    ///
+    /// let preprocessor = ConditionalPreprocessor::new(|_| true)
+    ///     .with_directives("when", "orwhen", "otherwise", "end");
+    /// ```
+    pub fn with_directives(
+        mut self,
+        if_name: &str,
+        elif_name: &str,
+        else_name: &str,
+        endif_name: &str,
+    ) -> Self {
+        self.if_name = if_name.to_string();
+        self.elif_name = elif_name.to_string();
+        self.else_name = else_name.to_string();
+        self.endif_name = endif_name.to_string();
+        self
+    }
+
+    /// Whether commands at the current position are included
+    fn active(&self) -> bool {
+        self.blocks.last().is_none_or(|block| block.active)
+    }
+
+    /// Get the innermost open block for a directive that needs one
+    fn current_block(&mut self, name: &str, source: &ParserLineSource) -> ParseResult<&mut Block> {
+        let if_name = &self.if_name;
+        match self.blocks.last_mut() {
+            Some(block) if block.seen_else && name != self.endif_name => Err(directive_error(
+                format!("`#{}` after `#{}`", name, self.else_name),
+                source,
+            )),
+            Some(block) => Ok(block),
+            None => Err(directive_error(
+                format!("`#{}` without a matching `#{}`", name, if_name),
+                source,
+            )),
+        }
+    }
+}
+
+impl<F: FnMut(&Command) -> bool> Preprocessor for ConditionalPreprocessor<F> {
+    fn process(
+        &mut self,
+        command: Command,
+        source: &ParserLineSource,
+    ) -> ParseResult<Option<Command>> {
+        let name = command.name();
+        if name == self.if_name {
+            let parent_active = self.active();
+            let active = parent_active && (self.condition)(&command);
+            self.blocks.push(Block {
+                lineno: source.lineno,
+                parent_active,
+                taken: active,
+                active,
+                seen_else: false,
+            });
+        } else if name == self.elif_name {
+            let block = self.current_block(name, source)?;
+            let evaluate = block.parent_active && !block.taken;
+            let active = evaluate && (self.condition)(&command);
+            let block = self.blocks.last_mut().expect("block checked above");
+            block.active = active;
+            block.taken |= active;
+        } else if name == self.else_name {
+            let block = self.current_block(name, source)?;
+            block.active = block.parent_active && !block.taken;
+            block.taken = true;
+            block.seen_else = true;
+        } else if name == self.endif_name {
+            self.current_block(name, source)?;
+            self.blocks.pop();
+        } else if self.active() {
+            return Ok(Some(command));
+        }
+        Ok(None)
+    }
+
+    fn finish(&mut self, lineno: usize) -> ParseResult<()> {
+        let blocks = std::mem::take(&mut self.blocks);
+        match blocks.last() {
+            Some(block) => Err(ParseError::unexpected_eof(
+                format!(
+                    "`#{}` to close `#{}` from line {}",
+                    self.endif_name, self.if_name, block.lineno
+                ),
+                lineno,
+                1,
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.blocks.clear();
+    }
+}
+
+/// Create an error pointing at a misplaced directive
+fn directive_error(message: String, source: &ParserLineSource) -> Box<ParseError> {
+    ParseError::syntax_with_context(message, source.lineno, 1, String::new())
+        .with_line_source(source.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Value;
+    use crate::parser::{Parser, ParserConfig, StringInputSource};
+
+    /// Parse with conditions true for `#if`/`#elif` commands whose first parameter is `yes`
+    fn parse(source: &str) -> ParseResult<Vec<String>> {
+        let mut parser = Parser::new(StringInputSource::new(source), ParserConfig::default());
+        parser.set_preprocessor(ConditionalPreprocessor::new(|command: &Command| {
+            command.params().first() == Some(&Value::from("yes").into())
+        }));
+        parser.map(|command| command.map(|command| command.to_string())).collect()
+    }
+
+    #[test]
+    fn test_conditional_branches() {
+        // True branch
+        assert_eq!(parse("#a\n#if yes\n#b\n#else\n#c\n#endif\n#d").unwrap(), ["a", "b", "d"]);
+        // False branch
+        assert_eq!(parse("#if no\n#b\n#else\n#c\n#endif").unwrap(), ["c"]);
+        assert_eq!(parse("#if no\n#b\n#endif\nText").unwrap(), ["@text Text"]);
+        // Only the first true branch is included
+        assert_eq!(
+            parse("#if no\n#a\n#elif yes\n#b\n#elif yes\n#c\n#else\n#d\n#endif").unwrap(),
+            ["b"]
+        );
+        // Nested blocks in a skipped branch are skipped whatever their condition
+        assert_eq!(
+            parse("#if no\n#if yes\n#a\n#endif\n#else\n#if yes\n#b\n#else\n#c\n#endif\n#endif")
+                .unwrap(),
+            ["b"]
+        );
+    }
+
+    #[test]
+    fn test_conditional_custom_directives() {
+        let source = "#when yes\n#a\n#otherwise\n#b\n#end\n#if\n";
+        let mut parser = Parser::new(StringInputSource::new(source), ParserConfig::default());
+        parser.set_preprocessor(
+            ConditionalPreprocessor::new(|command: &Command| !command.params().is_empty())
+                .with_directives("when", "orwhen", "otherwise", "end"),
+        );
+        let names: Vec<String> = parser.map(|c| c.unwrap().name().to_string()).collect();
+        assert_eq!(names, ["a", "if"]);
+    }
+
+    #[test]
+    fn test_conditional_unbalanced() {
+        let err = parse("#if yes\n#a\n#if no\n#b\n#endif\n").unwrap_err();
+        assert_eq!(
+            err.message(),
+            "Unexpected end of input, expected `#endif` to close `#if` from line 1"
+        );
+
+        let err = parse("#a\n#endif\n").unwrap_err();
+        assert_eq!(err.message(), "`#endif` without a matching `#if`");
+        assert_eq!(err.position(), Some((2, 1)));
+
+        let err = parse("#if yes\n#else\n#elif yes\n#endif\n").unwrap_err();
+        assert_eq!(err.message(), "`#elif` after `#else`");
+        assert_eq!(err.line(), Some(3));
+        assert!(parse("#if yes\n#else\n#else\n#endif\n").is_err());
+    }
+}
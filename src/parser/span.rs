@@ -0,0 +1,36 @@
+//! Source spans of parsed parameters
+//!
+//! Returned by `Parser::next_command_with_spans` for tools that need the
+//! exact source text of a value, e.g. to keep the formatting of a number.
+
+use std::ops::Range;
+
+use super::error::ParserLineSource;
+use crate::command::Command;
+
+/// A command with its source text and the span of each parameter
+#[derive(Debug, Clone)]
+pub struct SourcedCommand {
+    /// The parsed command
+    pub command: Command,
+    /// Where the command came from, including its full source text
+    pub source: ParserLineSource,
+    /// Byte ranges of the parameters in `source.text`, by position
+    ///
+    /// Shorter than the parameter list when a parameter has no source of its
+    /// own, e.g. the content of text and annotation commands.
+    pub param_spans: Vec<Range<usize>>,
+}
+
+impl SourcedCommand {
+    /// Get the source text of a parameter
+    ///
+    /// # Arguments
+    /// * `index` - Position of the parameter (0-based)
+    ///
+    /// Returns `None` if the parameter has no recorded span.
+    pub fn param_source(&self, index: usize) -> Option<&str> {
+        let span = self.param_spans.get(index)?;
+        self.source.text.get(span.clone())
+    }
+}
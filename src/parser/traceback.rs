@@ -33,6 +33,27 @@ pub(super) struct NomErrorNode<I> {
     children: Vec<NomErrorNode<I>>,
 }
 
+impl<I: core::ops::Deref<Target = str>> NomErrorNode<I> {
+    /// Collect the `context` labels in this tree, outermost first
+    ///
+    /// Each label is paired with the byte offset in `original_input` where the
+    /// labelled parser started.
+    pub(super) fn context_labels(&self, original_input: &str) -> Vec<(&'static str, usize)> {
+        let mut labels = Vec::new();
+        self.collect_context_labels(original_input, &mut labels);
+        labels
+    }
+
+    fn collect_context_labels(&self, original_input: &str, labels: &mut Vec<(&'static str, usize)>) {
+        if let NomErrorKind::Context(ctx) = self.kind {
+            labels.push((ctx, original_input.offset(&self.input)));
+        }
+        for child in &self.children {
+            child.collect_context_labels(original_input, labels);
+        }
+    }
+}
+
 impl<I: Input> ParseError<I> for NomErrorNode<I> {
     fn from_error_kind(input: I, kind: ErrorKind) -> Self {
         NomErrorNode {
@@ -115,6 +136,9 @@ pub struct TracebackEntry {
     pub lineno: usize,
     /// Column number where this traceback point occurred
     pub column_range: (usize, usize),
+    /// Byte offset of the start of `column_range` from the start of the input,
+    /// if known
+    pub offset: Option<usize>,
     /// Context description (from nom error kind or parsing context)
     pub context: String,
     /// Traceback children (for nested contexts)
@@ -126,19 +150,59 @@ impl TracebackEntry {
         Self {
             lineno,
             column_range,
+            offset: None,
             context,
             children: vec![],
         }
     }
 
+    /// Get the deepest frame of this traceback
+    ///
+    /// Descends through the children and returns the innermost entry. When an
+    /// entry has several children they are failed alternatives at the same
+    /// position, so the descent stops there and that entry is returned.
+    pub fn root_cause(&self) -> &TracebackEntry {
+        let mut current = self;
+        while let [child] = current.children.as_slice() {
+            current = child;
+        }
+        current
+    }
+
+    /// Get the innermost `koicore.*` parsing context on the way to the root cause
+    ///
+    /// Nom's own frames (`nom.Tag`, `nom.Alt`, ...) are skipped, so this names the
+    /// grammar rule that failed, e.g. `basic_value` or `dictionary`.
+    pub fn root_context(&self) -> Option<&str> {
+        let mut context = self.context.strip_prefix("koicore.");
+        let mut current = self;
+        while let [child] = current.children.as_slice() {
+            current = child;
+            context = current.context.strip_prefix("koicore.").or(context);
+        }
+        context
+    }
+
+    /// Convert a nom error tree into a traceback
+    ///
+    /// `input` starts at `column_offset` of line `line`, and at byte
+    /// `byte_offset` of the whole input.
     pub(super) fn build_error_trace<I: core::ops::Deref<Target = str> + Input>(
         input: I,
         line: usize,
         column_offset: usize,
+        byte_offset: usize,
         error: &NomErrorNode<I>,
     ) -> Self {
         let line_index = LineIndex::new(&input);
-        Self::build_error_trace_recursive(&input, &line_index, line, column_offset, error)
+        Self::build_error_trace_recursive(
+            &input,
+            &line_index,
+            line,
+            column_offset,
+            byte_offset,
+            error,
+        )
     }
 
     fn build_error_trace_recursive<I: core::ops::Deref<Target = str> + Input>(
@@ -146,6 +210,7 @@ impl TracebackEntry {
         line_index: &LineIndex,
         line_base: usize,
         column_offset: usize,
+        byte_offset: usize,
         error: &NomErrorNode<I>,
     ) -> Self {
         let (rel_line, rel_column) = line_index.get_location(input, &error.input);
@@ -160,6 +225,7 @@ impl TracebackEntry {
                     line_index,
                     line_base,
                     column_offset,
+                    byte_offset,
                     child,
                 )
             })
@@ -171,6 +237,7 @@ impl TracebackEntry {
                 rel_column + if rel_line == 1 { column_offset } else { 0 },
                 rel_column + error.input.len() + if rel_line == 1 { column_offset } else { 0 },
             ),
+            offset: Some(byte_offset + input.offset(&error.input)),
             context,
             children,
         }
@@ -281,7 +348,7 @@ mod tests {
     fn test_traceback_entry_convert_error() {
         let input = "line1\nline2\nline3";
         let error = NomErrorNode::from_char(input, 'a');
-        let entry = TracebackEntry::build_error_trace(input, 1, 0, &error);
+        let entry = TracebackEntry::build_error_trace(input, 1, 0, 0, &error);
         assert_eq!(entry.lineno, 1);
         assert_eq!(entry.column_range, (1, input.len() + 1));
         assert_eq!(entry.context, "nom.char<'a'>");
@@ -297,7 +364,7 @@ mod tests {
         println!("Parser error traceback: {:#?}", node);
         match node {
             nom::Err::Error(e) | nom::Err::Failure(e) => {
-                let traceback = TracebackEntry::build_error_trace(input, 1, 0, &e);
+                let traceback = TracebackEntry::build_error_trace(input, 1, 0, 0, &e);
                 println!("Converted traceback: {:#?}", traceback);
             }
             _ => unreachable!(),
@@ -310,7 +377,7 @@ mod tests {
         println!("Parser error traceback: {:#?}", node);
         match node {
             nom::Err::Error(e) | nom::Err::Failure(e) => {
-                let traceback = TracebackEntry::build_error_trace(input, 1, 0, &e);
+                let traceback = TracebackEntry::build_error_trace(input, 1, 0, 0, &e);
                 println!("Converted traceback: {:#?}", traceback);
             }
             _ => unreachable!(),
@@ -323,7 +390,7 @@ mod tests {
         println!("Parser error traceback: {:#?}", node);
         match node {
             nom::Err::Error(e) | nom::Err::Failure(e) => {
-                let traceback = TracebackEntry::build_error_trace(input, 1, 0, &e);
+                let traceback = TracebackEntry::build_error_trace(input, 1, 0, 0, &e);
                 println!("Converted traceback: {:#?}", traceback);
             }
             _ => unreachable!(),
@@ -0,0 +1,111 @@
+//! JSON Schema for the serde representation of commands
+//!
+//! Available with the `schema` feature. The schemas describe the JSON written
+//! by the `serde` implementations, e.g. by `koicli to-json`:
+//!
+//! - a [`Value`] is a JSON number, boolean or string
+//! - a [`Parameter`] is either a value or an object with a single key, the
+//!   composite name, mapping to a value, an array of values or an object of values
+//! - a [`Command`] is an object with a `name` and a `params` array
+
+use std::borrow::Cow;
+
+use schemars::{JsonSchema, Schema, SchemaGenerator, json_schema};
+
+use crate::command::{Command, CompositeValue, Parameter, Value};
+
+impl JsonSchema for Value {
+    fn schema_name() -> Cow<'static, str> {
+        "Value".into()
+    }
+
+    fn json_schema(_generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "A basic value; timestamps and durations are written as strings",
+            "type": ["integer", "number", "boolean", "string"],
+        })
+    }
+}
+
+impl JsonSchema for CompositeValue {
+    fn schema_name() -> Cow<'static, str> {
+        "CompositeValue".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        let value = generator.subschema_for::<Value>();
+        json_schema!({
+            "description": "A single value, a list or set of values, or a dictionary of values",
+            "anyOf": [
+                value,
+                { "type": "array", "items": value },
+                { "type": "object", "additionalProperties": value },
+            ],
+        })
+    }
+}
+
+impl JsonSchema for Parameter {
+    fn schema_name() -> Cow<'static, str> {
+        "Parameter".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        let value = generator.subschema_for::<Value>();
+        let composite = generator.subschema_for::<CompositeValue>();
+        json_schema!({
+            "description": "A basic value, or a composite parameter as a single-entry object",
+            "anyOf": [
+                value,
+                {
+                    "type": "object",
+                    "minProperties": 1,
+                    "maxProperties": 1,
+                    "additionalProperties": composite,
+                },
+            ],
+        })
+    }
+}
+
+impl JsonSchema for Command {
+    fn schema_name() -> Cow<'static, str> {
+        "Command".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        let param = generator.subschema_for::<Parameter>();
+        json_schema!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "params": { "type": "array", "items": param },
+                "span": {
+                    "type": "array",
+                    "items": { "type": "integer", "minimum": 1 },
+                    "minItems": 2,
+                    "maxItems": 2,
+                },
+            },
+            "required": ["name", "params"],
+            "additionalProperties": false,
+        })
+    }
+}
+
+/// Get the JSON Schema of a serialized command list
+///
+/// The root schema is an array of [`Command`]s, the shape written by
+/// `koicli to-json`, with `Command`, `Parameter`, `CompositeValue` and `Value`
+/// under `$defs`.
+///
+/// # Examples
+///
+/// ```rust
+/// let schema = koicore::json_schema();
+/// assert_eq!(schema["type"], "array");
+/// assert!(schema["$defs"]["Command"].is_object());
+/// ```
+pub fn json_schema() -> serde_json::Value {
+    schemars::schema_for!(Vec<Command>).to_value()
+}
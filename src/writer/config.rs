@@ -57,9 +57,34 @@ pub enum FloatFormat {
     Scientific,
     General(Option<usize>),
     Custom(String),
+    /// Shortest form that re-parses as the same float (e.g. `1.`, `.5`, `1e20`)
+    Shortest,
 }
 
 
+/// Separator written between (or after) commands
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum CommandSeparator {
+    /// Separate commands with a newline (default)
+    #[default]
+    Newline,
+    /// Separate commands with `;`
+    Semicolon,
+    /// Separate commands with a custom string
+    Custom(String),
+}
+
+impl CommandSeparator {
+    /// Get the text written for this separator
+    pub fn as_str(&self) -> &str {
+        match self {
+            CommandSeparator::Newline => "\n",
+            CommandSeparator::Semicolon => ";",
+            CommandSeparator::Custom(separator) => separator,
+        }
+    }
+}
+
 /// Selector for parameter-specific formatting options
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ParamFormatSelector {
@@ -94,6 +119,80 @@ pub struct FormatterOptions {
     pub newline_after_param: bool,
     /// Whether to override the base options completely
     pub should_override: bool,
+    /// Whether to emit type annotations on basic values (e.g. `5:int`)
+    pub type_annotations: bool,
+    /// Whether to write quoted string parameters as percent-encoded `u"..."` strings
+    ///
+    /// Output must be read back with `ParserConfig::enable_percent_strings`.
+    pub percent_encode_strings: bool,
+    /// Whether to write a space after commas in composite values
+    ///
+    /// `None` writes a space unless `compact` is set.
+    pub composite_space_after_comma: Option<bool>,
+    /// Whether to write a space after colons in dictionary entries
+    ///
+    /// `None` writes a space unless `compact` is set.
+    pub composite_space_after_colon: Option<bool>,
+    /// Whether to quote every string inside composite values
+    ///
+    /// Strings in lists, sets and dictionaries are then written the same way
+    /// regardless of their content, e.g. `meta(name: "hello", path: "x")`.
+    /// Top-level string parameters are not affected.
+    pub quote_dict_string_values: bool,
+    /// Whether to write composite values in a canonical order
+    ///
+    /// Dictionary entries are sorted by key, keeping the order of duplicate
+    /// keys, so dictionaries built in different orders are written the same
+    /// way, e.g. for content-addressed hashing. List order is meaningful and
+    /// kept as is; sets are always written in canonical order.
+    pub canonicalize_composites: bool,
+    /// Whether to write the command on a single line
+    ///
+    /// Overrides `newline_before_param` and `newline_after_param` of all
+    /// parameters of the command. Newlines in strings are still escaped, so
+    /// each command takes exactly one line, e.g. for log-style output.
+    pub force_single_line: bool,
+    /// Whether to write a blank line after commands with composite parameters
+    ///
+    /// Visually separates complex commands such as `#draw pos(x: 1, y: 2)`
+    /// from the simple commands around them. Like `newline_after`, but only
+    /// for commands with at least one composite parameter.
+    pub blank_after_composite_commands: bool,
+}
+
+impl FormatterOptions {
+    /// Whether a space follows commas in composite values
+    pub fn space_after_comma(&self) -> bool {
+        self.composite_space_after_comma.unwrap_or(!self.compact)
+    }
+
+    /// Whether a space follows colons in dictionary entries
+    pub fn space_after_colon(&self) -> bool {
+        self.composite_space_after_colon.unwrap_or(!self.compact)
+    }
+
+    /// Options for the shortest output that re-parses to identical commands
+    ///
+    /// No indentation or optional whitespace, decimal integers, and the
+    /// shortest float form that still parses as a float. Strings are only
+    /// quoted when required.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::writer::FormatterOptions;
+    ///
+    /// let options = FormatterOptions::minified();
+    /// assert!(options.compact);
+    /// ```
+    pub fn minified() -> Self {
+        Self {
+            compact: true,
+            number_format: NumberFormat::Decimal,
+            float_format: FloatFormat::Shortest,
+            ..Default::default()
+        }
+    }
 }
 
 /// Configuration for the KoiLang writer
@@ -105,6 +204,45 @@ pub struct WriterConfig {
     pub command_options: HashMap<String, FormatterOptions>,
     /// Command threshold (number of # required for commands)
     pub command_threshold: usize,
+    /// Whether every command is terminated by the command separator
+    ///
+    /// If set to false, separators are only written between commands, so the
+    /// output does not end with one.
+    pub trailing_newline: bool,
+    /// Separator written between commands
+    ///
+    /// To read the output back, set `ParserConfig::command_separator` to the
+    /// same separator text. The parser only splits command lines, so text and
+    /// annotation lines are always written on lines of their own, ended by a
+    /// newline.
+    pub command_separator: CommandSeparator,
+    /// Whether to write the `#` prefix before commands
+    ///
+    /// If set to false, commands are written bare (e.g. `draw Line 2`) for
+    /// embedding in formats that supply the prefix themselves. Text and
+    /// annotation lines are still written unchanged.
+    pub emit_prefix: bool,
+    /// Opening and closing characters of list and single-value composites
+    ///
+    /// To read the output back, set `ParserConfig::list_delimiters` to match.
+    pub list_delimiters: (char, char),
+    /// Opening and closing characters of dictionary composites
+    ///
+    /// To read the output back, set `ParserConfig::dict_delimiters` to match.
+    pub dict_delimiters: (char, char),
+    /// Maximum width of output lines
+    ///
+    /// When set, a parameter that would extend a command's line past this
+    /// width starts a new line, indented one level deeper and joined to the
+    /// previous one with a line continuation (`\`), so the output reads back
+    /// as the same command. Single parameters wider than the limit are not
+    /// split. Ignored for commands written with `force_single_line`.
+    pub max_width: Option<usize>,
+    /// Character written before commands and annotations
+    ///
+    /// Defaults to `#`; `command_threshold` counts this character. To read
+    /// the output back, set `ParserConfig::prefix_char` to match.
+    pub prefix_char: char,
 }
 
 impl Default for WriterConfig {
@@ -117,6 +255,49 @@ impl Default for WriterConfig {
             },
             command_options: HashMap::new(),
             command_threshold: 1,
+            trailing_newline: true,
+            command_separator: CommandSeparator::Newline,
+            emit_prefix: true,
+            list_delimiters: ('(', ')'),
+            dict_delimiters: ('(', ')'),
+            max_width: None,
+            prefix_char: '#',
+        }
+    }
+}
+
+impl WriterConfig {
+    /// Configuration for minimal, canonical output
+    ///
+    /// Uses [`FormatterOptions::minified`] and writes newlines only between
+    /// commands. Parsing the output yields the same commands that were written,
+    /// which makes it suitable for hashing or diffing documents. Floats that are
+    /// not finite have no KoiLang representation and do not round-trip.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::{Command, Parameter, Writer, WriterConfig};
+    ///
+    /// let mut output = Vec::new();
+    /// let mut writer = Writer::new(&mut output, WriterConfig::minimal());
+    /// writer.write_command(&Command::new("pos", vec![Parameter::from(("at", 1.0))]))?;
+    /// writer.write_command(&Command::new("say", vec![Parameter::from("hi there")]))?;
+    /// assert_eq!(String::from_utf8(output).unwrap(), "#pos at(1.)\n#say \"hi there\"");
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn minimal() -> Self {
+        Self {
+            global_options: FormatterOptions::minified(),
+            command_options: HashMap::new(),
+            command_threshold: 1,
+            trailing_newline: false,
+            command_separator: CommandSeparator::Newline,
+            emit_prefix: true,
+            list_delimiters: ('(', ')'),
+            dict_delimiters: ('(', ')'),
+            max_width: None,
+            prefix_char: '#',
         }
     }
 }
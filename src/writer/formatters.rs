@@ -3,8 +3,11 @@
 //! This module contains utilities for formatting different types of values
 //! and parameters in KoiLang text generation.
 
-use super::config::{FloatFormat, FormatterOptions};
-use crate::command::{CompositeValue, Parameter, Value};
+use std::cmp::Ordering;
+use std::io::{self, Write};
+
+use super::config::{FloatFormat, FormatterOptions, NumberFormat};
+use crate::command::{CompositeValue, Parameter, Value, string_needs_quotes};
 
 /// Formatting utilities for KoiLang values
 pub struct Formatters;
@@ -69,6 +72,39 @@ impl Formatters {
                 format!("{:.*}", p, f)
             }
             FloatFormat::Custom(fmt) => Self::apply_custom_float_format(f, fmt),
+            FloatFormat::Shortest => Self::format_float_shortest(f),
+        }
+    }
+
+    /// Format a float in the shortest form that still parses as a float.
+    ///
+    /// Picks the shorter of plain and scientific notation, making sure a plain
+    /// integral value keeps its decimal point (`1.`) and dropping a leading zero
+    /// (`.5`). Non-finite values are written with their default representation.
+    fn format_float_shortest(f: &f64) -> String {
+        if !f.is_finite() {
+            return f.to_string();
+        }
+
+        let mut plain = f.to_string();
+        if !plain.contains('.') {
+            plain.push('.');
+        }
+        if let Some(rest) = plain.strip_prefix("0.") {
+            plain = format!(".{}", rest);
+        } else if let Some(rest) = plain.strip_prefix("-0.") {
+            plain = format!("-.{}", rest);
+        }
+        // A lone "." or "-." is not a valid float; zero is written as "0."
+        if plain == "." || plain == "-." {
+            plain = plain.replace('.', "0.");
+        }
+
+        let scientific = format!("{:e}", f);
+        if scientific.len() < plain.len() {
+            scientific
+        } else {
+            plain
         }
     }
 
@@ -135,121 +171,226 @@ impl Formatters {
         }
     }
 
-    /// Check if a string matches variable naming rules.
+    /// Escape a string for use between double quotes.
     ///
-    /// Variable names must start with a letter or underscore, followed by letters, numbers, or underscores.
+    /// Quotes, backslashes, newlines, carriage returns and tabs are escaped;
+    /// everything else is kept as is.
     ///
     /// # Arguments
     ///
-    /// * `s` - The string to check
-    pub fn is_valid_variable_name(s: &str) -> bool {
-        if s.is_empty() {
-            return false;
-        }
-
-        let mut chars = s.chars();
-        let first_char = chars.next().unwrap();
-
-        // First character must be a letter or underscore
-        if !first_char.is_ascii_alphabetic() && first_char != '_' {
-            return false;
-        }
-
-        // Remaining characters must be letters, numbers, or underscores
-        for c in chars {
-            if !c.is_ascii_alphanumeric() && c != '_' {
-                return false;
+    /// * `s` - The string to escape
+    pub fn escape_string(s: &str) -> String {
+        let mut result = String::with_capacity(s.len());
+        for c in s.chars() {
+            match c {
+                '"' => result.push_str("\\\""),
+                '\\' => result.push_str("\\\\"),
+                '\n' => result.push_str("\\n"),
+                '\r' => result.push_str("\\r"),
+                '\t' => result.push_str("\\t"),
+                c => result.push(c),
             }
         }
-
-        true
+        result
     }
 
     /// Format a string value with appropriate quoting.
     ///
-    /// Adds double quotes if the string is not a valid variable name or if forced by options.
+    /// Adds double quotes if the string would not re-parse as the same string when
+    /// written bare, or if forced by options. Bare identifiers and quoted strings both
+    /// parse as strings, so quoting never changes the type: number-like text such as
+    /// `0x10` and the keywords `true` and `false` are quoted rather than rejected.
     ///
     /// # Arguments
     ///
     /// * `s` - The string to format
     /// * `options` - Formatting options
     pub fn format_string(s: &str, options: &FormatterOptions) -> String {
-        // Check if the string needs quotes
-        let needs_quotes = options.force_quotes_for_vars || !Self::is_valid_variable_name(s);
-
-        if needs_quotes {
-            let mut result = String::with_capacity(s.len() + 2);
-            result.push('"');
-            for c in s.chars() {
-                match c {
-                    '"' => result.push_str("\\\""),
-                    '\\' => result.push_str("\\\\"),
-                    '\n' => result.push_str("\\n"),
-                    '\r' => result.push_str("\\r"),
-                    '\t' => result.push_str("\\t"),
-                    c => result.push(c),
-                }
-            }
-            result.push('"');
-            result
+        if options.force_quotes_for_vars || string_needs_quotes(s) {
+            format!("\"{}\"", Self::escape_string(s))
         } else {
             s.to_string()
         }
     }
 
-    /// Format a composite value (List or Dictionary).
+    /// Format a string as a percent-encoded `u"..."` string if it needs quoting.
+    ///
+    /// Only `%`, `"`, `\` and control characters are encoded, so URLs and query
+    /// strings stay readable. Strings that can be written bare are left as is.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The string to format
+    /// * `options` - Formatting options
+    pub fn format_percent_string(s: &str, options: &FormatterOptions) -> String {
+        let formatted = Self::format_string(s, options);
+        if !formatted.starts_with('"') {
+            return formatted;
+        }
+
+        let mut result = String::with_capacity(s.len() + 3);
+        result.push_str("u\"");
+        for c in s.chars() {
+            match c {
+                '%' | '"' | '\\' => result.push_str(&format!("%{:02X}", c as u32)),
+                c if c.is_ascii_control() => result.push_str(&format!("%{:02X}", c as u32)),
+                c => result.push(c),
+            }
+        }
+        result.push('"');
+        result
+    }
+
+    /// Format a composite value (List, Dictionary or Set).
     ///
-    /// Recursively formats the values inside the composite structure.
+    /// Sets are written with braces, deduplicated and in canonical order.
+    /// A one-element list gets a trailing comma, `(v,)`, since `(v)` is read
+    /// back as a [`CompositeValue::Single`].
+    ///
+    /// Recursively formats the values inside the composite structure. With
+    /// `quote_dict_string_values`, every string inside is quoted. With
+    /// `canonicalize_composites`, dictionary entries are sorted by key.
     ///
     /// # Arguments
     ///
     /// * `value` - The composite value
     /// * `options` - Formatting options
     pub fn format_composite_value(value: &CompositeValue, options: &FormatterOptions) -> String {
+        Self::format_composite_value_with_delimiters(value, options, ('(', ')'), ('(', ')'))
+    }
+
+    /// Format a composite value with custom delimiters.
+    ///
+    /// Like [`Formatters::format_composite_value`], but lists and single values
+    /// are enclosed in `list_delimiters` and dictionaries in `dict_delimiters`,
+    /// e.g. `[255, 255, 255]` and `{x: 0, y: 0}`. Sets keep their braces.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The composite value
+    /// * `options` - Formatting options
+    /// * `list_delimiters` - Opening and closing characters of lists
+    /// * `dict_delimiters` - Opening and closing characters of dictionaries
+    pub fn format_composite_value_with_delimiters(
+        value: &CompositeValue,
+        options: &FormatterOptions,
+        list_delimiters: (char, char),
+        dict_delimiters: (char, char),
+    ) -> String {
+        let result = Self::format_composite_value_with(
+            value,
+            options,
+            list_delimiters,
+            dict_delimiters,
+            |val, options| Ok::<_, std::convert::Infallible>(Self::format_value(val, options)),
+        );
+        result.unwrap_or_else(|never| match never {})
+    }
+
+    /// Format a composite value, formatting each contained value with `format_value`.
+    ///
+    /// Like [`Formatters::format_composite_value_with_delimiters`], which this
+    /// implements, but the caller decides how values are written. The options
+    /// passed to `format_value` are adjusted for dictionaries as configured.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The composite value
+    /// * `options` - Formatting options
+    /// * `list_delimiters` - Opening and closing characters of lists
+    /// * `dict_delimiters` - Opening and closing characters of dictionaries
+    /// * `format_value` - Formats a single value
+    pub fn format_composite_value_with<E>(
+        value: &CompositeValue,
+        options: &FormatterOptions,
+        (list_open, list_close): (char, char),
+        (dict_open, dict_close): (char, char),
+        mut format_value: impl FnMut(&Value, &FormatterOptions) -> Result<String, E>,
+    ) -> Result<String, E> {
+        let quoted_options;
+        let options = if options.quote_dict_string_values && !options.force_quotes_for_vars {
+            quoted_options = FormatterOptions {
+                force_quotes_for_vars: true,
+                ..options.clone()
+            };
+            &quoted_options
+        } else {
+            options
+        };
         match value {
             CompositeValue::Single(val) => {
-                format!("({})", Self::format_value(val, options))
+                Ok(format!("{}{}{}", list_open, format_value(val, options)?, list_close))
             }
             CompositeValue::List(values) => {
-                let mut result = "(".to_string();
+                let mut result = list_open.to_string();
                 let mut first = true;
 
                 for val in values {
                     if !first {
                         result.push(',');
-                        if !options.compact {
+                        if options.space_after_comma() {
+                            result.push(' ');
+                        }
+                    }
+                    result.push_str(&format_value(val, options)?);
+                    first = false;
+                }
+
+                if values.len() == 1 {
+                    result.push(',');
+                }
+                result.push(list_close);
+                Ok(result)
+            }
+            CompositeValue::Set(values) => {
+                let mut sorted: Vec<&Value> = values.iter().collect();
+                sorted.sort_by(|a, b| a.canonical_cmp(b));
+                sorted.dedup_by(|a, b| a.canonical_cmp(b) == Ordering::Equal);
+
+                let mut result = "{".to_string();
+                let mut first = true;
+
+                for val in sorted {
+                    if !first {
+                        result.push(',');
+                        if options.space_after_comma() {
                             result.push(' ');
                         }
                     }
-                    result.push_str(&Self::format_value(val, options));
+                    result.push_str(&format_value(val, options)?);
                     first = false;
                 }
 
-                result.push(')');
-                result
+                result.push('}');
+                Ok(result)
             }
             CompositeValue::Dict(entries) => {
-                let mut result = "(".to_string();
+                let mut sorted: Vec<&(String, Value)> = entries.iter().collect();
+                if options.canonicalize_composites {
+                    sorted.sort_by(|(a, _), (b, _)| a.cmp(b));
+                }
+
+                let mut result = dict_open.to_string();
                 let mut first = true;
 
-                for (key, val) in entries {
+                for (key, val) in sorted {
                     if !first {
                         result.push(',');
-                        if !options.compact {
+                        if options.space_after_comma() {
                             result.push(' ');
                         }
                     }
                     result.push_str(key);
                     result.push(':');
-                    if !options.compact {
+                    if options.space_after_colon() {
                         result.push(' ');
                     }
-                    result.push_str(&Self::format_value(val, options));
+                    result.push_str(&format_value(val, options)?);
                     first = false;
                 }
 
-                result.push(')');
-                result
+                result.push(dict_close);
+                Ok(result)
             }
         }
     }
@@ -266,6 +407,10 @@ impl Formatters {
             Value::Float(f) => Self::format_float(f, options),
             Value::Bool(b) => b.to_string(),
             Value::String(s) => Self::format_string(s, options),
+            #[cfg(feature = "chrono")]
+            Value::DateTime(dt) => crate::command::format_datetime(dt),
+            #[cfg(feature = "chrono")]
+            Value::Duration(d) => crate::command::format_duration(d),
         }
     }
 
@@ -279,7 +424,19 @@ impl Formatters {
         // Space before is now handled by generators.rs to avoid double spaces
 
         let param_text = match param {
-            Parameter::Basic(value) => Self::format_value(value, options),
+            Parameter::Basic(value) => {
+                let text = match value {
+                    Value::String(s) if options.percent_encode_strings => {
+                        Self::format_percent_string(s, options)
+                    }
+                    _ => Self::format_value(value, options),
+                };
+                if options.type_annotations {
+                    format!("{}:{}", text, value.type_name())
+                } else {
+                    text
+                }
+            }
             Parameter::Composite(name, composite_value) => {
                 format!(
                     "{}{}",
@@ -291,6 +448,49 @@ impl Formatters {
 
         param_text.to_string()
     }
+
+    /// Write a parameter directly to `writer`.
+    ///
+    /// Produces the same text as [`Formatters::format_parameter`]. Decimal
+    /// integers, booleans, default-format floats and bare strings are written
+    /// without building an intermediate `String`; other parameters fall back
+    /// to `format_parameter`.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The output writer
+    /// * `param` - The parameter to write
+    /// * `options` - Formatting options
+    pub fn write_parameter<W: Write>(
+        writer: &mut W,
+        param: &Parameter,
+        options: &FormatterOptions,
+    ) -> io::Result<()> {
+        let Parameter::Basic(value) = param else {
+            return write!(writer, "{}", Self::format_parameter(param, options));
+        };
+        if options.type_annotations {
+            return write!(writer, "{}", Self::format_parameter(param, options));
+        }
+        match value {
+            Value::Int(i)
+                if matches!(
+                    options.number_format,
+                    NumberFormat::Unknown | NumberFormat::Decimal
+                ) =>
+            {
+                write!(writer, "{}", i)
+            }
+            Value::Float(f) if options.float_format == FloatFormat::Default => {
+                write!(writer, "{}", f)
+            }
+            Value::Bool(b) => write!(writer, "{}", b),
+            Value::String(s) if !options.force_quotes_for_vars && !string_needs_quotes(s) => {
+                writer.write_all(s.as_bytes())
+            }
+            _ => write!(writer, "{}", Self::format_parameter(param, options)),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -298,6 +498,54 @@ mod tests {
     use super::*;
     use crate::{command::{CompositeValue, Parameter, Value}, writer::NumberFormat};
 
+    #[test]
+    fn test_write_parameter_matches_format_parameter() {
+        let params = [
+            Parameter::from(42),
+            Parameter::from(-7),
+            Parameter::from(i64::MIN),
+            Parameter::from(1.5),
+            Parameter::from(1e300),
+            Parameter::from(true),
+            Parameter::from("Line"),
+            Parameter::from("true"),
+            Parameter::from("hello world"),
+            Parameter::from("a\"b"),
+            Parameter::from(("pos", vec![1, 2])),
+        ];
+        let option_sets = [
+            FormatterOptions::default(),
+            FormatterOptions::minified(),
+            FormatterOptions {
+                number_format: NumberFormat::Hex,
+                float_format: FloatFormat::Fixed(Some(2)),
+                ..Default::default()
+            },
+            FormatterOptions {
+                force_quotes_for_vars: true,
+                percent_encode_strings: true,
+                ..Default::default()
+            },
+            FormatterOptions {
+                type_annotations: true,
+                ..Default::default()
+            },
+        ];
+        for options in &option_sets {
+            for param in &params {
+                let mut written = Vec::new();
+                Formatters::write_parameter(&mut written, param, options).unwrap();
+                assert_eq!(
+                    String::from_utf8(written).unwrap(),
+                    Formatters::format_parameter(param, options),
+                    "{:?} with {:?}",
+                    param,
+                    options
+                );
+            }
+        }
+    }
+
     #[test]
     fn test_format_number() {
         let options = FormatterOptions::default();
@@ -345,22 +593,24 @@ mod tests {
     }
 
     #[test]
-    fn test_is_valid_variable_name() {
+    fn test_string_needs_quotes() {
         // Valid names
-        assert!(Formatters::is_valid_variable_name("valid_name"));
-        assert!(Formatters::is_valid_variable_name("_valid"));
-        assert!(Formatters::is_valid_variable_name("valid123"));
-        assert!(Formatters::is_valid_variable_name("a"));
-        assert!(Formatters::is_valid_variable_name("A"));
-
-        // Invalid names
-        assert!(!Formatters::is_valid_variable_name("123invalid"));
-        assert!(!Formatters::is_valid_variable_name("invalid-name"));
-        assert!(!Formatters::is_valid_variable_name("invalid name"));
-        assert!(!Formatters::is_valid_variable_name("invalid.name"));
-        assert!(!Formatters::is_valid_variable_name(""));
-        assert!(!Formatters::is_valid_variable_name("!invalid"));
-        assert!(!Formatters::is_valid_variable_name("invalid!"));
+        assert!(!string_needs_quotes("valid_name"));
+        assert!(!string_needs_quotes("_valid"));
+        assert!(!string_needs_quotes("valid123"));
+        assert!(!string_needs_quotes("a"));
+        assert!(!string_needs_quotes("A"));
+
+        // Invalid names and keywords
+        assert!(string_needs_quotes("123invalid"));
+        assert!(string_needs_quotes("invalid-name"));
+        assert!(string_needs_quotes("invalid name"));
+        assert!(string_needs_quotes("invalid.name"));
+        assert!(string_needs_quotes(""));
+        assert!(string_needs_quotes("!invalid"));
+        assert!(string_needs_quotes("invalid!"));
+        assert!(string_needs_quotes("true"));
+        assert!(string_needs_quotes("false"));
     }
 
     #[test]
@@ -385,6 +635,11 @@ mod tests {
         };
         let result = Formatters::format_string("valid_name", &options);
         assert_eq!(result, "\"valid_name\"");
+
+        // Test boolean keywords are quoted to stay strings
+        let options = FormatterOptions::default();
+        assert_eq!(Formatters::format_string("true", &options), "\"true\"");
+        assert_eq!(Formatters::format_string("false", &options), "\"false\"");
     }
 
     #[test]
@@ -413,6 +668,11 @@ mod tests {
         let result = Formatters::format_composite_value(&list_value, &options_compact);
         assert_eq!(result, "(1,two,3)");
 
+        // A one-element list is distinguished from a single value
+        let single_list = CompositeValue::List(vec![Value::Int(1)]);
+        let result = Formatters::format_composite_value(&single_list, &options);
+        assert_eq!(result, "(1,)");
+
         // Test Dict composite value
         let dict_entries = vec![
             ("key1".to_string(), Value::Int(1)),
@@ -425,6 +685,18 @@ mod tests {
         // Test Dict composite value in compact mode
         let result = Formatters::format_composite_value(&dict_value, &options_compact);
         assert_eq!(result, "(key1:1,key2:value2)");
+
+        // Test Set composite value is sorted and deduplicated
+        let set_value = CompositeValue::Set(vec![
+            Value::String("b".to_string()),
+            Value::Int(3),
+            Value::Int(1),
+            Value::Int(3),
+        ]);
+        let result = Formatters::format_composite_value(&set_value, &options);
+        assert_eq!(result, "{1, 3, b}");
+        let result = Formatters::format_composite_value(&set_value, &options_compact);
+        assert_eq!(result, "{1,3,b}");
     }
 
     #[test]
@@ -491,6 +763,55 @@ mod tests {
         assert_eq!(result, "dict_param(key: value)");
     }
 
+    #[test]
+    fn test_format_composite_spacing() {
+        let list = CompositeValue::List(vec![Value::Int(255), Value::Int(255), Value::Int(255)]);
+        let dict = CompositeValue::Dict(vec![
+            ("x".to_string(), Value::Int(0)),
+            ("y".to_string(), Value::Int(0)),
+        ]);
+
+        let spaced = FormatterOptions::default();
+        assert_eq!(Formatters::format_composite_value(&list, &spaced), "(255, 255, 255)");
+        assert_eq!(Formatters::format_composite_value(&dict, &spaced), "(x: 0, y: 0)");
+
+        let unspaced = FormatterOptions {
+            composite_space_after_comma: Some(false),
+            composite_space_after_colon: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(Formatters::format_composite_value(&list, &unspaced), "(255,255,255)");
+        assert_eq!(Formatters::format_composite_value(&dict, &unspaced), "(x:0,y:0)");
+
+        // Explicit settings take precedence over compact
+        let compact_spaced = FormatterOptions {
+            compact: true,
+            composite_space_after_colon: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(Formatters::format_composite_value(&dict, &compact_spaced), "(x: 0,y: 0)");
+    }
+
+    #[test]
+    fn test_format_percent_string() {
+        let options = FormatterOptions {
+            percent_encode_strings: true,
+            ..Default::default()
+        };
+
+        let param = Parameter::from("https://example.com/?q=a b&x=100%");
+        let result = Formatters::format_parameter(&param, &options);
+        assert_eq!(result, r#"u"https://example.com/?q=a b&x=100%25""#);
+
+        let param = Parameter::from("say \"hi\"\\\n");
+        let result = Formatters::format_parameter(&param, &options);
+        assert_eq!(result, r#"u"say %22hi%22%5C%0A""#);
+
+        // Strings that don't need quotes are left bare
+        let result = Formatters::format_parameter(&Parameter::from("name"), &options);
+        assert_eq!(result, "name");
+    }
+
     #[test]
     fn test_format_value_with_number_formats() {
         // Test different number formats for Int values
@@ -555,6 +876,18 @@ mod tests {
         let result = Formatters::format_float(&0.001, &options);
         assert_eq!(result, "1e-3");
 
+        // Test Shortest format keeps floats distinguishable from integers
+        let options = FormatterOptions {
+            float_format: FloatFormat::Shortest,
+            ..Default::default()
+        };
+        assert_eq!(Formatters::format_float(&1.0, &options), "1.");
+        assert_eq!(Formatters::format_float(&0.0, &options), "0.");
+        assert_eq!(Formatters::format_float(&0.5, &options), ".5");
+        assert_eq!(Formatters::format_float(&-0.25, &options), "-.25");
+        assert_eq!(Formatters::format_float(&1e20, &options), "1e20");
+        assert_eq!(Formatters::format_float(&0.001, &options), ".001");
+
         // Test Custom format with precision
         let options = FormatterOptions {
             float_format: FloatFormat::Custom(".3f".to_string()),
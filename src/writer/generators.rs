@@ -11,10 +11,24 @@ use crate::writer::NumberFormat;
 use std::collections::HashMap;
 use std::io::Write;
 
+/// Caller-supplied rendering of a single value, see `Writer::write_command_with`
+pub type RenderValue<'a> = dyn Fn(&Value, &mut dyn Write) -> std::io::Result<()> + 'a;
+
 /// Command generation utilities
 pub struct Generators;
 
 impl Generators {
+    /// The `#` prefix written before regular and number commands.
+    ///
+    /// Empty if `emit_prefix` is disabled.
+    fn command_prefix(config: &WriterConfig) -> String {
+        if config.emit_prefix {
+            config.prefix_char.to_string().repeat(config.command_threshold)
+        } else {
+            String::new()
+        }
+    }
+
     /// Write a command with parameter-specific formatting options.
     ///
     /// This function handles the core logic of writing a command to the output, including:
@@ -31,6 +45,8 @@ impl Generators {
     /// * `options` - The effective formatting options for this command
     /// * `param_options` - Optional map of parameter-specific formatting options
     /// * `current_indent` - The current indentation level (number of steps)
+    /// * `render` - Optional rendering of values, replacing their formatting;
+    ///   names, delimiters and separators are still written as configured
     ///
     /// # Returns
     ///
@@ -42,6 +58,7 @@ impl Generators {
         options: &FormatterOptions,
         param_options: Option<&HashMap<ParamFormatSelector, &FormatterOptions>>,
         current_indent: usize,
+        render: Option<&RenderValue<'_>>,
     ) -> std::io::Result<()> {
         match command.name.as_str() {
             "@text" => {
@@ -51,9 +68,14 @@ impl Generators {
                 }
             }
             "@annotation" => {
-                // Annotation command - write with extra # characters
+                // Annotation command - write with extra # characters, keeping the
+                // original depth if it was recorded and still reads as an annotation
                 if let Some(Parameter::Basic(Value::String(text))) = command.params.first() {
-                    let hashes = "#".repeat(config.command_threshold + 1);
+                    let level = command
+                        .annotation_level()
+                        .filter(|&level| level > config.command_threshold)
+                        .unwrap_or(config.command_threshold + 1);
+                    let hashes = config.prefix_char.to_string().repeat(level);
                     if text.trim_start().starts_with(&hashes) {
                         // If text already has enough #, just write it
                         write!(writer, "{}", text)?;
@@ -66,8 +88,9 @@ impl Generators {
             "@number" => {
                 // Number command - write as number with parameters
                 if let Some(Parameter::Basic(Value::Int(value))) = command.params.first() {
-                    let hashes = "#".repeat(config.command_threshold);
-                    write!(writer, "{}{}", hashes, value)?;
+                    let head = format!("{}{}", Self::command_prefix(config), value);
+                    write!(writer, "{}", head)?;
+                    let mut column = Self::indent_width(current_indent, options) + head.chars().count();
 
                     // Add remaining parameters
                     for (i, param) in command.params.iter().skip(1).enumerate() {
@@ -80,6 +103,9 @@ impl Generators {
                             param_options,
                         );
 
+                        // Format the parameter first if its width is needed
+                        let text = Self::format_parameter(param, config, &param_format_opt, render)?;
+
                         // Check if we need to add a newline before this parameter
                         // based on previous parameter's newline_after_param
                         if i > 0 {
@@ -91,7 +117,9 @@ impl Generators {
                                 param_options,
                             );
 
-                            if prev_opt.newline_after_param || param_format_opt.newline_before_param
+                            if !options.force_single_line
+                                && (prev_opt.newline_after_param
+                                    || param_format_opt.newline_before_param)
                             {
                                 writeln!(writer)?;
                                 // For non-compact mode, add one more indent level for parameters after newline
@@ -101,11 +129,19 @@ impl Generators {
                                     current_indent + 1
                                 };
                                 Self::write_indent(writer, indent_level, options)?;
+                                column = Self::indent_width(indent_level, options);
                             } else {
                                 // Always add a space between parameters for number commands
-                                write!(writer, " ")?;
+                                column = Self::write_param_space(
+                                    writer,
+                                    column,
+                                    text.as_deref(),
+                                    config,
+                                    current_indent,
+                                    options,
+                                )?;
                             }
-                        } else if param_format_opt.newline_before_param {
+                        } else if !options.force_single_line && param_format_opt.newline_before_param {
                             // First additional parameter (i=0) can have newline before
                             writeln!(writer)?;
                             // For non-compact mode, add one more indent level for parameters after newline
@@ -115,24 +151,37 @@ impl Generators {
                                 current_indent + 1
                             };
                             Self::write_indent(writer, indent_level, options)?;
+                            column = Self::indent_width(indent_level, options);
                         } else {
                             // Always add a space between number and first parameter
-                            write!(writer, " ")?;
+                            column = Self::write_param_space(
+                                writer,
+                                column,
+                                text.as_deref(),
+                                config,
+                                current_indent,
+                                options,
+                            )?;
                         }
 
                         // Write the parameter
-                        write!(
+                        column = Self::write_formatted_parameter(
                             writer,
-                            "{}",
-                            Formatters::format_parameter(param, &param_format_opt)
+                            column,
+                            text.as_deref(),
+                            param,
+                            config,
+                            &param_format_opt,
+                            render,
                         )?;
                     }
                 }
             }
             _ => {
                 // Regular command - write with # prefix
-                let hashes = "#".repeat(config.command_threshold);
-                write!(writer, "{}{}", hashes, command.name)?;
+                let head = format!("{}{}", Self::command_prefix(config), command.name);
+                write!(writer, "{}", head)?;
+                let mut column = Self::indent_width(current_indent, options) + head.chars().count();
 
                 // Add parameters with their specific formatting options
                 for (i, param) in command.params.iter().enumerate() {
@@ -144,6 +193,9 @@ impl Generators {
                         param_options,
                     );
 
+                    // Format the parameter first if its width is needed
+                    let text = Self::format_parameter(param, config, &param_format_opt, render)?;
+
                     // Check if we need to add a newline before this parameter
                     if i > 0 {
                         let prev_param = &command.params[i - 1];
@@ -154,7 +206,9 @@ impl Generators {
                             param_options,
                         );
 
-                        if prev_opt.newline_after_param || param_format_opt.newline_before_param {
+                        if !options.force_single_line
+                            && (prev_opt.newline_after_param || param_format_opt.newline_before_param)
+                        {
                             writeln!(writer)?;
                             // For non-compact mode, add one more indent level for parameters after newline
                             let indent_level = if options.compact {
@@ -163,12 +217,20 @@ impl Generators {
                                 current_indent + 1
                             };
                             Self::write_indent(writer, indent_level, options)?;
+                            column = Self::indent_width(indent_level, options);
                         } else {
                             // Always add a space between parameters, even in compact mode
                             // This ensures the parser can distinguish between parameters
-                            write!(writer, " ")?;
+                            column = Self::write_param_space(
+                                writer,
+                                column,
+                                text.as_deref(),
+                                config,
+                                current_indent,
+                                options,
+                            )?;
                         }
-                    } else if param_format_opt.newline_before_param {
+                    } else if !options.force_single_line && param_format_opt.newline_before_param {
                         // First parameter can have newline before
                         writeln!(writer)?;
                         // For non-compact mode, add one more indent level for parameters after newline
@@ -177,18 +239,30 @@ impl Generators {
                         } else {
                             current_indent + 1
                         };
-                        Self::write_indent(writer, indent_level, options)?
+                        Self::write_indent(writer, indent_level, options)?;
+                        column = Self::indent_width(indent_level, options);
                     } else {
                         // Always add a space between command name and first parameter
                         // This ensures the parser can distinguish between command and parameters
-                        write!(writer, " ")?;
+                        column = Self::write_param_space(
+                            writer,
+                            column,
+                            text.as_deref(),
+                            config,
+                            current_indent,
+                            options,
+                        )?;
                     }
 
                     // Write the parameter
-                    write!(
+                    column = Self::write_formatted_parameter(
                         writer,
-                        "{}",
-                        Formatters::format_parameter(param, &param_format_opt)
+                        column,
+                        text.as_deref(),
+                        param,
+                        config,
+                        &param_format_opt,
+                        render,
                     )?;
                 }
             }
@@ -197,6 +271,147 @@ impl Generators {
         Ok(())
     }
 
+    /// Format a parameter into a buffer, so its width is known before it is written.
+    ///
+    /// The width is only needed to wrap lines at `config.max_width`; without a
+    /// limit this returns None and the parameter is written directly.
+    fn format_parameter(
+        param: &Parameter,
+        config: &WriterConfig,
+        options: &FormatterOptions,
+        render: Option<&RenderValue<'_>>,
+    ) -> std::io::Result<Option<Vec<u8>>> {
+        if config.max_width.is_none() {
+            return Ok(None);
+        }
+        let mut text = Vec::new();
+        Self::write_parameter(&mut text, param, config, options, render)?;
+        Ok(Some(text))
+    }
+
+    /// Write a parameter, using the buffer from [`Self::format_parameter`] if
+    /// there is one, and return the column after it.
+    fn write_formatted_parameter<T: Write>(
+        writer: &mut T,
+        column: usize,
+        text: Option<&[u8]>,
+        param: &Parameter,
+        config: &WriterConfig,
+        options: &FormatterOptions,
+        render: Option<&RenderValue<'_>>,
+    ) -> std::io::Result<usize> {
+        match text {
+            Some(text) => {
+                writer.write_all(text)?;
+                Ok(Self::advance_column(column, text))
+            }
+            None => {
+                Self::write_parameter(writer, param, config, options, render)?;
+                Ok(column)
+            }
+        }
+    }
+
+    /// Write the space before a parameter, or a line continuation if the
+    /// parameter would extend the line past `config.max_width`.
+    ///
+    /// Returns the column after the separator. Parameters are only moved to a
+    /// continuation line if that makes the line shorter, so a single parameter
+    /// wider than the limit stays where it is. `text` is the formatted
+    /// parameter, or None if it was not buffered because there is no limit.
+    fn write_param_space<T: Write>(
+        writer: &mut T,
+        column: usize,
+        text: Option<&[u8]>,
+        config: &WriterConfig,
+        current_indent: usize,
+        options: &FormatterOptions,
+    ) -> std::io::Result<usize> {
+        let indent_level = if options.compact {
+            current_indent
+        } else {
+            current_indent + 1
+        };
+        let continuation_column = Self::indent_width(indent_level, options);
+        match (config.max_width, text) {
+            (Some(max_width), Some(text))
+                if !options.force_single_line
+                    && column + 1 + Self::first_line_width(text) > max_width
+                    && continuation_column < column =>
+            {
+                writeln!(writer, " \\")?;
+                Self::write_indent(writer, indent_level, options)?;
+                Ok(continuation_column)
+            }
+            _ => {
+                write!(writer, " ")?;
+                Ok(column + 1)
+            }
+        }
+    }
+
+    /// Get the width of the first line of `text`.
+    fn first_line_width(text: &[u8]) -> usize {
+        String::from_utf8_lossy(text)
+            .lines()
+            .next()
+            .map_or(0, |line| line.chars().count())
+    }
+
+    /// Get the column after writing `text` at `column`.
+    fn advance_column(column: usize, text: &[u8]) -> usize {
+        let text = String::from_utf8_lossy(text);
+        match text.rfind('\n') {
+            Some(pos) => text[pos + 1..].chars().count(),
+            None => column + text.chars().count(),
+        }
+    }
+
+    /// Write a parameter, enclosing composites in the delimiters of `config`.
+    fn write_parameter<T: Write>(
+        writer: &mut T,
+        param: &Parameter,
+        config: &WriterConfig,
+        options: &FormatterOptions,
+        render: Option<&RenderValue<'_>>,
+    ) -> std::io::Result<()> {
+        match (param, render) {
+            (Parameter::Composite(name, value), None) => write!(
+                writer,
+                "{}{}",
+                name,
+                Formatters::format_composite_value_with_delimiters(
+                    value,
+                    options,
+                    config.list_delimiters,
+                    config.dict_delimiters,
+                )
+            ),
+            (Parameter::Composite(name, value), Some(render)) => {
+                let text = Formatters::format_composite_value_with(
+                    value,
+                    options,
+                    config.list_delimiters,
+                    config.dict_delimiters,
+                    |val, _| {
+                        let mut buffer = Vec::new();
+                        render(val, &mut buffer)?;
+                        Ok::<_, std::io::Error>(String::from_utf8_lossy(&buffer).into_owned())
+                    },
+                )?;
+                write!(writer, "{}{}", name, text)
+            }
+            (Parameter::Basic(_), None) => Formatters::write_parameter(writer, param, options),
+            (Parameter::Basic(value), Some(render)) => {
+                render(value, writer)?;
+                if options.type_annotations {
+                    write!(writer, ":{}", value.type_name())?;
+                }
+                Ok(())
+            }
+        }
+    }
+
     /// Get the parameter name if it's a composite parameter.
     ///
     /// Returns `Some(name)` if the parameter is a `Composite` types, `None` otherwise.
@@ -297,6 +512,30 @@ impl Generators {
         if override_opt.newline_after_param {
             merged.newline_after_param = override_opt.newline_after_param;
         }
+        if override_opt.type_annotations {
+            merged.type_annotations = override_opt.type_annotations;
+        }
+        if override_opt.percent_encode_strings {
+            merged.percent_encode_strings = override_opt.percent_encode_strings;
+        }
+        if override_opt.composite_space_after_comma.is_some() {
+            merged.composite_space_after_comma = override_opt.composite_space_after_comma;
+        }
+        if override_opt.composite_space_after_colon.is_some() {
+            merged.composite_space_after_colon = override_opt.composite_space_after_colon;
+        }
+        if override_opt.quote_dict_string_values {
+            merged.quote_dict_string_values = override_opt.quote_dict_string_values;
+        }
+        if override_opt.canonicalize_composites {
+            merged.canonicalize_composites = override_opt.canonicalize_composites;
+        }
+        if override_opt.force_single_line {
+            merged.force_single_line = override_opt.force_single_line;
+        }
+        if override_opt.blank_after_composite_commands {
+            merged.blank_after_composite_commands = override_opt.blank_after_composite_commands;
+        }
 
         merged
     }
@@ -330,6 +569,16 @@ impl Generators {
         Ok(())
     }
 
+    /// Get the width of the indentation written by `write_indent`, counting
+    /// tabs as `options.indent` columns.
+    fn indent_width(current_indent: usize, options: &FormatterOptions) -> usize {
+        if options.compact {
+            0
+        } else {
+            current_indent * options.indent.max(1)
+        }
+    }
+
     /// Get the effective formatting options for a command.
     ///
     /// Resolves the final formatting options by merging:
@@ -670,6 +919,7 @@ mod tests {
             &options,
             None,
             0,
+            None,
         )
         .unwrap();
 
@@ -686,6 +936,7 @@ mod tests {
             &options,
             None,
             0,
+            None,
         )
         .unwrap();
 
@@ -702,12 +953,49 @@ mod tests {
             &options,
             None,
             0,
+            None,
         )
         .unwrap();
 
         let result = String::from_utf8(buffer).unwrap();
         assert_eq!(result, "## This is an annotation");
 
+        // Test annotation command with a recorded level
+        let command = Command::new_annotation_with_level("Documentation", 3);
+        let mut buffer = Vec::new();
+        Generators::write_command_with_param_options(
+            &mut buffer,
+            &command,
+            &config,
+            &options,
+            None,
+            0,
+            None,
+        )
+        .unwrap();
+
+        let result = String::from_utf8(buffer).unwrap();
+        assert_eq!(result, "### Documentation");
+
+        // A recorded level at or below the command threshold would read back
+        // as a command, so the threshold wins
+        let threshold_config = WriterConfig {
+            command_threshold: 3,
+            ..config.clone()
+        };
+        let mut buffer = Vec::new();
+        Generators::write_command_with_param_options(
+            &mut buffer,
+            &command,
+            &threshold_config,
+            &options,
+            None,
+            0,
+            None,
+        )
+        .unwrap();
+        assert_eq!(String::from_utf8(buffer).unwrap(), "#### Documentation");
+
         // Test number command
         let command = Command::new_number(123, vec![Parameter::from("extra")]);
         let mut buffer = Vec::new();
@@ -718,6 +1006,7 @@ mod tests {
             &options,
             None,
             0,
+            None,
         )
         .unwrap();
 
@@ -4,24 +4,111 @@
 //! It supports flexible formatting options and can write to any output that implements
 //! the `Write` trait.
 
-use crate::command::Command;
+use crate::command::{Command, CompositeValue, Parameter, Value};
+use crate::parser::ParamHint;
 use std::collections::HashMap;
 use std::io::Write;
 
 // Re-export configuration types
-pub use self::config::{FloatFormat, FormatterOptions, NumberFormat, ParamFormatSelector, WriterConfig};
+pub use self::config::{
+    CommandSeparator, FloatFormat, FormatterOptions, NumberFormat, ParamFormatSelector,
+    WriterConfig,
+};
 
 // Internal modules
 mod config;
 mod formatters;
 mod generators;
 
+/// Format a single value the way the writer emits it in a command
+///
+/// # Arguments
+/// * `value` - The value to format
+/// * `options` - Formatting options
+///
+/// # Examples
+///
+/// ```rust
+/// use koicore::command::Value;
+/// use koicore::writer::{format_value, FormatterOptions, NumberFormat};
+///
+/// let options = FormatterOptions {
+///     number_format: NumberFormat::Hex,
+///     ..Default::default()
+/// };
+/// assert_eq!(format_value(&Value::Int(255), &options), "0xff");
+/// assert_eq!(format_value(&Value::from("a b"), &options), "\"a b\"");
+/// ```
+pub fn format_value(value: &Value, options: &FormatterOptions) -> String {
+    formatters::Formatters::format_value(value, options)
+}
+
+/// Format a composite value, including its brackets, without the parameter name
+///
+/// # Arguments
+/// * `value` - The composite value to format
+/// * `options` - Formatting options
+///
+/// # Examples
+///
+/// ```rust
+/// use koicore::command::CompositeValue;
+/// use koicore::writer::{format_composite, FormatterOptions};
+///
+/// let list = CompositeValue::from(vec![1, 2]);
+/// assert_eq!(format_composite(&list, &FormatterOptions::default()), "(1, 2)");
+/// ```
+pub fn format_composite(value: &CompositeValue, options: &FormatterOptions) -> String {
+    formatters::Formatters::format_composite_value(value, options)
+}
+
+/// Escape a string for use between double quotes
+///
+/// Uses the same escapes as the writer, without adding the quotes. The result
+/// reads back with [`unescape_string`](crate::parser::unescape_string).
+///
+/// # Arguments
+/// * `s` - The string to escape
+///
+/// # Examples
+///
+/// ```rust
+/// use koicore::escape_string;
+///
+/// assert_eq!(escape_string("say \"hi\"\n"), r#"say \"hi\"\n"#);
+/// ```
+pub fn escape_string(s: &str) -> String {
+    formatters::Formatters::escape_string(s)
+}
+
+/// Output adapter counting the bytes written through it
+struct CountingWrite<T> {
+    inner: T,
+    count: usize,
+}
+
+impl<T: Write> Write for CountingWrite<T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
 /// KoiLang writer that can write to any output implementing the `Write` trait
 pub struct Writer<T: Write> {
-    writer: T,
+    writer: CountingWrite<T>,
     config: WriterConfig,
     current_indent: usize,
     last_was_newline: bool,
+    pending_separator: bool,
+    /// Whether the current line holds commands ended by a separator other
+    /// than a newline, so more commands can follow on it
+    mid_line: bool,
 }
 
 impl<T: Write> Writer<T> {
@@ -32,10 +119,15 @@ impl<T: Write> Writer<T> {
     /// * `config` - Configuration for the writer
     pub fn new(writer: T, config: WriterConfig) -> Self {
         Self {
-            writer,
+            writer: CountingWrite {
+                inner: writer,
+                count: 0,
+            },
             config,
             current_indent: 0,
             last_was_newline: false,
+            pending_separator: false,
+            mid_line: false,
         }
     }
 
@@ -44,28 +136,123 @@ impl<T: Write> Writer<T> {
         self.write_command_with_options(command, None, None)
     }
 
+    /// Write a command and return the number of bytes written
+    ///
+    /// The count covers everything written by this call, including a separator
+    /// left pending by the previous command (see [`Writer::finish_line`]), so
+    /// the counts of consecutive calls add up to the output length.
+    ///
+    /// # Arguments
+    /// * `command` - The command to write
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::{Command, Writer, WriterConfig};
+    ///
+    /// let mut buffer = Vec::new();
+    /// let mut writer = Writer::new(&mut buffer, WriterConfig::default());
+    /// let count = writer.write_command_counted(&"#draw Line 2".parse::<Command>()?)?;
+    /// assert_eq!(count, "#draw Line 2\n".len());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn write_command_counted(&mut self, command: &Command) -> std::io::Result<usize> {
+        let start = self.writer.count;
+        self.write_command(command)?;
+        Ok(self.writer.count - start)
+    }
+
+    /// Get the total number of bytes written so far
+    pub fn bytes_written(&self) -> usize {
+        self.writer.count
+    }
+
+    /// Write a command, rendering each value with a custom function
+    ///
+    /// `render` is called for every value of the basic and composite
+    /// parameters and writes its text to the given output. The writer still
+    /// handles the command name, indentation, spacing, composite delimiters
+    /// and separators. Text, annotation and number commands are written as
+    /// usual.
+    ///
+    /// # Arguments
+    /// * `command` - The command to write
+    /// * `render` - Writes the text of a single value
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::io::Write;
+    /// use koicore::{Command, Value, Writer, WriterConfig};
+    ///
+    /// let mut buffer = Vec::new();
+    /// let mut writer = Writer::new(&mut buffer, WriterConfig::default());
+    /// writer.write_command_with(&"#light red".parse::<Command>()?, |value, out| match value {
+    ///     Value::String(s) => write!(out, "Color::{}", s.to_uppercase()),
+    ///     other => write!(out, "{}", other),
+    /// })?;
+    /// drop(writer);
+    /// assert_eq!(String::from_utf8(buffer)?, "#light Color::RED\n");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn write_command_with<F: Fn(&Value, &mut dyn Write) -> std::io::Result<()>>(
+        &mut self,
+        command: &Command,
+        render: F,
+    ) -> std::io::Result<()> {
+        self.write_command_rendered(command, None, None, Some(&render), None)
+    }
+
     /// Write a command with custom formatting options, including parameter-specific options
     pub fn write_command_with_options(
         &mut self,
         command: &Command,
         options: Option<&FormatterOptions>,
         param_options: Option<&HashMap<ParamFormatSelector, &FormatterOptions>>,
+    ) -> std::io::Result<()> {
+        self.write_command_rendered(command, options, param_options, None, None)
+    }
+
+    /// Write a command with formatting options and an optional value renderer
+    ///
+    /// `indent` replaces the indentation of the current level if given.
+    fn write_command_rendered(
+        &mut self,
+        command: &Command,
+        options: Option<&FormatterOptions>,
+        param_options: Option<&HashMap<ParamFormatSelector, &FormatterOptions>>,
+        render: Option<&generators::RenderValue<'_>>,
+        indent: Option<&str>,
     ) -> std::io::Result<()> {
         // Get the appropriate formatting options
         let effective_options =
             generators::Generators::get_effective_options(&command.name, options, &self.config);
 
+        // The parser only splits command lines on the separator, so text and
+        // annotations start a line of their own and end it with a newline
+        let whole_line = matches!(command.name.as_str(), "@text" | "@annotation");
+        if whole_line && self.mid_line {
+            self.pending_separator = false;
+            self.newline()?;
+        }
+
+        // Without a trailing separator, the separator goes between commands instead
+        self.finish_line()?;
+
         // Write additional newline before if needed and not already at start of line
         if effective_options.newline_before && !self.last_was_newline {
             self.newline()?;
         }
 
         // Write indentation
-        generators::Generators::write_indent(
-            &mut self.writer,
-            self.current_indent,
-            &effective_options,
-        )?;
+        match indent {
+            Some(indent) => write!(self.writer, "{}", indent)?,
+            None => generators::Generators::write_indent(
+                &mut self.writer,
+                self.current_indent,
+                &effective_options,
+            )?,
+        }
 
         // Write the command with parameter-specific formatting
         generators::Generators::write_command_with_param_options(
@@ -75,13 +262,21 @@ impl<T: Write> Writer<T> {
             &effective_options,
             param_options,
             self.current_indent,
+            render,
         )?;
 
-        // Add a newline after the command
-        writeln!(self.writer)?;
+        // Terminate the command with the separator
+        self.mid_line = !whole_line && self.separates_in_line();
+        if self.config.trailing_newline {
+            self.write_separator()?;
+        } else {
+            self.pending_separator = true;
+        }
 
         // Write additional newline after if needed and not already at end of line
-        if effective_options.newline_after {
+        let blank_after = effective_options.blank_after_composite_commands
+            && command.params.iter().any(Parameter::is_composite);
+        if effective_options.newline_after || blank_after {
             self.newline()?;
         } else {
             // Update last_was_newline based on the command content
@@ -92,6 +287,101 @@ impl<T: Write> Writer<T> {
         Ok(())
     }
 
+    /// Write a command, reproducing how its parameters were originally written
+    ///
+    /// `hints` are the per-parameter hints returned by
+    /// `Parser::next_command_with_hints`. A hinted integer base is only used when
+    /// the writer configuration writes the command's integers in decimal (the
+    /// default), and quoted strings stay quoted. Missing hints leave parameters
+    /// unchanged. The indentation recorded in [`Command::indent`] is written
+    /// instead of the current indentation.
+    ///
+    /// # Arguments
+    /// * `command` - The command to write
+    /// * `hints` - Formatting hints by parameter position
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::parser::{Parser, ParserConfig, StringInputSource};
+    /// use koicore::writer::{Writer, WriterConfig};
+    ///
+    /// let input = StringInputSource::new("#color 0xff \"red\"");
+    /// let mut parser = Parser::new(input, ParserConfig::default());
+    /// let (command, hints) = parser.next_command_with_hints()?.unwrap();
+    ///
+    /// let mut buffer = Vec::new();
+    /// let mut writer = Writer::new(&mut buffer, WriterConfig::default());
+    /// writer.write_command_with_hints(&command, &hints)?;
+    /// drop(writer);
+    /// assert_eq!(String::from_utf8(buffer)?, "#color 0xff \"red\"\n");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn write_command_with_hints(
+        &mut self,
+        command: &Command,
+        hints: &[ParamHint],
+    ) -> std::io::Result<()> {
+        let effective_options =
+            generators::Generators::get_effective_options(&command.name, None, &self.config);
+        let hinted: Vec<(usize, FormatterOptions)> = hints
+            .iter()
+            .enumerate()
+            .map(|(position, hint)| {
+                let mut options = hint.to_options();
+                if !matches!(
+                    effective_options.number_format,
+                    NumberFormat::Unknown | NumberFormat::Decimal
+                ) {
+                    options.number_format = NumberFormat::Unknown;
+                }
+                (position, options)
+            })
+            .filter(|(_, options)| *options != FormatterOptions::default())
+            .collect();
+        let param_options = hinted
+            .iter()
+            .map(|(position, options)| (ParamFormatSelector::Position(*position), options))
+            .collect();
+        self.write_command_rendered(
+            command,
+            None,
+            Some(&param_options),
+            None,
+            command.indent.as_deref(),
+        )
+    }
+
+    /// Terminate the last command with the command separator if it isn't already
+    ///
+    /// With `trailing_newline` disabled, the separator after the last command is
+    /// left out; call this to write it anyway, e.g. to reproduce a source that
+    /// ended with a newline (see `Parser::ended_with_newline`). Does nothing if
+    /// no command is waiting for a separator.
+    pub fn finish_line(&mut self) -> std::io::Result<()> {
+        if self.pending_separator {
+            self.write_separator()?;
+            self.pending_separator = false;
+        }
+        Ok(())
+    }
+
+    /// Whether the command separator keeps commands on one line
+    fn separates_in_line(&self) -> bool {
+        self.config.command_separator.as_str() != "\n"
+    }
+
+    /// End the last command with the command separator, or with a newline
+    /// after text and annotations
+    fn write_separator(&mut self) -> std::io::Result<()> {
+        let separator = if self.mid_line {
+            self.config.command_separator.as_str()
+        } else {
+            "\n"
+        };
+        write!(self.writer, "{}", separator)
+    }
+
     /// Increase the indentation level by 1
     pub fn inc_indent(&mut self) {
         self.current_indent += 1;
@@ -109,11 +399,160 @@ impl<T: Write> Writer<T> {
         self.current_indent
     }
 
+    /// Write a block of annotation lines followed by a blank line
+    ///
+    /// Used for banners such as "generated file, do not edit". Each line is
+    /// written as an annotation at indentation 0, whatever the current
+    /// indentation level, with one `#` more than the command threshold.
+    /// Empty lines are written as the bare `#` prefix.
+    ///
+    /// # Arguments
+    /// * `lines` - The banner lines, without `#` prefix or line terminator
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use koicore::writer::{Writer, WriterConfig};
+    ///
+    /// let mut buffer = Vec::new();
+    /// let mut writer = Writer::new(&mut buffer, WriterConfig::default());
+    /// writer.write_header_block(&["Generated by koicli", "DO NOT EDIT"])?;
+    /// drop(writer);
+    /// assert_eq!(String::from_utf8(buffer)?, "## Generated by koicli\n## DO NOT EDIT\n\n");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn write_header_block(&mut self, lines: &[&str]) -> std::io::Result<()> {
+        self.finish_line()?;
+        let hashes = self
+            .config
+            .prefix_char
+            .to_string()
+            .repeat(self.config.command_threshold + 1);
+        for line in lines {
+            if line.is_empty() {
+                writeln!(self.writer, "{}", hashes)?;
+            } else {
+                writeln!(self.writer, "{} {}", hashes, line)?;
+            }
+        }
+        self.newline()
+    }
+
+    /// Write a template, replacing `{{name}}` placeholders with commands
+    ///
+    /// Text outside placeholders is written unchanged, so generated commands
+    /// can be embedded in surrounding boilerplate. Each placeholder is replaced
+    /// by the command of that name in `commands`, written with the formatting
+    /// options of this writer but without separator; whitespace around the name
+    /// is ignored. Lines after the first of a multi-line command are prefixed
+    /// with the leading whitespace of the placeholder's line. With `max_width`,
+    /// the text before a placeholder counts against the width of the command.
+    /// An unclosed `{{` is written as is.
+    ///
+    /// # Arguments
+    /// * `template` - The template text
+    /// * `commands` - The commands by placeholder name
+    ///
+    /// # Errors
+    /// Fails with [`std::io::ErrorKind::InvalidInput`] if a placeholder has no
+    /// command, before anything is written.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// use koicore::{Command, Writer, WriterConfig};
+    ///
+    /// let title: Command = "#title \"Chapter 1\"".parse()?;
+    /// let mut buffer = Vec::new();
+    /// let mut writer = Writer::new(&mut buffer, WriterConfig::default());
+    /// writer.write_template("## generated\n{{ title }}\n", &HashMap::from([("title", &title)]))?;
+    /// drop(writer);
+    /// assert_eq!(String::from_utf8(buffer)?, "## generated\n#title \"Chapter 1\"\n");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn write_template(
+        &mut self,
+        template: &str,
+        commands: &HashMap<&str, &Command>,
+    ) -> std::io::Result<()> {
+        let mut output = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find("{{") {
+            let Some(len) = rest[start + 2..].find("}}") else {
+                break;
+            };
+            let name = rest[start + 2..start + 2 + len].trim();
+            let command = commands.get(name).ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("no command for template placeholder `{{{{{}}}}}`", name),
+                )
+            })?;
+            output.push_str(&rest[..start]);
+
+            // Continuation lines of the command line up with the placeholder's line
+            let line_start = output.rfind('\n').map_or(0, |pos| pos + 1);
+            let line = &output[line_start..];
+            let indent = format!("\n{}", &line[..line.len() - line.trim_start().len()]);
+            let text = self.render_command(command, line.chars().count())?;
+            output.push_str(&text.replace('\n', &indent));
+
+            rest = &rest[start + 2 + len + 2..];
+        }
+        output.push_str(rest);
+
+        self.finish_line()?;
+        write!(self.writer, "{}", output)?;
+        self.last_was_newline = output.ends_with('\n');
+        self.mid_line = !self.last_was_newline && self.separates_in_line();
+        Ok(())
+    }
+
+    /// Format a command with this writer's options, without indentation or separator
+    ///
+    /// `column` is where the command starts on its line, which leaves less
+    /// room before `max_width`.
+    fn render_command(&self, command: &Command, column: usize) -> std::io::Result<String> {
+        let narrowed;
+        let config = match self.config.max_width {
+            Some(max_width) if column > 0 => {
+                narrowed = WriterConfig {
+                    max_width: Some(max_width.saturating_sub(column)),
+                    ..self.config.clone()
+                };
+                &narrowed
+            }
+            _ => &self.config,
+        };
+        let options = generators::Generators::get_effective_options(&command.name, None, config);
+        let mut buffer = Vec::new();
+        generators::Generators::write_command_with_param_options(
+            &mut buffer,
+            command,
+            config,
+            &options,
+            None,
+            0,
+            None,
+        )?;
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+
     pub fn newline(&mut self) -> std::io::Result<()> {
         writeln!(self.writer)?;
         self.last_was_newline = true;
+        self.mid_line = false;
         Ok(())
     }
+
+    /// Flush the underlying output
+    ///
+    /// Buffered outputs such as `BufWriter` may hold written commands until
+    /// flushed. A pending separator (see [`Writer::finish_line`]) is not written.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
 }
 
 #[cfg(test)]
@@ -121,6 +560,105 @@ mod tests {
     use super::*;
     use crate::command::{Command, Parameter};
 
+    /// Output that records what reaches it, to observe buffering
+    #[derive(Clone, Default)]
+    struct SharedSink(std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+
+    impl Write for SharedSink {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_flush_buffered_output() {
+        let sink = SharedSink::default();
+        let output = std::io::BufWriter::new(sink.clone());
+        let mut writer = Writer::new(output, WriterConfig::default());
+
+        writer.write_command(&Command::new("a", vec![Parameter::from(1)])).unwrap();
+        assert!(sink.0.borrow().is_empty());
+        writer.flush().unwrap();
+        assert_eq!(sink.0.borrow().as_slice(), b"#a 1\n");
+    }
+
+    #[test]
+    fn test_write_command_counted() {
+        let commands = [
+            Command::new("draw", vec![Parameter::from("Line"), Parameter::from(("pos", vec![1, 2]))]),
+            Command::new_text("Text with é and 😂"),
+            Command::new("end", vec![]),
+        ];
+
+        for config in [
+            WriterConfig::default(),
+            WriterConfig {
+                trailing_newline: false,
+                ..Default::default()
+            },
+        ] {
+            let sink = SharedSink::default();
+            let mut writer = Writer::new(sink.clone(), config);
+            let mut offsets = Vec::new();
+            for command in &commands {
+                let before = sink.0.borrow().len();
+                let count = writer.write_command_counted(command).unwrap();
+                assert_eq!(count, sink.0.borrow().len() - before);
+                offsets.push(before);
+            }
+            assert_eq!(writer.bytes_written(), sink.0.borrow().len());
+
+            // Offsets index the start of each command in the output
+            let output = String::from_utf8(sink.0.borrow().clone()).unwrap();
+            assert!(output[offsets[1]..].trim_start().starts_with("Text with"));
+        }
+    }
+
+    #[test]
+    fn test_write_command_with_renderer() {
+        let mut buffer = Vec::new();
+        let mut writer = Writer::new(&mut buffer, WriterConfig::default());
+        let command: Command = "#draw 255 \"a b\" 1.5 pos(x: 16, y: 1) list(1, 2)\n".parse().unwrap();
+        writer
+            .write_command_with(&command, |value, out| match value {
+                Value::Int(n) => write!(out, "0x{:x}", n),
+                other => write!(out, "{}", format_value(other, &FormatterOptions::default())),
+            })
+            .unwrap();
+        writer.write_command_with(&Command::new_text("plain 1"), |_, _| unreachable!()).unwrap();
+        writer.write_command(&command).unwrap();
+        drop(writer);
+
+        assert_eq!(String::from_utf8(buffer).unwrap(), concat!(
+            "#draw 0xff \"a b\" 1.5 pos(x: 0x10, y: 0x1) list(0x1, 0x2)\n",
+            "plain 1\n",
+            "#draw 255 \"a b\" 1.5 pos(x: 16, y: 1) list(1, 2)\n",
+        ));
+    }
+
+    #[test]
+    fn test_write_header_block() {
+        let config = WriterConfig {
+            command_threshold: 2,
+            ..Default::default()
+        };
+        let mut buffer = Vec::new();
+        let mut writer = Writer::new(&mut buffer, config);
+
+        writer.inc_indent();
+        writer.write_header_block(&["Generated file", "", "DO NOT EDIT"]).unwrap();
+        writer.write_command(&Command::new("a", vec![])).unwrap();
+        assert_eq!(writer.get_indent(), 1);
+
+        let result = String::from_utf8(buffer).unwrap();
+        assert_eq!(result, "### Generated file\n###\n### DO NOT EDIT\n\n    ##a\n");
+    }
+
     #[test]
     fn test_write_basic_command() {
         let cmd = Command::new(
@@ -138,6 +676,57 @@ mod tests {
         assert_eq!(result, "#character Alice \"Hello, world!\"\n");
     }
 
+    #[test]
+    fn test_format_value_matches_writer() {
+        let options = FormatterOptions {
+            number_format: NumberFormat::Binary,
+            compact: true,
+            ..Default::default()
+        };
+        let values = [Value::Int(5), Value::Float(0.25), Value::from("x y"), Value::Bool(true)];
+        let composite = CompositeValue::Dict(vec![("k".to_string(), Value::Int(3))]);
+
+        let mut params: Vec<Parameter> = values.iter().cloned().map(Parameter::Basic).collect();
+        params.push(Parameter::Composite("c".to_string(), composite.clone()));
+        let config = WriterConfig {
+            global_options: options.clone(),
+            ..Default::default()
+        };
+        let mut buffer = Vec::new();
+        let mut writer = Writer::new(&mut buffer, config);
+        writer.write_command(&Command::new("cmd", params)).unwrap();
+
+        let mut expected = "#cmd".to_string();
+        for value in &values {
+            expected.push(' ');
+            expected.push_str(&format_value(value, &options));
+        }
+        expected.push_str(" c");
+        expected.push_str(&format_composite(&composite, &options));
+        expected.push('\n');
+        assert_eq!(String::from_utf8(buffer).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_write_without_prefix() {
+        let config = WriterConfig {
+            emit_prefix: false,
+            ..Default::default()
+        };
+        let mut buffer = Vec::new();
+        let mut writer = Writer::new(&mut buffer, config);
+
+        writer
+            .write_command(&Command::new("draw", vec![Parameter::from("Line"), Parameter::from(2)]))
+            .unwrap();
+        writer.write_command(&Command::new_number(42, vec![])).unwrap();
+        writer.write_command(&Command::new_text("plain text")).unwrap();
+        writer.write_command(&Command::new_annotation("note")).unwrap();
+
+        let result = String::from_utf8(buffer).unwrap();
+        assert_eq!(result, "draw Line 2\n42\nplain text\n## note\n");
+    }
+
     #[test]
     fn test_write_text_command() {
         let cmd = Command::new_text("Hello, world!");
@@ -426,4 +1015,51 @@ mod tests {
             "#test regular composite(42) another\n#test regular composite(42) another\n"
         );
     }
+
+    #[test]
+    fn test_write_template() {
+        let title = Command::new("title", vec![Parameter::from("Chapter 1")]);
+        let draw = Command::new(
+            "draw",
+            vec![
+                Parameter::from("Line"),
+                Parameter::from(("start", vec![0, 0])),
+                Parameter::from(("end", vec![100, 200])),
+            ],
+        );
+        let commands = HashMap::from([("title", &title), ("draw", &draw)]);
+        let template = "## generated\n{{title}}\nscene:\n    {{ draw }}\nend {{draw}\n";
+
+        let config = WriterConfig {
+            max_width: Some(24),
+            ..Default::default()
+        };
+        let mut buffer = Vec::new();
+        let mut writer = Writer::new(&mut buffer, config);
+        writer.write_template(template, &commands).unwrap();
+        writer.write_command(&title).unwrap();
+
+        let result = String::from_utf8(buffer).unwrap();
+        assert_eq!(
+            result,
+            concat!(
+                "## generated\n",
+                "#title \"Chapter 1\"\n",
+                "scene:\n",
+                "    #draw Line \\\n",
+                "        start(0, 0) \\\n",
+                "        end(100, 200)\n",
+                "end {{draw}\n",
+                "#title \"Chapter 1\"\n",
+            )
+        );
+
+        // Unknown placeholders fail before anything is written
+        let mut buffer = Vec::new();
+        let mut writer = Writer::new(&mut buffer, WriterConfig::default());
+        let err = writer.write_template("{{title}} {{missing}}", &commands).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(err.to_string(), "no command for template placeholder `{{missing}}`");
+        assert!(buffer.is_empty());
+    }
 }
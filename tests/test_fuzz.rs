@@ -0,0 +1,144 @@
+//! Randomized robustness tests: the parser must never panic or hang
+//!
+//! Inputs are generated from a fixed seed, so failures are reproducible. The
+//! `fuzz` directory has a libFuzzer target for longer runs.
+
+use koicore::parser::{Parser, ParserConfig, StringInputSource};
+
+/// Small xorshift generator, so the test needs no extra dependencies
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+/// Fragments that exercise the interesting parts of the grammar
+const FRAGMENTS: &[&str] = &[
+    "#", "##", "###", " ", "  ", "\t", "\n", "\r\n", "\\", "\\\n", "\"", "'", "(", ")", "[", "]",
+    "{", "}", ",", ":", ";", "//", "u\"", "%", "%2", "%FF", "0x", "0b", "0o", "-", "+", ".", "e", "1",
+    "42", "9999999999999999999999", "1e999", "name", "_x", "true", "int", ":int", ":string",
+    "\\x4", "\\u00e9", "\\U0001F602", "\\q", "é", "§", "😂", "中文", "\u{0}", "\u{FEFF}", "#draw", "#10",
+];
+
+fn random_input(rng: &mut Rng) -> String {
+    let mut input = String::new();
+    for _ in 0..rng.below(40) {
+        if rng.below(8) == 0 {
+            // Arbitrary code points, including multibyte ones
+            input.extend(char::from_u32(rng.below(0x3000) as u32));
+        } else {
+            input.push_str(FRAGMENTS[rng.below(FRAGMENTS.len())]);
+        }
+    }
+    input
+}
+
+fn configs() -> Vec<ParserConfig> {
+    vec![
+        ParserConfig::default(),
+        ParserConfig::format_preserving(),
+        ParserConfig::default()
+            .with_command_threshold(2)
+            .with_enable_set_literals(true)
+            .with_enable_percent_strings(true)
+            .with_numbers_as_strings(true)
+            .with_text_line_continuation(true),
+        ParserConfig::default()
+            .with_indent_continuation(true)
+            .with_command_separator(";")
+            .with_annotation_keep_hashes(true)
+            .with_comment_marker("//"),
+        ParserConfig::default()
+            .with_list_delimiters('[', ']')
+            .with_dict_delimiters('{', '}')
+            .with_prefix_char('§'),
+    ]
+}
+
+/// Parse the whole input, rendering every error, and check that it ends
+fn parse_all(input: &str, config: &ParserConfig) {
+    let mut parser = Parser::new(StringInputSource::new(input), config.clone());
+    // Every command consumes part of the input, so this bounds the loop
+    let limit = input.len() + 2;
+    for _ in 0..limit {
+        match parser.next_command_with_spans() {
+            Ok(Some(sourced)) => {
+                for i in 0..sourced.param_spans.len() {
+                    let _ = sourced.param_source(i);
+                }
+                let _ = sourced.command.to_string();
+            }
+            Ok(None) => return,
+            Err(e) => {
+                let _ = e.to_string();
+                let _ = e.message();
+                let _ = e.root_cause_message();
+                #[cfg(feature = "color")]
+                let _ = e.display_colored().to_string();
+                return;
+            }
+        }
+    }
+    panic!("parser did not finish within {} commands: {:?}", limit, input);
+}
+
+/// Run the single-value and single-line entry points over the input
+fn parse_pieces(input: &str) {
+    let _ = input.parse::<koicore::Value>().map_err(|e| e.to_string());
+    let _ = input.parse::<koicore::Command>().map_err(|e| e.to_string());
+    let _ = koicore::unescape_string(input).map_err(|e| e.to_string());
+    let _ = koicore::parser::ParamHint::from_source(input);
+}
+
+#[test]
+fn test_fuzz_parser_never_panics() {
+    let configs = configs();
+    let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+    for _ in 0..20_000 {
+        let input = random_input(&mut rng);
+        let config = &configs[rng.below(configs.len())];
+        let result = std::panic::catch_unwind(|| {
+            parse_all(&input, config);
+            parse_pieces(&input);
+        });
+        assert!(result.is_ok(), "parser panicked on {:?} with {:?}", input, config);
+    }
+}
+
+#[test]
+fn test_fuzz_regressions() {
+    // Inputs around multibyte characters, error columns and line ends
+    let cases = [
+        "#é )",
+        "#draw ééé )",
+        "#draw\n  ) x",
+        "\u{FEFF}#draw (",
+        "#draw \"unterminated\\",
+        "#draw 'x",
+        "#draw u\"%",
+        "#draw 0x",
+        "#draw -",
+        "#draw 1e999 -9223372036854775809",
+        "#draw a;#;;#b (",
+        "#draw\\\n",
+        "#\n#",
+        "#😂",
+        "#a (x: 1, 2)",
+        "#a [1, {x: 1}]",
+    ];
+    for input in cases {
+        for config in configs() {
+            parse_all(input, &config);
+        }
+        parse_pieces(input);
+    }
+}
@@ -219,16 +219,15 @@ fn test_parser_edge_cases_unclosed() {
 
 #[test]
 fn test_parser_edge_cases_trailing_comma() {
-    // Current implementation uses separated_list1 which generally strictly requires separator between items
-    // and DOES NOT support trailing separator unless explicitly handled.
-    // Based on command_parser.rs, it doesn't look like it handles trailing commas.
-    // So we test that it FAILS.
-    let input_str = "#cmd param(1, 2,)";
+    // A trailing comma after the last item doesn't add an element
+    let input_str = "#cmd param(1, 2,)\n#cmd param(,)";
     let input = parser::StringInputSource::new(input_str);
     let mut parser = parser::Parser::new(input, parser::ParserConfig::default());
+    let cmd = parser.next_command().unwrap().unwrap();
+    assert_eq!(cmd, "#cmd param(1, 2)".parse().unwrap());
     assert!(
         parser.next_command().is_err(),
-        "Should fail for trailing comma"
+        "Should fail for a comma without values"
     );
 }
 
@@ -324,3 +323,168 @@ fn test_parser_escape_sequences() {
         panic!("p5");
     }
 }
+
+#[cfg(feature = "compression")]
+#[test]
+fn test_parse_gzip_example() {
+    fn parse_all(input: parser::FileInputSource) -> Vec<command::Command> {
+        let mut parser = parser::Parser::new(input, parser::ParserConfig::default());
+        let mut commands = Vec::new();
+        while let Some(cmd) = parser.next_command().expect("Failed to parse file") {
+            commands.push(cmd);
+        }
+        commands
+    }
+
+    let plain = parse_all(
+        parser::FileInputSource::new(Path::new("examples/ktxt/example1.ktxt"))
+            .expect("Failed to open file"),
+    );
+    let compressed = parse_all(
+        parser::FileInputSource::new(Path::new("examples/ktxt/example1.ktxt.gz"))
+            .expect("Failed to open file"),
+    );
+    assert!(!plain.is_empty());
+    assert_eq!(compressed, plain);
+
+    let explicit = parse_all(
+        parser::FileInputSource::with_compression(
+            Path::new("examples/ktxt/example1.ktxt.gz"),
+            parser::Compression::Gzip,
+        )
+        .expect("Failed to open file"),
+    );
+    assert_eq!(explicit, plain);
+}
+
+#[test]
+fn test_following_input_source() {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::thread;
+    use std::time::Duration;
+
+    let mut path = std::env::temp_dir();
+    path.push("koi_test_follow.ktxt");
+    std::fs::write(&path, "#a 1\n").unwrap();
+
+    let input = parser::FollowingInputSource::new(&path)
+        .unwrap()
+        .with_poll_interval(Duration::from_millis(5));
+    let stop = input.stop_handle();
+    let mut parser = parser::Parser::new(input, parser::ParserConfig::default());
+    assert_eq!(parser.next_command().unwrap().unwrap().name(), "a");
+
+    let writer_path = path.clone();
+    let writer = thread::spawn(move || {
+        let mut file = OpenOptions::new().append(true).open(&writer_path).unwrap();
+        thread::sleep(Duration::from_millis(20));
+        // A command written in two parts is only read once complete
+        file.write_all(b"#b ").unwrap();
+        file.flush().unwrap();
+        thread::sleep(Duration::from_millis(20));
+        file.write_all(b"2\n#c 3").unwrap();
+        file.flush().unwrap();
+        thread::sleep(Duration::from_millis(20));
+        stop.stop();
+    });
+
+    let cmd = parser.next_command().unwrap().unwrap();
+    assert_eq!(cmd, command::Command::new("b", vec![2.into()]));
+    // The unterminated last line is returned once following stops
+    let cmd = parser.next_command().unwrap().unwrap();
+    assert_eq!(cmd, command::Command::new("c", vec![3.into()]));
+    assert!(parser.next_command().unwrap().is_none());
+
+    writer.join().unwrap();
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_chained_input_source() {
+    use parser::{ChainedInputSource, StringInputSource, TextInputSource};
+
+    fn chain(per_source: bool) -> parser::Parser<ChainedInputSource> {
+        let sources: Vec<Box<dyn TextInputSource>> = vec![
+            Box::new(StringInputSource::with_name("#a\n#b\n", "first.koi")),
+            Box::new(StringInputSource::with_name("", "empty.koi")),
+            Box::new(StringInputSource::with_name("#c\n#d pos(x: 1\n", "second.koi")),
+        ];
+        let input = ChainedInputSource::new(sources).with_per_source_numbering(per_source);
+        parser::Parser::new(input, parser::ParserConfig::default())
+    }
+
+    let mut parser = chain(false);
+    let mut names = Vec::new();
+    let err = loop {
+        match parser.next_command_with_source() {
+            Ok(Some((cmd, source))) => names.push((cmd.name, source.filename, source.lineno)),
+            Ok(None) => panic!("Expected a parse error"),
+            Err(e) => break e,
+        }
+    };
+    assert_eq!(names, vec![
+        ("a".to_string(), "first.koi".to_string(), 1),
+        ("b".to_string(), "first.koi".to_string(), 2),
+        ("c".to_string(), "second.koi".to_string(), 3),
+    ]);
+    let source = err.source.as_ref().unwrap();
+    assert_eq!(source.filename, "second.koi");
+    assert_eq!(source.lineno, 4);
+    assert!(err.to_string().contains("second.koi:4"));
+
+    // With per-source numbering, the error is on line 2 of the second source
+    let mut parser = chain(true);
+    for _ in 0..3 {
+        parser.next_command().unwrap().unwrap();
+    }
+    let err = parser.next_command().unwrap_err();
+    assert_eq!(err.source.as_ref().unwrap().filename, "second.koi");
+    assert_eq!(err.source.as_ref().unwrap().lineno, 2);
+}
+
+#[test]
+fn test_fenced_input_source() {
+    use parser::{FencedInputSource, StringInputSource};
+
+    let markdown = concat!(
+        "# Scene one\n",
+        "\n",
+        "Some prose with `#not_a_command`.\n",
+        "\n",
+        "```koi\n",
+        "#begin\n",
+        "Hello there.\n",
+        "```\n",
+        "\n",
+        "```rust\n",
+        "#[derive(Debug)]\n",
+        "```\n",
+        "\n",
+        "~~~~ koi\n",
+        "```\n",
+        "#end pos(x: 1\n",
+        "~~~~\n",
+        "#after\n",
+    );
+    let input = FencedInputSource::new(StringInputSource::with_name(markdown, "story.md"), "koi");
+    let mut parser = parser::Parser::new(input, parser::ParserConfig::default());
+
+    let mut commands = Vec::new();
+    let err = loop {
+        match parser.next_command_with_source() {
+            Ok(Some((cmd, source))) => commands.push((cmd.name, source.lineno)),
+            Ok(None) => panic!("Expected a parse error"),
+            Err(e) => break e,
+        }
+    };
+    assert_eq!(commands, vec![
+        ("begin".to_string(), 6),
+        ("@text".to_string(), 7),
+        ("@text".to_string(), 15),
+    ]);
+    let source = err.source.as_ref().unwrap();
+    assert_eq!(source.filename, "story.md");
+    assert_eq!(source.lineno, 16);
+    assert!(parser.next_command().unwrap().is_none());
+}
@@ -1,5 +1,6 @@
 #![cfg(feature = "serde")]
 use koicore::command::{Command, CompositeValue, Parameter, Value};
+use koicore::parser::{Parser, ParserConfig, StringInputSource};
 use serde_json;
 
 #[test]
@@ -78,10 +79,85 @@ fn test_text_command_serialization() {
     assert_eq!(cmd, decoded);
 }
 
+#[test]
+fn test_span_serialization() {
+    // Spans are only recorded, and so serialized, if enabled
+    let cmd: Command = "#end".parse().unwrap();
+    let json = serde_json::to_string(&cmd).unwrap();
+    assert_eq!(json, r#"{"name":"end","params":[]}"#);
+
+    let input = StringInputSource::new("\n\n    #end");
+    let config = ParserConfig::default().with_record_spans(true);
+    let cmd = Parser::new(input, config).next_command().unwrap().unwrap();
+    let json = serde_json::to_string(&cmd).unwrap();
+    assert_eq!(json, r#"{"name":"end","params":[],"span":[3,5]}"#);
+    let decoded: Command = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded.span(), Some((3, 5)));
+}
+
 #[test]
 fn test_annotation_command_serialization() {
     let cmd = Command::new_annotation("This is an annotation");
     let json = serde_json::to_string(&cmd).unwrap();
     let decoded: Command = serde_json::from_str(&json).unwrap();
     assert_eq!(cmd, decoded);
+
+    // The recorded `#` depth is formatting, not content
+    let cmd = Command::new_annotation_with_level("note", 2);
+    assert_eq!(serde_json::to_string(&cmd).unwrap(), r#"{"name":"@annotation","params":["note"]}"#);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_compact_json_round_trip() {
+    let commands = vec![
+        Command::new("draw", vec![
+            Parameter::from("Line"),
+            Parameter::from(2),
+            Parameter::from(1.5),
+            Parameter::from(true),
+            Parameter::Composite(
+                "pos".to_string(),
+                CompositeValue::Dict(vec![("x".to_string(), Value::Int(0)), ("y".to_string(), Value::Int(-1))]),
+            ),
+            Parameter::Composite("tags".to_string(), CompositeValue::List(vec![Value::from("a"), Value::from("b")])),
+        ]),
+        Command::new("end", vec![]),
+        Command::new_text("Hello, world!"),
+    ];
+
+    for cmd in &commands {
+        let compact = cmd.to_compact_json().unwrap();
+        assert!(compact.len() < serde_json::to_string(cmd).unwrap().len());
+        assert_eq!(&Command::from_compact_json(&compact).unwrap(), cmd);
+    }
+    assert_eq!(
+        commands[0].to_compact_json().unwrap(),
+        r#"["draw",["Line",2,1.5,true,{"pos":{"x":0,"y":-1}},{"tags":["a","b"]}]]"#
+    );
+    assert_eq!(commands[1].to_compact_json().unwrap(), r#"["end",[]]"#);
+
+    assert!(Command::from_compact_json(r#"{"name":"end","params":[]}"#).is_err());
+    assert!(Command::from_compact_json(r#"["end"]"#).is_err());
+}
+
+#[test]
+fn test_non_finite_float_serialization() {
+    for (value, text) in [(f64::NAN, "NaN"), (f64::INFINITY, "inf"), (f64::NEG_INFINITY, "-inf")] {
+        let err = serde_json::to_string(&Value::Float(value)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("cannot serialize non-finite float {}, only finite floats are supported", text)
+        );
+
+        // Also rejected inside parameters and composites
+        let cmd = Command::new("scale", vec![Parameter::from(("factor", vec![value]))]);
+        assert!(serde_json::to_string(&cmd).is_err());
+        assert!(serde_json::to_string(&Parameter::from(value)).is_err());
+    }
+
+    // Finite floats are unaffected
+    let json = serde_json::to_string(&Value::Float(-1.5)).unwrap();
+    assert_eq!(json, "-1.5");
+    assert_eq!(serde_json::from_str::<Value>(&json).unwrap(), Value::Float(-1.5));
 }
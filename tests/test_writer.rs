@@ -380,6 +380,100 @@ fn test_writer_parser_newlines() {
     assert_eq!(parsed_command.name(), command.name());
 }
 
+#[test]
+fn test_writer_force_single_line() {
+    let command = Command::new("log", vec![
+        Parameter::from("info"),
+        Parameter::from("two\nlines"),
+        Parameter::from(("at", vec![1, 2])),
+    ]);
+
+    let newline_options = FormatterOptions {
+        newline_before_param: true,
+        newline_after_param: true,
+        ..Default::default()
+    };
+    let mut param_options = HashMap::new();
+    for i in 0..3 {
+        param_options.insert(ParamFormatSelector::Position(i), &newline_options);
+    }
+
+    let write = |options: Option<&FormatterOptions>| {
+        let mut output = Vec::new();
+        let mut writer = Writer::new(&mut output, WriterConfig::default());
+        writer
+            .write_command_with_options(&command, options, Some(&param_options))
+            .expect("Failed to write command");
+        String::from_utf8(output).unwrap()
+    };
+
+    // Without the option, the parameters are wrapped
+    assert!(write(None).trim_end().contains('\n'));
+
+    let single_line = FormatterOptions {
+        indent: 4,
+        force_single_line: true,
+        ..Default::default()
+    };
+    let generated = write(Some(&single_line));
+    assert_eq!(generated, "#log info \"two\\nlines\" at(1, 2)\n");
+
+    let input = StringInputSource::new(generated.as_str());
+    let mut parser = Parser::new(input, ParserConfig::default());
+    assert_eq!(parser.next_command().unwrap(), Some(command.clone()));
+}
+
+#[test]
+fn test_writer_parser_anonymous_composite() {
+    let command = Command::new("point", vec![
+        Parameter::Composite(String::new(), vec![1, 2].into()),
+        Parameter::from(("", vec![("x".to_string(), 0)].into_iter().collect::<HashMap<_, _>>())),
+    ]);
+
+    let mut output = Vec::new();
+    let mut writer = Writer::new(&mut output, WriterConfig::default());
+    writer.write_command(&command).expect("Failed to write command");
+    drop(writer);
+    let generated = String::from_utf8(output).unwrap();
+    assert_eq!(generated, "#point (1, 2) (x: 0)\n");
+
+    let input = StringInputSource::new(generated.as_str());
+    let mut parser = Parser::new(input, ParserConfig::default());
+    assert_eq!(parser.next_command().unwrap(), Some(command));
+}
+
+#[test]
+fn test_writer_blank_after_composite_commands() {
+    let source = "#title Demo\n#draw Line pos(x: 1, y: 2)\n#wait 1\n#color rgb(255, 0, 0)\n#tags t(a)\n#end\n";
+    let config = WriterConfig {
+        global_options: FormatterOptions {
+            blank_after_composite_commands: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let mut parser = Parser::new(StringInputSource::new(source), ParserConfig::default());
+    let mut output = Vec::new();
+    let mut writer = Writer::new(&mut output, config);
+    let mut commands = Vec::new();
+    while let Some(command) = parser.next_command().expect("Failed to parse") {
+        writer.write_command(&command).expect("Failed to write command");
+        commands.push(command);
+    }
+    drop(writer);
+    let generated = String::from_utf8(output).unwrap();
+    assert_eq!(
+        generated,
+        "#title Demo\n#draw Line pos(x: 1, y: 2)\n\n#wait 1\n#color rgb(255, 0, 0)\n\n#tags t(a)\n\n#end\n"
+    );
+
+    // Blank lines are skipped when read back
+    let mut parser = Parser::new(StringInputSource::new(&generated), ParserConfig::default());
+    let reparsed: Vec<Command> = std::iter::from_fn(|| parser.next_command().unwrap()).collect();
+    assert_eq!(reparsed, commands);
+}
+
 // Test Writer-Parser compatibility with compact formatting
 #[test]
 fn test_writer_parser_compact() {
@@ -557,3 +651,772 @@ fn test_write_special_strings() {
         panic!("Wrong type for empty string");
     }
 }
+
+// Test Writer-Parser round trip for set literals
+#[test]
+fn test_writer_parser_set() {
+    use koicore::command::{CompositeValue, Value};
+
+    let command = Command::new(
+        "tags",
+        vec![Parameter::from((
+            "t",
+            CompositeValue::Set(vec![
+                Value::String("b".to_string()),
+                Value::Int(2),
+                Value::String("a".to_string()),
+                Value::Int(2),
+            ]),
+        ))],
+    );
+
+    let mut output = Vec::new();
+    let mut writer = Writer::new(&mut output, WriterConfig::default());
+    writer.write_command(&command).expect("Failed to write set");
+    let generated = String::from_utf8(output).unwrap();
+    assert_eq!(generated.trim_end(), "#tags t{2, a, b}");
+
+    let input = StringInputSource::new(generated.as_str());
+    let parser_config = ParserConfig::default().with_enable_set_literals(true);
+    let mut parser = Parser::new(input, parser_config);
+    let parsed = parser
+        .next_command()
+        .expect("Failed to parse back")
+        .unwrap();
+    assert_eq!(
+        parsed.params[0],
+        Parameter::from((
+            "t",
+            CompositeValue::Set(vec![
+                Value::Int(2),
+                Value::String("a".to_string()),
+                Value::String("b".to_string()),
+            ])
+        ))
+    );
+}
+
+// Test Writer-Parser round trip for type annotations
+#[test]
+fn test_writer_parser_type_annotations() {
+    let command = Command::new(
+        "set",
+        vec![
+            Parameter::from(5),
+            Parameter::from(0.5),
+            Parameter::from(true),
+            Parameter::from("count"),
+            Parameter::from(("pos", 1)),
+        ],
+    );
+
+    let mut config = WriterConfig::default();
+    config.global_options.type_annotations = true;
+    let mut output = Vec::new();
+    let mut writer = Writer::new(&mut output, config);
+    writer.write_command(&command).expect("Failed to write command");
+    let generated = String::from_utf8(output).unwrap();
+    assert_eq!(
+        generated.trim_end(),
+        "#set 5:int 0.5:float true:bool count:string pos(1)"
+    );
+
+    let input = StringInputSource::new(generated.as_str());
+    let mut parser = Parser::new(input, ParserConfig::default());
+    let parsed = parser
+        .next_command()
+        .expect("Failed to parse back")
+        .unwrap();
+    assert_eq!(parsed, command);
+
+    // A mismatching annotation is rejected by the parser
+    let input = StringInputSource::new("#set 5:string\n");
+    let mut parser = Parser::new(input, ParserConfig::default());
+    assert!(parser.next_command().is_err());
+}
+
+// Test Writer-Parser round trip for timestamps and durations
+#[cfg(feature = "chrono")]
+#[test]
+fn test_writer_parser_datetime_duration() {
+    use chrono::{DateTime, TimeDelta};
+    use koicore::command::Value;
+
+    let command = Command::new(
+        "at",
+        vec![
+            Parameter::from(Value::DateTime(
+                DateTime::parse_from_rfc3339("2024-01-01T08:00:00+00:00").unwrap(),
+            )),
+            Parameter::from(Value::Duration(TimeDelta::seconds(300))),
+            Parameter::from(Value::Duration(TimeDelta::milliseconds(1500))),
+            Parameter::from(Value::Duration(TimeDelta::seconds(90))),
+        ],
+    );
+
+    let mut output = Vec::new();
+    let mut writer = Writer::new(&mut output, WriterConfig::default());
+    writer.write_command(&command).expect("Failed to write command");
+    let generated = String::from_utf8(output).unwrap();
+    assert_eq!(generated.trim_end(), "#at 2024-01-01T08:00:00Z 5m 1500ms 90s");
+
+    let input = StringInputSource::new(generated.as_str());
+    let mut parser = Parser::new(input, ParserConfig::default());
+    let parsed = parser
+        .next_command()
+        .expect("Failed to parse back")
+        .unwrap();
+    assert_eq!(parsed, command);
+}
+
+// Annotated timestamps must stop before the `:datetime` suffix
+#[cfg(feature = "chrono")]
+#[test]
+fn test_writer_parser_annotated_datetime() {
+    let source = "#at 2024-01-01T00:00:00Z:datetime 2024-01-01T08:30:00.5+02:00 5m:duration";
+    let command: Command = source.parse().expect("Failed to parse annotated datetime");
+
+    let mut config = WriterConfig::default();
+    config.global_options.type_annotations = true;
+    let mut output = Vec::new();
+    let mut writer = Writer::new(&mut output, config);
+    writer.write_command(&command).expect("Failed to write command");
+    let generated = String::from_utf8(output).unwrap();
+    assert!(generated.contains(":datetime"), "{}", generated);
+
+    let input = StringInputSource::new(generated.as_str());
+    let mut parser = Parser::new(input, ParserConfig::default());
+    let parsed = parser
+        .next_command()
+        .expect("Failed to parse back")
+        .unwrap();
+    assert_eq!(parsed, command);
+}
+
+// Minimal output must re-parse to the identical commands
+#[test]
+fn test_writer_parser_minimal_roundtrip() {
+    use koicore::command::{CompositeValue, Value};
+
+    // Small deterministic generator so failures are reproducible
+    struct Rng(u64);
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+        fn below(&mut self, n: u64) -> u64 {
+            self.next() % n
+        }
+    }
+
+    fn ident(rng: &mut Rng) -> String {
+        let names = ["a", "_x", "pos", "Line2", "color", "true_", "int"];
+        names[rng.below(names.len() as u64) as usize].to_string()
+    }
+
+    fn value(rng: &mut Rng) -> Value {
+        match rng.below(4) {
+            0 => {
+                let ints = [0, 1, -1, 42, i64::MAX, i64::MIN, rng.next() as i64];
+                Value::Int(ints[rng.below(ints.len() as u64) as usize])
+            }
+            1 => {
+                let floats = [0.0, -0.0, 1.0, 0.5, -2.25, 1e20, 1.5e-7, 123456.789];
+                let f = if rng.below(2) == 0 {
+                    floats[rng.below(floats.len() as u64) as usize]
+                } else {
+                    f64::from_bits(rng.next())
+                };
+                Value::Float(if f.is_finite() { f } else { 3.0 })
+            }
+            2 => Value::Bool(rng.below(2) == 0),
+            3 if rng.below(4) == 0 => {
+                let keywords = ["true", "false"];
+                Value::String(keywords[rng.below(2) as usize].to_string())
+            }
+            _ => {
+                let pieces = ["a", "Z", "_", "0", " ", "\"", "\\", "\n", "\t", "é", "#"];
+                let len = rng.below(4);
+                Value::String(
+                    (0..len)
+                        .map(|_| pieces[rng.below(pieces.len() as u64) as usize])
+                        .collect(),
+                )
+            }
+        }
+    }
+
+    fn param(rng: &mut Rng) -> Parameter {
+        match rng.below(4) {
+            0 => Parameter::Composite(ident(rng), CompositeValue::Single(value(rng))),
+            1 => Parameter::Composite(
+                ident(rng),
+                CompositeValue::List((0..2 + rng.below(3)).map(|_| value(rng)).collect()),
+            ),
+            2 => Parameter::Composite(
+                ident(rng),
+                CompositeValue::Dict(
+                    (0..1 + rng.below(3))
+                        .map(|_| (ident(rng), value(rng)))
+                        .collect(),
+                ),
+            ),
+            _ => Parameter::Basic(value(rng)),
+        }
+    }
+
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    for _ in 0..200 {
+        let commands: Vec<Command> = (0..1 + rng.below(4))
+            .map(|_| {
+                let params = (0..rng.below(5)).map(|_| param(&mut rng)).collect();
+                Command::new(ident(&mut rng), params)
+            })
+            .collect();
+
+        let mut output = Vec::new();
+        let mut writer = Writer::new(&mut output, WriterConfig::minimal());
+        for command in &commands {
+            writer.write_command(command).expect("Failed to write command");
+        }
+        let generated = String::from_utf8(output).unwrap();
+        assert!(!generated.ends_with('\n'));
+
+        let input = StringInputSource::new(generated.as_str());
+        let mut parser = Parser::new(input, ParserConfig::default());
+        let mut parsed = Vec::new();
+        while let Some(command) = parser
+            .next_command()
+            .unwrap_or_else(|e| panic!("Failed to parse {:?}: {}", generated, e))
+        {
+            parsed.push(command);
+        }
+        assert_eq!(parsed, commands, "Round trip failed for {:?}", generated);
+    }
+}
+
+#[test]
+fn test_writer_semicolon_separator() {
+    use koicore::writer::CommandSeparator;
+
+    let commands = vec![
+        Command::new_text("hello; world"),
+        Command::new("a", vec![Parameter::from(1)]),
+        Command::new_annotation("note"),
+        Command::new("b", vec![Parameter::from("x;y")]),
+        Command::new("c", vec![]),
+    ];
+    let config = WriterConfig {
+        command_separator: CommandSeparator::Semicolon,
+        ..Default::default()
+    };
+
+    let mut output = Vec::new();
+    let mut writer = Writer::new(&mut output, config);
+    for command in &commands {
+        writer.write_command(command).expect("Failed to write command");
+    }
+    let generated = String::from_utf8(output).unwrap();
+    // Text and annotations are never split, so they keep lines of their own
+    assert_eq!(generated, "hello; world\n#a 1;\n## note\n#b \"x;y\";#c;");
+
+    let input = StringInputSource::new(generated.as_str());
+    let mut parser = Parser::new(input, ParserConfig::default().with_command_separator(";"));
+    let mut parsed = Vec::new();
+    while let Some(command) = parser.next_command().expect("Failed to parse") {
+        parsed.push(command);
+    }
+    assert_eq!(parsed, commands);
+}
+
+#[test]
+fn test_writer_custom_separator() {
+    use koicore::writer::CommandSeparator;
+
+    let config = WriterConfig {
+        command_separator: CommandSeparator::Custom(" | ".to_string()),
+        trailing_newline: false,
+        ..Default::default()
+    };
+
+    let mut output = Vec::new();
+    let mut writer = Writer::new(&mut output, config);
+    writer.write_command(&Command::new("a", vec![Parameter::from(1)])).unwrap();
+    writer.write_command(&Command::new("b", vec![Parameter::from(2)])).unwrap();
+    writer.write_command(&Command::new_text("text")).unwrap();
+    writer.write_command(&Command::new("c", vec![])).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), "#a 1 | #b 2\ntext\n#c");
+}
+
+#[test]
+fn test_writer_parser_percent_strings() {
+    let command = Command::new("link", vec![
+        Parameter::from("https://example.com/search?q=koi lang&page=2&rate=50%"),
+        Parameter::from("back\\slash \"quoted\"\n"),
+    ]);
+    let mut config = WriterConfig::default();
+    config.global_options.percent_encode_strings = true;
+
+    let mut output = Vec::new();
+    let mut writer = Writer::new(&mut output, config);
+    writer.write_command(&command).expect("Failed to write command");
+    let generated = String::from_utf8(output).unwrap();
+    assert_eq!(
+        generated,
+        "#link u\"https://example.com/search?q=koi lang&page=2&rate=50%25\" u\"back%5Cslash %22quoted%22%0A\"\n"
+    );
+
+    let input = StringInputSource::new(generated.as_str());
+    let mut parser = Parser::new(input, ParserConfig::default().with_enable_percent_strings(true));
+    let parsed = parser.next_command().expect("Failed to parse").unwrap();
+    assert_eq!(parsed, command);
+}
+
+#[test]
+fn test_writer_parser_preserve_trailing_newline() {
+    for source in ["#a 1\n#b \"x y\"\n", "#a 1\n#b \"x y\"", "#a 1\n"] {
+        let input = StringInputSource::new(source);
+        let mut parser = Parser::new(input, ParserConfig::default());
+        let config = WriterConfig {
+            trailing_newline: false,
+            ..Default::default()
+        };
+
+        let mut output = Vec::new();
+        let mut writer = Writer::new(&mut output, config);
+        while let Some(command) = parser.next_command().expect("Failed to parse") {
+            writer.write_command(&command).expect("Failed to write command");
+        }
+        if parser.ended_with_newline() {
+            writer.finish_line().expect("Failed to write newline");
+        }
+        assert_eq!(String::from_utf8(output).unwrap(), source);
+    }
+}
+
+#[test]
+fn test_writer_parser_annotation_levels() {
+    let source = "## metadata\n### documentation\n#cmd 1\n";
+    let input = StringInputSource::new(source);
+    let mut parser = Parser::new(input, ParserConfig::default());
+
+    let mut output = Vec::new();
+    let mut writer = Writer::new(&mut output, WriterConfig::default());
+    while let Some(command) = parser.next_command().expect("Failed to parse") {
+        writer.write_command(&command).expect("Failed to write command");
+    }
+    assert_eq!(String::from_utf8(output).unwrap(), source);
+}
+
+#[test]
+fn test_writer_parser_composite_spacing() {
+    let command = Command::new("set", vec![
+        Parameter::from(("color", vec![255, 255, 255])),
+        Parameter::Composite(
+            "pos".to_string(),
+            koicore::command::CompositeValue::Dict(vec![
+                ("x".to_string(), 0.into()),
+                ("y".to_string(), 0.into()),
+            ]),
+        ),
+    ]);
+
+    for (space, expected) in [
+        (true, "#set color(255, 255, 255) pos(x: 0, y: 0)\n"),
+        (false, "#set color(255,255,255) pos(x:0,y:0)\n"),
+    ] {
+        let mut config = WriterConfig::default();
+        config.global_options.composite_space_after_comma = Some(space);
+        config.global_options.composite_space_after_colon = Some(space);
+
+        let mut output = Vec::new();
+        let mut writer = Writer::new(&mut output, config);
+        writer.write_command(&command).expect("Failed to write command");
+        let generated = String::from_utf8(output).unwrap();
+        assert_eq!(generated, expected);
+
+        let input = StringInputSource::new(generated.as_str());
+        let mut parser = Parser::new(input, ParserConfig::default());
+        assert_eq!(parser.next_command().unwrap(), Some(command.clone()));
+    }
+}
+
+#[test]
+fn test_writer_parser_composite_delimiters() {
+    let command = Command::new("set", vec![
+        Parameter::from(("color", vec![255, 255, 255])),
+        Parameter::Composite(
+            "pos".to_string(),
+            koicore::command::CompositeValue::Dict(vec![
+                ("x".to_string(), 0.into()),
+                ("y".to_string(), 0.into()),
+            ]),
+        ),
+        Parameter::from(("scale", 2)),
+    ]);
+
+    let config = WriterConfig {
+        list_delimiters: ('[', ']'),
+        dict_delimiters: ('{', '}'),
+        ..Default::default()
+    };
+    let mut output = Vec::new();
+    let mut writer = Writer::new(&mut output, config);
+    writer.write_command(&command).expect("Failed to write command");
+    let generated = String::from_utf8(output).unwrap();
+    assert_eq!(generated, "#set color[255, 255, 255] pos{x: 0, y: 0} scale[2]\n");
+
+    let input = StringInputSource::new(generated.as_str());
+    let config = ParserConfig::default()
+        .with_list_delimiters('[', ']')
+        .with_dict_delimiters('{', '}');
+    let mut parser = Parser::new(input, config);
+    assert_eq!(parser.next_command().unwrap(), Some(command));
+}
+
+#[test]
+fn test_writer_canonicalize_composites() {
+    let first: Command = "#style font(size: 12, family: serif, bold: true) tags(b, a) pad(y: 2, x: 1, x: 0)".parse().unwrap();
+    let second: Command = "#style font(bold: true, family: serif, size: 12) tags(b, a) pad(x: 1, y: 2, x: 0)".parse().unwrap();
+    assert_ne!(first, second);
+
+    let write = |command: &Command, canonicalize: bool| {
+        let mut config = WriterConfig::default();
+        config.global_options.canonicalize_composites = canonicalize;
+        let mut output = Vec::new();
+        let mut writer = Writer::new(&mut output, config);
+        writer.write_command(command).expect("Failed to write command");
+        String::from_utf8(output).unwrap()
+    };
+
+    let canonical = write(&first, true);
+    assert_eq!(canonical, write(&second, true));
+    // Lists and duplicate keys keep their order
+    assert_eq!(
+        canonical,
+        "#style font(bold: true, family: serif, size: 12) tags(b, a) pad(x: 1, x: 0, y: 2)\n"
+    );
+    assert_ne!(write(&first, false), write(&second, false));
+}
+
+#[test]
+fn test_writer_quote_dict_string_values() {
+    let command: Command = "#meta meta(name: hello, path: \"x\") tags(a, \"b c\") plain".parse().unwrap();
+
+    let mut config = WriterConfig::default();
+    config.global_options.quote_dict_string_values = true;
+    let mut output = Vec::new();
+    let mut writer = Writer::new(&mut output, config);
+    writer.write_command(&command).expect("Failed to write command");
+    let generated = String::from_utf8(output).unwrap();
+    // Top-level strings keep the usual quoting rules
+    assert_eq!(generated, "#meta meta(name: \"hello\", path: \"x\") tags(\"a\", \"b c\") plain\n");
+
+    let input = StringInputSource::new(generated.as_str());
+    let mut parser = Parser::new(input, ParserConfig::default());
+    assert_eq!(parser.next_command().unwrap(), Some(command.clone()));
+
+    let mut output = Vec::new();
+    let mut writer = Writer::new(&mut output, WriterConfig::default());
+    writer.write_command(&command).expect("Failed to write command");
+    let generated = String::from_utf8(output).unwrap();
+    assert_eq!(generated, "#meta meta(name: hello, path: x) tags(a, \"b c\") plain\n");
+}
+
+#[test]
+fn test_writer_parser_ambiguous_literals() {
+    // Bare, these would re-parse as an integer, booleans and a float
+    let command = Command::new("cmd", vec![
+        Parameter::from("0x10"),
+        Parameter::from("true"),
+        Parameter::from("false"),
+        Parameter::from("1e5"),
+        Parameter::from("truthy"),
+    ]);
+
+    let mut output = Vec::new();
+    let mut writer = Writer::new(&mut output, WriterConfig::default());
+    writer.write_command(&command).expect("Failed to write command");
+    let generated = String::from_utf8(output).unwrap();
+    assert_eq!(generated, "#cmd \"0x10\" \"true\" \"false\" \"1e5\" truthy\n");
+
+    let input = StringInputSource::new(generated.as_str());
+    let mut parser = Parser::new(input, ParserConfig::default());
+    assert_eq!(parser.next_command().unwrap(), Some(command));
+}
+
+#[test]
+fn test_writer_parser_single_element_list() {
+    use koicore::command::{CompositeValue, Value};
+
+    let command = Command::new("cmd", vec![
+        Parameter::Composite("single".to_string(), CompositeValue::Single(Value::Int(2))),
+        Parameter::Composite("list".to_string(), CompositeValue::List(vec![Value::Int(2)])),
+        Parameter::Composite(
+            "pair".to_string(),
+            CompositeValue::List(vec![Value::Int(2), Value::Int(3)]),
+        ),
+    ]);
+
+    for compact in [false, true] {
+        let mut config = WriterConfig::default();
+        config.global_options.compact = compact;
+        let mut output = Vec::new();
+        let mut writer = Writer::new(&mut output, config);
+        writer.write_command(&command).expect("Failed to write command");
+        let generated = String::from_utf8(output).unwrap();
+        if compact {
+            assert_eq!(generated, "#cmd single(2) list(2,) pair(2,3)\n");
+        } else {
+            assert_eq!(generated, "#cmd single(2) list(2,) pair(2, 3)\n");
+        }
+
+        let input = StringInputSource::new(generated.as_str());
+        let mut parser = Parser::new(input, ParserConfig::default());
+        assert_eq!(parser.next_command().unwrap(), Some(command.clone()));
+    }
+
+    // Display round-trips through FromStr as well
+    assert_eq!(command.to_string().parse::<Command>().unwrap(), command);
+
+    // A trailing comma makes a single element a list, and is ignored otherwise
+    let input = StringInputSource::new("#cmd list( 2 , )\n#cmd pair(2, 3,)");
+    let mut parser = Parser::new(input, ParserConfig::default());
+    let list = |name: &str, values| Command::new("cmd", vec![Parameter::Composite(
+        name.to_string(),
+        CompositeValue::List(values),
+    )]);
+    assert_eq!(parser.next_command().unwrap(), Some(list("list", vec![Value::Int(2)])));
+    assert_eq!(
+        parser.next_command().unwrap(),
+        Some(list("pair", vec![Value::Int(2), Value::Int(3)]))
+    );
+}
+
+#[test]
+fn test_writer_parser_hints_round_trip() {
+    let source = concat!(
+        "#style 0xff \"double\" bare 0b101 0o17 12\n",
+        "#tags names(\"Alice\", \"Bob\") mask(0x1, 0x2) opts(key: \"v\", n: 0b11)\n",
+        "#10 0x7f\n",
+        "Some text\n",
+    );
+    let mut parser = Parser::new(StringInputSource::new(source), ParserConfig::default());
+    let mut buffer = Vec::new();
+    let mut writer = Writer::new(&mut buffer, WriterConfig::default());
+    while let Some((command, hints)) = parser.next_command_with_hints().unwrap() {
+        assert_eq!(command.params.len(), hints.len());
+        writer.write_command_with_hints(&command, &hints).unwrap();
+    }
+    drop(writer);
+    assert_eq!(String::from_utf8(buffer).unwrap(), source);
+
+    // Quoting is kept per parameter, not per element
+    let mut parser = Parser::new(
+        StringInputSource::new("#mixed list(\"a\", b) 1.5"),
+        ParserConfig::default(),
+    );
+    let (command, hints) = parser.next_command_with_hints().unwrap().unwrap();
+    let mut buffer = Vec::new();
+    let mut writer = Writer::new(&mut buffer, WriterConfig::default());
+    writer.write_command_with_hints(&command, &hints).unwrap();
+    drop(writer);
+    assert_eq!(String::from_utf8(buffer).unwrap(), "#mixed list(a, b) 1.5\n");
+
+    // A non-decimal number format in the writer configuration wins over hints
+    let mut parser = Parser::new(StringInputSource::new("#a 0xff \"q\""), ParserConfig::default());
+    let (command, hints) = parser.next_command_with_hints().unwrap().unwrap();
+    let config = WriterConfig {
+        global_options: FormatterOptions {
+            number_format: NumberFormat::Binary,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut buffer = Vec::new();
+    let mut writer = Writer::new(&mut buffer, config);
+    writer.write_command_with_hints(&command, &hints).unwrap();
+    drop(writer);
+    assert_eq!(String::from_utf8(buffer).unwrap(), "#a 0b11111111 \"q\"\n");
+}
+
+#[test]
+fn test_writer_parser_format_preserving() {
+    let source = concat!(
+        "## Header annotation\n",
+        "#Scene Forest 0x1f \"Dark Woods\"\n",
+        "\n",
+        "  Indented narration, kept as is.\n",
+        "#7 0b1010 agent\n",
+        "#MOVE pos(x: 0o17, y: 0o3) dir(-1, 2) tags(\"a\", \"b\") 1.5\n",
+        "  #flags mask(0x1, 0x2) on true \"quoted\"\n",
+        "    ### nested annotation\n",
+        "\t#color 0xff0000\n",
+        "\n",
+        "#end\n",
+    );
+    let normalized = concat!(
+        "#Scene   Forest 0x1F \"Dark Woods\"  \n",
+        "#007 x\n",
+        "#MOVE pos( x : 0o17 , y:3 ) 1.50:float\n",
+    );
+
+    let round_trip = |source: &str| {
+        let input = StringInputSource::new(source);
+        let mut parser = Parser::new(input, ParserConfig::format_preserving());
+        let mut commands = Vec::new();
+        let mut buffer = Vec::new();
+        let mut writer = Writer::new(&mut buffer, WriterConfig::default());
+        while let Some((command, hints)) = parser.next_command_with_hints().unwrap() {
+            writer.write_command_with_hints(&command, &hints).unwrap();
+            commands.push(command);
+        }
+        drop(writer);
+        (commands, String::from_utf8(buffer).unwrap())
+    };
+
+    // Representation is preserved byte for byte
+    let (commands, output) = round_trip(source);
+    assert_eq!(output, source);
+    let (reparsed, _) = round_trip(&output);
+    assert_eq!(reparsed, commands);
+
+    // Structure: every line is a command and names keep their case
+    let names: Vec<&str> = commands.iter().map(Command::name).collect();
+    assert_eq!(names, [
+        "@annotation", "Scene", "@text", "@text", "@number", "MOVE", "flags", "@annotation", "color",
+        "@text", "end",
+    ]);
+    assert_eq!(commands[6].indent.as_deref(), Some("  "));
+    assert_eq!(commands[8].indent.as_deref(), Some("\t"));
+    assert_eq!(commands[1].indent.as_deref(), Some(""));
+    assert_eq!(commands[3].params[0], Parameter::from("  Indented narration, kept as is."));
+
+    // Whitespace, hex digit case, number names, float spelling and type annotations are
+    // normalized, and a parameter mixing integer bases is written in decimal
+    let (_, output) = round_trip(normalized);
+    assert_eq!(output, concat!(
+        "#Scene Forest 0x1f \"Dark Woods\"\n",
+        "#7 x\n",
+        "#MOVE pos(x: 15, y: 3) 1.5\n",
+    ));
+}
+
+#[test]
+fn test_max_width_wrapping() {
+    let command = Command::new("draw", vec![
+        Parameter::from("Line"),
+        Parameter::from(("start", vec![0, 0])),
+        Parameter::from(("end", vec![100, 200])),
+        Parameter::from("a long caption"),
+        Parameter::from(3),
+    ]);
+
+    let write = |config: WriterConfig, indent: bool| {
+        let mut output = Vec::new();
+        let mut writer = Writer::new(&mut output, config);
+        if indent {
+            writer.inc_indent();
+        }
+        writer.write_command(&command).expect("Failed to write command");
+        drop(writer);
+        String::from_utf8(output).unwrap()
+    };
+
+    let generated = write(WriterConfig { max_width: Some(24), ..Default::default() }, false);
+    assert_eq!(generated, concat!(
+        "#draw Line start(0, 0) \\\n",
+        "    end(100, 200) \\\n",
+        "    \"a long caption\" 3\n",
+    ));
+    assert!(generated.lines().all(|line| line.chars().count() <= 24));
+
+    // Indented commands continue one level deeper
+    let indented = write(WriterConfig { max_width: Some(24), ..Default::default() }, true);
+    assert_eq!(indented, concat!(
+        "    #draw Line \\\n",
+        "        start(0, 0) \\\n",
+        "        end(100, 200) \\\n",
+        "        \"a long caption\" \\\n",
+        "        3\n",
+    ));
+
+    // A limit narrower than any parameter still writes one parameter per line
+    let narrow = write(WriterConfig { max_width: Some(4), ..Default::default() }, false);
+    assert_eq!(narrow.lines().count(), 6);
+
+    // Wide limits and force_single_line leave the command on one line
+    let single_line = "#draw Line start(0, 0) end(100, 200) \"a long caption\" 3\n";
+    assert_eq!(write(WriterConfig { max_width: Some(80), ..Default::default() }, false), single_line);
+    let mut config = WriterConfig { max_width: Some(24), ..Default::default() };
+    config.global_options.force_single_line = true;
+    assert_eq!(write(config, false), single_line);
+
+    // Wrapped output reads back as the same command
+    for output in [generated, indented, narrow] {
+        let mut parser = Parser::new(StringInputSource::new(&output), ParserConfig::default());
+        assert_eq!(parser.next_command().unwrap(), Some(command.clone()));
+        assert_eq!(parser.next_command().unwrap(), None);
+    }
+}
+
+#[test]
+fn test_prefix_char_round_trip() {
+    let source = "%%% Generated\n%scene forest 1\nOnce upon a time\n%7 x\n%draw \"#hash\" pos(x: 1)\n";
+    let commands = Parser::new(
+        StringInputSource::new(source),
+        ParserConfig::default().with_prefix_char('%'),
+    )
+    .into_commands()
+    .unwrap();
+    let names: Vec<&str> = commands.iter().map(Command::name).collect();
+    assert_eq!(names, ["@annotation", "scene", "@text", "@number", "draw"]);
+    assert_eq!(commands[0].annotation_level(), Some(3));
+    assert_eq!(commands[4].params[0], Parameter::from("#hash"));
+
+    // `#` lines are text with a `%` prefix
+    let config = ParserConfig::default().with_prefix_char('%');
+    let text = Parser::new(StringInputSource::new("# not a command\n"), config)
+        .into_commands()
+        .unwrap();
+    assert_eq!(text, vec![Command::new_text("# not a command")]);
+
+    let mut output = Vec::new();
+    let mut writer = Writer::new(&mut output, WriterConfig {
+        prefix_char: '%',
+        ..Default::default()
+    });
+    for command in &commands {
+        writer.write_command(command).unwrap();
+    }
+    drop(writer);
+    let generated = String::from_utf8(output).unwrap();
+    assert_eq!(generated, source);
+
+    // The threshold counts the configured character
+    let mut output = Vec::new();
+    let mut writer = Writer::new(&mut output, WriterConfig {
+        prefix_char: '@',
+        command_threshold: 2,
+        ..Default::default()
+    });
+    writer.write_header_block(&["banner"]).unwrap();
+    for command in &commands {
+        writer.write_command(command).unwrap();
+    }
+    drop(writer);
+    let generated = String::from_utf8(output).unwrap();
+    assert!(generated.starts_with("@@@ banner\n\n@@@ Generated\n@@scene forest 1\n"));
+
+    let config = ParserConfig::default()
+        .with_prefix_char('@')
+        .with_command_threshold(2);
+    let reparsed = Parser::new(StringInputSource::new(generated.as_str()), config)
+        .into_commands()
+        .unwrap();
+    assert_eq!(reparsed[0], Command::new_annotation_with_level("banner", 3));
+    assert_eq!(reparsed[1..], commands[..]);
+}